@@ -0,0 +1,110 @@
+use clvmr::allocator::{Allocator, NodePtr, SExp};
+use clvmr::serde::node_from_bytes;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::include_bytes;
+use std::time::Instant;
+
+// the shape of a generator's output: a long list of CREATE_COIN conditions,
+// (51 puzzle-hash amount (memo)). Apart from the puzzle hashes, the atoms are
+// short, which is what inline atom storage is for
+const NUM_CONDITIONS: u64 = 10000;
+
+fn create_conditions(a: &mut Allocator) -> NodePtr {
+    let mut list = a.nil();
+    for i in 0..NUM_CONDITIONS {
+        let mut puzzle_hash = [0x5a_u8; 32];
+        puzzle_hash[..8].copy_from_slice(&i.to_be_bytes());
+        let memo = a
+            .new_atom(&(i * 0x1337_u64).to_be_bytes())
+            .expect("new_atom");
+        let memos = a.new_pair(memo, a.nil()).expect("new_pair");
+        let cond = a.new_pair(memos, a.nil()).expect("new_pair");
+        let amount = a
+            .new_number((1_000_000_000_000_u64 + i).into())
+            .expect("new_number");
+        let cond = a.new_pair(amount, cond).expect("new_pair");
+        let puzzle_hash = a.new_atom(&puzzle_hash).expect("new_atom");
+        let cond = a.new_pair(puzzle_hash, cond).expect("new_pair");
+        let opcode = a.new_small_number(51).expect("new_small_number");
+        let cond = a.new_pair(opcode, cond).expect("new_pair");
+        list = a.new_pair(cond, list).expect("new_pair");
+    }
+    list
+}
+
+// parse the conditions the way a consumer of generator output would
+fn walk_conditions(a: &Allocator, mut list: NodePtr) -> u64 {
+    let mut total = 0;
+    while let Some((cond, rest)) = a.next(list) {
+        list = rest;
+        let (opcode, cond) = a.next(cond).expect("opcode");
+        assert_eq!(a.small_number(opcode), Some(51));
+        let (puzzle_hash, cond) = a.next(cond).expect("puzzle hash");
+        total += a.atom(puzzle_hash).as_ref()[31] as u64;
+        let (amount, cond) = a.next(cond).expect("amount");
+        total += u64::try_from(a.number(amount)).expect("amount");
+        let (memos, _) = a.next(cond).expect("memos");
+        let (memo, _) = a.next(memos).expect("memo");
+        total += a.atom_len(memo) as u64;
+    }
+    total
+}
+
+// visits every atom in the tree, reading its bytes
+fn walk_atoms(a: &Allocator, node: NodePtr) -> u64 {
+    let mut total = 0;
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        match a.sexp(node) {
+            SExp::Pair(first, rest) => {
+                stack.push(rest);
+                stack.push(first);
+            }
+            SExp::Atom => {
+                total += a.atom(node).as_ref().iter().map(|b| *b as u64).sum::<u64>();
+            }
+        }
+    }
+    total
+}
+
+fn allocator_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("allocator");
+
+    let mut a = Allocator::new();
+    let iter_checkpoint = a.checkpoint();
+    group.bench_function("create-conditions", |b| {
+        b.iter(|| {
+            a.restore_checkpoint(&iter_checkpoint);
+            let start = Instant::now();
+            black_box(create_conditions(&mut a));
+            start.elapsed()
+        })
+    });
+
+    let mut a = Allocator::new();
+    let conditions = create_conditions(&mut a);
+    group.bench_function("walk-conditions", |b| {
+        b.iter(|| {
+            let start = Instant::now();
+            black_box(walk_conditions(&a, conditions));
+            start.elapsed()
+        })
+    });
+
+    let block = include_bytes!("block_af9c3d98.bin");
+    let mut a = Allocator::new();
+    let generator = node_from_bytes(&mut a, block).expect("node_from_bytes");
+    group.bench_function("walk-block", |b| {
+        b.iter(|| {
+            let start = Instant::now();
+            black_box(walk_atoms(&a, generator));
+            start.elapsed()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(allocator, allocator_benchmark);
+criterion_main!(allocator);
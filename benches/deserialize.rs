@@ -1,6 +1,6 @@
 use clvmr::allocator::Allocator;
 use clvmr::serde::{
-    node_from_bytes, node_from_bytes_backrefs, node_to_bytes_backrefs,
+    node_from_bytes, node_from_bytes_backrefs, node_from_bytes_trusted, node_to_bytes_backrefs,
     serialized_length_from_bytes, serialized_length_from_bytes_trusted, tree_hash_from_stream,
 };
 use criterion::{criterion_group, criterion_main, Criterion};
@@ -77,6 +77,17 @@ fn deserialize_benchmark(c: &mut Criterion) {
         })
     });
 
+    let mut a = Allocator::new();
+    let iter_checkpoint = a.checkpoint();
+    group.bench_function("node_from_bytes_trusted", |b| {
+        b.iter(|| {
+            a.restore_checkpoint(&iter_checkpoint);
+            let start = Instant::now();
+            node_from_bytes_trusted(&mut a, block).expect("node_from_bytes_trusted");
+            start.elapsed()
+        })
+    });
+
     group.finish();
 }
 
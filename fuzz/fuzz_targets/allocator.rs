@@ -14,7 +14,7 @@ fn run_tests(a: &mut Allocator, atom1: NodePtr, data: &[u8]) {
 
     // small_number
     if let Some(val) = a.small_number(atom1) {
-        let atom2 = a.new_small_number(val).expect("new_small_number()");
+        let atom2 = a.new_small_number(val.into()).expect("new_small_number()");
         assert_eq!(a.atom(atom1), a.atom(atom2));
         assert_eq!(a.atom(atom2).as_ref(), data);
         assert!(a.atom_eq(atom1, atom2));
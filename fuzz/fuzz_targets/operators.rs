@@ -3,16 +3,20 @@ use libfuzzer_sys::fuzz_target;
 
 use clvmr::allocator::{Allocator, NodePtr};
 use clvmr::bls_ops::{
-    op_bls_g1_multiply, op_bls_g1_negate, op_bls_g1_subtract, op_bls_g2_add, op_bls_g2_multiply,
-    op_bls_g2_negate, op_bls_g2_subtract, op_bls_map_to_g1, op_bls_map_to_g2,
-    op_bls_pairing_identity, op_bls_verify,
+    op_bls_g1_multiply, op_bls_g1_negate, op_bls_g1_subtract, op_bls_g1_valid, op_bls_g2_add,
+    op_bls_g2_multiply, op_bls_g2_negate, op_bls_g2_subtract, op_bls_g2_valid, op_bls_map_to_g1,
+    op_bls_map_to_g2, op_bls_pairing_identity, op_bls_verify,
+};
+use clvmr::core_ops::{
+    op_cons, op_eq, op_first, op_if, op_listp, op_raise, op_rest, op_tree_depth, op_tree_size,
 };
-use clvmr::core_ops::{op_cons, op_eq, op_first, op_if, op_listp, op_raise, op_rest};
 use clvmr::cost::Cost;
+use clvmr::ed25519_ops::op_ed25519_verify;
 use clvmr::more_ops::{
-    op_add, op_all, op_any, op_ash, op_coinid, op_concat, op_div, op_divmod, op_gr, op_gr_bytes,
-    op_logand, op_logior, op_lognot, op_logxor, op_lsh, op_mod, op_modpow, op_multiply, op_not,
-    op_point_add, op_pubkey_for_exp, op_sha256, op_strlen, op_substr, op_subtract,
+    op_add, op_all, op_any, op_ash, op_bitlength, op_coinid, op_concat, op_div, op_divexact,
+    op_divmod, op_gcd, op_gr, op_gr_bytes, op_logand, op_logior, op_lognot, op_logxor, op_lsh,
+    op_mod, op_modpow, op_mul_div_floor, op_multiply, op_not, op_point_add, op_popcount,
+    op_pubkey_for_exp, op_sha256, op_sqrt_floor, op_strlen, op_substr, op_subtract,
 };
 use clvmr::reduction::{EvalErr, Response};
 use clvmr::secp_ops::{op_secp256k1_verify, op_secp256r1_verify};
@@ -20,7 +24,7 @@ use clvmr::serde::node_from_bytes;
 
 type Opf = fn(&mut Allocator, NodePtr, Cost) -> Response;
 
-const FUNS: [Opf; 45] = [
+const FUNS: [Opf; 56] = [
     op_if as Opf,
     op_cons as Opf,
     op_first as Opf,
@@ -63,11 +67,22 @@ const FUNS: [Opf; 45] = [
     op_bls_map_to_g2 as Opf,
     op_bls_pairing_identity as Opf,
     op_bls_verify as Opf,
+    op_bls_g1_valid as Opf,
+    op_bls_g2_valid as Opf,
     op_mod as Opf,
     op_modpow as Opf,
     // Secp operators
     op_secp256k1_verify as Opf,
     op_secp256r1_verify as Opf,
+    op_ed25519_verify as Opf,
+    op_divexact as Opf,
+    op_gcd as Opf,
+    op_mul_div_floor as Opf,
+    op_sqrt_floor as Opf,
+    op_popcount as Opf,
+    op_bitlength as Opf,
+    op_tree_size as Opf,
+    op_tree_depth as Opf,
 ];
 
 fuzz_target!(|data: &[u8]| {
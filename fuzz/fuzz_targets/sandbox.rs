@@ -0,0 +1,27 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use clvm_rs_test_tools::sandbox::{run_generated, SandboxLimits};
+use clvmr::chia_dialect::{ENABLE_BLS_OPS_OUTSIDE_GUARD, MEMPOOL_MODE};
+use clvmr::hex;
+use std::time::Duration;
+
+// runs programs generated from the fuzz input in the sandbox, and fails on any
+// violation of the interpreter's guarantees (a panic, exceeding the heap limit
+// or taking too long)
+fuzz_target!(|data: &[u8]| {
+    let limits = SandboxLimits {
+        heap_limit: 16 * 1024 * 1024,
+        atom_limit: 1024 * 1024,
+        max_cost: 11000000000,
+        timeout: Duration::from_secs(5),
+    };
+    for flags in [0, ENABLE_BLS_OPS_OUTSIDE_GUARD, MEMPOOL_MODE] {
+        let (program, outcome) = run_generated(data, flags, &limits);
+        assert!(
+            outcome.is_sound(),
+            "program {} flags {flags:x}: {outcome:?}",
+            hex::encode(program)
+        );
+    }
+});
@@ -0,0 +1,22 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use clvm_rs_test_tools::differential::{check_generated, check_strict_differential};
+use clvmr::chia_dialect::ENABLE_BLS_OPS_OUTSIDE_GUARD;
+use clvmr::hex;
+
+// runs the fuzz input, both as a serialized program and as the input to
+// ProgramGenerator, in strict and permissive mode, and fails if the strict
+// mode accepts something the permissive mode doesn't (or returns something
+// different)
+fuzz_target!(|data: &[u8]| {
+    for flags in [0, ENABLE_BLS_OPS_OUTSIDE_GUARD] {
+        if let Err(e) = check_strict_differential(data, &[0x80], flags) {
+            panic!("program {} flags {flags:x}: {e}", hex::encode(data));
+        }
+        let (program, r) = check_generated(data, flags);
+        if let Err(e) = r {
+            panic!("program {} flags {flags:x}: {e}", hex::encode(program));
+        }
+    }
+});
@@ -1,20 +1,152 @@
+use crate::chia_dialect::LIMIT_HEAP;
 use crate::err_utils::err;
+use crate::error_catalog::{
+    fill, ATOM_OUT_OF_BOUNDS, ATOM_TOO_LARGE, CONCAT_EXPECTED_ATOM, CONCAT_INVALID_SIZE,
+    IMAGE_EXTERNAL_ATOMS, INVALID_IMAGE, NOT_G1_POINT, NOT_G1_SIZE, NOT_G2_POINT, NOT_G2_SIZE,
+    OUT_OF_MEMORY, PAIR_NOT_G1, PAIR_NOT_G2, SUBSTR_END_OUT_OF_BOUNDS, SUBSTR_EXPECTED_ATOM,
+    SUBSTR_INVALID_BOUNDS, SUBSTR_START_OUT_OF_BOUNDS, TOO_MANY_ATOMS, TOO_MANY_PAIRS,
+};
+use crate::node_map::NodeMap;
+#[cfg(feature = "arena-allocator")]
+use crate::node_storage::ArenaVec;
+use crate::node_storage::NodeStorage;
 use crate::number::{node_from_number, number_from_u8, Number};
 use crate::reduction::EvalErr;
 use chia_bls::{G1Element, G2Element};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
 use std::hash::Hash;
 use std::hash::Hasher;
-
+use std::io;
+use std::io::{Read, Write};
+use std::ops::Deref;
+use std::sync::{Arc, OnceLock};
+
+// the heap limit of allocators for programs run with the LIMIT_HEAP flag. See
+// Allocator::new_with_flags()
+pub const LIMITED_HEAP_SIZE: usize = 500000000;
+
+// the integer NodePtrs are made of. With the large-heap feature, NodePtrs are
+// 64 bits, with 32 bit indices, which lifts the limits on the number of atoms
+// and pairs to what the indices can address. This is meant for tools and test
+// harnesses working with very large trees. The heap is still limited to 4 GiB,
+// and the results of running programs that hit the default limits differ
+#[cfg(not(feature = "large-heap"))]
+type NodePtrRepr = u32;
+#[cfg(feature = "large-heap")]
+type NodePtrRepr = u64;
+
+#[cfg(not(feature = "large-heap"))]
 const MAX_NUM_ATOMS: usize = 62500000;
+#[cfg(not(feature = "large-heap"))]
 const MAX_NUM_PAIRS: usize = 62500000;
+#[cfg(not(feature = "large-heap"))]
 const NODE_PTR_IDX_BITS: u32 = 26;
-const NODE_PTR_IDX_MASK: u32 = (1 << NODE_PTR_IDX_BITS) - 1;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct NodePtr(u32);
+// the highest index is left unused, for FREED
+#[cfg(feature = "large-heap")]
+const MAX_NUM_ATOMS: usize = u32::MAX as usize - 1;
+#[cfg(feature = "large-heap")]
+const MAX_NUM_PAIRS: usize = u32::MAX as usize - 1;
+#[cfg(feature = "large-heap")]
+const NODE_PTR_IDX_BITS: u32 = 32;
+
+const NODE_PTR_IDX_MASK: NodePtrRepr = (1 << NODE_PTR_IDX_BITS) - 1;
+
+// small atoms hold values of up to 26 bits, regardless of the size of NodePtr
+const SMALL_ATOM_MASK: u32 = (1 << 26) - 1;
+
+// the maximum number of decompressed G1 (and G2) points kept alongside the
+// atoms created by new_g1() and new_g2(). Points allocated past this limit are
+// decompressed every time they're used, like before
+const MAX_NATIVE_POINTS: usize = 65536;
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(
+    not(feature = "debug-allocator"),
+    derive(PartialEq, Eq, PartialOrd, Ord, Hash)
+)]
+pub struct NodePtr(
+    NodePtrRepr,
+    // with the debug-allocator feature, the generation the node was allocated
+    // in, or 0 for small atoms. See Allocator::check_node()
+    #[cfg(feature = "debug-allocator")] u32,
+);
+
+// the generation is not part of the node's identity, NodePtrs compare the
+// same with and without the debug-allocator feature
+#[cfg(feature = "debug-allocator")]
+impl PartialEq for NodePtr {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+#[cfg(feature = "debug-allocator")]
+impl Eq for NodePtr {}
+
+#[cfg(feature = "debug-allocator")]
+impl PartialOrd for NodePtr {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(feature = "debug-allocator")]
+impl Ord for NodePtr {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+#[cfg(feature = "debug-allocator")]
+impl std::hash::Hash for NodePtr {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+// With the debug-allocator feature, every allocator, and every restored
+// checkpoint, starts a new generation of nodes. The tags are unique across
+// allocators, so a NodePtr from another allocator is detected too
+#[cfg(feature = "debug-allocator")]
+static NEXT_GENERATION: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(1);
+
+#[cfg(feature = "debug-allocator")]
+fn next_generation() -> u32 {
+    NEXT_GENERATION.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+}
+
+#[cfg(feature = "debug-allocator")]
+#[derive(Debug, Clone, Copy)]
+struct Generation {
+    tag: u32,
+    // the number of pairs and atoms when the generation started
+    pairs: usize,
+    atoms: usize,
+}
+
+// With the poison-allocator feature, every atom buffer is followed by these
+// bytes, which are verified whenever the atom is accessed
+#[cfg(feature = "poison-allocator")]
+const CANARY: [u8; 4] = [0xca, 0xfe, 0xba, 0xbe];
+
+// With the poison-allocator feature, restore_checkpoint() overwrites freed
+// heap bytes with this value
+#[cfg(feature = "poison-allocator")]
+const POISON: u8 = 0xdb;
+
+// With the poison-allocator feature, atoms and pairs freed by
+// restore_checkpoint() are marked with this value
+#[cfg(feature = "poison-allocator")]
+const FREED: u32 = u32::MAX;
+
+// and pairs freed by restore_checkpoint() have children with this value
+#[cfg(feature = "poison-allocator")]
+const FREED_NODE: NodePtrRepr = NodePtrRepr::MAX;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-enum ObjectType {
+pub(crate) enum ObjectType {
     // The low bits form an index into the pair_vec
     Pair,
     // The low bits form an index into the atom_vec
@@ -29,7 +161,20 @@ impl NodePtr {
 
     const fn new(object_type: ObjectType, index: usize) -> Self {
         debug_assert!(index <= NODE_PTR_IDX_MASK as usize);
-        NodePtr(((object_type as u32) << NODE_PTR_IDX_BITS) | (index as u32))
+        Self::raw(((object_type as NodePtrRepr) << NODE_PTR_IDX_BITS) | (index as NodePtrRepr))
+    }
+
+    // a NodePtr without a generation
+    const fn raw(val: NodePtrRepr) -> Self {
+        #[cfg(feature = "debug-allocator")]
+        return NodePtr(val, 0);
+        #[cfg(not(feature = "debug-allocator"))]
+        NodePtr(val)
+    }
+
+    #[cfg(feature = "debug-allocator")]
+    const fn with_generation(self, tag: u32) -> Self {
+        NodePtr(self.0, tag)
     }
 
     pub fn is_atom(self) -> bool {
@@ -43,7 +188,7 @@ impl NodePtr {
         self.object_type() == ObjectType::Pair
     }
 
-    fn object_type(self) -> ObjectType {
+    pub(crate) fn object_type(self) -> ObjectType {
         match self.0 >> NODE_PTR_IDX_BITS {
             0 => ObjectType::Pair,
             1 => ObjectType::Bytes,
@@ -52,8 +197,10 @@ impl NodePtr {
         }
     }
 
-    fn index(self) -> u32 {
-        self.0 & NODE_PTR_IDX_MASK
+    // the cast is only needed with large-heap
+    #[allow(clippy::unnecessary_cast)]
+    pub(crate) fn index(self) -> u32 {
+        (self.0 & NODE_PTR_IDX_MASK) as u32
     }
 }
 
@@ -69,15 +216,166 @@ pub enum SExp {
     Pair(NodePtr, NodePtr),
 }
 
+// An iterator over the nodes reachable from a root, returned by
+// Allocator::iter_reachable(). Nodes are visited depth-first, a pair before
+// its first and then its rest, and each node is yielded once, even if it's
+// shared by several pairs.
+pub struct Reachable<'a> {
+    allocator: &'a Allocator,
+    stack: Vec<NodePtr>,
+    seen: NodeMap<()>,
+}
+
+impl Iterator for Reachable<'_> {
+    type Item = NodePtr;
+
+    fn next(&mut self) -> Option<NodePtr> {
+        loop {
+            let node = self.stack.pop()?;
+            if self.seen.insert(node, ()).is_some() {
+                continue;
+            }
+            if let SExp::Pair(first, rest) = self.allocator.sexp(node) {
+                self.stack.push(rest);
+                self.stack.push(first);
+            }
+            return Some(node);
+        }
+    }
+}
+
+// The kind of value an atom was created as. Atoms created by one of the typed
+// constructors (new_number(), new_g1(), new_g2()) are tagged with their kind,
+// which means their bytes are known to be a valid representation of it.
+// Atoms created from raw bytes (new_atom(), new_substr(), new_concat() and
+// deserialization) are tagged as Bytes, and have to be validated before being
+// interpreted as anything else. Small atoms are always canonical integers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AtomKind {
+    Bytes,
+    Int,
+    G1,
+    G2,
+}
+
+// atoms of up to this many bytes are stored inline, in their atom_vec entry,
+// rather than in u8_vec. Most atoms in conditions (amounts, opcodes, short
+// memos) are this small, and accessing them doesn't touch the heap
+const INLINE_ATOM_SIZE: usize = 8;
+
+// the value of AtomBuf::len for atoms stored in u8_vec
+const HEAP_ATOM: u8 = u8::MAX;
+
+// the value of AtomBuf::len for atoms referring to an external buffer. See
+// new_atom_shared()
+const EXTERNAL_ATOM: u8 = u8::MAX - 1;
+
+// the value of AtomBuf::len for atoms referring to a rope. See new_concat()
+const ROPE_ATOM: u8 = u8::MAX - 2;
+
+// concatenations of at least this many bytes are created as ropes, rather
+// than by copying the bytes of the atoms being concatenated
+const MIN_ROPE_SIZE: usize = 1024;
+
+// ropes may refer to ropes (that haven't been flattened) nested up to this
+// deep. Deeper ropes are flattened when they're concatenated, which bounds the
+// work of flattening a rope to a few steps per byte
+const MAX_ROPE_DEPTH: u8 = 8;
+
+// an atom_vec entry. For atoms stored inline, the first len bytes of buf are
+// the atom. For atoms stored in u8_vec, len is HEAP_ATOM and buf holds the
+// start and end offsets of the atom. Atoms referring to external buffers are
+// like heap atoms, with len EXTERNAL_ATOM and offsets into the external
+// buffers, and so are atoms referring to ropes, with len ROPE_ATOM. Keeping
+// this in 10 bytes (rather than using an enum) keeps atom_vec compact
 #[derive(Clone, Copy, Debug)]
 struct AtomBuf {
-    start: u32,
-    end: u32,
+    buf: [u8; INLINE_ATOM_SIZE],
+    len: u8,
+    kind: AtomKind,
 }
 
 impl AtomBuf {
+    #[inline(always)]
+    fn with_offsets(start: u32, end: u32, len: u8, kind: AtomKind) -> Self {
+        let mut buf = [0_u8; INLINE_ATOM_SIZE];
+        buf[..4].copy_from_slice(&start.to_ne_bytes());
+        buf[4..].copy_from_slice(&end.to_ne_bytes());
+        Self { buf, len, kind }
+    }
+
+    #[inline(always)]
+    fn heap(start: u32, end: u32, kind: AtomKind) -> Self {
+        Self::with_offsets(start, end, HEAP_ATOM, kind)
+    }
+
+    #[inline(always)]
+    fn external(start: u32, end: u32, kind: AtomKind) -> Self {
+        Self::with_offsets(start, end, EXTERNAL_ATOM, kind)
+    }
+
+    #[inline(always)]
+    fn rope(start: u32, end: u32, kind: AtomKind) -> Self {
+        Self::with_offsets(start, end, ROPE_ATOM, kind)
+    }
+
+    #[inline(always)]
+    fn offsets(&self) -> (u32, u32) {
+        let start = u32::from_ne_bytes(self.buf[..4].try_into().unwrap());
+        let end = u32::from_ne_bytes(self.buf[4..].try_into().unwrap());
+        (start, end)
+    }
+
+    #[inline(always)]
+    fn inline(v: &[u8], kind: AtomKind) -> Self {
+        debug_assert!(v.len() <= INLINE_ATOM_SIZE);
+        let mut buf = [0_u8; INLINE_ATOM_SIZE];
+        buf[..v.len()].copy_from_slice(v);
+        Self {
+            buf,
+            len: v.len() as u8,
+            kind,
+        }
+    }
+
+    // the range of u8_vec holding the atom, or None if it's stored inline
+    #[inline(always)]
+    fn range(&self) -> Option<(u32, u32)> {
+        if self.len != HEAP_ATOM {
+            return None;
+        }
+        Some(self.offsets())
+    }
+
+    // the range of the external buffers holding the atom, or None if it's
+    // not an external atom
+    #[inline(always)]
+    fn external_range(&self) -> Option<(u32, u32)> {
+        if self.len != EXTERNAL_ATOM {
+            return None;
+        }
+        Some(self.offsets())
+    }
+
+    // the range of the ropes' address space holding the atom, or None if it's
+    // not a rope atom
+    #[inline(always)]
+    fn rope_range(&self) -> Option<(u32, u32)> {
+        if self.len != ROPE_ATOM {
+            return None;
+        }
+        Some(self.offsets())
+    }
+
+    #[inline(always)]
     pub fn len(&self) -> usize {
-        (self.end - self.start) as usize
+        match self.len {
+            HEAP_ATOM | EXTERNAL_ATOM | ROPE_ATOM => {
+                let (start, end) = self.offsets();
+                (end - start) as usize
+            }
+            len => len as usize,
+        }
     }
 }
 
@@ -87,14 +385,124 @@ pub struct IntPair {
     rest: NodePtr,
 }
 
+// the resource usage of an allocator, see Allocator::stats(). The counts are
+// the ones the allocator's limits apply to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AllocatorStats {
+    pub pairs: usize,
+    // including small atoms, and the nil and one atoms every allocator
+    // starts out with
+    pub atoms: usize,
+    // the number of bytes counting towards the heap limit
+    pub atom_bytes: usize,
+
+    // the highest values of the above since the allocator was created.
+    // Restoring a checkpoint lowers the current values, but not these
+    pub max_pairs: usize,
+    pub max_atoms: usize,
+    pub max_atom_bytes: usize,
+}
+
 // this represents a specific (former) state of an allocator. This can be used
 // to restore an allocator to a previous state. It cannot be used to re-create
 // the state from some other allocator.
 pub struct Checkpoint {
     u8s: usize,
+    // the part of u8s stored inline
+    inline_u8s: usize,
+    // the part of u8s referring to external buffers
+    external_u8s: usize,
+    // the part of u8s held by ropes
+    rope_u8s: usize,
     pairs: usize,
     atoms: usize,
     small_atoms: usize,
+
+    // the actual sizes of the vectors. With the poison-allocator feature,
+    // these include freed entries and canaries, and differ from the sizes
+    // above
+    #[cfg(feature = "poison-allocator")]
+    real_u8s: usize,
+    #[cfg(feature = "poison-allocator")]
+    real_pairs: usize,
+    #[cfg(feature = "poison-allocator")]
+    real_atoms: usize,
+
+    // the generation the checkpoint was created in
+    #[cfg(feature = "debug-allocator")]
+    generation: u32,
+}
+
+impl Checkpoint {
+    // the number of pair_vec and atom_vec entries when the checkpoint was
+    // created. NodePtrs with higher indices are invalidated by restoring it
+    pub(crate) fn node_counts(&self) -> (usize, usize) {
+        #[cfg(not(feature = "poison-allocator"))]
+        return (self.pairs, self.atoms);
+        #[cfg(feature = "poison-allocator")]
+        return (self.real_pairs, self.real_atoms);
+    }
+}
+
+// maps the NodePtrs kept by Allocator::compact() to their new values
+#[derive(Debug, Clone)]
+pub struct RemapTable {
+    pairs: Vec<Option<NodePtr>>,
+    atoms: Vec<Option<NodePtr>>,
+}
+
+impl RemapTable {
+    // the new NodePtr of a node from before compact(), or None if it wasn't
+    // reachable from the roots and was dropped. Small atoms are never moved
+    pub fn get(&self, node: NodePtr) -> Option<NodePtr> {
+        let idx = node.index() as usize;
+        match node.object_type() {
+            ObjectType::Pair => self.pairs.get(idx).copied().flatten(),
+            ObjectType::Bytes => self.atoms.get(idx).copied().flatten(),
+            ObjectType::SmallAtom => Some(node),
+        }
+    }
+}
+
+// The poison-allocator feature is meant for tests and fuzzing. Instead of
+// truncating the heap, restore_checkpoint() poisons the freed bytes, atoms
+// and pairs, so they are never reused. Any access through a stale NodePtr
+// (one created after the checkpoint) panics rather than silently referring
+// to a node allocated later. Every atom buffer is also followed by canary
+// bytes, to catch writes past the end of an atom. None of the extra entries
+// count towards the heap, atom or pair limits, so programs behave the same
+// with and without the feature.
+#[cfg(feature = "poison-allocator")]
+#[derive(Debug, Default, Clone, Copy)]
+struct PoisonState {
+    // the number of bytes in u8_vec that are canaries or freed
+    overhead: usize,
+    dead_atoms: usize,
+    dead_pairs: usize,
+}
+
+// returns the bytes in the range of the external buffers' address space
+#[inline(always)]
+fn external_bytes(external: &[(u32, Arc<[u8]>)], start: u32, end: u32) -> &[u8] {
+    let idx = external.partition_point(|(base, _)| *base <= start) - 1;
+    let (base, buf) = &external[idx];
+    &buf[(start - base) as usize..(end - base) as usize]
+}
+
+// an atom created by new_concat() that refers to the atoms it's the
+// concatenation of, rather than holding a copy of their bytes. The bytes are
+// copied the first time the atom is read. Ropes are laid out one after the
+// other in a 32 bit address space, each starting at base. Substrings of a rope
+// refer to a range of it, like substrings of heap atoms
+#[derive(Clone, Debug)]
+struct Rope {
+    base: u32,
+    len: u32,
+    // the non-empty atoms the rope is the concatenation of
+    parts: Box<[NodePtr]>,
+    // the deepest nesting of unflattened ropes in parts, plus one
+    depth: u8,
+    bytes: OnceLock<Box<[u8]>>,
 }
 
 pub enum NodeVisitor<'a> {
@@ -136,7 +544,13 @@ impl<'a> Borrow<[u8]> for Atom<'a> {
     }
 }
 
-#[derive(Debug)]
+// the storage of pair_vec and atom_vec. See NodeStorage
+#[cfg(not(feature = "arena-allocator"))]
+type NodeVec<T> = Vec<T>;
+#[cfg(feature = "arena-allocator")]
+type NodeVec<T> = ArenaVec<T>;
+
+#[derive(Debug, Clone)]
 pub struct Allocator {
     // this is effectively a grow-only stack where atoms are allocated. Atoms
     // are immutable, so once they are created, they will stay around until the
@@ -144,19 +558,76 @@ pub struct Allocator {
     u8_vec: Vec<u8>,
 
     // storage for all pairs (positive indices)
-    pair_vec: Vec<IntPair>,
+    pair_vec: NodeVec<IntPair>,
 
     // storage for all atoms (negative indices).
     // node index -1 refers to index 0 in this vector, -2 refers to 1 and so
     // on.
-    atom_vec: Vec<AtomBuf>,
+    atom_vec: NodeVec<AtomBuf>,
+
+    // the number of bytes of atoms stored inline (see INLINE_ATOM_SIZE) that
+    // count towards the heap limit. Inline atoms take up the same amount of
+    // the heap limit they would if they were stored in u8_vec, so the limit
+    // behaves as it did before they were introduced
+    inline_heap: usize,
 
     // the atom_vec may not grow past this
     heap_limit: usize,
 
+    // no single atom may be larger than this. See set_atom_limit()
+    atom_limit: usize,
+
     // the number of small atoms we've allocated. We keep track of these to ensure the limit on the
     // number of atoms is identical to what it was before the small-atom optimization
     small_atoms: usize,
+
+    // buffers shared with the caller, which atoms created by
+    // new_atom_shared() refer to instead of holding a copy of their bytes.
+    // The buffers are laid out one after the other in a 32 bit address space,
+    // each along with the offset of its first byte. Buffers are kept until the
+    // allocator is dropped
+    external: Vec<(u32, Arc<[u8]>)>,
+
+    // the number of bytes of atoms referring to external buffers. Like inline
+    // atoms, these count towards the heap limit as if they were stored in
+    // u8_vec
+    external_heap: usize,
+
+    // see Rope. The ropes created since the last checkpoint was restored are
+    // dropped along with their atoms
+    ropes: Vec<Rope>,
+
+    // the total length of the ropes. Like inline atoms, these count towards
+    // the heap limit as if they were stored in u8_vec. Substrings of ropes
+    // don't count towards the heap limit
+    rope_heap: usize,
+
+    // the highest pair count, atom count and heap usage before restoring a
+    // checkpoint. Usage only decreases when restoring a checkpoint, so the
+    // high-water marks are the higher of these and the current usage
+    peak: (usize, usize, usize),
+
+    // the decompressed points of atoms created by new_g1() and new_g2(). This
+    // saves BLS operators from decompressing (and validating) the same point
+    // every time it's passed to them. These don't count towards the heap
+    // limit, and are not part of the image written by save()
+    g1_points: NodeMap<G1Element>,
+    g2_points: NodeMap<G2Element>,
+
+    // when interning is enabled (see set_interning()), the atoms created by
+    // new_atom(), keyed by the hash of their bytes. Not part of the image
+    // written by save()
+    interned: Option<HashMap<u64, NodePtr>>,
+
+    #[cfg(feature = "poison-allocator")]
+    poison: PoisonState,
+
+    // the live generations, oldest first, and the tags of the generations
+    // invalidated by restoring a checkpoint. See check_node()
+    #[cfg(feature = "debug-allocator")]
+    generations: Vec<Generation>,
+    #[cfg(feature = "debug-allocator")]
+    dead_generations: Vec<u32>,
 }
 
 impl Default for Allocator {
@@ -165,6 +636,35 @@ impl Default for Allocator {
     }
 }
 
+// An allocator that can no longer be modified, which can be shared between
+// threads, e.g. to hash or serialize parts of the result of a program in
+// parallel. Cloning it is cheap, all clones refer to the same allocator.
+// Create one with Allocator::freeze()
+#[derive(Debug, Clone)]
+pub struct FrozenAllocator(Arc<Allocator>);
+
+impl Deref for FrozenAllocator {
+    type Target = Allocator;
+
+    fn deref(&self) -> &Allocator {
+        &self.0
+    }
+}
+
+impl FrozenAllocator {
+    // returns a copy of the allocator that can be modified. All NodePtrs
+    // into this allocator refer to the same nodes in the copy
+    pub fn thaw(&self) -> Allocator {
+        (*self.0).clone()
+    }
+
+    // returns the allocator if this is the only reference to it, without
+    // copying it
+    pub fn try_unwrap(self) -> Result<Allocator, Self> {
+        Arc::try_unwrap(self.0).map_err(Self)
+    }
+}
+
 pub fn fits_in_small_atom(v: &[u8]) -> Option<u32> {
     if !v.is_empty()
         && (v.len() > 4
@@ -211,19 +711,52 @@ impl Allocator {
         Self::new_limited(u32::MAX as usize)
     }
 
+    // returns an allocator suitable for running programs with the specified
+    // ChiaDialect flags. With LIMIT_HEAP, the heap is limited to
+    // LIMITED_HEAP_SIZE bytes. Running out of heap (or atoms or pairs) fails
+    // the program with "out of memory" (or "too many atoms" and "too many
+    // pairs"), rather than aborting the process
+    pub fn new_with_flags(flags: u32) -> Self {
+        if (flags & LIMIT_HEAP) != 0 {
+            Self::new_limited(LIMITED_HEAP_SIZE)
+        } else {
+            Self::new()
+        }
+    }
+
     pub fn new_limited(heap_limit: usize) -> Self {
         // we have a maximum of 4 GiB heap, because pointers are 32 bit unsigned
         assert!(heap_limit <= u32::MAX as usize);
 
         let mut r = Self {
             u8_vec: Vec::new(),
-            pair_vec: Vec::new(),
-            atom_vec: Vec::new(),
+            pair_vec: NodeVec::default(),
+            atom_vec: NodeVec::default(),
+            inline_heap: 0,
             // subtract 1 to compensate for the one() we used to allocate unconfitionally
             heap_limit: heap_limit - 1,
+            atom_limit: usize::MAX,
             // initialize this to 2 to behave as if we had allocated atoms for
             // nil() and one(), like we used to
             small_atoms: 2,
+            external: Vec::new(),
+            external_heap: 0,
+            ropes: Vec::new(),
+            rope_heap: 0,
+            peak: (0, 0, 0),
+            g1_points: NodeMap::new(),
+            g2_points: NodeMap::new(),
+            interned: None,
+            #[cfg(feature = "poison-allocator")]
+            poison: PoisonState::default(),
+            #[cfg(feature = "debug-allocator")]
+            generations: vec![Generation {
+                tag: next_generation(),
+                pairs: 0,
+                atoms: 0,
+            }],
+            #[cfg(feature = "debug-allocator")]
+            dead_generations: Vec::new(),
         };
         r.u8_vec.reserve(1024 * 1024);
         r.atom_vec.reserve(256);
@@ -231,15 +764,61 @@ impl Allocator {
         r
     }
 
+    // like new(), but with room for the specified number of atoms, atom bytes
+    // and pairs, to avoid growing the buffers while e.g. deserializing a large
+    // program. A serialized program of n bytes has at most n atoms, n atom
+    // bytes and n / 2 pairs. The hints are capped at the allocator's limits
+    pub fn with_capacity(atoms: usize, atom_bytes: usize, pairs: usize) -> Self {
+        let mut r = Self::new();
+        r.u8_vec.reserve(atom_bytes.min(r.heap_limit));
+        r.atom_vec.reserve(atoms.min(MAX_NUM_ATOMS));
+        r.pair_vec.reserve(pairs.min(MAX_NUM_PAIRS));
+        r
+    }
+
     // create a checkpoint for the current state of the allocator. This can be
     // used to go back to an earlier allocator state by passing the Checkpoint
     // to restore_checkpoint().
     pub fn checkpoint(&self) -> Checkpoint {
         Checkpoint {
-            u8s: self.u8_vec.len(),
-            pairs: self.pair_vec.len(),
-            atoms: self.atom_vec.len(),
+            u8s: self.heap_used(),
+            inline_u8s: self.inline_heap,
+            external_u8s: self.external_heap,
+            rope_u8s: self.rope_heap,
+            pairs: self.pairs_used(),
+            atoms: self.atoms_used(),
             small_atoms: self.small_atoms,
+            #[cfg(feature = "poison-allocator")]
+            real_u8s: self.u8_vec.len(),
+            #[cfg(feature = "poison-allocator")]
+            real_pairs: self.pair_vec.len(),
+            #[cfg(feature = "poison-allocator")]
+            real_atoms: self.atom_vec.len(),
+            #[cfg(feature = "debug-allocator")]
+            generation: self.generation(),
+        }
+    }
+
+    // limits the size of any single atom allocated from now on, e.g. the
+    // result of concat. Allocating a larger atom fails with "atom too large",
+    // even if the heap limit and cost would allow it. This is meant for
+    // embedders with constrained memory. The limit is not part of the image
+    // written by save()
+    pub fn set_atom_limit(&mut self, limit: usize) {
+        self.atom_limit = limit;
+    }
+
+    // when enabled, new_atom() returns the existing NodePtr when an atom with
+    // the same bytes was already created by new_atom(), instead of storing
+    // another copy. The returned atom still counts towards the atom and heap
+    // limits as if it was a new one, so the limits are hit at the same point
+    // either way. Since atoms are immutable, this is only observable by
+    // comparing NodePtrs. Interning is off by default
+    pub fn set_interning(&mut self, enabled: bool) {
+        if !enabled {
+            self.interned = None;
+        } else if self.interned.is_none() {
+            self.interned = Some(HashMap::new());
         }
     }
 
@@ -248,65 +827,679 @@ impl Allocator {
         // a state that has already been "long-jumped" passed (via another
         // restore to an earlier state). You can only restore backwards in time,
         // not forwards.
-        assert!(self.u8_vec.len() >= cp.u8s);
-        assert!(self.pair_vec.len() >= cp.pairs);
-        assert!(self.atom_vec.len() >= cp.atoms);
-        self.u8_vec.truncate(cp.u8s);
-        self.pair_vec.truncate(cp.pairs);
-        self.atom_vec.truncate(cp.atoms);
+        assert!(self.heap_used() >= cp.u8s);
+        assert!(self.pairs_used() >= cp.pairs);
+        assert!(self.atoms_used() >= cp.atoms);
+        let stats = self.stats();
+        self.peak = (stats.max_pairs, stats.max_atoms, stats.max_atom_bytes);
+        #[cfg(not(feature = "poison-allocator"))]
+        {
+            self.u8_vec
+                .truncate(cp.u8s - cp.inline_u8s - cp.external_u8s - cp.rope_u8s);
+            self.pair_vec.truncate(cp.pairs);
+            self.atom_vec.truncate(cp.atoms);
+        }
+        #[cfg(feature = "poison-allocator")]
+        {
+            self.u8_vec[cp.real_u8s..].fill(POISON);
+            for idx in cp.real_atoms..self.atom_vec.len() {
+                let atom = &mut self.atom_vec[idx];
+                *atom = AtomBuf::heap(FREED, FREED, atom.kind);
+            }
+            for idx in cp.real_pairs..self.pair_vec.len() {
+                let pair = &mut self.pair_vec[idx];
+                pair.first = NodePtr::raw(FREED_NODE);
+                pair.rest = NodePtr::raw(FREED_NODE);
+            }
+            self.poison = PoisonState {
+                overhead: self.u8_vec.len()
+                    - (cp.u8s - cp.inline_u8s - cp.external_u8s - cp.rope_u8s),
+                dead_atoms: self.atom_vec.len() - cp.atoms,
+                dead_pairs: self.pair_vec.len() - cp.pairs,
+            };
+        }
+        self.inline_heap = cp.inline_u8s;
+        self.external_heap = cp.external_u8s;
+        let ropes = self
+            .ropes
+            .partition_point(|rope| (rope.base as usize) < cp.rope_u8s);
+        self.ropes.truncate(ropes);
+        self.rope_heap = cp.rope_u8s;
         self.small_atoms = cp.small_atoms;
+        self.g1_points.restore_checkpoint(cp);
+        self.g2_points.restore_checkpoint(cp);
+        if let Some(interned) = &mut self.interned {
+            interned.retain(|_, node| (node.index() as usize) < cp.atoms);
+        }
+        #[cfg(feature = "debug-allocator")]
+        {
+            // the generations started after the checkpoint are gone, and the
+            // nodes allocated after it in the checkpoint's generation are
+            // told apart by their index, which is past the start of the new
+            // generation
+            let live = self.generations.partition_point(|g| g.tag <= cp.generation);
+            self.dead_generations
+                .extend(self.generations.drain(live..).map(|g| g.tag));
+            let (pairs, atoms) = cp.node_counts();
+            self.generations.push(Generation {
+                tag: next_generation(),
+                pairs,
+                atoms,
+            });
+        }
     }
 
-    pub fn new_atom(&mut self, v: &[u8]) -> Result<NodePtr, EvalErr> {
-        let start = self.u8_vec.len() as u32;
-        if (self.heap_limit - start as usize) < v.len() {
-            return err(self.nil(), "out of memory");
+    pub fn freeze(self) -> FrozenAllocator {
+        FrozenAllocator(Arc::new(self))
+    }
+
+    // returns the allocator to the state it was created in, so it can be
+    // reused for another evaluation without reallocating its buffers. The heap
+    // and atom limits are kept. Like restoring a checkpoint, this invalidates
+    // all NodePtrs into the allocator, except nil() and one()
+    pub fn reset(&mut self) {
+        let cp = Checkpoint {
+            u8s: 0,
+            inline_u8s: 0,
+            external_u8s: 0,
+            rope_u8s: 0,
+            pairs: 0,
+            atoms: 0,
+            small_atoms: 2,
+            #[cfg(feature = "poison-allocator")]
+            real_u8s: 0,
+            #[cfg(feature = "poison-allocator")]
+            real_pairs: 0,
+            #[cfg(feature = "poison-allocator")]
+            real_atoms: 0,
+            // older than any generation
+            #[cfg(feature = "debug-allocator")]
+            generation: 0,
+        };
+        self.restore_checkpoint(&cp);
+        self.external.clear();
+        self.peak = (0, 0, 0);
+    }
+
+    // drops the pairs and atoms that aren't reachable from the roots, and
+    // moves the remaining ones to the front, in the order they were allocated.
+    // Atoms sharing bytes (like substrings) keep sharing them. Ropes are
+    // flattened into the heap. This invalidates all NodePtrs into the
+    // allocator, and all checkpoints. The returned table maps the kept nodes
+    // to their new NodePtrs
+    pub fn compact(&mut self, roots: &[NodePtr]) -> RemapTable {
+        // mark
+        let mut live_pairs = vec![false; self.pair_vec.len()];
+        let mut live_atoms = vec![false; self.atom_vec.len()];
+        let mut stack: Vec<NodePtr> = roots.to_vec();
+        while let Some(node) = stack.pop() {
+            let idx = node.index() as usize;
+            match node.object_type() {
+                ObjectType::Pair => {
+                    if !live_pairs[idx] {
+                        live_pairs[idx] = true;
+                        let pair = self.int_pair(node);
+                        stack.push(pair.first);
+                        stack.push(pair.rest);
+                    }
+                }
+                ObjectType::Bytes => {
+                    self.atom_buf(node);
+                    live_atoms[idx] = true;
+                }
+                ObjectType::SmallAtom => {}
+            }
+        }
+
+        // the ranges of the heap (0), the ropes (1) and the external buffers
+        // (2) referred to by live atoms, merged where they overlap
+        let mut ranges: Vec<(u8, u32, u32)> = live_atoms
+            .iter()
+            .zip(self.atom_vec.entries())
+            .filter(|(live, _)| **live)
+            .filter_map(|(_, atom)| {
+                if let Some((start, end)) = atom.range() {
+                    Some((0, start, end))
+                } else if let Some((start, end)) = atom.rope_range() {
+                    Some((1, start, end))
+                } else {
+                    atom.external_range().map(|(start, end)| (2, start, end))
+                }
+            })
+            .collect();
+        ranges.sort_unstable();
+        let mut merged = Vec::<(u8, u32, u32)>::new();
+        for (space, start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if last.0 == space && start < last.2 => last.2 = last.2.max(end),
+                _ => merged.push((space, start, end)),
+            }
+        }
+
+        // copy the heap and rope ranges to the new heap, recording where each
+        // one ended up
+        let mut u8_vec = Vec::<u8>::new();
+        let mut offsets = Vec::<u32>::with_capacity(merged.len());
+        let mut external_heap = 0;
+        #[cfg(feature = "poison-allocator")]
+        let mut overhead = 0;
+        for (space, start, end) in &merged {
+            offsets.push(u8_vec.len() as u32);
+            match space {
+                0 => u8_vec.extend_from_slice(&self.u8_vec[*start as usize..*end as usize]),
+                1 => u8_vec.extend_from_slice(self.rope_bytes(*start, *end)),
+                _ => {
+                    external_heap += (end - start) as usize;
+                    continue;
+                }
+            }
+            #[cfg(feature = "poison-allocator")]
+            {
+                u8_vec.extend_from_slice(&CANARY);
+                overhead += CANARY.len();
+            }
+        }
+        let new_start = |space: u8, start: u32| {
+            let idx = merged.partition_point(|r| (r.0, r.1) <= (space, start)) - 1;
+            offsets[idx] + start - merged[idx].1
+        };
+
+        let stats = self.stats();
+        self.peak = (stats.max_pairs, stats.max_atoms, stats.max_atom_bytes);
+        #[cfg(feature = "debug-allocator")]
+        {
+            self.dead_generations
+                .extend(self.generations.drain(..).map(|g| g.tag));
+            self.generations.push(Generation {
+                tag: next_generation(),
+                pairs: 0,
+                atoms: 0,
+            });
+        }
+
+        let mut table = RemapTable {
+            pairs: vec![None; self.pair_vec.len()],
+            atoms: vec![None; self.atom_vec.len()],
+        };
+        let mut atom_vec = NodeVec::<AtomBuf>::default();
+        let mut inline_heap = 0;
+        for (idx, atom) in self.atom_vec.entries().enumerate() {
+            if !live_atoms[idx] {
+                continue;
+            }
+            let moved = match (atom.range(), atom.rope_range()) {
+                (Some((start, end)), _) => Some((new_start(0, start), end - start)),
+                (_, Some((start, end))) => Some((new_start(1, start), end - start)),
+                _ => None,
+            };
+            let atom = if let Some((start, len)) = moved {
+                AtomBuf::heap(start, start + len, atom.kind)
+            } else {
+                if atom.external_range().is_none() {
+                    inline_heap += atom.len();
+                }
+                *atom
+            };
+            table.atoms[idx] = Some(self.node_ptr(ObjectType::Bytes, atom_vec.len()));
+            atom_vec.push(atom);
+        }
+
+        // pairs only refer to nodes allocated before them, so their children
+        // have already been remapped
+        let mut pair_vec = NodeVec::<IntPair>::default();
+        for (idx, pair) in self.pair_vec.entries().enumerate() {
+            if !live_pairs[idx] {
+                continue;
+            }
+            let new_pair = IntPair {
+                first: table.get(pair.first).expect("live node"),
+                rest: table.get(pair.rest).expect("live node"),
+            };
+            table.pairs[idx] = Some(self.node_ptr(ObjectType::Pair, pair_vec.len()));
+            pair_vec.push(new_pair);
+        }
+
+        let mut g1_points = NodeMap::new();
+        for (node, point) in self.g1_points.iter() {
+            if let Some(node) = table.get(node) {
+                g1_points.insert(node, point.clone());
+            }
+        }
+        let mut g2_points = NodeMap::new();
+        for (node, point) in self.g2_points.iter() {
+            if let Some(node) = table.get(node) {
+                g2_points.insert(node, point.clone());
+            }
+        }
+
+        self.u8_vec = u8_vec;
+        self.atom_vec = atom_vec;
+        self.pair_vec = pair_vec;
+        self.inline_heap = inline_heap;
+        self.external_heap = external_heap;
+        self.ropes.clear();
+        self.rope_heap = 0;
+        self.g1_points = g1_points;
+        self.g2_points = g2_points;
+        if let Some(interned) = &mut self.interned {
+            interned.retain(|_, node| match table.get(*node) {
+                Some(new_node) => {
+                    *node = new_node;
+                    true
+                }
+                None => false,
+            });
+        }
+        #[cfg(feature = "poison-allocator")]
+        {
+            self.poison = PoisonState {
+                overhead,
+                dead_atoms: 0,
+                dead_pairs: 0,
+            };
+        }
+        table
+    }
+
+    // the number of heap bytes, atoms and pairs counting towards the limits
+    #[inline(always)]
+    fn heap_used(&self) -> usize {
+        let heap = self.u8_vec.len() + self.inline_heap + self.external_heap + self.rope_heap;
+        #[cfg(feature = "poison-allocator")]
+        return heap - self.poison.overhead;
+        #[cfg(not(feature = "poison-allocator"))]
+        heap
+    }
+
+    #[inline(always)]
+    fn atoms_used(&self) -> usize {
+        #[cfg(feature = "poison-allocator")]
+        return self.atom_vec.len() - self.poison.dead_atoms;
+        #[cfg(not(feature = "poison-allocator"))]
+        self.atom_vec.len()
+    }
+
+    #[inline(always)]
+    fn pairs_used(&self) -> usize {
+        #[cfg(feature = "poison-allocator")]
+        return self.pair_vec.len() - self.poison.dead_pairs;
+        #[cfg(not(feature = "poison-allocator"))]
+        self.pair_vec.len()
+    }
+
+    #[cfg(feature = "debug-allocator")]
+    fn generation(&self) -> u32 {
+        self.generations.last().expect("generations").tag
+    }
+
+    // tags a node loaded from an image with the generation it was allocated
+    // in, which is the latest one started before it
+    #[cfg(feature = "debug-allocator")]
+    fn tag_node(&self, node: NodePtr) -> NodePtr {
+        #[cfg(feature = "poison-allocator")]
+        if node.0 == FREED_NODE {
+            return node;
+        }
+        let idx = node.index() as usize;
+        let generation = match node.object_type() {
+            ObjectType::Pair => self.generations.iter().rfind(|g| g.pairs <= idx),
+            ObjectType::Bytes => self.generations.iter().rfind(|g| g.atoms <= idx),
+            ObjectType::SmallAtom => None,
+        };
+        match generation {
+            Some(g) => node.with_generation(g.tag),
+            None => node,
+        }
+    }
+
+    // a NodePtr to the pair or atom just allocated at idx
+    #[inline(always)]
+    fn node_ptr(&self, object_type: ObjectType, idx: usize) -> NodePtr {
+        let node = NodePtr::new(object_type, idx);
+        #[cfg(feature = "debug-allocator")]
+        return node.with_generation(self.generation());
+        #[cfg(not(feature = "debug-allocator"))]
+        node
+    }
+
+    // with the debug-allocator feature, panics if the pair or atom was
+    // invalidated by restoring a checkpoint (or resetting the allocator), or
+    // was allocated by another allocator. Without the feature, stale NodePtrs
+    // silently refer to whatever node was allocated in their place
+    #[inline(always)]
+    fn check_node(&self, _node: NodePtr) {
+        #[cfg(feature = "debug-allocator")]
+        {
+            let node = _node;
+            let Some(pos) = self.generations.iter().rposition(|g| g.tag == node.1) else {
+                if self.dead_generations.contains(&node.1) {
+                    panic!("{node:?} used after being freed by restore_checkpoint() or reset()");
+                }
+                panic!("{node:?} belongs to a different allocator");
+            };
+            // a node is only valid until the start of the next generation
+            if let Some(next) = self.generations.get(pos + 1) {
+                let end = match node.object_type() {
+                    ObjectType::Pair => next.pairs,
+                    _ => next.atoms,
+                };
+                assert!(
+                    (node.index() as usize) < end,
+                    "{node:?} used after being freed by restore_checkpoint() or reset()"
+                );
+            }
+        }
+    }
+
+    // called after appending a new atom buffer to u8_vec
+    #[inline(always)]
+    fn push_canary(&mut self) {
+        #[cfg(feature = "poison-allocator")]
+        {
+            self.u8_vec.extend_from_slice(&CANARY);
+            self.poison.overhead += CANARY.len();
         }
+    }
+
+    #[inline(always)]
+    fn atom_buf(&self, node: NodePtr) -> &AtomBuf {
+        self.check_node(node);
+        let buf = &self.atom_vec[node.index() as usize];
+        #[cfg(feature = "poison-allocator")]
+        if let Some((start, end)) = buf.range() {
+            assert!(
+                start != FREED,
+                "atom accessed after being freed by restore_checkpoint()"
+            );
+            let end = end as usize;
+            assert!(
+                self.u8_vec[end..end + CANARY.len()] == CANARY,
+                "atom canary overwritten"
+            );
+        }
+        buf
+    }
+
+    #[inline(always)]
+    fn atom_bytes<'a>(&'a self, atom: &'a AtomBuf) -> &'a [u8] {
+        match atom.len {
+            HEAP_ATOM => {
+                let (start, end) = atom.offsets();
+                &self.u8_vec[start as usize..end as usize]
+            }
+            EXTERNAL_ATOM => {
+                let (start, end) = atom.offsets();
+                external_bytes(&self.external, start, end)
+            }
+            ROPE_ATOM => {
+                let (start, end) = atom.offsets();
+                self.rope_bytes(start, end)
+            }
+            len => &atom.buf[..len as usize],
+        }
+    }
+
+    // the rope holding the address in the ropes' address space
+    #[inline(always)]
+    fn rope_at(&self, addr: u32) -> &Rope {
+        &self.ropes[self.ropes.partition_point(|rope| rope.base <= addr) - 1]
+    }
+
+    // returns the bytes in the range of the ropes' address space, flattening
+    // the rope if it hasn't been already
+    fn rope_bytes(&self, start: u32, end: u32) -> &[u8] {
+        let rope = self.rope_at(start);
+        let bytes = rope.bytes.get_or_init(|| self.flatten(rope));
+        &bytes[(start - rope.base) as usize..(end - rope.base) as usize]
+    }
+
+    // copies the bytes of the rope's parts. Parts that are ropes themselves
+    // are traversed rather than flattened, unless they already are. This
+    // doesn't recurse, however deeply the ropes are nested
+    fn flatten(&self, rope: &Rope) -> Box<[u8]> {
+        let mut buf = Vec::<u8>::with_capacity(rope.len as usize);
+        let mut stack: Vec<NodePtr> = rope.parts.iter().rev().copied().collect();
+        while let Some(node) = stack.pop() {
+            if node.object_type() != ObjectType::Bytes {
+                buf.extend_from_slice(self.atom(node).as_ref());
+                continue;
+            }
+            let atom = self.atom_buf(node);
+            let Some((start, end)) = atom.rope_range() else {
+                buf.extend_from_slice(self.atom_bytes(atom));
+                continue;
+            };
+            let part = self.rope_at(start);
+            match part.bytes.get() {
+                Some(bytes) => buf.extend_from_slice(
+                    &bytes[(start - part.base) as usize..(end - part.base) as usize],
+                ),
+                // substrings are only taken of flattened ropes, so this is
+                // the whole rope
+                None => stack.extend(part.parts.iter().rev()),
+            }
+        }
+        debug_assert_eq!(buf.len(), rope.len as usize);
+        buf.into()
+    }
+
+    // appends a new atom, stored inline if it's small enough. The caller is
+    // responsible for checking the limits
+    #[inline(always)]
+    fn push_atom(&mut self, v: &[u8], kind: AtomKind) -> NodePtr {
         let idx = self.atom_vec.len();
+        let atom = if v.len() <= INLINE_ATOM_SIZE {
+            self.inline_heap += v.len();
+            AtomBuf::inline(v, kind)
+        } else {
+            let start = self.u8_vec.len() as u32;
+            self.u8_vec.extend_from_slice(v);
+            let end = self.u8_vec.len() as u32;
+            self.push_canary();
+            AtomBuf::heap(start, end, kind)
+        };
+        self.atom_vec.push(atom);
+        self.node_ptr(ObjectType::Bytes, idx)
+    }
+
+    #[inline(always)]
+    fn int_pair(&self, node: NodePtr) -> IntPair {
+        self.check_node(node);
+        let pair = self.pair_vec[node.index() as usize];
+        #[cfg(feature = "poison-allocator")]
+        assert!(
+            pair.first.0 != FREED_NODE,
+            "pair accessed after being freed by restore_checkpoint()"
+        );
+        pair
+    }
+
+    pub fn new_atom(&mut self, v: &[u8]) -> Result<NodePtr, EvalErr> {
+        self.new_atom_with_kind(v, AtomKind::Bytes)
+    }
+
+    // the caller is responsible for v being a valid representation of kind
+    pub(crate) fn new_atom_with_kind(
+        &mut self,
+        v: &[u8],
+        kind: AtomKind,
+    ) -> Result<NodePtr, EvalErr> {
+        if v.len() > self.atom_limit {
+            return err(self.nil(), ATOM_TOO_LARGE);
+        }
+        if (self.heap_limit - self.heap_used()) < v.len() {
+            return err(self.nil(), OUT_OF_MEMORY);
+        }
         self.check_atom_limit()?;
         if let Some(ret) = fits_in_small_atom(v) {
             self.small_atoms += 1;
             Ok(NodePtr::new(ObjectType::SmallAtom, ret as usize))
+        } else if self.interned.is_some() && kind == AtomKind::Bytes {
+            Ok(self.intern_atom(v))
         } else {
-            self.u8_vec.extend_from_slice(v);
-            let end = self.u8_vec.len() as u32;
-            self.atom_vec.push(AtomBuf { start, end });
-            Ok(NodePtr::new(ObjectType::Bytes, idx))
+            Ok(self.push_atom(v, kind))
+        }
+    }
+
+    // the caller has checked the limits. An interned atom is counted like a
+    // small atom, and its bytes like an inline atom's, so restoring a
+    // checkpoint gives them back. Atoms whose hash collides with a different
+    // one are not interned
+    fn intern_atom(&mut self, v: &[u8]) -> NodePtr {
+        let mut hasher = DefaultHasher::new();
+        v.hash(&mut hasher);
+        let hash = hasher.finish();
+        let existing = self.interned.as_ref().and_then(|i| i.get(&hash)).copied();
+        match existing {
+            Some(node) if self.atom(node).as_ref() == v => {
+                self.small_atoms += 1;
+                self.inline_heap += v.len();
+                node
+            }
+            Some(_) => self.push_atom(v, AtomKind::Bytes),
+            None => {
+                let node = self.push_atom(v, AtomKind::Bytes);
+                if let Some(interned) = &mut self.interned {
+                    interned.insert(hash, node);
+                }
+                node
+            }
+        }
+    }
+
+    // like new_atom(buf[start..end]), except that atoms larger than
+    // INLINE_ATOM_SIZE refer to the bytes in buf rather than holding a copy of
+    // them. This is for deserializing large inputs, like block generators,
+    // without copying every atom. The allocator keeps a reference to buf. The
+    // atoms count towards the heap limit as if they were copied
+    pub fn new_atom_shared(
+        &mut self,
+        buf: &Arc<[u8]>,
+        start: usize,
+        end: usize,
+    ) -> Result<NodePtr, EvalErr> {
+        let Some(v) = buf.get(start..end) else {
+            return err(self.nil(), ATOM_OUT_OF_BOUNDS);
+        };
+        if v.len() <= INLINE_ATOM_SIZE {
+            return self.new_atom(v);
+        }
+        if v.len() > self.atom_limit {
+            return err(self.nil(), ATOM_TOO_LARGE);
+        }
+        if (self.heap_limit - self.heap_used()) < v.len() {
+            return err(self.nil(), OUT_OF_MEMORY);
+        }
+        self.check_atom_limit()?;
+        let base = self.external_base(buf)?;
+        self.external_heap += v.len();
+        let idx = self.atom_vec.len();
+        self.atom_vec.push(AtomBuf::external(
+            base + start as u32,
+            base + end as u32,
+            AtomKind::Bytes,
+        ));
+        Ok(self.node_ptr(ObjectType::Bytes, idx))
+    }
+
+    // returns the offset of buf in the external buffers' address space,
+    // adding it if it's not there yet
+    fn external_base(&mut self, buf: &Arc<[u8]>) -> Result<u32, EvalErr> {
+        if let Some((base, _)) = self
+            .external
+            .iter()
+            .rev()
+            .find(|(_, b)| Arc::ptr_eq(b, buf))
+        {
+            return Ok(*base);
+        }
+        let base = match self.external.last() {
+            Some((base, last)) => *base as usize + last.len(),
+            None => 0,
+        };
+        if base + buf.len() > u32::MAX as usize {
+            return err(self.nil(), OUT_OF_MEMORY);
         }
+        self.external.push((base as u32, buf.clone()));
+        Ok(base as u32)
     }
 
-    pub fn new_small_number(&mut self, v: u32) -> Result<NodePtr, EvalErr> {
-        debug_assert!(v <= NODE_PTR_IDX_MASK);
+    // allocates an atom holding v, in the canonical (minimal, big-endian two's
+    // complement) encoding. Values that fit in 26 bits are small atoms, which
+    // don't use the heap. See number.rs
+    pub fn new_small_number(&mut self, v: u64) -> Result<NodePtr, EvalErr> {
+        if v > SMALL_ATOM_MASK as u64 {
+            return node_from_number(self, &v.into());
+        }
         self.check_atom_limit()?;
         self.small_atoms += 1;
         Ok(NodePtr::new(ObjectType::SmallAtom, v as usize))
     }
 
+    // allocates an atom holding v, in the canonical encoding. This (and
+    // new_small_number()) is the only way integers should be created
     pub fn new_number(&mut self, v: Number) -> Result<NodePtr, EvalErr> {
         use num_traits::ToPrimitive;
-        if let Some(val) = v.to_u32() {
-            if val <= NODE_PTR_IDX_MASK {
-                return self.new_small_number(val);
-            }
+        if let Some(val) = v.to_u64() {
+            return self.new_small_number(val);
         }
         node_from_number(self, &v)
     }
 
     pub fn new_g1(&mut self, g1: G1Element) -> Result<NodePtr, EvalErr> {
-        self.new_atom(&g1.to_bytes())
+        let node = self.new_atom_with_kind(&g1.to_bytes(), AtomKind::G1)?;
+        if self.g1_points.len() < MAX_NATIVE_POINTS {
+            self.g1_points.insert(node, g1);
+        }
+        Ok(node)
     }
 
     pub fn new_g2(&mut self, g2: G2Element) -> Result<NodePtr, EvalErr> {
-        self.new_atom(&g2.to_bytes())
+        let node = self.new_atom_with_kind(&g2.to_bytes(), AtomKind::G2)?;
+        if self.g2_points.len() < MAX_NATIVE_POINTS {
+            self.g2_points.insert(node, g2);
+        }
+        Ok(node)
     }
 
     pub fn new_pair(&mut self, first: NodePtr, rest: NodePtr) -> Result<NodePtr, EvalErr> {
         let idx = self.pair_vec.len();
-        if idx == MAX_NUM_PAIRS {
-            return err(self.nil(), "too many pairs");
+        if self.pairs_used() == MAX_NUM_PAIRS {
+            return err(self.nil(), TOO_MANY_PAIRS);
+        }
+        self.pair_vec.push(IntPair { first, rest });
+        Ok(self.node_ptr(ObjectType::Pair, idx))
+    }
+
+    // like new_atom() and new_pair(), except the heap, atom and pair limits
+    // are only checked in debug builds. These are for input known to fit,
+    // such as trusted deserialization of our own output
+    pub(crate) fn new_atom_trusted(&mut self, v: &[u8]) -> NodePtr {
+        debug_assert!(v.len() <= self.atom_limit, "trusted atom too large");
+        debug_assert!(
+            self.heap_limit - self.heap_used() >= v.len(),
+            "trusted atom exceeds heap limit"
+        );
+        debug_assert!(
+            self.atoms_used() + self.small_atoms < MAX_NUM_ATOMS,
+            "trusted atom exceeds atom limit"
+        );
+        if let Some(ret) = fits_in_small_atom(v) {
+            self.small_atoms += 1;
+            NodePtr::new(ObjectType::SmallAtom, ret as usize)
+        } else {
+            self.push_atom(v, AtomKind::Bytes)
         }
+    }
+
+    pub(crate) fn new_pair_trusted(&mut self, first: NodePtr, rest: NodePtr) -> NodePtr {
+        debug_assert!(
+            self.pairs_used() < MAX_NUM_PAIRS,
+            "trusted pair exceeds pair limit"
+        );
+        let idx = self.pair_vec.len();
         self.pair_vec.push(IntPair { first, rest });
-        Ok(NodePtr::new(ObjectType::Pair, idx))
+        self.node_ptr(ObjectType::Pair, idx)
     }
 
     pub fn new_substr(&mut self, node: NodePtr, start: u32, end: u32) -> Result<NodePtr, EvalErr> {
@@ -314,29 +1507,76 @@ impl Allocator {
 
         fn bounds_check(node: NodePtr, start: u32, end: u32, len: u32) -> Result<(), EvalErr> {
             if start > len {
-                return err(node, "substr start out of bounds");
+                return err(node, SUBSTR_START_OUT_OF_BOUNDS);
             }
             if end > len {
-                return err(node, "substr end out of bounds");
+                return err(node, SUBSTR_END_OUT_OF_BOUNDS);
             }
             if end < start {
-                return err(node, "substr invalid bounds");
+                return err(node, SUBSTR_INVALID_BOUNDS);
             }
             Ok(())
         }
 
         match node.object_type() {
-            ObjectType::Pair => err(node, "(internal error) substr expected atom, got pair"),
+            ObjectType::Pair => err(node, SUBSTR_EXPECTED_ATOM),
             ObjectType::Bytes => {
-                let atom = self.atom_vec[node.index() as usize];
-                let atom_len = atom.end - atom.start;
-                bounds_check(node, start, end, atom_len)?;
+                let atom = *self.atom_buf(node);
+                bounds_check(node, start, end, atom.len() as u32)?;
                 let idx = self.atom_vec.len();
-                self.atom_vec.push(AtomBuf {
-                    start: atom.start + start,
-                    end: atom.start + end,
-                });
-                Ok(NodePtr::new(ObjectType::Bytes, idx))
+                // substrings share the bytes of the atom they're taken from,
+                // so they don't count towards the heap limit. Short ones are
+                // copied inline
+                let atom = match atom.range() {
+                    _ if (end - start) as usize <= INLINE_ATOM_SIZE => AtomBuf::inline(
+                        &self.atom_bytes(&atom)[start as usize..end as usize],
+                        AtomKind::Bytes,
+                    ),
+                    None => match (atom.external_range(), atom.rope_range()) {
+                        (Some((base, _)), _) => {
+                            AtomBuf::external(base + start, base + end, AtomKind::Bytes)
+                        }
+                        (None, Some((base, rope_end))) => {
+                            // substrings refer to the flattened rope
+                            #[cfg(not(feature = "poison-allocator"))]
+                            {
+                                self.rope_bytes(base, rope_end);
+                                AtomBuf::rope(base + start, base + end, AtomKind::Bytes)
+                            }
+                            // With poison-allocator, they get their own copy,
+                            // like substrings of heap atoms
+                            #[cfg(feature = "poison-allocator")]
+                            {
+                                debug_assert!(base + end <= rope_end);
+                                let bytes = self.rope_bytes(base + start, base + end).to_vec();
+                                let new_start = self.u8_vec.len() as u32;
+                                self.u8_vec.extend_from_slice(&bytes);
+                                self.poison.overhead += bytes.len();
+                                self.push_canary();
+                                AtomBuf::heap(new_start, new_start + end - start, AtomKind::Bytes)
+                            }
+                        }
+                        (None, None) => unreachable!("inline atom too large"),
+                    },
+                    Some((base, _)) => {
+                        let start = base + start;
+                        let end = base + end;
+                        // With poison-allocator, substrings get their own copy
+                        // (not counted towards the heap limit), followed by a
+                        // canary
+                        #[cfg(feature = "poison-allocator")]
+                        let (start, end) = {
+                            let new_start = self.u8_vec.len() as u32;
+                            self.u8_vec.extend_from_within(start as usize..end as usize);
+                            self.poison.overhead += (end - start) as usize;
+                            self.push_canary();
+                            (new_start, new_start + end - start)
+                        };
+                        AtomBuf::heap(start, end, AtomKind::Bytes)
+                    }
+                };
+                self.atom_vec.push(atom);
+                Ok(self.node_ptr(ObjectType::Bytes, idx))
             }
             ObjectType::SmallAtom => {
                 let val = node.index();
@@ -349,25 +1589,27 @@ impl Allocator {
                     self.small_atoms += 1;
                     Ok(NodePtr::new(ObjectType::SmallAtom, new_val as usize))
                 } else {
-                    let start = self.u8_vec.len();
-                    let end = start + substr.len();
-                    self.u8_vec.extend_from_slice(substr);
-                    let idx = self.atom_vec.len();
-                    self.atom_vec.push(AtomBuf {
-                        start: start as u32,
-                        end: end as u32,
-                    });
-                    Ok(NodePtr::new(ObjectType::Bytes, idx))
+                    Ok(self.push_atom(substr, AtomKind::Bytes))
                 }
             }
         }
     }
 
+    // large atoms are created as ropes, which refer to the nodes rather than
+    // copying their bytes until the atom is read. This keeps building a large
+    // atom by repeatedly concatenating to it from copying the bytes over and
+    // over again. Ropes count towards the heap limit like any other atom
     pub fn new_concat(&mut self, new_size: usize, nodes: &[NodePtr]) -> Result<NodePtr, EvalErr> {
         self.check_atom_limit()?;
         let start = self.u8_vec.len();
-        if self.heap_limit - start < new_size {
-            return err(self.nil(), "out of memory");
+        if new_size > self.atom_limit {
+            return err(self.nil(), ATOM_TOO_LARGE);
+        }
+        if self.heap_limit - self.heap_used() < new_size {
+            return err(self.nil(), OUT_OF_MEMORY);
+        }
+        if new_size >= MIN_ROPE_SIZE {
+            return self.new_rope(new_size, nodes);
         }
         // TODO: maybe it would make sense to have a special case where
         // nodes.len() == 1. We can just return the same node
@@ -379,16 +1621,33 @@ impl Allocator {
             match node.object_type() {
                 ObjectType::Pair => {
                     self.u8_vec.truncate(start);
-                    return err(*node, "(internal error) concat expected atom, got pair");
+                    return err(*node, CONCAT_EXPECTED_ATOM);
                 }
                 ObjectType::Bytes => {
-                    let term = self.atom_vec[node.index() as usize];
+                    let term = *self.atom_buf(*node);
                     if counter + term.len() > new_size {
                         self.u8_vec.truncate(start);
-                        return err(*node, "(internal error) concat passed invalid new_size");
+                        return err(*node, CONCAT_INVALID_SIZE);
+                    }
+                    match term.len {
+                        HEAP_ATOM => {
+                            let (start, end) = term.offsets();
+                            self.u8_vec.extend_from_within(start as usize..end as usize)
+                        }
+                        EXTERNAL_ATOM => {
+                            let (start, end) = term.offsets();
+                            self.u8_vec.extend_from_slice(external_bytes(
+                                &self.external,
+                                start,
+                                end,
+                            ))
+                        }
+                        ROPE_ATOM => {
+                            let bytes = self.atom_bytes(&term).to_vec();
+                            self.u8_vec.extend_from_slice(&bytes)
+                        }
+                        len => self.u8_vec.extend_from_slice(&term.buf[..len as usize]),
                     }
-                    self.u8_vec
-                        .extend_from_within(term.start as usize..term.end as usize);
                     counter += term.len();
                 }
                 ObjectType::SmallAtom => {
@@ -403,18 +1662,75 @@ impl Allocator {
         }
         if counter != new_size {
             self.u8_vec.truncate(start);
-            return err(
-                self.nil(),
-                "(internal error) concat passed invalid new_size",
-            );
+            return err(self.nil(), CONCAT_INVALID_SIZE);
         }
-        let end = self.u8_vec.len() as u32;
+        let atom = if new_size <= INLINE_ATOM_SIZE {
+            let atom = AtomBuf::inline(&self.u8_vec[start..], AtomKind::Bytes);
+            self.u8_vec.truncate(start);
+            self.inline_heap += new_size;
+            atom
+        } else {
+            let end = self.u8_vec.len() as u32;
+            self.push_canary();
+            AtomBuf::heap(start as u32, end, AtomKind::Bytes)
+        };
         let idx = self.atom_vec.len();
-        self.atom_vec.push(AtomBuf {
-            start: (start as u32),
-            end,
+        self.atom_vec.push(atom);
+        Ok(self.node_ptr(ObjectType::Bytes, idx))
+    }
+
+    // the rope part of new_concat(). The caller is responsible for checking
+    // the limits
+    fn new_rope(&mut self, new_size: usize, nodes: &[NodePtr]) -> Result<NodePtr, EvalErr> {
+        let mut parts = Vec::<NodePtr>::with_capacity(nodes.len());
+        let mut depth = 0;
+        let mut counter: usize = 0;
+        for node in nodes {
+            let len = match node.object_type() {
+                ObjectType::Pair => {
+                    return err(*node, CONCAT_EXPECTED_ATOM);
+                }
+                ObjectType::Bytes => {
+                    let term = *self.atom_buf(*node);
+                    if counter + term.len() > new_size {
+                        return err(*node, CONCAT_INVALID_SIZE);
+                    }
+                    if let Some((start, end)) = term.rope_range() {
+                        let part = self.rope_at(start);
+                        if part.bytes.get().is_none() {
+                            if part.depth < MAX_ROPE_DEPTH {
+                                depth = depth.max(part.depth);
+                            } else {
+                                self.rope_bytes(start, end);
+                            }
+                        }
+                    }
+                    term.len()
+                }
+                ObjectType::SmallAtom => len_for_value(node.index()),
+            };
+            if len > 0 {
+                parts.push(*node);
+            }
+            counter += len;
+        }
+        if counter != new_size {
+            return err(self.nil(), CONCAT_INVALID_SIZE);
+        }
+        // the heap limit keeps this within the 32 bit address space
+        let base = self.rope_heap as u32;
+        self.ropes.push(Rope {
+            base,
+            len: new_size as u32,
+            parts: parts.into(),
+            depth: depth + 1,
+            bytes: OnceLock::new(),
         });
-        Ok(NodePtr::new(ObjectType::Bytes, idx))
+        self.rope_heap += new_size;
+        let idx = self.atom_vec.len();
+        self.atom_vec
+            .push(AtomBuf::rope(base, base + new_size as u32, AtomKind::Bytes));
+        Ok(self.node_ptr(ObjectType::Bytes, idx))
     }
 
     pub fn atom_eq(&self, lhs: NodePtr, rhs: NodePtr) -> bool {
@@ -426,31 +1742,31 @@ impl Allocator {
                 panic!("atom_eq() called on pair");
             }
             (ObjectType::Bytes, ObjectType::Bytes) => {
-                let lhs = self.atom_vec[lhs.index() as usize];
-                let rhs = self.atom_vec[rhs.index() as usize];
-                self.u8_vec[lhs.start as usize..lhs.end as usize]
-                    == self.u8_vec[rhs.start as usize..rhs.end as usize]
+                let lhs = self.atom_buf(lhs);
+                let rhs = self.atom_buf(rhs);
+                self.atom_bytes(lhs) == self.atom_bytes(rhs)
             }
             (ObjectType::SmallAtom, ObjectType::SmallAtom) => lhs.index() == rhs.index(),
             (ObjectType::SmallAtom, ObjectType::Bytes) => {
-                self.bytes_eq_int(self.atom_vec[rhs.index() as usize], lhs.index())
+                self.bytes_eq_int(self.atom_buf(rhs), lhs.index())
             }
             (ObjectType::Bytes, ObjectType::SmallAtom) => {
-                self.bytes_eq_int(self.atom_vec[lhs.index() as usize], rhs.index())
+                self.bytes_eq_int(self.atom_buf(lhs), rhs.index())
             }
         }
     }
 
-    fn bytes_eq_int(&self, atom: AtomBuf, val: u32) -> bool {
-        let len = len_for_value(val) as u32;
-        if (atom.end - atom.start) != len {
+    fn bytes_eq_int(&self, atom: &AtomBuf, val: u32) -> bool {
+        let len = len_for_value(val);
+        if atom.len() != len {
             return false;
         }
         if val == 0 {
             return true;
         }
 
-        if self.u8_vec[atom.start as usize] & 0x80 != 0 {
+        let buf = self.atom_bytes(atom);
+        if buf[0] & 0x80 != 0 {
             // SmallAtom only represents positive values
             // if the byte buffer is negative, they can't match
             return false;
@@ -459,9 +1775,9 @@ impl Allocator {
         // since we know the value of atom is small, we can turn it into a u32 and compare
         // against val
         let mut atom_val: u32 = 0;
-        for i in atom.start..atom.end {
+        for b in buf {
             atom_val <<= 8;
-            atom_val |= self.u8_vec[i as usize] as u32;
+            atom_val |= *b as u32;
         }
         val == atom_val
     }
@@ -470,10 +1786,7 @@ impl Allocator {
         let index = node.index();
 
         match node.object_type() {
-            ObjectType::Bytes => {
-                let atom = self.atom_vec[index as usize];
-                Atom::Borrowed(&self.u8_vec[atom.start as usize..atom.end as usize])
-            }
+            ObjectType::Bytes => Atom::Borrowed(self.atom_bytes(self.atom_buf(node))),
             ObjectType::SmallAtom => {
                 let len = len_for_value(index);
                 let bytes = index.to_be_bytes();
@@ -487,10 +1800,7 @@ impl Allocator {
         let index = node.index();
 
         match node.object_type() {
-            ObjectType::Bytes => {
-                let atom = self.atom_vec[index as usize];
-                (atom.end - atom.start) as usize
-            }
+            ObjectType::Bytes => self.atom_buf(node).len(),
             ObjectType::SmallAtom => len_for_value(index),
             _ => {
                 panic!("expected atom, got pair");
@@ -501,23 +1811,18 @@ impl Allocator {
     pub fn small_number(&self, node: NodePtr) -> Option<u32> {
         match node.object_type() {
             ObjectType::SmallAtom => Some(node.index()),
-            ObjectType::Bytes => {
-                let atom = self.atom_vec[node.index() as usize];
-                let buf = &self.u8_vec[atom.start as usize..atom.end as usize];
-                fits_in_small_atom(buf)
-            }
+            ObjectType::Bytes => fits_in_small_atom(self.atom_bytes(self.atom_buf(node))),
             _ => None,
         }
     }
 
+    // interprets the atom as a big-endian two's complement integer. Any
+    // encoding is accepted, including ones with redundant leading bytes
     pub fn number(&self, node: NodePtr) -> Number {
         let index = node.index();
 
         match node.object_type() {
-            ObjectType::Bytes => {
-                let atom = self.atom_vec[index as usize];
-                number_from_u8(&self.u8_vec[atom.start as usize..atom.end as usize])
-            }
+            ObjectType::Bytes => number_from_u8(self.atom_bytes(self.atom_buf(node))),
             ObjectType::SmallAtom => Number::from(index),
             _ => {
                 panic!("number() calld on pair");
@@ -526,63 +1831,135 @@ impl Allocator {
     }
 
     pub fn g1(&self, node: NodePtr) -> Result<G1Element, EvalErr> {
-        let idx = match node.object_type() {
-            ObjectType::Bytes => node.index(),
+        match node.object_type() {
+            ObjectType::Bytes => {}
             ObjectType::SmallAtom => {
-                return err(node, "atom is not G1 size, 48 bytes");
+                return err(node, NOT_G1_SIZE);
             }
             ObjectType::Pair => {
-                return err(node, "pair found, expected G1 point");
+                return err(node, PAIR_NOT_G1);
             }
         };
-        let atom = self.atom_vec[idx as usize];
-        if atom.end - atom.start != 48 {
-            return err(node, "atom is not G1 size, 48 bytes");
+        let atom = self.atom_buf(node);
+        if atom.len() != 48 {
+            return err(node, NOT_G1_SIZE);
         }
 
-        let array: &[u8; 48] = &self.u8_vec[atom.start as usize..atom.end as usize]
+        let array: &[u8; 48] = self
+            .atom_bytes(atom)
             .try_into()
             .expect("atom size is not 48 bytes");
-        G1Element::from_bytes(array)
-            .map_err(|_| EvalErr(node, "atom is not a G1 point".to_string()))
+        // atoms created by new_g1() are known to be valid points
+        if atom.kind == AtomKind::G1 {
+            if let Some(point) = self.g1_points.get(node) {
+                return Ok(point.clone());
+            }
+            return G1Element::from_bytes_unchecked(array)
+                .map_err(|_| EvalErr(node, NOT_G1_POINT.into()));
+        }
+        G1Element::from_bytes(array).map_err(|_| EvalErr(node, NOT_G1_POINT.into()))
     }
 
     pub fn g2(&self, node: NodePtr) -> Result<G2Element, EvalErr> {
-        let idx = match node.object_type() {
-            ObjectType::Bytes => node.index(),
+        match node.object_type() {
+            ObjectType::Bytes => {}
             ObjectType::SmallAtom => {
-                return err(node, "atom is not G2 size, 96 bytes");
+                return err(node, NOT_G2_SIZE);
             }
             ObjectType::Pair => {
-                return err(node, "pair found, expected G2 point");
+                return err(node, PAIR_NOT_G2);
             }
         };
 
-        let atom = self.atom_vec[idx as usize];
-        if atom.end - atom.start != 96 {
-            return err(node, "atom is not G2 size, 96 bytes");
+        let atom = self.atom_buf(node);
+        if atom.len() != 96 {
+            return err(node, NOT_G2_SIZE);
         }
 
-        let array: &[u8; 96] = &self.u8_vec[atom.start as usize..atom.end as usize]
+        let array: &[u8; 96] = self
+            .atom_bytes(atom)
             .try_into()
             .expect("atom size is not 96 bytes");
 
-        G2Element::from_bytes(array)
-            .map_err(|_| EvalErr(node, "atom is not a G2 point".to_string()))
+        // atoms created by new_g2() are known to be valid points
+        if atom.kind == AtomKind::G2 {
+            if let Some(point) = self.g2_points.get(node) {
+                return Ok(point.clone());
+            }
+            return G2Element::from_bytes_unchecked(array)
+                .map_err(|_| EvalErr(node, NOT_G2_POINT.into()));
+        }
+        G2Element::from_bytes(array).map_err(|_| EvalErr(node, NOT_G2_POINT.into()))
+    }
+
+    // like g1(), but an atom that isn't a valid G1 point (including an atom of
+    // the wrong size) is not an error, it returns None. Passing a pair is
+    // still an error
+    pub fn try_g1(&self, node: NodePtr) -> Result<Option<G1Element>, EvalErr> {
+        match node.object_type() {
+            ObjectType::Bytes => {}
+            ObjectType::SmallAtom => {
+                return Ok(None);
+            }
+            ObjectType::Pair => {
+                return err(node, PAIR_NOT_G1);
+            }
+        };
+        let atom = self.atom_buf(node);
+        let Ok(array) = <&[u8; 48]>::try_from(self.atom_bytes(atom)) else {
+            return Ok(None);
+        };
+        if atom.kind == AtomKind::G1 {
+            if let Some(point) = self.g1_points.get(node) {
+                return Ok(Some(point.clone()));
+            }
+            return Ok(G1Element::from_bytes_unchecked(array).ok());
+        }
+        Ok(G1Element::from_bytes(array).ok())
+    }
+
+    // like g2(), but an atom that isn't a valid G2 point is not an error
+    pub fn try_g2(&self, node: NodePtr) -> Result<Option<G2Element>, EvalErr> {
+        match node.object_type() {
+            ObjectType::Bytes => {}
+            ObjectType::SmallAtom => {
+                return Ok(None);
+            }
+            ObjectType::Pair => {
+                return err(node, PAIR_NOT_G2);
+            }
+        };
+        let atom = self.atom_buf(node);
+        let Ok(array) = <&[u8; 96]>::try_from(self.atom_bytes(atom)) else {
+            return Ok(None);
+        };
+        if atom.kind == AtomKind::G2 {
+            if let Some(point) = self.g2_points.get(node) {
+                return Ok(Some(point.clone()));
+            }
+            return Ok(G2Element::from_bytes_unchecked(array).ok());
+        }
+        Ok(G2Element::from_bytes(array).ok())
+    }
+
+    // returns the kind the atom was created as, or None if node is a pair.
+    // See AtomKind
+    pub fn atom_kind(&self, node: NodePtr) -> Option<AtomKind> {
+        match node.object_type() {
+            ObjectType::Bytes => Some(self.atom_buf(node).kind),
+            ObjectType::SmallAtom => Some(AtomKind::Int),
+            ObjectType::Pair => None,
+        }
     }
 
     pub fn node(&self, node: NodePtr) -> NodeVisitor {
         let index = node.index();
 
         match node.object_type() {
-            ObjectType::Bytes => {
-                let atom = self.atom_vec[index as usize];
-                let buf = &self.u8_vec[atom.start as usize..atom.end as usize];
-                NodeVisitor::Buffer(buf)
-            }
+            ObjectType::Bytes => NodeVisitor::Buffer(self.atom_bytes(self.atom_buf(node))),
             ObjectType::SmallAtom => NodeVisitor::U32(index),
             ObjectType::Pair => {
-                let pair = self.pair_vec[index as usize];
+                let pair = self.int_pair(node);
                 NodeVisitor::Pair(pair.first, pair.rest)
             }
         }
@@ -592,7 +1969,7 @@ impl Allocator {
         match node.object_type() {
             ObjectType::Bytes | ObjectType::SmallAtom => SExp::Atom,
             ObjectType::Pair => {
-                let pair = self.pair_vec[node.index() as usize];
+                let pair = self.int_pair(node);
                 SExp::Pair(pair.first, pair.rest)
             }
         }
@@ -610,6 +1987,16 @@ impl Allocator {
         }
     }
 
+    // iterates over root and every node reachable from it, in a deterministic
+    // order. See Reachable
+    pub fn iter_reachable(&self, root: NodePtr) -> Reachable<'_> {
+        Reachable {
+            allocator: self,
+            stack: vec![root],
+            seen: NodeMap::new(),
+        }
+    }
+
     pub fn nil(&self) -> NodePtr {
         NodePtr::new(ObjectType::SmallAtom, 0)
     }
@@ -618,33 +2005,481 @@ impl Allocator {
         NodePtr::new(ObjectType::SmallAtom, 1)
     }
 
+    // the atom for a value of 0 - 255, in its canonical encoding (0 is nil).
+    // Like nil() and one(), these are small atoms that always exist, so this
+    // neither allocates nor counts towards the atom limit. Operators whose
+    // results count towards the limit (to match the limits enforced by
+    // consensus) must keep using new_small_number()
+    pub fn small(&self, v: u8) -> NodePtr {
+        NodePtr::new(ObjectType::SmallAtom, v as usize)
+    }
+
     #[inline]
     fn check_atom_limit(&self) -> Result<(), EvalErr> {
-        if self.atom_vec.len() + self.small_atoms == MAX_NUM_ATOMS {
-            err(self.nil(), "too many atoms")
+        if self.atoms_used() + self.small_atoms == MAX_NUM_ATOMS {
+            err(self.nil(), TOO_MANY_ATOMS)
         } else {
             Ok(())
         }
-    }
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        let pairs = self.pairs_used();
+        let atoms = self.atoms_used() + self.small_atoms;
+        let atom_bytes = self.heap_used();
+        AllocatorStats {
+            pairs,
+            atoms,
+            atom_bytes,
+            max_pairs: std::cmp::max(self.peak.0, pairs),
+            max_atoms: std::cmp::max(self.peak.1, atoms),
+            max_atom_bytes: std::cmp::max(self.peak.2, atom_bytes),
+        }
+    }
+
+    #[cfg(feature = "counters")]
+    pub fn atom_count(&self) -> usize {
+        self.atoms_used()
+    }
+
+    #[cfg(feature = "counters")]
+    pub fn small_atom_count(&self) -> usize {
+        self.small_atoms
+    }
+
+    #[cfg(feature = "counters")]
+    pub fn pair_count(&self) -> usize {
+        self.pairs_used()
+    }
+
+    #[cfg(feature = "counters")]
+    pub fn heap_size(&self) -> usize {
+        self.heap_used()
+    }
+}
+
+// copies the tree at node from src to dst, e.g. to keep the result of a run
+// in a scratch allocator. Subtrees that occur more than once in the tree are
+// only copied once, and are shared in dst too. Atoms referring to an external
+// buffer (see new_atom_shared()) refer to the same buffer in dst. The copy
+// counts towards dst's limits like any other allocation
+pub fn copy_node(src: &Allocator, node: NodePtr, dst: &mut Allocator) -> Result<NodePtr, EvalErr> {
+    let mut copied = NodeMap::<NodePtr>::new();
+    // the bool is set once the children of the pair have been copied
+    let mut stack = vec![(node, false)];
+    while let Some((node, children_copied)) = stack.pop() {
+        if copied.contains_key(node) {
+            continue;
+        }
+        let new_node = match node.object_type() {
+            ObjectType::SmallAtom => dst.new_small_number(node.index() as u64)?,
+            ObjectType::Bytes => {
+                let atom = src.atom_buf(node);
+                match atom.external_range() {
+                    Some((start, end)) if atom.len() > INLINE_ATOM_SIZE => {
+                        let idx = src.external.partition_point(|(base, _)| *base <= start) - 1;
+                        let (base, buf) = &src.external[idx];
+                        dst.new_atom_shared(buf, (start - base) as usize, (end - base) as usize)?
+                    }
+                    _ => dst.new_atom_with_kind(src.atom_bytes(atom), atom.kind)?,
+                }
+            }
+            ObjectType::Pair => {
+                let pair = src.int_pair(node);
+                if !children_copied {
+                    stack.push((node, true));
+                    stack.push((pair.rest, false));
+                    stack.push((pair.first, false));
+                    continue;
+                }
+                let first = *copied.get(pair.first).expect("copied");
+                let rest = *copied.get(pair.rest).expect("copied");
+                dst.new_pair(first, rest)?
+            }
+        };
+        copied.insert(node, new_node);
+    }
+    Ok(*copied.get(node).expect("copied"))
+}
+
+// identifies a saved allocator image. It's followed by IMAGE_VERSION
+#[cfg(not(any(feature = "poison-allocator", feature = "debug-allocator")))]
+const IMAGE_MAGIC: &[u8; 7] = b"clvmalc";
+
+// images saved with the poison-allocator feature include freed entries and
+// canaries, and can only be loaded with the feature enabled
+#[cfg(all(feature = "poison-allocator", not(feature = "debug-allocator")))]
+const IMAGE_MAGIC: &[u8; 7] = b"clvmalp";
+
+// images saved with the debug-allocator feature end with the generations of
+// the allocator, and can only be loaded with the feature enabled
+#[cfg(all(feature = "debug-allocator", not(feature = "poison-allocator")))]
+const IMAGE_MAGIC: &[u8; 7] = b"clvmald";
+#[cfg(all(feature = "debug-allocator", feature = "poison-allocator"))]
+const IMAGE_MAGIC: &[u8; 7] = b"clvmalq";
+
+// the version of the image format. Images saved with the large-heap feature
+// have 64 bit nodes, and can only be loaded with the feature enabled
+#[cfg(not(feature = "large-heap"))]
+const IMAGE_VERSION: u8 = 0x02;
+#[cfg(feature = "large-heap")]
+const IMAGE_VERSION: u8 = 0x82;
+
+// set in the kind byte of atoms stored inline in the image
+const IMAGE_INLINE_ATOM: u8 = 0x80;
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut buf = [0_u8; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+fn read_node<R: Read>(r: &mut R) -> io::Result<NodePtrRepr> {
+    let mut buf = [0_u8; std::mem::size_of::<NodePtrRepr>()];
+    r.read_exact(&mut buf)?;
+    Ok(NodePtrRepr::from_be_bytes(buf))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut buf = [0_u8; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+fn bad_image(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, fill(INVALID_IMAGE, &[&msg]))
+}
+
+impl Allocator {
+    // writes the full state of the allocator to w. Loading it back with
+    // load() results in an identical allocator, where all NodePtr values
+    // refer to the same nodes as in this one. This lets long-lived processes
+    // snapshot commonly used (e.g. deserialized) trees and restore them
+    // without re-parsing them.
+    pub fn save<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        // atoms referring to external buffers can't be saved, since the image
+        // doesn't hold the buffers
+        if self
+            .atom_vec
+            .entries()
+            .any(|atom| atom.len == EXTERNAL_ATOM)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                IMAGE_EXTERNAL_ATOMS,
+            ));
+        }
+        // ropes are written as heap atoms, with their bytes following u8_vec
+        // in the image (each followed by a canary, with poison-allocator)
+        #[cfg(not(feature = "poison-allocator"))]
+        let canary_len = 0;
+        #[cfg(feature = "poison-allocator")]
+        let canary_len = CANARY.len();
+        let rope_offset = |addr: u32| {
+            let idx = self.ropes.partition_point(|rope| rope.base <= addr) - 1;
+            self.u8_vec.len() as u32 + addr + (idx * canary_len) as u32
+        };
+
+        w.write_all(IMAGE_MAGIC)?;
+        w.write_all(&[IMAGE_VERSION])?;
+        w.write_all(&(self.heap_limit as u64).to_be_bytes())?;
+        w.write_all(&(self.small_atoms as u64).to_be_bytes())?;
+        w.write_all(&(self.inline_heap as u64).to_be_bytes())?;
+        #[cfg(feature = "poison-allocator")]
+        for v in [
+            self.poison.overhead + self.ropes.len() * canary_len,
+            self.poison.dead_atoms,
+            self.poison.dead_pairs,
+        ] {
+            w.write_all(&(v as u64).to_be_bytes())?;
+        }
+
+        let heap_size = self.u8_vec.len() + self.rope_heap + self.ropes.len() * canary_len;
+        w.write_all(&(heap_size as u32).to_be_bytes())?;
+        w.write_all(&self.u8_vec)?;
+        for rope in &self.ropes {
+            w.write_all(self.rope_bytes(rope.base, rope.base + rope.len))?;
+            #[cfg(feature = "poison-allocator")]
+            w.write_all(&CANARY)?;
+        }
+
+        w.write_all(&(self.atom_vec.len() as u32).to_be_bytes())?;
+        // each atom is its kind, followed by either its range of the heap or,
+        // for inline atoms, its length and bytes
+        for atom in self.atom_vec.entries() {
+            let kind: u8 = match atom.kind {
+                AtomKind::Bytes => 0,
+                AtomKind::Int => 1,
+                AtomKind::G1 => 2,
+                AtomKind::G2 => 3,
+            };
+            let range = match atom.rope_range() {
+                Some((start, end)) => Some((rope_offset(start), rope_offset(start) + end - start)),
+                None => atom.range(),
+            };
+            match range {
+                Some((start, end)) => {
+                    w.write_all(&[kind])?;
+                    w.write_all(&start.to_be_bytes())?;
+                    w.write_all(&end.to_be_bytes())?;
+                }
+                None => {
+                    w.write_all(&[kind | IMAGE_INLINE_ATOM, atom.len])?;
+                    w.write_all(&atom.buf[..atom.len as usize])?;
+                }
+            }
+        }
+
+        w.write_all(&(self.pair_vec.len() as u32).to_be_bytes())?;
+        for pair in self.pair_vec.entries() {
+            w.write_all(&pair.first.0.to_be_bytes())?;
+            w.write_all(&pair.rest.0.to_be_bytes())?;
+        }
+        // the loaded allocator continues the same generations, so NodePtrs
+        // into this allocator are valid in it too
+        #[cfg(feature = "debug-allocator")]
+        {
+            w.write_all(&(self.generations.len() as u32).to_be_bytes())?;
+            for g in &self.generations {
+                w.write_all(&g.tag.to_be_bytes())?;
+                w.write_all(&(g.pairs as u32).to_be_bytes())?;
+                w.write_all(&(g.atoms as u32).to_be_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    // loads an allocator previously written by save(). The image is
+    // validated, so a corrupt image results in an error rather than an
+    // allocator with dangling nodes.
+    pub fn load<R: Read>(r: &mut R) -> io::Result<Self> {
+        let mut magic = [0_u8; 8];
+        r.read_exact(&mut magic)?;
+        if magic[..7] != IMAGE_MAGIC[..] || magic[7] != IMAGE_VERSION {
+            return Err(bad_image("unknown format"));
+        }
+        let heap_limit = read_u64(r)?;
+        if heap_limit >= u32::MAX as u64 {
+            return Err(bad_image("heap limit out of range"));
+        }
+        let heap_limit = heap_limit as usize;
+        let small_atoms = read_u64(r)? as usize;
+        let inline_heap = read_u64(r)?;
+        #[cfg(feature = "poison-allocator")]
+        let poison = PoisonState {
+            overhead: read_u64(r)? as usize,
+            dead_atoms: read_u64(r)? as usize,
+            dead_pairs: read_u64(r)? as usize,
+        };
+        // the number of heap bytes, atoms and pairs not counting towards the
+        // limits
+        #[cfg(not(feature = "poison-allocator"))]
+        let (heap_overhead, dead_atoms, dead_pairs) = (0, 0, 0);
+        #[cfg(feature = "poison-allocator")]
+        let (heap_overhead, dead_atoms, dead_pairs) =
+            (poison.overhead, poison.dead_atoms, poison.dead_pairs);
+
+        let heap_size = read_u32(r)? as usize;
+        if heap_size < heap_overhead
+            || (heap_size - heap_overhead) as u64 + inline_heap > heap_limit as u64
+        {
+            return Err(bad_image("heap exceeds limit"));
+        }
+        let inline_heap = inline_heap as usize;
+        let mut u8_vec = Vec::new();
+        r.take(heap_size as u64).read_to_end(&mut u8_vec)?;
+        if u8_vec.len() != heap_size {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let num_atoms = read_u32(r)? as usize;
+        if num_atoms < dead_atoms || num_atoms - dead_atoms + small_atoms > MAX_NUM_ATOMS {
+            return Err(bad_image("too many atoms"));
+        }
+        let mut atom_vec = NodeVec::<AtomBuf>::default();
+        for _ in 0..num_atoms {
+            let mut kind = [0_u8; 1];
+            r.read_exact(&mut kind)?;
+            let mut atom = if kind[0] & IMAGE_INLINE_ATOM != 0 {
+                let mut len = [0_u8; 1];
+                r.read_exact(&mut len)?;
+                let len = len[0] as usize;
+                if len > INLINE_ATOM_SIZE {
+                    return Err(bad_image("inline atom too large"));
+                }
+                let mut buf = [0_u8; INLINE_ATOM_SIZE];
+                r.read_exact(&mut buf[..len])?;
+                AtomBuf::inline(&buf[..len], AtomKind::Bytes)
+            } else {
+                let start = read_u32(r)?;
+                let end = read_u32(r)?;
+                #[cfg(feature = "poison-allocator")]
+                if start == FREED {
+                    atom_vec.push(AtomBuf::heap(start, end, AtomKind::Bytes));
+                    continue;
+                }
+                if start > end || end as usize > heap_size {
+                    return Err(bad_image("atom out of bounds"));
+                }
+                AtomBuf::heap(start, end, AtomKind::Bytes)
+            };
+            let buf = match atom.range() {
+                Some((start, end)) => &u8_vec[start as usize..end as usize],
+                None => &atom.buf[..atom.len as usize],
+            };
+            atom.kind = match kind[0] & !IMAGE_INLINE_ATOM {
+                0 => AtomKind::Bytes,
+                1 => AtomKind::Int,
+                // atoms tagged as points are used without validation, so we
+                // have to make sure they really are valid points
+                2 => {
+                    if G1Element::from_bytes(buf.try_into().map_err(|_| bad_image("invalid G1"))?)
+                        .is_err()
+                    {
+                        return Err(bad_image("invalid G1"));
+                    }
+                    AtomKind::G1
+                }
+                3 => {
+                    if G2Element::from_bytes(buf.try_into().map_err(|_| bad_image("invalid G2"))?)
+                        .is_err()
+                    {
+                        return Err(bad_image("invalid G2"));
+                    }
+                    AtomKind::G2
+                }
+                _ => {
+                    return Err(bad_image("unknown atom kind"));
+                }
+            };
+            atom_vec.push(atom);
+        }
+
+        let num_pairs = read_u32(r)? as usize;
+        if num_pairs < dead_pairs || num_pairs - dead_pairs > MAX_NUM_PAIRS {
+            return Err(bad_image("too many pairs"));
+        }
+        let mut pair_vec = NodeVec::<IntPair>::default();
+        for idx in 0..num_pairs {
+            let mut pair = [NodePtr::NIL; 2];
+            for node in &mut pair {
+                let val = read_node(r)?;
+                #[cfg(feature = "poison-allocator")]
+                if val == FREED_NODE {
+                    *node = NodePtr::raw(val);
+                    continue;
+                }
+                if (val >> NODE_PTR_IDX_BITS) > 2 {
+                    return Err(bad_image("invalid node"));
+                }
+                *node = NodePtr::raw(val);
+                // pairs can only refer to nodes that were created before them
+                let valid = match node.object_type() {
+                    ObjectType::Pair => (node.index() as usize) < idx,
+                    ObjectType::Bytes => (node.index() as usize) < num_atoms,
+                    ObjectType::SmallAtom => true,
+                };
+                if !valid {
+                    return Err(bad_image("pair refers to unknown node"));
+                }
+            }
+            pair_vec.push(IntPair {
+                first: pair[0],
+                rest: pair[1],
+            });
+        }
 
-    #[cfg(feature = "counters")]
-    pub fn atom_count(&self) -> usize {
-        self.atom_vec.len()
-    }
+        #[cfg(feature = "debug-allocator")]
+        let generations = {
+            let count = read_u32(r)? as usize;
+            if count == 0 {
+                return Err(bad_image("no generations"));
+            }
+            let mut generations = Vec::with_capacity(count.min(1024));
+            for _ in 0..count {
+                generations.push(Generation {
+                    tag: read_u32(r)?,
+                    pairs: read_u32(r)? as usize,
+                    atoms: read_u32(r)? as usize,
+                });
+            }
+            generations
+        };
 
-    #[cfg(feature = "counters")]
-    pub fn small_atom_count(&self) -> usize {
-        self.small_atoms
+        #[allow(unused_mut)]
+        let mut a = Self {
+            u8_vec,
+            pair_vec,
+            atom_vec,
+            inline_heap,
+            heap_limit,
+            atom_limit: usize::MAX,
+            small_atoms,
+            external: Vec::new(),
+            external_heap: 0,
+            ropes: Vec::new(),
+            rope_heap: 0,
+            peak: (0, 0, 0),
+            g1_points: NodeMap::new(),
+            g2_points: NodeMap::new(),
+            interned: None,
+            #[cfg(feature = "poison-allocator")]
+            poison,
+            #[cfg(feature = "debug-allocator")]
+            generations,
+            #[cfg(feature = "debug-allocator")]
+            dead_generations: Vec::new(),
+        };
+        #[cfg(feature = "debug-allocator")]
+        for idx in 0..a.pair_vec.len() {
+            let pair = a.pair_vec[idx];
+            a.pair_vec[idx] = IntPair {
+                first: a.tag_node(pair.first),
+                rest: a.tag_node(pair.rest),
+            };
+        }
+        Ok(a)
     }
 
-    #[cfg(feature = "counters")]
-    pub fn pair_count(&self) -> usize {
-        self.pair_vec.len()
+    // like save(), followed by the roots, e.g. the deserialized puzzles the
+    // image is a cache of. load_with_roots() returns them along with the
+    // allocator
+    pub fn save_with_roots<W: Write>(&self, w: &mut W, roots: &[NodePtr]) -> io::Result<()> {
+        self.save(w)?;
+        w.write_all(&(roots.len() as u32).to_be_bytes())?;
+        for root in roots {
+            w.write_all(&root.0.to_be_bytes())?;
+        }
+        Ok(())
     }
 
-    #[cfg(feature = "counters")]
-    pub fn heap_size(&self) -> usize {
-        self.u8_vec.len()
+    // loads an allocator and its roots written by save_with_roots(). Like
+    // the nodes in the image, the roots are validated
+    pub fn load_with_roots<R: Read>(r: &mut R) -> io::Result<(Self, Vec<NodePtr>)> {
+        let a = Self::load(r)?;
+        let num_roots = read_u32(r)? as usize;
+        // don't trust the count for the allocation, every root takes 4 bytes
+        // of the image
+        let mut roots = Vec::with_capacity(num_roots.min(1024));
+        for _ in 0..num_roots {
+            let val = read_node(r)?;
+            if (val >> NODE_PTR_IDX_BITS) > 2 {
+                return Err(bad_image("invalid node"));
+            }
+            let node = NodePtr::raw(val);
+            let valid = match node.object_type() {
+                ObjectType::Pair => (node.index() as usize) < a.pair_vec.len(),
+                ObjectType::Bytes => (node.index() as usize) < a.atom_vec.len(),
+                ObjectType::SmallAtom => true,
+            };
+            if !valid {
+                return Err(bad_image("root refers to unknown node"));
+            }
+            #[cfg(feature = "debug-allocator")]
+            let node = a.tag_node(node);
+            roots.push(node);
+        }
+        Ok((a, roots))
     }
 }
 
@@ -825,11 +2660,31 @@ fn test_number_pair() {
 #[test]
 #[should_panic]
 fn test_invalid_node_ptr_type() {
-    let node = NodePtr(3 << NODE_PTR_IDX_BITS);
+    let node = NodePtr::raw(3 << NODE_PTR_IDX_BITS);
     // unknown NodePtr type
     let _ = node.object_type();
 }
 
+#[test]
+fn test_node_ptr_size() {
+    #[cfg(not(feature = "large-heap"))]
+    assert_eq!(std::mem::size_of::<NodePtrRepr>(), 4);
+    #[cfg(feature = "large-heap")]
+    assert_eq!(std::mem::size_of::<NodePtrRepr>(), 8);
+
+    let idx = NODE_PTR_IDX_MASK as usize;
+    let node = NodePtr::new(ObjectType::Bytes, idx);
+    assert_eq!(node.object_type(), ObjectType::Bytes);
+    assert_eq!(node.index() as usize, idx);
+
+    // small atoms are limited to 26 bits either way
+    let mut a = Allocator::new();
+    let small = a.new_small_number(SMALL_ATOM_MASK as u64).unwrap();
+    assert_eq!(small.object_type(), ObjectType::SmallAtom);
+    let big = a.new_small_number(SMALL_ATOM_MASK as u64 + 1).unwrap();
+    assert_eq!(big.object_type(), ObjectType::Bytes);
+}
+
 #[cfg(dbg)]
 #[test]
 #[should_panic]
@@ -837,12 +2692,24 @@ fn test_node_ptr_overflow() {
     NodePtr::new(ObjectType::Bytes, NODE_PTR_IDX_MASK + 1);
 }
 
-#[cfg(dbg)]
-#[test]
-#[should_panic]
-fn test_invalid_small_number() {
+#[cfg(test)]
+#[rstest]
+#[case(0, &[])]
+#[case(0x7f, &[0x7f])]
+#[case(0x80, &[0x00, 0x80])]
+#[case(0x3ffffff, &[0x03, 0xff, 0xff, 0xff])]
+// too large for a small atom
+#[case(0x4000000, &[0x04, 0x00, 0x00, 0x00])]
+#[case(0x80000000, &[0x00, 0x80, 0x00, 0x00, 0x00])]
+#[case(u64::MAX, &[0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff])]
+fn test_new_small_number_canonical(#[case] value: u64, #[case] expected: &[u8]) {
     let mut a = Allocator::new();
-    a.new_small_number(NODE_PTR_IDX_MASK + 1);
+    let atom = a.new_small_number(value).expect("new_small_number()");
+    assert_eq!(a.atom(atom).as_ref(), expected);
+    assert_eq!(a.number(atom), value.into());
+    assert_eq!(a.atom_kind(atom), Some(AtomKind::Int));
+    let atom = a.new_number(value.into()).expect("new_number()");
+    assert_eq!(a.atom(atom).as_ref(), expected);
 }
 
 #[cfg(test)]
@@ -877,6 +2744,22 @@ fn test_one() {
     assert_eq!(a.sexp(a.one()), SExp::Atom);
 }
 
+#[test]
+fn test_small() {
+    let mut a = Allocator::new();
+    assert_eq!(a.small(0), a.nil());
+    assert_eq!(a.small(1), a.one());
+    let before = a.small_atoms;
+    for v in 0..=255_u8 {
+        let node = a.small(v);
+        assert_eq!(node, a.new_small_number(v as u64).unwrap());
+        assert_eq!(a.number(node), Number::from(v));
+    }
+    assert_eq!(a.atom(a.small(0x80)).as_ref(), [0, 0x80]);
+    // only new_small_number() counted towards the limit
+    assert_eq!(a.small_atoms, before + 256);
+}
+
 #[test]
 fn test_allocate_atom() {
     let mut a = Allocator::new();
@@ -907,6 +2790,38 @@ fn test_allocate_heap_limit() {
     let _atom = a.new_atom(b"fooba").unwrap();
 }
 
+#[test]
+fn test_new_with_flags() {
+    use crate::chia_dialect::MEMPOOL_MODE;
+
+    let a = Allocator::new_with_flags(MEMPOOL_MODE);
+    assert_eq!(a.heap_limit, LIMITED_HEAP_SIZE - 1);
+    let a = Allocator::new_with_flags(0);
+    assert_eq!(a.heap_limit, Allocator::new().heap_limit);
+}
+
+#[test]
+fn test_atom_size_limit() {
+    let mut a = Allocator::new();
+    a.set_atom_limit(5);
+    assert_eq!(a.new_atom(b"foobar").unwrap_err().1, "atom too large");
+    let atom = a.new_atom(b"fooba").unwrap();
+
+    assert_eq!(
+        a.new_concat(6, &[atom, a.one()]).unwrap_err().1,
+        "atom too large"
+    );
+    a.new_concat(5, &[atom]).unwrap();
+
+    assert_eq!(
+        a.new_number(0x10000000000_u64.into()).unwrap_err().1,
+        "atom too large"
+    );
+    a.new_number(0x100000000_u64.into()).unwrap();
+}
+
+// with large-heap, the limits are too high to reach in a test
+#[cfg(not(feature = "large-heap"))]
 #[test]
 fn test_allocate_atom_limit() {
     let mut a = Allocator::new();
@@ -920,6 +2835,8 @@ fn test_allocate_atom_limit() {
     assert_eq!(a.small_atoms, MAX_NUM_ATOMS);
 }
 
+// with large-heap, the limits are too high to reach in a test
+#[cfg(not(feature = "large-heap"))]
 #[test]
 fn test_allocate_small_number_limit() {
     let mut a = Allocator::new();
@@ -933,6 +2850,8 @@ fn test_allocate_small_number_limit() {
     assert_eq!(a.small_atoms, MAX_NUM_ATOMS);
 }
 
+// with large-heap, the limits are too high to reach in a test
+#[cfg(not(feature = "large-heap"))]
 #[test]
 fn test_allocate_substr_limit() {
     let mut a = Allocator::new();
@@ -947,6 +2866,8 @@ fn test_allocate_substr_limit() {
     assert_eq!(a.small_atoms, MAX_NUM_ATOMS);
 }
 
+// with large-heap, the limits are too high to reach in a test
+#[cfg(not(feature = "large-heap"))]
 #[test]
 fn test_allocate_concat_limit() {
     let mut a = Allocator::new();
@@ -961,6 +2882,58 @@ fn test_allocate_concat_limit() {
     assert_eq!(a.small_atoms, MAX_NUM_ATOMS);
 }
 
+#[test]
+fn test_inline_atoms() {
+    // the number of bytes counting towards the heap limit, stored in u8_vec
+    // and inline respectively
+    fn heap(a: &Allocator) -> (usize, usize) {
+        let cp = a.checkpoint();
+        (cp.u8s - cp.inline_u8s, cp.inline_u8s)
+    }
+
+    // the heap limit is 39 bytes
+    let mut a = Allocator::new_limited(40);
+    let cp = a.checkpoint();
+
+    // inline atoms count towards the heap limit, but don't use u8_vec
+    let short = a.new_atom(&[0xff; 8]).unwrap();
+    let long = a.new_atom(&[0xff; 9]).unwrap();
+    assert_eq!(heap(&a), (9, 8));
+
+    // substrings don't count towards the heap limit. Short ones are inline
+    let sub1 = a.new_substr(long, 1, 9).unwrap();
+    let sub2 = a.new_substr(short, 0, 4).unwrap();
+    assert!(a.atom_eq(sub1, short));
+    assert_eq!(a.atom(sub2).as_ref(), &[0xff; 4]);
+    assert_eq!(a.atom_len(sub2), 4);
+    assert_eq!(heap(&a), (9, 8));
+
+    // concatenations of up to 8 bytes are inline
+    let cat1 = a.new_concat(8, &[sub2, sub2]).unwrap();
+    assert!(a.atom_eq(cat1, short));
+    assert_eq!(heap(&a), (9, 16));
+    let cat2 = a.new_concat(9, &[a.one(), short]).unwrap();
+    assert_eq!(
+        a.atom(cat2).as_ref(),
+        &[1, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]
+    );
+    assert_eq!(heap(&a), (18, 16));
+
+    // the heap limit applies to inline atoms
+    assert_eq!(a.new_atom(&[0xff; 6]).unwrap_err().1, "out of memory");
+    let n = a.new_atom(&[0xff; 5]).unwrap();
+    assert_eq!(a.number(n), (-1).into());
+    assert_eq!(heap(&a), (18, 21));
+
+    a.restore_checkpoint(&cp);
+    assert_eq!(heap(&a), (0, 0));
+    a.new_atom(&[0xff; 8]).unwrap();
+    a.new_atom(&[0xff; 31]).unwrap();
+    assert_eq!(a.new_atom(&[0xff; 1]).unwrap_err().1, "out of memory");
+}
+
+// with large-heap, the limits are too high to reach in a test
+#[cfg(not(feature = "large-heap"))]
 #[test]
 fn test_allocate_pair_limit() {
     let mut a = Allocator::new();
@@ -1235,8 +3208,143 @@ fn test_checkpoints() {
     let atom3 = a.new_atom(&[6, 5, 4, 3]).unwrap();
     assert!(a.atom(atom3).as_ref() == [6, 5, 4, 3]);
 
-    // since atom2 was removed, atom3 should actually be using that slot
+    // since atom2 was removed, atom3 should actually be using that slot.
+    // With poison-allocator, freed slots are never reused
+    #[cfg(not(feature = "poison-allocator"))]
     assert_eq!(atom2, atom3);
+    #[cfg(feature = "poison-allocator")]
+    assert_ne!(atom2, atom3);
+}
+
+// with debug-allocator, check_node() panics first
+#[cfg(all(feature = "poison-allocator", not(feature = "debug-allocator")))]
+#[test]
+#[should_panic(expected = "atom accessed after being freed by restore_checkpoint()")]
+fn test_poison_stale_atom() {
+    let mut a = Allocator::new();
+    let cp = a.checkpoint();
+    let atom = a.new_atom(&[6, 5, 4, 3]).unwrap();
+    a.restore_checkpoint(&cp);
+    a.new_atom(&[1, 2, 3, 4]).unwrap();
+    a.atom(atom);
+}
+
+// with debug-allocator, check_node() panics first
+#[cfg(all(feature = "poison-allocator", not(feature = "debug-allocator")))]
+#[test]
+#[should_panic(expected = "pair accessed after being freed by restore_checkpoint()")]
+fn test_poison_stale_pair() {
+    let mut a = Allocator::new();
+    let cp = a.checkpoint();
+    let pair = a.new_pair(a.nil(), a.one()).unwrap();
+    a.restore_checkpoint(&cp);
+    a.new_pair(a.one(), a.nil()).unwrap();
+    a.sexp(pair);
+}
+
+#[cfg(feature = "poison-allocator")]
+#[test]
+#[should_panic(expected = "atom canary overwritten")]
+fn test_poison_canary() {
+    let mut a = Allocator::new();
+    // inline atoms don't have canaries, this one is too large to be inline
+    let atom = a.new_atom(&[9, 8, 7, 6, 5, 4, 3, 2, 1]).unwrap();
+    // simulate a write past the end of the atom
+    let (_, end) = a.atom_vec[atom.index() as usize]
+        .range()
+        .expect("expected heap atom");
+    a.u8_vec[end as usize] = 0;
+    a.atom(atom);
+}
+
+#[cfg(feature = "poison-allocator")]
+#[test]
+fn test_poison_limits() {
+    // the freed memory and canaries don't count towards the heap limit
+    let mut a = Allocator::new_limited(100);
+    let cp = a.checkpoint();
+    for _ in 0..10 {
+        let atom = a.new_atom(&[0xff; 80]).unwrap();
+        a.new_substr(atom, 0, 10).unwrap();
+        a.new_pair(atom, atom).unwrap();
+        assert_eq!(a.checkpoint().u8s, 80);
+        assert_eq!(a.checkpoint().atoms, 2);
+        assert_eq!(a.checkpoint().pairs, 1);
+        a.restore_checkpoint(&cp);
+        assert_eq!(a.u8_vec[a.u8_vec.len() - 1], POISON);
+    }
+    assert_eq!(a.new_atom(&[0xff; 100]).unwrap_err().1, "out of memory");
+    a.new_atom(&[0xff; 99]).unwrap();
+}
+
+#[cfg(feature = "debug-allocator")]
+#[test]
+#[should_panic(expected = "used after being freed by restore_checkpoint() or reset()")]
+fn test_debug_stale_atom() {
+    let mut a = Allocator::new();
+    let cp = a.checkpoint();
+    let atom = a.new_atom(&[1; 40]).unwrap();
+    a.restore_checkpoint(&cp);
+    // the slot is reused, but the new atom is from a later generation
+    a.new_atom(&[2; 40]).unwrap();
+    a.atom(atom);
+}
+
+#[cfg(feature = "debug-allocator")]
+#[test]
+#[should_panic(expected = "used after being freed by restore_checkpoint() or reset()")]
+fn test_debug_reset() {
+    let mut a = Allocator::new();
+    let atom = a.new_atom(&[1; 40]).unwrap();
+    let pair = a.new_pair(atom, atom).unwrap();
+    a.reset();
+    a.sexp(pair);
+}
+
+#[cfg(feature = "debug-allocator")]
+#[test]
+#[should_panic(expected = "belongs to a different allocator")]
+fn test_debug_other_allocator() {
+    let mut a = Allocator::new();
+    let mut b = Allocator::new();
+    let atom = a.new_atom(&[1; 40]).unwrap();
+    b.new_atom(&[2; 40]).unwrap();
+    b.atom(atom);
+}
+
+#[cfg(feature = "debug-allocator")]
+#[test]
+fn test_debug_generations() {
+    let mut a = Allocator::new();
+    let atom1 = a.new_atom(&[1; 40]).unwrap();
+    let cp1 = a.checkpoint();
+    let atom2 = a.new_atom(&[2; 40]).unwrap();
+    let cp2 = a.checkpoint();
+    let pair = a.new_pair(atom1, atom2).unwrap();
+    a.restore_checkpoint(&cp2);
+    // the nodes allocated before the checkpoint stay valid
+    let pair2 = a.new_pair(atom2, atom1).unwrap();
+    assert_eq!(a.atom(atom2).as_ref(), [2; 40]);
+    assert_eq!(a.sexp(pair2), SExp::Pair(atom2, atom1));
+    // NodePtrs compare by their slot, regardless of generation. With
+    // poison-allocator, freed slots are never reused
+    #[cfg(not(feature = "poison-allocator"))]
+    assert_eq!(pair, pair2);
+    #[cfg(feature = "poison-allocator")]
+    assert_ne!(pair, pair2);
+
+    // loading an image continues its generations
+    let mut image = Vec::new();
+    a.save(&mut image).unwrap();
+    let b = Allocator::load(&mut &image[..]).unwrap();
+    assert_eq!(b.atom(atom1).as_ref(), [1; 40]);
+    let SExp::Pair(first, _) = b.sexp(pair2) else {
+        panic!("expected pair");
+    };
+    assert_eq!(b.atom(first).as_ref(), [2; 40]);
+
+    a.restore_checkpoint(&cp1);
+    assert_eq!(a.atom(atom1).as_ref(), [1; 40]);
 }
 
 #[cfg(test)]
@@ -1286,6 +3394,40 @@ fn test_point_atom_pair(#[case] fun: TestFun, #[case] expected: &str) {
     assert_eq!(r.1, expected.to_string());
 }
 
+#[test]
+fn test_try_g1_g2() {
+    let mut a = Allocator::new();
+
+    // atoms that aren't valid points are not errors
+    for size in [0, 3, 47, 48, 49, 95, 96, 97] {
+        let n = a.new_atom(&vec![0xcc; size]).unwrap();
+        assert!(a.try_g1(n).unwrap().is_none());
+        assert!(a.try_g2(n).unwrap().is_none());
+    }
+    assert!(a.try_g1(a.one()).unwrap().is_none());
+    assert!(a.try_g2(a.one()).unwrap().is_none());
+
+    // but pairs are
+    let n = a.new_pair(a.nil(), a.one()).unwrap();
+    assert_eq!(a.try_g1(n).unwrap_err().1, "pair found, expected G1 point");
+    assert_eq!(a.try_g2(n).unwrap_err().1, "pair found, expected G2 point");
+
+    let g1 = a.new_g1(G1Element::generator()).unwrap();
+    let g2 = a.new_g2(G2Element::generator()).unwrap();
+    assert_eq!(a.try_g1(g1).unwrap(), Some(G1Element::generator()));
+    assert_eq!(a.try_g2(g2).unwrap(), Some(G2Element::generator()));
+    assert!(a.try_g1(g2).unwrap().is_none());
+    assert!(a.try_g2(g1).unwrap().is_none());
+
+    // valid points that weren't created by new_g1() / new_g2()
+    let buf = G1Element::generator().to_bytes();
+    let n = a.new_atom(&buf).unwrap();
+    assert_eq!(a.try_g1(n).unwrap(), Some(G1Element::generator()));
+    let buf = G2Element::generator().to_bytes();
+    let n = a.new_atom(&buf).unwrap();
+    assert_eq!(a.try_g2(n).unwrap(), Some(G2Element::generator()));
+}
+
 #[cfg(test)]
 #[rstest]
 #[case(
@@ -1726,7 +3868,9 @@ fn test_number_roundtrip(#[case] value: Number) {
 #[case(0x3ffffff)]
 fn test_small_number_roundtrip(#[case] value: u32) {
     let mut a = Allocator::new();
-    let atom = a.new_small_number(value).expect("new_small_number()");
+    let atom = a
+        .new_small_number(value.into())
+        .expect("new_small_number()");
     assert_eq!(a.small_number(atom).expect("small_number()"), value);
 }
 
@@ -1833,3 +3977,693 @@ fn test_auto_small_number_from_buf(#[case] buf: &[u8], #[case] expect_small: boo
 fn test_fits_in_small_atom(#[case] buf: &[u8], #[case] expected: Option<u32>) {
     assert_eq!(fits_in_small_atom(buf), expected);
 }
+
+#[test]
+fn test_freeze() {
+    use crate::serde::{node_to_bytes, tree_hash};
+    use std::thread;
+
+    let mut a = Allocator::new();
+    let mut list = a.nil();
+    for i in 0..1000_u32 {
+        let atom = a.new_atom(&i.to_be_bytes().repeat(4)).unwrap();
+        list = a.new_pair(atom, list).unwrap();
+    }
+    let expected = (node_to_bytes(&a, list).unwrap(), tree_hash(&a, list));
+
+    let frozen = a.freeze();
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let frozen = frozen.clone();
+            thread::spawn(move || {
+                (
+                    node_to_bytes(&frozen, list).unwrap(),
+                    tree_hash(&frozen, list),
+                )
+            })
+        })
+        .collect();
+    for t in threads {
+        assert_eq!(t.join().unwrap(), expected);
+    }
+
+    // a thawed copy can be modified, without affecting the frozen allocator
+    let mut b = frozen.thaw();
+    let atoms = frozen.stats().atoms;
+    let pair = b.new_pair(list, list).unwrap();
+    assert_eq!(b.stats().atoms, atoms);
+    assert_eq!(b.stats().pairs, frozen.stats().pairs + 1);
+    let SExp::Pair(first, _) = b.sexp(pair) else {
+        panic!("expected pair");
+    };
+    assert_eq!(node_to_bytes(&b, first).unwrap(), expected.0);
+
+    let frozen = frozen.try_unwrap().unwrap().freeze();
+    let clone = frozen.clone();
+    let frozen = frozen.try_unwrap().unwrap_err();
+    drop(clone);
+    assert!(frozen.try_unwrap().is_ok());
+}
+
+#[test]
+fn test_with_capacity() {
+    let mut a = Allocator::with_capacity(1000, 10_000_000, 2000);
+    assert!(a.atom_vec.capacity() >= 1000);
+    assert!(a.u8_vec.capacity() >= 10_000_000);
+    assert!(a.pair_vec.capacity() >= 2000);
+
+    // it behaves like new()
+    let atom = a.new_atom(&[1; 100]).unwrap();
+    let pair = a.new_pair(atom, atom).unwrap();
+    assert_eq!(a.sexp(pair), SExp::Pair(atom, atom));
+    assert_eq!(a.stats().max_atom_bytes, 100);
+}
+
+#[test]
+fn test_copy_node() {
+    use crate::serde::node_to_bytes;
+
+    let mut a = Allocator::new();
+    let shared: Arc<[u8]> = vec![3; 50].into();
+    let external = a.new_atom_shared(&shared, 0, 50).unwrap();
+    let g1 = a.new_g1(G1Element::generator()).unwrap();
+    let big = a.new_atom(&[1; 100]).unwrap();
+    let small = a.new_small_number(1000).unwrap();
+    let mut list = a.nil();
+    for node in [external, g1, big, small] {
+        list = a.new_pair(node, list).unwrap();
+    }
+    // the list occurs twice
+    let tree = a.new_pair(list, list).unwrap();
+
+    let mut b = Allocator::new();
+    b.new_atom(&[2; 20]).unwrap();
+    let copy = copy_node(&a, tree, &mut b).unwrap();
+    assert_eq!(
+        node_to_bytes(&b, copy).unwrap(),
+        node_to_bytes(&a, tree).unwrap()
+    );
+    let Some((first, rest)) = b.next(copy) else {
+        panic!("expected pair");
+    };
+    assert_eq!(first, rest);
+    assert_eq!(b.pair_vec.len(), 5);
+    assert_eq!(b.external.len(), 1);
+    assert!(Arc::ptr_eq(&b.external[0].1, &shared));
+    let Some((g1_copy, _)) = b.next(b.next(b.next(first).unwrap().1).unwrap().1) else {
+        panic!("expected pair");
+    };
+    assert_eq!(b.atom_kind(g1_copy), Some(AtomKind::G1));
+
+    // the copy counts towards the limits
+    let mut c = Allocator::new_limited(100);
+    assert_eq!(copy_node(&a, tree, &mut c).unwrap_err().1, "out of memory");
+}
+
+#[test]
+fn test_iter_reachable() {
+    let mut a = Allocator::new();
+    let atom = a.new_atom(&[1; 10]).unwrap();
+    let one = a.one();
+    let inner = a.new_pair(atom, one).unwrap();
+    // inner is reachable twice, but only visited once
+    let rest = a.new_pair(inner, NodePtr::NIL).unwrap();
+    let root = a.new_pair(inner, rest).unwrap();
+    // not reachable from root
+    a.new_pair(one, one).unwrap();
+
+    let nodes: Vec<NodePtr> = a.iter_reachable(root).collect();
+    assert_eq!(nodes, [root, inner, atom, one, rest, NodePtr::NIL]);
+    assert_eq!(a.iter_reachable(atom).collect::<Vec<_>>(), [atom]);
+}
+
+#[test]
+fn test_compact() {
+    use crate::serde::node_to_bytes;
+
+    let mut a = Allocator::new();
+    let garbage = a.new_atom(&[0xaa; 5000]).unwrap();
+    a.new_pair(garbage, garbage).unwrap();
+    let big = a.new_atom(&[1; 100]).unwrap();
+    // shares its bytes with big
+    let substr = a.new_substr(big, 10, 90).unwrap();
+    let inline = a.new_atom(&[2; 5]).unwrap();
+    let small = a.new_small_number(1000).unwrap();
+    let shared: Arc<[u8]> = vec![3; 50].into();
+    let external = a.new_atom_shared(&shared, 0, 50).unwrap();
+    let g1 = a.new_g1(G1Element::generator()).unwrap();
+    let part = a.new_atom(&[4; 1000]).unwrap();
+    let rope = a
+        .new_concat(
+            2000,
+            &[part, big, big, big, big, big, big, big, big, big, big],
+        )
+        .unwrap();
+    let rope_substr = a.new_substr(rope, 1000, 1100).unwrap();
+    a.new_pair(garbage, rope).unwrap();
+
+    let mut tree = a.nil();
+    for node in [substr, inline, small, external, g1, rope_substr, substr] {
+        tree = a.new_pair(node, tree).unwrap();
+    }
+    let other = a.new_pair(big, a.nil()).unwrap();
+    let expected = node_to_bytes(&a, tree).unwrap();
+    let before = a.checkpoint();
+
+    let table = a.compact(&[tree, other]);
+    let tree = table.get(tree).unwrap();
+    assert_eq!(node_to_bytes(&a, tree).unwrap(), expected);
+    assert_eq!(
+        a.atom(table.get(other).and_then(|n| a.next(n)).unwrap().0)
+            .as_ref(),
+        [1; 100]
+    );
+    assert_eq!(
+        a.g1(table.get(g1).unwrap()).unwrap(),
+        G1Element::generator()
+    );
+    assert_eq!(table.get(small), Some(small));
+    assert_eq!(table.get(garbage), None);
+    assert_eq!(table.get(rope), None);
+
+    // 7 pairs in the tree, 1 in other. The atoms are big, substr, inline,
+    // external, g1 and rope_substr
+    let after = a.checkpoint();
+    assert_eq!(after.pairs, 8);
+    assert_eq!(after.atoms, 6);
+    // big (shared by substr), inline, external, g1 and rope_substr. With
+    // poison-allocator, substr has its own copy
+    #[cfg(not(feature = "poison-allocator"))]
+    assert_eq!(after.u8s, 100 + 5 + 50 + 48 + 100);
+    #[cfg(feature = "poison-allocator")]
+    assert_eq!(after.u8s, 100 + 80 + 5 + 50 + 48 + 100);
+    assert!(after.u8s < before.u8s);
+    assert!(a.ropes.is_empty());
+
+    // the allocator can be used as usual
+    let cp = a.checkpoint();
+    let pair = a.new_pair(tree, tree).unwrap();
+    assert_eq!(a.next(pair), Some((tree, tree)));
+    a.restore_checkpoint(&cp);
+    assert_eq!(a.compact(&[]).get(tree), None);
+    assert_eq!(a.checkpoint().u8s, 0);
+}
+
+#[test]
+fn test_reset() {
+    let mut a = Allocator::new_limited(1000);
+    a.set_atom_limit(100);
+    let shared: Arc<[u8]> = vec![0x55; 20].into();
+    let atom = a.new_atom(&[0x55; 20]).unwrap();
+    a.new_atom_shared(&shared, 0, 20).unwrap();
+    a.new_g1(G1Element::generator()).unwrap();
+    a.new_small_number(1000).unwrap();
+    a.new_pair(atom, atom).unwrap();
+    let capacity = a.u8_vec.capacity();
+
+    a.reset();
+    assert_eq!(a.stats(), Allocator::new().stats());
+    assert!(a.external.is_empty());
+    assert!(a.g1_points.is_empty());
+    assert_eq!(a.u8_vec.capacity(), capacity);
+    assert_eq!(a.atom(a.one()).as_ref(), [1]);
+
+    // the limits are kept
+    assert_eq!(a.new_atom(&[0; 101]).unwrap_err().1, "atom too large");
+    a.set_atom_limit(usize::MAX);
+    assert_eq!(a.new_atom(&[0; 1000]).unwrap_err().1, "out of memory");
+    let atom = a.new_atom(&[0x55; 20]).unwrap();
+    assert_eq!(a.atom(atom).as_ref(), [0x55; 20]);
+}
+
+#[test]
+fn test_interning() {
+    let mut a = Allocator::new_limited(1000);
+    let first = a.new_atom(&[0x55; 20]).unwrap();
+    assert_ne!(a.new_atom(&[0x55; 20]).unwrap(), first);
+
+    a.set_interning(true);
+    let first = a.new_atom(&[0x66; 20]).unwrap();
+    let stats = a.stats();
+    let cp = a.checkpoint();
+    let second = a.new_atom(&[0x66; 20]).unwrap();
+    assert_eq!(second, first);
+    assert_ne!(a.new_atom(&[0x66; 21]).unwrap(), first);
+    assert_ne!(
+        a.new_atom_with_kind(&[0x66; 20], AtomKind::G1).unwrap(),
+        first
+    );
+
+    // interned atoms count towards the limits like new ones
+    assert_eq!(a.stats().atoms, stats.atoms + 3);
+    assert_eq!(a.stats().atom_bytes, stats.atom_bytes + 61);
+    assert_eq!(a.new_atom(&[0x66; 900]).unwrap_err().1, "out of memory");
+
+    // atoms allocated after a checkpoint are forgotten when it's restored
+    a.new_atom(&[0x77; 20]).unwrap();
+    a.restore_checkpoint(&cp);
+    assert_eq!(a.stats().atoms, stats.atoms);
+    assert_eq!(a.stats().atom_bytes, stats.atom_bytes);
+    assert_eq!(a.new_atom(&[0x66; 20]).unwrap(), first);
+    let third = a.new_atom(&[0x77; 20]).unwrap();
+    assert_eq!(a.new_atom(&[0x77; 20]).unwrap(), third);
+    assert_eq!(a.atom(third).as_ref(), [0x77; 20]);
+
+    a.set_interning(false);
+    assert_ne!(a.new_atom(&[0x66; 20]).unwrap(), first);
+}
+
+#[test]
+fn test_stats() {
+    let mut a = Allocator::new();
+    let stats = a.stats();
+    // nil and one
+    assert_eq!((stats.pairs, stats.atoms, stats.atom_bytes), (0, 2, 0));
+
+    let atom = a.new_atom(&[0x55; 20]).unwrap();
+    a.new_small_number(1000).unwrap();
+    a.new_pair(atom, atom).unwrap();
+    let cp = a.checkpoint();
+    a.new_atom(&[0x55; 100]).unwrap();
+    a.new_pair(atom, atom).unwrap();
+    a.new_pair(atom, atom).unwrap();
+    let stats = a.stats();
+    assert_eq!((stats.pairs, stats.atoms, stats.atom_bytes), (3, 5, 120));
+    assert_eq!(
+        (stats.max_pairs, stats.max_atoms, stats.max_atom_bytes),
+        (3, 5, 120)
+    );
+
+    // restoring the checkpoint doesn't lower the high-water marks
+    a.restore_checkpoint(&cp);
+    a.new_atom(&[0x55; 10]).unwrap();
+    let stats = a.stats();
+    assert_eq!((stats.pairs, stats.atoms, stats.atom_bytes), (1, 5, 30));
+    assert_eq!(
+        (stats.max_pairs, stats.max_atoms, stats.max_atom_bytes),
+        (3, 5, 120)
+    );
+}
+
+#[test]
+fn test_new_atom_shared() {
+    let buf: Arc<[u8]> = (0..100_u8).collect::<Vec<u8>>().into();
+    let other: Arc<[u8]> = vec![0xaa; 20].into();
+    let mut a = Allocator::new_limited(1000);
+    let heap = a.heap_used();
+
+    // small atoms are copied
+    let small = a.new_atom_shared(&buf, 1, 2).unwrap();
+    assert_eq!(a.small_number(small), Some(1));
+    let inline = a.new_atom_shared(&buf, 0, 8).unwrap();
+    assert_eq!(a.atom(inline).as_ref(), &buf[0..8]);
+    assert!(a.external.is_empty());
+
+    let n1 = a.new_atom_shared(&buf, 10, 50).unwrap();
+    let n2 = a.new_atom_shared(&other, 0, 20).unwrap();
+    let n3 = a.new_atom_shared(&buf, 60, 100).unwrap();
+    assert_eq!(a.external.len(), 2);
+    assert_eq!(a.atom(n1).as_ref(), &buf[10..50]);
+    assert_eq!(a.atom(n2).as_ref(), &other[..]);
+    assert_eq!(a.atom(n3).as_ref(), &buf[60..100]);
+    assert_eq!(a.atom_len(n3), 40);
+    // they count towards the heap limit as if they were copied
+    assert_eq!(a.heap_used(), heap + 8 + 40 + 20 + 40);
+    assert_eq!(a.u8_vec.len(), 0);
+
+    // substrings share the buffer too, and concatenations are copies
+    let sub = a.new_substr(n1, 5, 30).unwrap();
+    assert_eq!(a.atom(sub).as_ref(), &buf[15..40]);
+    let cat = a.new_concat(68, &[inline, n2, n3]).unwrap();
+    let mut expected = buf[0..8].to_vec();
+    expected.extend_from_slice(&other);
+    expected.extend_from_slice(&buf[60..100]);
+    assert_eq!(a.atom(cat).as_ref(), expected);
+
+    // the image doesn't hold the external buffers
+    assert_eq!(
+        a.save(&mut Vec::new()).unwrap_err().kind(),
+        io::ErrorKind::Unsupported
+    );
+
+    let cp = a.checkpoint();
+    let heap = a.heap_used();
+    a.new_atom_shared(&buf, 0, 100).unwrap();
+    a.restore_checkpoint(&cp);
+    assert_eq!(a.heap_used(), heap);
+    assert_eq!(a.atom(n3).as_ref(), &buf[60..100]);
+
+    assert_eq!(
+        a.new_atom_shared(&buf, 0, 101).unwrap_err().1,
+        "(internal error) atom out of bounds"
+    );
+    let mut a = Allocator::new_limited(100);
+    assert_eq!(
+        a.new_atom_shared(&buf, 0, 100).unwrap_err().1,
+        "out of memory"
+    );
+    a.set_atom_limit(10);
+    assert_eq!(
+        a.new_atom_shared(&buf, 0, 11).unwrap_err().1,
+        "atom too large"
+    );
+}
+
+#[test]
+fn test_atom_kind() {
+    let mut a = Allocator::new();
+
+    // small atoms are always integers
+    assert_eq!(a.atom_kind(a.nil()), Some(AtomKind::Int));
+    let n = a.new_atom(&[0x7f]).unwrap();
+    assert_eq!(a.atom_kind(n), Some(AtomKind::Int));
+
+    let n = a.new_atom(&[0xff, 0xff]).unwrap();
+    assert_eq!(a.atom_kind(n), Some(AtomKind::Bytes));
+    let n = a.new_number((-1).into()).unwrap();
+    assert_eq!(a.atom_kind(n), Some(AtomKind::Int));
+    let n = a.new_number(0x1234567890_u64.into()).unwrap();
+    assert_eq!(a.atom_kind(n), Some(AtomKind::Int));
+
+    let g1 = a.new_g1(G1Element::generator()).unwrap();
+    assert_eq!(a.atom_kind(g1), Some(AtomKind::G1));
+    let g2 = a.new_g2(G2Element::generator()).unwrap();
+    assert_eq!(a.atom_kind(g2), Some(AtomKind::G2));
+
+    // the same bytes, not created by new_g1(), are just bytes
+    let g1_bytes = a.atom(g1).as_ref().to_vec();
+    let n = a.new_atom(&g1_bytes).unwrap();
+    assert_eq!(a.atom_kind(n), Some(AtomKind::Bytes));
+    assert_eq!(a.g1(n).unwrap(), G1Element::generator());
+    assert_eq!(a.g1(g1).unwrap(), G1Element::generator());
+    assert_eq!(a.g2(g2).unwrap(), G2Element::generator());
+
+    // derived atoms lose their kind
+    let n = a.new_substr(g1, 0, 48).unwrap();
+    assert_eq!(a.atom_kind(n), Some(AtomKind::Bytes));
+    let n = a.new_concat(96, &[g1, g1]).unwrap();
+    assert_eq!(a.atom_kind(n), Some(AtomKind::Bytes));
+
+    let p = a.new_pair(g1, g2).unwrap();
+    assert_eq!(a.atom_kind(p), None);
+}
+
+#[test]
+fn test_native_points() {
+    let mut a = Allocator::new();
+    let g1 = a.new_g1(G1Element::generator()).unwrap();
+    let g2 = a.new_g2(G2Element::generator()).unwrap();
+    assert_eq!(a.g1_points.len(), 1);
+    assert_eq!(a.g2_points.len(), 1);
+    assert_eq!(a.g1(g1).unwrap(), G1Element::generator());
+    assert_eq!(a.try_g1(g1).unwrap(), Some(G1Element::generator()));
+    assert_eq!(a.g2(g2).unwrap(), G2Element::generator());
+    assert_eq!(a.try_g2(g2).unwrap(), Some(G2Element::generator()));
+
+    // the points of atoms freed by restoring a checkpoint are dropped
+    let cp = a.checkpoint();
+    let mut point = G1Element::generator();
+    point.negate();
+    a.new_g1(point.clone()).unwrap();
+    a.new_g2(G2Element::default()).unwrap();
+    assert_eq!(a.g1_points.len(), 2);
+    a.restore_checkpoint(&cp);
+    assert_eq!(a.g1_points.len(), 1);
+    assert_eq!(a.g2_points.len(), 1);
+
+    // an atom allocated in place of a freed one isn't mistaken for its point
+    let n = a.new_atom(&[0xc0; 48]).unwrap();
+    assert_eq!(a.g1(n).unwrap_err().1, "atom is not a G1 point");
+    assert_eq!(a.g1(g1).unwrap(), G1Element::generator());
+}
+
+#[test]
+fn test_save_load() {
+    let mut a = Allocator::new_limited(1000000);
+    let n1 = a.new_atom(&[1, 2, 3, 4, 5]).unwrap();
+    let n2 = a.new_number(1337.into()).unwrap();
+    let n3 = a.new_number((-1000000000000_i64).into()).unwrap();
+    let g1 = a.new_g1(G1Element::generator()).unwrap();
+    let g2 = a.new_g2(G2Element::generator()).unwrap();
+    let p1 = a.new_pair(n1, n2).unwrap();
+    let p2 = a.new_pair(p1, g1).unwrap();
+    let p3 = a.new_pair(p2, a.nil()).unwrap();
+
+    let mut image = Vec::new();
+    a.save(&mut image).unwrap();
+    let mut b = Allocator::load(&mut image.as_slice()).unwrap();
+
+    // the same NodePtr values refer to the same nodes
+    assert_eq!(b.atom(n1).as_ref(), &[1, 2, 3, 4, 5]);
+    assert_eq!(b.number(n2), 1337.into());
+    assert_eq!(b.number(n3), (-1000000000000_i64).into());
+    assert_eq!(b.atom_kind(n3), Some(AtomKind::Int));
+    assert_eq!(b.atom_kind(g1), Some(AtomKind::G1));
+    assert_eq!(b.g1(g1).unwrap(), G1Element::generator());
+    assert_eq!(b.atom_kind(g2), Some(AtomKind::G2));
+    assert_eq!(b.g2(g2).unwrap(), G2Element::generator());
+    assert_eq!(b.sexp(p1), SExp::Pair(n1, n2));
+    assert_eq!(b.sexp(p2), SExp::Pair(p1, g1));
+    assert_eq!(b.sexp(p3), SExp::Pair(p2, b.nil()));
+
+    // and both allocators continue to allocate identically
+    assert_eq!(a.new_pair(p3, p1).unwrap(), b.new_pair(p3, p1).unwrap());
+    assert_eq!(a.new_atom(&[0xff; 10]), b.new_atom(&[0xff; 10]));
+    let cp_a = a.checkpoint();
+    let cp_b = b.checkpoint();
+    assert_eq!(cp_a.u8s, cp_b.u8s);
+    assert_eq!(cp_a.atoms, cp_b.atoms);
+    assert_eq!(cp_a.pairs, cp_b.pairs);
+    assert_eq!(cp_a.small_atoms, cp_b.small_atoms);
+    assert_eq!(a.heap_limit, b.heap_limit);
+
+    // a truncated image is an error
+    for len in [0, 8, 20, image.len() - 1] {
+        assert!(Allocator::load(&mut &image[..len]).is_err());
+    }
+}
+
+#[test]
+fn test_save_load_roots() {
+    use crate::serde::{node_from_bytes, node_to_bytes};
+
+    let mut a = Allocator::new();
+    // (a (q . (+ 2 5)) (q 7 8))
+    let puzzle = hex::decode("ff02ffff01ff10ff02ff0580ffff01ff07ff088080").unwrap();
+    let p1 = node_from_bytes(&mut a, &puzzle).unwrap();
+    let p2 = a.new_atom(&[1, 2, 3]).unwrap();
+    let roots = [p1, p2, a.nil()];
+
+    let mut image = Vec::new();
+    a.save_with_roots(&mut image, &roots).unwrap();
+    let (b, loaded) = Allocator::load_with_roots(&mut image.as_slice()).unwrap();
+    assert_eq!(loaded, roots);
+    assert_eq!(node_to_bytes(&b, loaded[0]).unwrap(), puzzle);
+    assert_eq!(b.atom(loaded[1]).as_ref(), [1, 2, 3]);
+
+    // an image without roots can't be loaded with them
+    let mut plain = Vec::new();
+    a.save(&mut plain).unwrap();
+    assert!(Allocator::load_with_roots(&mut plain.as_slice()).is_err());
+
+    // roots referring to nodes that aren't in the image
+    let mut image = Vec::new();
+    a.save_with_roots(&mut image, &[NodePtr::new(ObjectType::Pair, 1000)])
+        .unwrap();
+    assert_eq!(
+        Allocator::load_with_roots(&mut image.as_slice())
+            .unwrap_err()
+            .to_string(),
+        "invalid allocator image: root refers to unknown node"
+    );
+    let mut image = Vec::new();
+    a.save_with_roots(&mut image, &[NodePtr::raw(NodePtrRepr::MAX)])
+        .unwrap();
+    assert_eq!(
+        Allocator::load_with_roots(&mut image.as_slice())
+            .unwrap_err()
+            .to_string(),
+        "invalid allocator image: invalid node"
+    );
+}
+
+// the image layout is different with poison-allocator and large-heap
+#[cfg(not(any(feature = "poison-allocator", feature = "large-heap")))]
+#[test]
+fn test_load_invalid() {
+    let mut a = Allocator::new();
+    let n = a.new_atom(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]).unwrap();
+    let g1 = a.new_g1(G1Element::generator()).unwrap();
+    a.new_atom(&[0x80, 1, 2]).unwrap();
+    a.new_pair(n, g1).unwrap();
+    let mut image = Vec::new();
+    a.save(&mut image).unwrap();
+    assert!(Allocator::load(&mut image.as_slice()).is_ok());
+
+    let error = |image: &[u8]| {
+        Allocator::load(&mut &image[..])
+            .unwrap_err()
+            .get_ref()
+            .unwrap()
+            .to_string()
+    };
+
+    // layout: magic (8), heap_limit (8), small_atoms (8), inline heap (8),
+    // heap size (4), heap (58), atom count (4), 2 heap atoms (9 each),
+    // 1 inline atom (5), pair count (4), 1 pair (8). With debug-allocator,
+    // followed by the generation count (4) and 1 generation (12)
+    let atoms = 8 + 8 + 8 + 8 + 4 + 58 + 4;
+    let inline = atoms + 2 * 9;
+    let pairs = inline + 5 + 4;
+    let generations = if cfg!(feature = "debug-allocator") {
+        4 + 12
+    } else {
+        0
+    };
+    assert_eq!(image.len(), pairs + 8 + generations);
+
+    let mut bad = image.clone();
+    bad[0] = b'x';
+    assert_eq!(error(&bad), "invalid allocator image: unknown format");
+
+    // the inline atoms don't fit in the heap limit
+    let mut bad = image.clone();
+    bad[24..32].copy_from_slice(&(u32::MAX as u64).to_be_bytes());
+    assert_eq!(error(&bad), "invalid allocator image: heap exceeds limit");
+
+    // atom end past the end of the heap
+    let mut bad = image.clone();
+    bad[atoms + 5..atoms + 9].copy_from_slice(&59_u32.to_be_bytes());
+    assert_eq!(error(&bad), "invalid allocator image: atom out of bounds");
+
+    // unknown atom kind
+    let mut bad = image.clone();
+    bad[atoms] = 4;
+    assert_eq!(error(&bad), "invalid allocator image: unknown atom kind");
+
+    // the 10 byte atom tagged as a G1 point
+    let mut bad = image.clone();
+    bad[atoms] = 2;
+    assert_eq!(error(&bad), "invalid allocator image: invalid G1");
+
+    // an inline atom that's too large
+    let mut bad = image.clone();
+    bad[inline + 1] = INLINE_ATOM_SIZE as u8 + 1;
+    assert_eq!(
+        error(&bad),
+        "invalid allocator image: inline atom too large"
+    );
+
+    // a pair referring to itself
+    let mut bad = image.clone();
+    bad[pairs..pairs + 4].copy_from_slice(&0_u32.to_be_bytes());
+    assert_eq!(
+        error(&bad),
+        "invalid allocator image: pair refers to unknown node"
+    );
+
+    // a pair referring to an atom that doesn't exist
+    let mut bad = image.clone();
+    bad[pairs..pairs + 4].copy_from_slice(&NodePtr::new(ObjectType::Bytes, 3).0.to_be_bytes());
+    assert_eq!(
+        error(&bad),
+        "invalid allocator image: pair refers to unknown node"
+    );
+
+    // an invalid object type
+    let mut bad = image;
+    bad[pairs..pairs + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+    assert_eq!(error(&bad), "invalid allocator image: invalid node");
+}
+
+#[test]
+fn test_rope() {
+    let mut a = Allocator::new();
+    let x = a.new_atom(&[0x11; 600]).unwrap();
+    let y = a.new_atom(&[0x22; 600]).unwrap();
+
+    // smaller concatenations are copies
+    let small = a.new_concat(601, &[x, a.one()]).unwrap();
+    assert_eq!(a.atom_len(small), 601);
+    assert!(a.ropes.is_empty());
+
+    let heap = a.heap_used();
+    let cat = a.new_concat(1200, &[x, a.nil(), y]).unwrap();
+    assert_eq!(a.ropes.len(), 1);
+    // empty atoms aren't parts of the rope
+    assert_eq!(a.ropes[0].parts.len(), 2);
+    // ropes count towards the heap limit as if they were copied
+    assert_eq!(a.heap_used(), heap + 1200);
+    assert_eq!(a.atom_len(cat), 1200);
+    assert!(a.ropes[0].bytes.get().is_none());
+
+    // build a large atom by repeatedly concatenating to it
+    let mut expected = [[0x11; 600], [0x22; 600]].concat();
+    let mut acc = cat;
+    for i in 0..40_u32 {
+        let len = expected.len() + 600 + 3;
+        let n = a.new_number((1000000 + i).into()).unwrap();
+        acc = a.new_concat(len, &[acc, y, n]).unwrap();
+        expected.extend_from_slice(&[0x22; 600]);
+        expected.extend_from_slice(a.atom(n).as_ref());
+    }
+    assert!(a.ropes.iter().all(|rope| rope.depth <= MAX_ROPE_DEPTH));
+    // most of the ropes were never flattened
+    let flattened = a.ropes.iter().filter(|r| r.bytes.get().is_some()).count();
+    assert!(flattened < 10);
+    assert_eq!(a.atom(acc).as_ref(), expected);
+    assert_eq!(a.atom(cat).as_ref(), &expected[..1200]);
+    assert!(a.atom_eq(acc, acc));
+
+    // substrings refer to the flattened rope, and don't count towards the
+    // heap limit
+    let heap = a.heap_used();
+    let sub = a.new_substr(acc, 500, 1500).unwrap();
+    assert_eq!(a.atom(sub).as_ref(), &expected[500..1500]);
+    let short = a.new_substr(acc, 1195, 1205).unwrap();
+    assert_eq!(a.atom(short).as_ref(), &expected[1195..1205]);
+    assert_eq!(a.heap_used(), heap);
+    let sub_cat = a.new_concat(1010, &[sub, short]).unwrap();
+    assert_eq!(
+        a.atom(sub_cat).as_ref(),
+        [&expected[500..1500], &expected[1195..1205]].concat()
+    );
+    let copy = a.new_concat(20, &[short, short]).unwrap();
+    assert_eq!(
+        a.atom(copy).as_ref(),
+        [&expected[1195..1205], &expected[1195..1205]].concat()
+    );
+
+    let pair = a.new_pair(x, y).unwrap();
+    assert_eq!(
+        a.new_concat(1200, &[x, pair]).unwrap_err().1,
+        "(internal error) concat expected atom, got pair"
+    );
+    assert_eq!(
+        a.new_concat(1100, &[x, y]).unwrap_err().1,
+        "(internal error) concat passed invalid new_size"
+    );
+
+    // the image holds ropes as heap atoms
+    let mut image = Vec::new();
+    a.save(&mut image).unwrap();
+    let b = Allocator::load(&mut image.as_slice()).unwrap();
+    assert!(b.ropes.is_empty());
+    assert_eq!(b.heap_used(), a.heap_used());
+    for node in [cat, acc, sub, short, sub_cat, copy] {
+        assert_eq!(b.atom(node).as_ref(), a.atom(node).as_ref());
+    }
+
+    // ropes are dropped along with their atoms
+    let cp = a.checkpoint();
+    let heap = a.heap_used();
+    let ropes = a.ropes.len();
+    a.new_concat(2 * expected.len(), &[acc, acc]).unwrap();
+    a.new_concat(1200, &[x, y]).unwrap();
+    a.restore_checkpoint(&cp);
+    assert_eq!(a.heap_used(), heap);
+    assert_eq!(a.ropes.len(), ropes);
+    let cat2 = a.new_concat(1200, &[y, x]).unwrap();
+    assert_eq!(a.atom(cat2).as_ref(), [[0x22; 600], [0x11; 600]].concat());
+    assert_eq!(a.atom(acc).as_ref(), expected);
+}
@@ -0,0 +1,191 @@
+use crate::allocator::Allocator;
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
+// A pool of allocators, for services running many programs, possibly on
+// several threads. Constructing an allocator reserves its buffers up front,
+// and reusing one without resetting it keeps every node ever allocated in it.
+// The pool hands out allocators that have been reset, but kept their buffers,
+// and resets them again when they're returned.
+//
+// An allocator that grew larger than max_retained_heap (e.g. for one very
+// large generator) is dropped when it's returned, rather than holding on to
+// its buffers for the lifetime of the pool.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PoolStats {
+    // the number of allocators constructed, and handed out again after being
+    // returned
+    pub created: u64,
+    pub reused: u64,
+    // the number of returned allocators that were dropped, because the pool
+    // was full or they grew too large
+    pub dropped: u64,
+    pub in_use: usize,
+    pub idle: usize,
+    // the high-water marks of the allocators returned to the pool
+    pub max_pairs: usize,
+    pub max_atoms: usize,
+    pub max_atom_bytes: usize,
+}
+
+pub struct AllocatorPool {
+    flags: u32,
+    max_idle: usize,
+    max_retained_heap: usize,
+    idle: Mutex<Vec<Allocator>>,
+    stats: Mutex<PoolStats>,
+}
+
+impl AllocatorPool {
+    // allocators are created with Allocator::new_with_flags(flags). At most
+    // max_idle allocators are kept when they're not in use
+    pub fn new(flags: u32, max_idle: usize, max_retained_heap: usize) -> Self {
+        Self {
+            flags,
+            max_idle,
+            max_retained_heap,
+            idle: Mutex::new(Vec::new()),
+            stats: Mutex::new(PoolStats::default()),
+        }
+    }
+
+    // returns an idle allocator, or a new one if there is none. The allocator
+    // is returned to the pool when the PooledAllocator is dropped
+    pub fn get(&self) -> PooledAllocator<'_> {
+        let allocator = self.idle.lock().unwrap().pop();
+        let mut stats = self.stats.lock().unwrap();
+        let allocator = match allocator {
+            Some(a) => {
+                stats.reused += 1;
+                stats.idle -= 1;
+                a
+            }
+            None => {
+                stats.created += 1;
+                Allocator::new_with_flags(self.flags)
+            }
+        };
+        stats.in_use += 1;
+        PooledAllocator {
+            pool: self,
+            allocator: Some(allocator),
+        }
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        *self.stats.lock().unwrap()
+    }
+
+    fn put(&self, mut a: Allocator) {
+        let usage = a.stats();
+        a.reset();
+        let mut idle = self.idle.lock().unwrap();
+        let mut stats = self.stats.lock().unwrap();
+        stats.in_use -= 1;
+        stats.max_pairs = stats.max_pairs.max(usage.max_pairs);
+        stats.max_atoms = stats.max_atoms.max(usage.max_atoms);
+        stats.max_atom_bytes = stats.max_atom_bytes.max(usage.max_atom_bytes);
+        if idle.len() >= self.max_idle || usage.max_atom_bytes > self.max_retained_heap {
+            stats.dropped += 1;
+            return;
+        }
+        idle.push(a);
+        stats.idle += 1;
+    }
+}
+
+// an allocator borrowed from an AllocatorPool
+pub struct PooledAllocator<'a> {
+    pool: &'a AllocatorPool,
+    // only None while being dropped
+    allocator: Option<Allocator>,
+}
+
+impl Deref for PooledAllocator<'_> {
+    type Target = Allocator;
+    fn deref(&self) -> &Allocator {
+        self.allocator.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledAllocator<'_> {
+    fn deref_mut(&mut self) -> &mut Allocator {
+        self.allocator.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledAllocator<'_> {
+    fn drop(&mut self) {
+        if let Some(a) = self.allocator.take() {
+            self.pool.put(a);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::allocator::NodePtr;
+    use crate::chia_dialect::{ChiaDialect, LIMIT_HEAP};
+    use crate::run_program::run_program;
+    use crate::serde::node_to_bytes;
+    use crate::test_ops::parse_exp;
+
+    #[test]
+    fn test_pool() {
+        let pool = AllocatorPool::new(0, 1, 1000);
+        let mut a = pool.get();
+        let atom = a.new_atom(&[1; 100]).unwrap();
+        let mut b = pool.get();
+        b.new_atom(&[2; 2000]).unwrap();
+        assert_eq!(pool.stats().created, 2);
+        assert_eq!(pool.stats().in_use, 2);
+
+        // b grew too large to keep
+        drop(b);
+        assert_eq!(pool.stats().dropped, 1);
+        assert_eq!(pool.stats().max_atom_bytes, 2000);
+        assert_eq!(a.atom(atom).as_ref(), [1; 100]);
+        drop(a);
+        assert_eq!(pool.stats().idle, 1);
+
+        // the allocator is handed out again, reset
+        let a = pool.get();
+        assert_eq!(a.stats().atom_bytes, 0);
+        let b = pool.get();
+        let stats = pool.stats();
+        assert_eq!((stats.created, stats.reused, stats.idle), (3, 1, 0));
+        drop(a);
+        // the pool only keeps one idle allocator
+        drop(b);
+        let stats = pool.stats();
+        assert_eq!((stats.in_use, stats.idle, stats.dropped), (0, 1, 2));
+        assert_eq!(stats.max_atom_bytes, 2000);
+        assert_eq!(stats.max_pairs, 0);
+    }
+
+    #[test]
+    fn test_threads() {
+        let pool = AllocatorPool::new(LIMIT_HEAP, 4, usize::MAX);
+        std::thread::scope(|s| {
+            for i in 0..8_u8 {
+                let pool = &pool;
+                s.spawn(move || {
+                    for _ in 0..10 {
+                        let mut a = pool.get();
+                        let program = parse_exp(&mut a, &format!("(+ (q . {i}) (q . 1))")).0;
+                        let dialect = ChiaDialect::new(LIMIT_HEAP);
+                        let r = run_program(&mut *a, &dialect, program, NodePtr::NIL, 0).unwrap();
+                        assert_eq!(node_to_bytes(&a, r.1).unwrap(), [i + 1]);
+                    }
+                });
+            }
+        });
+        let stats = pool.stats();
+        assert_eq!(stats.created + stats.reused, 80);
+        assert_eq!(stats.in_use, 0);
+        assert!(stats.idle <= 4);
+        assert!(stats.max_pairs > 0);
+    }
+}
@@ -0,0 +1,558 @@
+use crate::allocator::{Allocator, NodePtr, SExp};
+use crate::dialect::OperatorSet;
+use crate::operator_info::{chia_operators, OperatorInfo};
+use crate::serde::node_from_bytes;
+use hex_literal::hex;
+use std::io;
+
+// generated programs are not allowed to nest operator calls deeper than this
+const MAX_DEPTH: u32 = 6;
+
+// the operators we pick from, as (opcode, number of arguments). An argument
+// count of 0 means the operator takes a variable number of arguments
+const OPERATORS: [(u32, u32); 44] = [
+    (3, 3),
+    (4, 2),
+    (5, 1),
+    (6, 1),
+    (7, 1),
+    (8, 1),
+    (9, 2),
+    (10, 2),
+    (11, 0),
+    (12, 3),
+    (13, 1),
+    (14, 0),
+    (16, 0),
+    (17, 0),
+    (18, 0),
+    (19, 2),
+    (20, 2),
+    (21, 2),
+    (22, 2),
+    (23, 2),
+    (24, 0),
+    (25, 0),
+    (26, 0),
+    (27, 1),
+    (29, 0),
+    (30, 1),
+    (32, 1),
+    (33, 0),
+    (34, 0),
+    (48, 3),
+    (49, 0),
+    (50, 2),
+    (51, 1),
+    (52, 0),
+    (53, 0),
+    (54, 2),
+    (55, 1),
+    (56, 1),
+    (57, 1),
+    (58, 0),
+    (59, 0),
+    (60, 3),
+    (61, 2),
+    (0x13d61f00, 3),
+];
+
+// paths to the first few items of the environment list, and the whole list
+const ENV_PATHS: [u32; 5] = [1, 2, 5, 11, 23];
+
+const G1POINTS: [[u8; 48]; 2] = [
+    hex!("c00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000"),
+    hex!("97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb"),
+];
+
+// selects an entry from a table based on a sample. The modulo is computed in
+// u32, like the rest of the decoding. Widening the sample to usize first would
+// pick the same entry on 32 and 64 bit hosts too, so this is only for
+// consistency
+fn pick<T: Copy>(table: &[T], sample: u32) -> T {
+    let len = u32::try_from(table.len()).expect("table too large");
+    table[(sample % len) as usize]
+}
+
+// The shape of the next node to generate, decoded from a 32 bit sample. The
+// top 16 bits select the kind of node and the bottom 16 bits its size
+#[derive(Debug, PartialEq, Eq)]
+pub struct CollectArgumentStructure {
+    pub kind: u32,
+    pub size: u32,
+}
+
+impl CollectArgumentStructure {
+    pub fn from_sample(sample: u32) -> Self {
+        Self {
+            kind: sample >> 16,
+            size: sample & 0xffff,
+        }
+    }
+}
+
+// Deterministically turns an arbitrary byte buffer into a CLVM program and
+// environment. The same buffer always produces the same structure. Once the
+// buffer is exhausted, all further samples are 0, which terminates the
+// generation with nil atoms.
+pub struct ArgumentGenerator<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ArgumentGenerator<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn sample(&mut self) -> u32 {
+        let mut buf = [0_u8; 4];
+        let len = std::cmp::min(4, self.data.len());
+        buf[..len].copy_from_slice(&self.data[..len]);
+        self.data = &self.data[len..];
+        u32::from_be_bytes(buf)
+    }
+
+    fn bytes(&mut self, len: u32) -> Vec<u8> {
+        let mut ret = vec![0_u8; len as usize];
+        let available = std::cmp::min(len as usize, self.data.len());
+        ret[..available].copy_from_slice(&self.data[..available]);
+        self.data = &self.data[available..];
+        ret
+    }
+
+    pub fn atom(&mut self, a: &mut Allocator, size: u32) -> NodePtr {
+        match size % 4 {
+            0 => a.new_number((size >> 2).into()).unwrap(),
+            1 => {
+                let buf = self.bytes((size >> 2) % 64);
+                a.new_atom(&buf).unwrap()
+            }
+            2 => {
+                let buf = self.bytes(32);
+                a.new_atom(&buf).unwrap()
+            }
+            _ => a.new_atom(&pick(&G1POINTS, size >> 2)).unwrap(),
+        }
+    }
+
+    fn quote(&mut self, a: &mut Allocator, value: NodePtr) -> NodePtr {
+        let q = a.one();
+        a.new_pair(q, value).unwrap()
+    }
+
+    pub fn program(&mut self, a: &mut Allocator, depth: u32) -> NodePtr {
+        let s = CollectArgumentStructure::from_sample(self.sample());
+        let kind = if depth >= MAX_DEPTH {
+            s.kind % 2
+        } else {
+            s.kind % 8
+        };
+        match kind {
+            0 => {
+                let atom = self.atom(a, s.size);
+                self.quote(a, atom)
+            }
+            // a path into the environment
+            1 => a.new_number(pick(&ENV_PATHS, s.size).into()).unwrap(),
+            2..=5 => self.call(a, s.size, depth),
+            6 => {
+                let list = self.list(a, s.size % 8);
+                self.quote(a, list)
+            }
+            _ => {
+                let first = self.atom(a, s.size);
+                let rest = self.atom(a, s.size >> 4);
+                let pair = a.new_pair(first, rest).unwrap();
+                self.quote(a, pair)
+            }
+        }
+    }
+
+    // an operator call, with each argument being a generated program
+    pub fn call(&mut self, a: &mut Allocator, size: u32, depth: u32) -> NodePtr {
+        let (opcode, arity) = pick(&OPERATORS, size);
+        let argc = if arity == 0 { (size >> 8) % 4 } else { arity };
+        let mut operands = Vec::new();
+        for _ in 0..argc {
+            operands.push(self.program(a, depth + 1));
+        }
+        let mut args = a.nil();
+        for op in operands.into_iter().rev() {
+            args = a.new_pair(op, args).unwrap();
+        }
+        let op = a.new_number(opcode.into()).unwrap();
+        a.new_pair(op, args).unwrap()
+    }
+
+    pub fn list(&mut self, a: &mut Allocator, len: u32) -> NodePtr {
+        let mut items = Vec::new();
+        for _ in 0..len {
+            let s = CollectArgumentStructure::from_sample(self.sample());
+            items.push(self.atom(a, s.size));
+        }
+        let mut ret = a.nil();
+        for item in items.into_iter().rev() {
+            ret = a.new_pair(item, ret).unwrap();
+        }
+        ret
+    }
+
+    pub fn env(&mut self, a: &mut Allocator) -> NodePtr {
+        let s = CollectArgumentStructure::from_sample(self.sample());
+        self.list(a, 1 + s.size % 5)
+    }
+
+    // returns (program, env). The program is always an operator call
+    pub fn generate(&mut self, a: &mut Allocator) -> (NodePtr, NodePtr) {
+        let size = CollectArgumentStructure::from_sample(self.sample()).size;
+        let program = self.call(a, size, 0);
+        let env = self.env(a);
+        (program, env)
+    }
+}
+
+// returns an environment with the same structure as the prototype (a hex
+// encoded, serialized CLVM tree), with every atom replaced by one generated
+// from data. This is for fuzzing a specific program whose environment (e.g. a
+// solution) needs a particular shape to get past its first few operators
+pub fn generate_env_for(
+    a: &mut Allocator,
+    prototype_hex: &str,
+    data: &[u8],
+) -> io::Result<NodePtr> {
+    let buf = crate::hex::decode(prototype_hex)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let mut proto = Allocator::new();
+    let prototype = node_from_bytes(&mut proto, &buf)?;
+
+    let mut args = ArgumentGenerator::new(data);
+    // nodes are visited first to last, and the new tree is built from the
+    // values stack, like the deserializer does
+    enum Op {
+        Visit(NodePtr),
+        Cons,
+    }
+    let mut ops = vec![Op::Visit(prototype)];
+    let mut values = Vec::<NodePtr>::new();
+    while let Some(op) = ops.pop() {
+        match op {
+            Op::Visit(node) => match proto.sexp(node) {
+                SExp::Pair(first, rest) => {
+                    ops.push(Op::Cons);
+                    ops.push(Op::Visit(rest));
+                    ops.push(Op::Visit(first));
+                }
+                SExp::Atom => {
+                    let s = CollectArgumentStructure::from_sample(args.sample());
+                    values.push(args.atom(a, s.size));
+                }
+            },
+            Op::Cons => {
+                let rest = values.pop().expect("values stack");
+                let first = values.pop().expect("values stack");
+                values.push(a.new_pair(first, rest).map_err(|e| io::Error::other(e.1))?);
+            }
+        }
+    }
+    Ok(values.pop().expect("values stack"))
+}
+
+// count pseudo-random 256 byte buffers, to drive the generators in tests.
+// This is xorshift, so the same seed (which must not be 0) always produces the
+// same buffers
+pub fn random_inputs(seed: u32, count: usize) -> impl Iterator<Item = Vec<u8>> {
+    let mut rng = seed;
+    (0..count).map(move |_| {
+        (0..256)
+            .map(|_| {
+                rng ^= rng << 13;
+                rng ^= rng >> 17;
+                rng ^= rng << 5;
+                rng as u8
+            })
+            .collect()
+    })
+}
+
+// Generates programs that are syntactically valid CLVM, from the operators
+// available in ChiaDialect with the specified flags. Besides operator calls
+// with a plausible number of arguments (taken from the operator table), the
+// programs use the interpreter's constructs: quote, apply and the
+// (a (i C (q . T) (q . E)) 1) idiom for conditionals. Most random trees fail
+// on their first operator, programs generated this way mostly get to run.
+// Like ArgumentGenerator, the same buffer always produces the same program.
+pub struct ProgramGenerator<'a> {
+    args: ArgumentGenerator<'a>,
+    operators: Vec<&'static OperatorInfo>,
+}
+
+impl<'a> ProgramGenerator<'a> {
+    pub fn new(data: &'a [u8], flags: u32) -> Self {
+        let operators = chia_operators(flags, OperatorSet::Default)
+            // quote and apply are generated separately
+            .filter(|op| op.opcode != [1] && op.opcode != [2])
+            .collect();
+        Self {
+            args: ArgumentGenerator::new(data),
+            operators,
+        }
+    }
+
+    fn list(&mut self, a: &mut Allocator, items: Vec<NodePtr>) -> NodePtr {
+        let mut ret = a.nil();
+        for item in items.into_iter().rev() {
+            ret = a.new_pair(item, ret).unwrap();
+        }
+        ret
+    }
+
+    fn call(&mut self, a: &mut Allocator, opcode: NodePtr, args: Vec<NodePtr>) -> NodePtr {
+        let args = self.list(a, args);
+        a.new_pair(opcode, args).unwrap()
+    }
+
+    pub fn program(&mut self, a: &mut Allocator, depth: u32) -> NodePtr {
+        let s = CollectArgumentStructure::from_sample(self.args.sample());
+        let kind = if depth >= MAX_DEPTH {
+            s.kind % 2
+        } else {
+            s.kind % 10
+        };
+        match kind {
+            0 => {
+                let atom = self.args.atom(a, s.size);
+                self.args.quote(a, atom)
+            }
+            // a path into the environment
+            1 => a.new_number(pick(&ENV_PATHS, s.size).into()).unwrap(),
+            // (a (i C (q . T) (q . E)) 1)
+            2 => {
+                let cond = self.program(a, depth + 1);
+                let then = self.program(a, depth + 1);
+                let then = self.args.quote(a, then);
+                let otherwise = self.program(a, depth + 1);
+                let otherwise = self.args.quote(a, otherwise);
+                let i = a.new_small_number(3).unwrap();
+                let branch = self.call(a, i, vec![cond, then, otherwise]);
+                let apply = a.new_small_number(2).unwrap();
+                let env = a.one();
+                self.call(a, apply, vec![branch, env])
+            }
+            // (a (q . P) E), where E is either the current environment or
+            // a new value consed onto it
+            3 => {
+                let program = self.program(a, depth + 1);
+                let program = self.args.quote(a, program);
+                let env = match s.size % 2 {
+                    0 => a.one(),
+                    _ => {
+                        let first = self.program(a, depth + 1);
+                        let c = a.new_small_number(4).unwrap();
+                        let env = a.one();
+                        self.call(a, c, vec![first, env])
+                    }
+                };
+                let apply = a.new_small_number(2).unwrap();
+                self.call(a, apply, vec![program, env])
+            }
+            4..=8 => self.operator_call(a, s.size, depth),
+            _ => {
+                let list = self.args.list(a, s.size % 8);
+                self.args.quote(a, list)
+            }
+        }
+    }
+
+    // an operator call, with a number of arguments the operator accepts, each
+    // being a generated program
+    pub fn operator_call(&mut self, a: &mut Allocator, size: u32, depth: u32) -> NodePtr {
+        let op = pick(&self.operators, size);
+        let extra = match op.max_args {
+            Some(max) => (max - op.min_args) as u32,
+            None => 3,
+        };
+        let argc = op.min_args as u32 + (size >> 8) % (extra + 1);
+        let mut args = Vec::new();
+        for _ in 0..argc {
+            args.push(self.program(a, depth + 1));
+        }
+        let opcode = a.new_atom(op.opcode).unwrap();
+        self.call(a, opcode, args)
+    }
+
+    // returns (program, env). The program is always an operator call
+    pub fn generate(&mut self, a: &mut Allocator) -> (NodePtr, NodePtr) {
+        let size = CollectArgumentStructure::from_sample(self.args.sample()).size;
+        let program = self.operator_call(a, size, 0);
+        let env = self.args.env(a);
+        (program, env)
+    }
+}
+
+#[cfg(test)]
+use crate::serde::node_to_bytes;
+
+#[test]
+fn test_deterministic() {
+    let data: Vec<u8> = (0..=255).collect();
+    let mut a = Allocator::new();
+    let (p1, e1) = ArgumentGenerator::new(&data).generate(&mut a);
+    let (p2, e2) = ArgumentGenerator::new(&data).generate(&mut a);
+    assert_eq!(
+        node_to_bytes(&a, p1).unwrap(),
+        node_to_bytes(&a, p2).unwrap()
+    );
+    assert_eq!(
+        node_to_bytes(&a, e1).unwrap(),
+        node_to_bytes(&a, e2).unwrap()
+    );
+}
+
+#[test]
+fn test_empty_input() {
+    // an exhausted buffer samples zeros, which is (i (q . 0) (q . 0) (q . 0))
+    // and an environment of (0)
+    let mut a = Allocator::new();
+    let (program, env) = ArgumentGenerator::new(&[]).generate(&mut a);
+    assert_eq!(
+        node_to_bytes(&a, program).unwrap(),
+        [
+            0xff, 0x03, 0xff, 0xff, 0x01, 0x80, 0xff, 0xff, 0x01, 0x80, 0xff, 0xff, 0x01, 0x80,
+            0x80
+        ]
+    );
+    assert_eq!(node_to_bytes(&a, env).unwrap(), [0xff, 0x80, 0x80]);
+}
+
+#[test]
+fn test_argument_structure() {
+    assert_eq!(
+        CollectArgumentStructure::from_sample(0x0002_0010),
+        CollectArgumentStructure { kind: 2, size: 16 }
+    );
+    assert_eq!(
+        CollectArgumentStructure::from_sample(0xffff_ffff),
+        CollectArgumentStructure {
+            kind: 0xffff,
+            size: 0xffff
+        }
+    );
+}
+
+#[test]
+fn test_pick() {
+    assert_eq!(pick(&ENV_PATHS, 0), 1);
+    assert_eq!(pick(&ENV_PATHS, 4), 23);
+    assert_eq!(pick(&ENV_PATHS, 5), 1);
+    // 0xffffffff % 5 == 0
+    assert_eq!(pick(&ENV_PATHS, u32::MAX), 1);
+    // 0xffffffff % 44 == 3
+    assert_eq!(pick(&OPERATORS, u32::MAX), (6, 1));
+}
+
+#[test]
+fn test_fixed_structure() {
+    // this pins the exact output of the generator for a fixed input. It must be
+    // the same on all platforms, regardless of pointer width
+    let data = [
+        // operator selector: cons
+        0x00, 0x00, 0x00, 0x01, //
+        // kind 0: quoted atom, size 4: the number 1
+        0x00, 0x00, 0x00, 0x04, //
+        // kind 1: environment path, size 2: the path 5
+        0x00, 0x01, 0x00, 0x02, //
+        // environment of 1 + 2 items
+        0x00, 0x00, 0x00, 0x02, //
+        // the numbers 2 and 3
+        0x00, 0x00, 0x00, 0x08, //
+        0x00, 0x00, 0x00, 0x0c, //
+        // an empty buffer
+        0x00, 0x00, 0x00, 0x01,
+    ];
+    let mut a = Allocator::new();
+    let (program, env) = ArgumentGenerator::new(&data).generate(&mut a);
+    // (c (q . 1) 5)
+    assert_eq!(
+        node_to_bytes(&a, program).unwrap(),
+        [0xff, 0x04, 0xff, 0xff, 0x01, 0x01, 0xff, 0x05, 0x80]
+    );
+    // (2 3 ())
+    assert_eq!(
+        node_to_bytes(&a, env).unwrap(),
+        [0xff, 0x02, 0xff, 0x03, 0xff, 0x80, 0x80]
+    );
+}
+
+#[test]
+fn test_program_generator() {
+    use crate::chia_dialect::ChiaDialect;
+    use crate::run_program::run_program;
+
+    // an exhausted buffer generates the first operator, with its minimum
+    // number of arguments: (i (q . 0) (q . 0) (q . 0))
+    let mut a = Allocator::new();
+    let (program, env) = ProgramGenerator::new(&[], 0).generate(&mut a);
+    assert_eq!(
+        node_to_bytes(&a, program).unwrap(),
+        [
+            0xff, 0x03, 0xff, 0xff, 0x01, 0x80, 0xff, 0xff, 0x01, 0x80, 0xff, 0xff, 0x01, 0x80,
+            0x80
+        ]
+    );
+    assert_eq!(node_to_bytes(&a, env).unwrap(), [0xff, 0x80, 0x80]);
+
+    // the number of arguments is always within the operator's arity, and a
+    // good share of programs run to completion
+    let dialect = ChiaDialect::new(0);
+    let mut completed = 0;
+    for data in random_inputs(0x8765_4321, 500) {
+        let mut a = Allocator::new();
+        let (program, env) = ProgramGenerator::new(&data, 0).generate(&mut a);
+        let (op, mut args) = a.next(program).unwrap();
+        let info = chia_operators(0, OperatorSet::Default)
+            .find(|info| info.opcode == a.atom(op).as_ref())
+            .unwrap();
+        let mut argc = 0;
+        while let Some((_, rest)) = a.next(args) {
+            argc += 1;
+            args = rest;
+        }
+        assert!(argc >= info.min_args);
+        assert!(info.max_args.is_none_or(|max| argc <= max));
+        if run_program(&mut a, &dialect, program, env, 11000000000).is_ok() {
+            completed += 1;
+        }
+    }
+    assert!(completed > 100, "{completed}");
+}
+
+#[test]
+fn test_generate_env_for() {
+    let data: Vec<u8> = (0..=255).collect();
+    let mut a = Allocator::new();
+    // (1 (2 . 3) 4)
+    let env = generate_env_for(&mut a, "ff01ffff0203ff0480", &data).unwrap();
+    let SExp::Pair(first, rest) = a.sexp(env) else {
+        panic!("expected a pair");
+    };
+    assert_eq!(a.sexp(first), SExp::Atom);
+    let SExp::Pair(pair, rest) = a.sexp(rest) else {
+        panic!("expected a pair");
+    };
+    assert!(matches!(a.sexp(pair), SExp::Pair(..)));
+    let SExp::Pair(_, rest) = a.sexp(rest) else {
+        panic!("expected a pair");
+    };
+    assert_eq!(a.sexp(rest), SExp::Atom);
+
+    // deterministic
+    let mut b = Allocator::new();
+    let env2 = generate_env_for(&mut b, "ff01ffff0203ff0480", &data).unwrap();
+    assert_eq!(
+        node_to_bytes(&a, env).unwrap(),
+        node_to_bytes(&b, env2).unwrap()
+    );
+
+    assert!(generate_env_for(&mut a, "ff01", &data).is_err());
+    assert!(generate_env_for(&mut a, "zz", &data).is_err());
+}
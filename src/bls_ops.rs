@@ -1,72 +1,77 @@
-use crate::allocator::{Allocator, Atom, NodePtr};
-use crate::cost::{check_cost, Cost};
+use crate::allocator::{Allocator, Atom, AtomKind, NodePtr};
+use crate::cost::{Cost, CostAcc};
+use crate::cost_schedule_v1::{
+    BLS_G1_MULTIPLY_BASE_COST, BLS_G1_MULTIPLY_COST_PER_BYTE, BLS_G1_NEGATE_BASE_COST,
+    BLS_G1_SUBTRACT_BASE_COST, BLS_G1_SUBTRACT_COST_PER_ARG, BLS_G1_VALID_BASE_COST,
+    BLS_G2_ADD_BASE_COST, BLS_G2_ADD_COST_PER_ARG, BLS_G2_MULTIPLY_BASE_COST,
+    BLS_G2_MULTIPLY_COST_PER_BYTE, BLS_G2_NEGATE_BASE_COST, BLS_G2_SUBTRACT_BASE_COST,
+    BLS_G2_SUBTRACT_COST_PER_ARG, BLS_G2_VALID_BASE_COST, BLS_MAP_TO_G1_BASE_COST,
+    BLS_MAP_TO_G1_COST_PER_BYTE, BLS_MAP_TO_G1_COST_PER_DST_BYTE, BLS_MAP_TO_G2_BASE_COST,
+    BLS_MAP_TO_G2_COST_PER_BYTE, BLS_MAP_TO_G2_COST_PER_DST_BYTE, BLS_PAIRING_BASE_COST,
+    BLS_PAIRING_COST_PER_ARG, MALLOC_COST_PER_BYTE,
+};
 use crate::err_utils::err;
+use crate::error_catalog::{
+    fill, ARGUMENT_ERROR, BLS_PAIRING_IDENTITY_FAILED, BLS_VERIFY_FAILED, G1_MAP_ARG_COUNT,
+    G2_MAP_ARG_COUNT, INVALID_G1_POINT, INVALID_G2_POINT, NOT_G1_SIZE, NOT_G2_SIZE,
+};
 use crate::op_utils::{
     atom, first, get_args, get_varargs, int_atom, mod_group_order, new_atom_and_cost, nilp, rest,
-    MALLOC_COST_PER_BYTE,
 };
 use crate::reduction::{EvalErr, Reduction, Response};
+#[cfg(not(feature = "parallel"))]
+use chia_bls::aggregate_verify;
 use chia_bls::{
-    aggregate_pairing, aggregate_verify, hash_to_g1_with_dst, hash_to_g2_with_dst, G1Element,
-    G2Element, PublicKey,
+    aggregate_pairing, hash_to_g1_with_dst, hash_to_g2_with_dst, G1Element, G2Element, PublicKey,
 };
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::borrow::Borrow;
 
-// the same cost as point_add (aka g1_add)
-const BLS_G1_SUBTRACT_BASE_COST: Cost = 101094;
-const BLS_G1_SUBTRACT_COST_PER_ARG: Cost = 1343980;
-
-const BLS_G1_MULTIPLY_BASE_COST: Cost = 705500;
-const BLS_G1_MULTIPLY_COST_PER_BYTE: Cost = 10;
-
-// this is the same cost as XORing the top bit (minus the heap allocation of the
-// return value, which the operator is adding back)
-const BLS_G1_NEGATE_BASE_COST: Cost = 1396 - 480;
-
-// g2_add and g2_subtract have the same cost
-const BLS_G2_ADD_BASE_COST: Cost = 80000;
-const BLS_G2_ADD_COST_PER_ARG: Cost = 1950000;
-const BLS_G2_SUBTRACT_BASE_COST: Cost = 80000;
-const BLS_G2_SUBTRACT_COST_PER_ARG: Cost = 1950000;
-
-const BLS_G2_MULTIPLY_BASE_COST: Cost = 2100000;
-const BLS_G2_MULTIPLY_COST_PER_BYTE: Cost = 5;
-
-// this is the same cost as XORing the top bit (minus the heap allocation of the
-// return value, which the operator is adding back)
-const BLS_G2_NEGATE_BASE_COST: Cost = 2164 - 960;
-
-const BLS_MAP_TO_G1_BASE_COST: Cost = 195000;
-const BLS_MAP_TO_G1_COST_PER_BYTE: Cost = 4;
-const BLS_MAP_TO_G1_COST_PER_DST_BYTE: Cost = 4;
-
-const BLS_MAP_TO_G2_BASE_COST: Cost = 815000;
-const BLS_MAP_TO_G2_COST_PER_BYTE: Cost = 4;
-const BLS_MAP_TO_G2_COST_PER_DST_BYTE: Cost = 4;
+const DST_G2: &[u8; 43] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_AUG_";
 
-const BLS_PAIRING_BASE_COST: Cost = 3000000;
-const BLS_PAIRING_COST_PER_ARG: Cost = 1200000;
+// parses argument number idx (counting from 0) of the operator as a G1 point.
+// The error message says which argument failed, since some operators take
+// many points
+pub(crate) fn g1_arg(
+    a: &Allocator,
+    node: NodePtr,
+    op: &str,
+    idx: usize,
+) -> Result<G1Element, EvalErr> {
+    a.g1(node)
+        .map_err(|e| EvalErr(e.0, fill(ARGUMENT_ERROR, &[&op, &idx, &e.1])))
+}
 
-const DST_G2: &[u8; 43] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_AUG_";
+pub(crate) fn g2_arg(
+    a: &Allocator,
+    node: NodePtr,
+    op: &str,
+    idx: usize,
+) -> Result<G2Element, EvalErr> {
+    a.g2(node)
+        .map_err(|e| EvalErr(e.0, fill(ARGUMENT_ERROR, &[&op, &idx, &e.1])))
+}
 
 pub fn op_bls_g1_subtract(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Response {
-    let mut cost = BLS_G1_SUBTRACT_BASE_COST;
-    check_cost(a, cost, max_cost)?;
+    let mut cost = CostAcc::new(BLS_G1_SUBTRACT_BASE_COST);
+    cost.check(a, max_cost)?;
     let mut total = G1Element::default();
-    let mut is_first = true;
+    let mut idx = 0;
     while let Some((arg, rest)) = a.next(input) {
         input = rest;
-        let point = a.g1(arg)?;
-        cost += BLS_G1_SUBTRACT_COST_PER_ARG;
-        check_cost(a, cost, max_cost)?;
-        if is_first {
+        let point = g1_arg(a, arg, "g1_subtract", idx)?;
+        cost.add(BLS_G1_SUBTRACT_COST_PER_ARG);
+        cost.check(a, max_cost)?;
+        if idx == 0 {
             total = point;
         } else {
             total -= &point;
         };
-        is_first = false;
+        idx += 1;
     }
     Ok(Reduction(
-        cost + 48 * MALLOC_COST_PER_BYTE,
+        cost.plus_per(48, MALLOC_COST_PER_BYTE).cost(),
         a.new_g1(total)?,
     ))
 }
@@ -74,19 +79,19 @@ pub fn op_bls_g1_subtract(a: &mut Allocator, mut input: NodePtr, max_cost: Cost)
 pub fn op_bls_g1_multiply(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Response {
     let [point, scalar] = get_args::<2>(a, input, "g1_multiply")?;
 
-    let mut cost = BLS_G1_MULTIPLY_BASE_COST;
-    check_cost(a, cost, max_cost)?;
+    let mut cost = CostAcc::new(BLS_G1_MULTIPLY_BASE_COST);
+    cost.check(a, max_cost)?;
 
-    let mut total = a.g1(point)?;
+    let mut total = g1_arg(a, point, "g1_multiply", 0)?;
     let (scalar, scalar_len) = int_atom(a, scalar, "g1_multiply")?;
-    cost += scalar_len as Cost * BLS_G1_MULTIPLY_COST_PER_BYTE;
-    check_cost(a, cost, max_cost)?;
+    cost.add_per(scalar_len, BLS_G1_MULTIPLY_COST_PER_BYTE);
+    cost.check(a, max_cost)?;
 
     let scalar = mod_group_order(scalar);
     total.scalar_multiply(scalar.to_bytes_be().1.as_slice());
 
     Ok(Reduction(
-        cost + 48 * MALLOC_COST_PER_BYTE,
+        cost.plus_per(48, MALLOC_COST_PER_BYTE).cost(),
         a.new_g1(total)?,
     ))
 }
@@ -95,13 +100,16 @@ pub fn op_bls_g1_negate(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> R
     let [point] = get_args::<1>(a, input, "g1_negate")?;
 
     let blob = atom(a, point, "G1 atom")?;
-    // this is here to validate the point
-    let _g1 = G1Element::from_bytes(
-        blob.as_ref()
-            .try_into()
-            .map_err(|_| EvalErr(point, "atom is not G1 size, 48 bytes".to_string()))?,
-    )
-    .map_err(|_| EvalErr(point, "atom is not a valid G1 point".to_string()))?;
+    // this is here to validate the point. Atoms created by new_g1() are
+    // already known to be valid
+    if a.atom_kind(point) != Some(AtomKind::G1) {
+        let _g1 = G1Element::from_bytes(
+            blob.as_ref()
+                .try_into()
+                .map_err(|_| EvalErr(point, NOT_G1_SIZE.into()))?,
+        )
+        .map_err(|_| EvalErr(point, INVALID_G1_POINT.into()))?;
+    }
 
     if (blob.as_ref()[0] & 0xe0) == 0xc0 {
         // This is compressed infinity. negating it is a no-op
@@ -119,41 +127,43 @@ pub fn op_bls_g1_negate(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> R
 }
 
 pub fn op_bls_g2_add(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Response {
-    let mut cost = BLS_G2_ADD_BASE_COST;
-    check_cost(a, cost, max_cost)?;
+    let mut cost = CostAcc::new(BLS_G2_ADD_BASE_COST);
+    cost.check(a, max_cost)?;
     let mut total = G2Element::default();
+    let mut idx = 0;
     while let Some((arg, rest)) = a.next(input) {
         input = rest;
-        let point = a.g2(arg)?;
-        cost += BLS_G2_ADD_COST_PER_ARG;
-        check_cost(a, cost, max_cost)?;
+        let point = g2_arg(a, arg, "g2_add", idx)?;
+        cost.add(BLS_G2_ADD_COST_PER_ARG);
+        cost.check(a, max_cost)?;
         total += &point;
+        idx += 1;
     }
     Ok(Reduction(
-        cost + 96 * MALLOC_COST_PER_BYTE,
+        cost.plus_per(96, MALLOC_COST_PER_BYTE).cost(),
         a.new_g2(total)?,
     ))
 }
 
 pub fn op_bls_g2_subtract(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Response {
-    let mut cost = BLS_G2_SUBTRACT_BASE_COST;
-    check_cost(a, cost, max_cost)?;
+    let mut cost = CostAcc::new(BLS_G2_SUBTRACT_BASE_COST);
+    cost.check(a, max_cost)?;
     let mut total = G2Element::default();
-    let mut is_first = true;
+    let mut idx = 0;
     while let Some((arg, rest)) = a.next(input) {
         input = rest;
-        let point = a.g2(arg)?;
-        cost += BLS_G2_SUBTRACT_COST_PER_ARG;
-        check_cost(a, cost, max_cost)?;
-        if is_first {
+        let point = g2_arg(a, arg, "g2_subtract", idx)?;
+        cost.add(BLS_G2_SUBTRACT_COST_PER_ARG);
+        cost.check(a, max_cost)?;
+        if idx == 0 {
             total = point;
         } else {
             total -= &point;
         };
-        is_first = false;
+        idx += 1;
     }
     Ok(Reduction(
-        cost + 96 * MALLOC_COST_PER_BYTE,
+        cost.plus_per(96, MALLOC_COST_PER_BYTE).cost(),
         a.new_g2(total)?,
     ))
 }
@@ -161,19 +171,19 @@ pub fn op_bls_g2_subtract(a: &mut Allocator, mut input: NodePtr, max_cost: Cost)
 pub fn op_bls_g2_multiply(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Response {
     let [point, scalar] = get_args::<2>(a, input, "g2_multiply")?;
 
-    let mut cost = BLS_G2_MULTIPLY_BASE_COST;
-    check_cost(a, cost, max_cost)?;
+    let mut cost = CostAcc::new(BLS_G2_MULTIPLY_BASE_COST);
+    cost.check(a, max_cost)?;
 
-    let mut total = a.g2(point)?;
+    let mut total = g2_arg(a, point, "g2_multiply", 0)?;
     let (scalar, scalar_len) = int_atom(a, scalar, "g2_multiply")?;
-    cost += scalar_len as Cost * BLS_G2_MULTIPLY_COST_PER_BYTE;
-    check_cost(a, cost, max_cost)?;
+    cost.add_per(scalar_len, BLS_G2_MULTIPLY_COST_PER_BYTE);
+    cost.check(a, max_cost)?;
 
     let scalar = mod_group_order(scalar);
     total.scalar_multiply(scalar.to_bytes_be().1.as_slice());
 
     Ok(Reduction(
-        cost + 96 * MALLOC_COST_PER_BYTE,
+        cost.plus_per(96, MALLOC_COST_PER_BYTE).cost(),
         a.new_g2(total)?,
     ))
 }
@@ -186,13 +196,16 @@ pub fn op_bls_g2_negate(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> R
     let blob_atom = atom(a, point, "G2 atom")?;
     let blob = blob_atom.as_ref();
 
-    // this is here to validate the point
-    let _g2 = G2Element::from_bytes(
-        blob.as_ref()
-            .try_into()
-            .map_err(|_| EvalErr(point, "atom is not G2 size, 96 bytes".to_string()))?,
-    )
-    .map_err(|_| EvalErr(point, "atom is not a valid G2 point".to_string()))?;
+    // this is here to validate the point. Atoms created by new_g2() are
+    // already known to be valid
+    if a.atom_kind(point) != Some(AtomKind::G2) {
+        let _g2 = G2Element::from_bytes(
+            blob.as_ref()
+                .try_into()
+                .map_err(|_| EvalErr(point, NOT_G2_SIZE.into()))?,
+        )
+        .map_err(|_| EvalErr(point, INVALID_G2_POINT.into()))?;
+    }
 
     if (blob[0] & 0xe0) == 0xc0 {
         // This is compressed infinity. negating it is a no-op
@@ -209,17 +222,42 @@ pub fn op_bls_g2_negate(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> R
     }
 }
 
+// returns 1 if the argument is a valid (compressed) G1 point, and nil
+// otherwise. Unlike the other G1 operators, an atom of the wrong size is not an
+// error, it's just not a valid point.
+pub fn op_bls_g1_valid(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+    let [point] = get_args::<1>(a, input, "g1_valid?")?;
+    atom(a, point, "g1_valid?")?;
+    let valid = a.try_g1(point)?.is_some();
+    Ok(Reduction(
+        BLS_G1_VALID_BASE_COST,
+        if valid { a.one() } else { a.nil() },
+    ))
+}
+
+// returns 1 if the argument is a valid (compressed) G2 point, and nil
+// otherwise.
+pub fn op_bls_g2_valid(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+    let [point] = get_args::<1>(a, input, "g2_valid?")?;
+    atom(a, point, "g2_valid?")?;
+    let valid = a.try_g2(point)?.is_some();
+    Ok(Reduction(
+        BLS_G2_VALID_BASE_COST,
+        if valid { a.one() } else { a.nil() },
+    ))
+}
+
 pub fn op_bls_map_to_g1(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Response {
     let ([msg, dst], argc) = get_varargs::<2>(a, input, "g1_map")?;
     if !(1..=2).contains(&argc) {
-        return err(input, "g1_map takes exactly 1 or 2 arguments");
+        return err(input, G1_MAP_ARG_COUNT);
     }
-    let mut cost: Cost = BLS_MAP_TO_G1_BASE_COST;
-    check_cost(a, cost, max_cost)?;
+    let mut cost = CostAcc::new(BLS_MAP_TO_G1_BASE_COST);
+    cost.check(a, max_cost)?;
 
     let msg = atom(a, msg, "g1_map")?;
-    cost += msg.as_ref().len() as Cost * BLS_MAP_TO_G1_COST_PER_BYTE;
-    check_cost(a, cost, max_cost)?;
+    cost.add_per(msg.as_ref().len(), BLS_MAP_TO_G1_COST_PER_BYTE);
+    cost.check(a, max_cost)?;
 
     let dst = if argc == 2 {
         atom(a, dst, "g1_map")?
@@ -227,12 +265,12 @@ pub fn op_bls_map_to_g1(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Re
         Atom::Borrowed(b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_AUG_".as_slice())
     };
 
-    cost += dst.as_ref().len() as Cost * BLS_MAP_TO_G1_COST_PER_DST_BYTE;
-    check_cost(a, cost, max_cost)?;
+    cost.add_per(dst.as_ref().len(), BLS_MAP_TO_G1_COST_PER_DST_BYTE);
+    cost.check(a, max_cost)?;
 
     let point = hash_to_g1_with_dst(msg.as_ref(), dst.as_ref());
     Ok(Reduction(
-        cost + 48 * MALLOC_COST_PER_BYTE,
+        cost.plus_per(48, MALLOC_COST_PER_BYTE).cost(),
         a.new_g1(point)?,
     ))
 }
@@ -240,13 +278,13 @@ pub fn op_bls_map_to_g1(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Re
 pub fn op_bls_map_to_g2(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Response {
     let ([msg, dst], argc) = get_varargs::<2>(a, input, "g2_map")?;
     if !(1..=2).contains(&argc) {
-        return err(input, "g2_map takes exactly 1 or 2 arguments");
+        return err(input, G2_MAP_ARG_COUNT);
     }
-    let mut cost: Cost = BLS_MAP_TO_G2_BASE_COST;
-    check_cost(a, cost, max_cost)?;
+    let mut cost = CostAcc::new(BLS_MAP_TO_G2_BASE_COST);
+    cost.check(a, max_cost)?;
 
     let msg = atom(a, msg, "g2_map")?;
-    cost += msg.as_ref().len() as Cost * BLS_MAP_TO_G2_COST_PER_BYTE;
+    cost.add_per(msg.as_ref().len(), BLS_MAP_TO_G2_COST_PER_BYTE);
 
     let dst = if argc == 2 {
         atom(a, dst, "g2_map")?
@@ -254,12 +292,12 @@ pub fn op_bls_map_to_g2(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Re
         Atom::Borrowed(DST_G2.as_slice())
     };
 
-    cost += dst.as_ref().len() as Cost * BLS_MAP_TO_G2_COST_PER_DST_BYTE;
-    check_cost(a, cost, max_cost)?;
+    cost.add_per(dst.as_ref().len(), BLS_MAP_TO_G2_COST_PER_DST_BYTE);
+    cost.check(a, max_cost)?;
 
     let point = hash_to_g2_with_dst(msg.as_ref(), dst.as_ref());
     Ok(Reduction(
-        cost + 96 * MALLOC_COST_PER_BYTE,
+        cost.plus_per(96, MALLOC_COST_PER_BYTE).cost(),
         a.new_g2(point)?,
     ))
 }
@@ -269,63 +307,188 @@ pub fn op_bls_map_to_g2(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Re
 // It performs a low-level pairing operation of the (G1, G2)-pairs
 // and returns if the resulting Gt point is the
 // identity, otherwise terminates the program with a validation error.
-pub fn op_bls_pairing_identity(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Response {
-    let mut cost = BLS_PAIRING_BASE_COST;
-    check_cost(a, cost, max_cost)?;
+// a check an operator can defer, to run it on another thread. It returns
+// whether the check passed. See executor.rs
+pub type DeferredCheck = Box<dyn FnOnce() -> bool + Send>;
+
+// parses the arguments of bls_pairing_identity and returns its cost, along
+// with the pairing check, without performing it
+pub fn bls_pairing_identity_check(
+    a: &mut Allocator,
+    input: NodePtr,
+    max_cost: Cost,
+) -> Result<(Cost, DeferredCheck), EvalErr> {
+    let mut cost = CostAcc::new(BLS_PAIRING_BASE_COST);
+    cost.check(a, max_cost)?;
     let mut items = Vec::<(G1Element, G2Element)>::new();
 
     let mut args = input;
+    let mut idx = 0;
     while !nilp(a, args) {
-        cost += BLS_PAIRING_COST_PER_ARG;
-        check_cost(a, cost, max_cost)?;
-        let g1 = a.g1(first(a, args)?)?;
+        cost.add(BLS_PAIRING_COST_PER_ARG);
+        cost.check(a, max_cost)?;
+        let g1 = g1_arg(a, first(a, args)?, "bls_pairing_identity", idx)?;
         args = rest(a, args)?;
-        let g2 = a.g2(first(a, args)?)?;
+        let g2 = g2_arg(a, first(a, args)?, "bls_pairing_identity", idx + 1)?;
         args = rest(a, args)?;
         items.push((g1, g2));
+        idx += 2;
     }
 
-    if !aggregate_pairing(items) {
-        err(input, "bls_pairing_identity failed")
+    Ok((cost.cost(), Box::new(move || aggregate_pairing(items))))
+}
+
+pub fn op_bls_pairing_identity(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Response {
+    let (cost, check) = bls_pairing_identity_check(a, input, max_cost)?;
+    if !check() {
+        err(input, BLS_PAIRING_IDENTITY_FAILED)
     } else {
         Ok(Reduction(cost, a.nil()))
     }
 }
 
-// expects: G2 G1 msg G1 msg ...
-// G2 is the signature
-// G1 is a public key
-// the G1 and its corresponding message must be passed in pairs.
-pub fn op_bls_verify(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Response {
-    let mut cost = BLS_PAIRING_BASE_COST;
-    check_cost(a, cost, max_cost)?;
+#[cfg(not(feature = "parallel"))]
+fn verify_items<M: Borrow<[u8]>>(signature: &G2Element, items: Vec<(PublicKey, M)>) -> bool {
+    aggregate_verify(signature, items)
+}
+
+// hashing the (augmented) messages to G2 is about half the cost of verifying
+// the signature. Do it on multiple threads, and then check that the product of
+// the pairings e(pk, H(pk | msg)) and e(-g1, signature) is the identity. This
+// is equivalent to aggregate_verify(), including rejecting public keys at
+// infinity
+#[cfg(feature = "parallel")]
+fn verify_items<M: Borrow<[u8]> + Sync>(signature: &G2Element, items: Vec<(PublicKey, M)>) -> bool {
+    if !signature.is_valid() {
+        return false;
+    }
+    if items.is_empty() {
+        return *signature == G2Element::default();
+    }
+    if items
+        .iter()
+        .any(|(pk, _)| !pk.is_valid() || *pk == PublicKey::default())
+    {
+        return false;
+    }
+    let mut pairs: Vec<(PublicKey, G2Element)> = items
+        .par_iter()
+        .map(|(pk, msg)| {
+            let mut aug_msg = pk.to_bytes().to_vec();
+            aug_msg.extend_from_slice(msg.borrow());
+            (pk.clone(), hash_to_g2_with_dst(&aug_msg, DST_G2))
+        })
+        .collect();
+    let mut g1 = G1Element::generator();
+    g1.negate();
+    pairs.push((g1, signature.clone()));
+    aggregate_pairing(pairs)
+}
+
+// parses the arguments of bls_verify and returns its cost, along with the
+// signature check, without performing it. The messages are copied, since the
+// check may outlive the allocator
+pub fn bls_verify_check(
+    a: &mut Allocator,
+    input: NodePtr,
+    max_cost: Cost,
+) -> Result<(Cost, DeferredCheck), EvalErr> {
+    let mut cost = CostAcc::new(BLS_PAIRING_BASE_COST);
+    cost.check(a, max_cost)?;
 
     let mut args = input;
 
     // the first argument is the signature
-    let signature = a.g2(first(a, args)?)?;
+    let signature = g2_arg(a, first(a, args)?, "bls_verify", 0)?;
 
     // followed by a variable number of (G1, msg)-pairs (as a flat list)
     args = rest(a, args)?;
 
-    let mut items = Vec::<(PublicKey, Atom)>::new();
+    let mut items = Vec::<(PublicKey, Vec<u8>)>::new();
+    let mut idx = 1;
     while !nilp(a, args) {
-        let pk = a.g1(first(a, args)?)?;
+        let pk = g1_arg(a, first(a, args)?, "bls_verify", idx)?;
         args = rest(a, args)?;
         let msg = atom(a, first(a, args)?, "bls_verify message")?;
         args = rest(a, args)?;
+        idx += 2;
 
-        cost += BLS_PAIRING_COST_PER_ARG;
-        cost += msg.as_ref().len() as Cost * BLS_MAP_TO_G2_COST_PER_BYTE;
-        cost += DST_G2.len() as Cost * BLS_MAP_TO_G2_COST_PER_DST_BYTE;
-        check_cost(a, cost, max_cost)?;
+        cost.add(BLS_PAIRING_COST_PER_ARG);
+        cost.add_per(msg.as_ref().len(), BLS_MAP_TO_G2_COST_PER_BYTE);
+        cost.add_per(DST_G2.len(), BLS_MAP_TO_G2_COST_PER_DST_BYTE);
+        cost.check(a, max_cost)?;
 
-        items.push((pk, msg));
+        items.push((pk, msg.as_ref().to_vec()));
     }
 
-    if !aggregate_verify(&signature, items) {
-        err(input, "bls_verify failed")
+    Ok((
+        cost.cost(),
+        Box::new(move || verify_items(&signature, items)),
+    ))
+}
+
+// expects: G2 G1 msg G1 msg ...
+// G2 is the signature
+// G1 is a public key
+// the G1 and its corresponding message must be passed in pairs.
+pub fn op_bls_verify(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Response {
+    let (cost, check) = bls_verify_check(a, input, max_cost)?;
+    if !check() {
+        err(input, BLS_VERIFY_FAILED)
     } else {
         Ok(Reduction(cost, a.nil()))
     }
 }
+
+#[cfg(all(test, feature = "parallel"))]
+use chia_bls::{aggregate, sign, SecretKey};
+
+#[cfg(feature = "parallel")]
+#[test]
+fn test_parallel_verify() {
+    let mut a = Allocator::new();
+    let keys: Vec<SecretKey> = (0..8_u8).map(|i| SecretKey::from_seed(&[i; 32])).collect();
+    let msgs: Vec<NodePtr> = (0..8_u8)
+        .map(|i| a.new_atom(&vec![i; i as usize * 10]).unwrap())
+        .collect();
+    let items = |pks: &[PublicKey]| -> Vec<(PublicKey, Atom)> {
+        pks.iter()
+            .zip(msgs.iter())
+            .map(|(pk, msg)| (pk.clone(), a.atom(*msg)))
+            .collect()
+    };
+    let pks: Vec<PublicKey> = keys.iter().map(|sk| sk.public_key()).collect();
+    let sig = aggregate(
+        keys.iter()
+            .zip(msgs.iter())
+            .map(|(sk, msg)| sign(sk, a.atom(*msg).as_ref())),
+    );
+
+    let check = |sig: &G2Element, items: Vec<(PublicKey, Atom)>, expect: bool| {
+        assert_eq!(chia_bls::aggregate_verify(sig, items.clone()), expect);
+        assert_eq!(verify_items(sig, items), expect);
+    };
+
+    check(&sig, items(&pks), true);
+    check(&sig, items(&pks[..7]), false);
+    check(&G2Element::default(), items(&pks), false);
+    check(&G2Element::default(), vec![], true);
+    check(&sig, vec![], false);
+
+    let mut swapped = pks.clone();
+    swapped.swap(0, 1);
+    check(&sig, items(&swapped), false);
+
+    // public keys at infinity are rejected, even though their pairing is the
+    // identity
+    let rest_sig = aggregate(
+        keys.iter()
+            .zip(msgs.iter())
+            .skip(1)
+            .map(|(sk, msg)| sign(sk, a.atom(*msg).as_ref())),
+    );
+    let mut inf = pks.clone();
+    inf[0] = PublicKey::default();
+    check(&rest_sig, items(&inf), false);
+    check(&G2Element::default(), items(&[PublicKey::default()]), false);
+}
@@ -0,0 +1,164 @@
+use crate::allocator::{Allocator, NodePtr};
+use crate::bls_ops::{op_bls_map_to_g1, op_bls_map_to_g2, op_bls_verify};
+use crate::cost::Cost;
+use crate::hex::{decode, encode};
+use crate::reduction::{Reduction, Response};
+
+// Reference test vectors for the BLS operators, run through the operator
+// interface (atoms in, atoms out). They are public so that other
+// implementations of the operators (and dialects wrapping them) can check
+// themselves against the same vectors.
+
+// a message hashed to a curve with the specified domain separation tag. The
+// expected point is hex encoded, in compressed form
+pub struct HashToCurveVector {
+    pub msg: &'static [u8],
+    pub dst: &'static [u8],
+    pub expected: &'static str,
+}
+
+// RFC 9380, appendix J.9.1 (BLS12381G1_XMD:SHA-256_SSWU_RO_)
+pub const G1_RFC9380_DST: &[u8] = b"QUUX-V01-CS02-with-BLS12381G1_XMD:SHA-256_SSWU_RO_";
+
+// RFC 9380, appendix J.10.1 (BLS12381G2_XMD:SHA-256_SSWU_RO_)
+pub const G2_RFC9380_DST: &[u8] = b"QUUX-V01-CS02-with-BLS12381G2_XMD:SHA-256_SSWU_RO_";
+
+pub const G1_MAP_VECTORS: &[HashToCurveVector] = &[
+    HashToCurveVector {
+        msg: b"",
+        dst: G1_RFC9380_DST,
+        expected: "852926add2207b76ca4fa57a8734416c8dc95e24501772c814278700eed6d1e4e8cf62d9c09db0fac349612b759e79a1",
+    },
+    HashToCurveVector {
+        msg: b"abc",
+        dst: G1_RFC9380_DST,
+        expected: "83567bc5ef9c690c2ab2ecdf6a96ef1c139cc0b2f284dca0a9a7943388a49a3aee664ba5379a7655d3c68900be2f6903",
+    },
+    HashToCurveVector {
+        msg: b"abcdef0123456789",
+        dst: G1_RFC9380_DST,
+        expected: "91e0b079dea29a68f0383ee94fed1b940995272407e3bb916bbf268c263ddd57a6a27200a784cbc248e84f357ce82d98",
+    },
+];
+
+pub const G2_MAP_VECTORS: &[HashToCurveVector] = &[
+    HashToCurveVector {
+        msg: b"",
+        dst: G2_RFC9380_DST,
+        expected: "a5cb8437535e20ecffaef7752baddf98034139c38452458baeefab379ba13dff5bf5dd71b72418717047f5b0f37da03d0141ebfbdca40eb85b87142e130ab689c673cf60f1a3e98d69335266f30d9b8d4ac44c1038e9dcdd5393faf5c41fb78a",
+    },
+    // from the chia BLS library, with the AUG and NUL scheme tags
+    HashToCurveVector {
+        msg: b"abcdef0123456789",
+        dst: b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_AUG_",
+        expected: "92596412844e12c4733b5a6bfc5727cde4c20b345665d2de99de163266f3ba6a944c6c0fdd9d9fe57b9a4acb769bf3780456f8aab4cd41a70836dba57a5278a85fbd18eb96a2b56cfbda853186c9d190c43e63bc3e6a181aed692e97bbdb1944",
+    },
+    HashToCurveVector {
+        msg: b"abcdef0123456789",
+        dst: b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_",
+        expected: "8ee1ff66094b8975401c86ad424076d97fed9c2025db5f9dfde6ed455c7bff34b55e96379c1f9ee3c173633587f425e50aed3e807c6c7cd7bed35d40542eee99891955b2ea5321ebde37172e2c01155138494c2d725b03c02765828679bf011e",
+    },
+];
+
+// an aggregate signature (AUG scheme) over the (public key, message) pairs.
+// Keys and the signature are hex encoded
+pub struct AugSchemeVector {
+    pub pairs: &'static [(&'static str, &'static [u8])],
+    pub signature: &'static str,
+}
+
+const PK_FOOBAR: &str = "997cc43ed8788f841fcf3071f6f212b89ba494b6ebaf1bda88c3f9de9d968a61f3b7284a5ee13889399ca71a026549a2";
+// the keys derived from the seeds [2; 32] and [3; 32]
+const PK_SEED2: &str = "b6144137baa6440c17386d1a407fb3670d3b3627b4fa8bf4b56433f861eaba4e076cefac1d9365de56a0e5d976ad7354";
+const PK_SEED3: &str = "954a331766f0584949a2376fbd96ac5a1f0a9e90c916383a5a16762b11c29120f5a072ea43f64774d77ad1ac4ba98dac";
+
+pub const AUG_SCHEME_VECTORS: &[AugSchemeVector] = &[
+    // blspy: AugSchemeMPL.sign(sk, b"foobar")
+    AugSchemeVector {
+        pairs: &[(PK_FOOBAR, b"foobar")],
+        signature: "b45825c0ee7759945c0189b4c38b7e54231ebadc83a851bec3bb7cf954a124ae0cc8e8e5146558332ea152f63bf8846e04826185ef60e817f271f8d500126561319203f9acb95809ed20c193757233454be1562a5870570941a84605bd2c9c9a",
+    },
+    // bls-signatures: "Chia test vector 2 (Augmented, aggregate of aggregates)"
+    AugSchemeVector {
+        pairs: &[
+            (PK_SEED2, &[1, 2, 3, 40]),
+            (PK_SEED3, &[5, 6, 70, 201]),
+            (PK_SEED3, &[1, 2, 3, 40]),
+            (PK_SEED2, &[9, 10, 11, 12, 13]),
+            (PK_SEED2, &[1, 2, 3, 40]),
+            (PK_SEED2, &[15, 63, 244, 92, 0, 1]),
+        ],
+        signature: "a1d5360dcb418d33b29b90b912b4accde535cf0e52caf467a005dc632d9f7af44b6c4e9acd46eac218b28cdb07a3e3bc087df1cd1e3213aa4e11322a3ff3847bbba0b2fd19ddc25ca964871997b9bceeab37a4c2565876da19382ea32a962200",
+    },
+];
+
+fn make_list(a: &mut Allocator, items: &[NodePtr]) -> NodePtr {
+    let mut ret = a.nil();
+    for item in items.iter().rev() {
+        ret = a.new_pair(*item, ret).expect("new_pair");
+    }
+    ret
+}
+
+type MapOp = fn(&mut Allocator, NodePtr, Cost) -> Response;
+
+// returns a description of the first vector the operator fails, if any
+fn check_map(op: MapOp, vectors: &[HashToCurveVector]) -> Result<(), String> {
+    let mut a = Allocator::new();
+    for (i, v) in vectors.iter().enumerate() {
+        let msg = a.new_atom(v.msg).expect("new_atom");
+        let dst = a.new_atom(v.dst).expect("new_atom");
+        let args = make_list(&mut a, &[msg, dst]);
+        let Reduction(_, result) =
+            op(&mut a, args, Cost::MAX).map_err(|e| format!("{i}: {}", e.1))?;
+        let result = encode(a.atom(result).as_ref());
+        if result != v.expected {
+            return Err(format!("{i}: expected {} got {result}", v.expected));
+        }
+    }
+    Ok(())
+}
+
+pub fn check_g1_map_vectors() -> Result<(), String> {
+    check_map(op_bls_map_to_g1, G1_MAP_VECTORS)
+}
+
+pub fn check_g2_map_vectors() -> Result<(), String> {
+    check_map(op_bls_map_to_g2, G2_MAP_VECTORS)
+}
+
+// each vector must verify, and must fail to verify if the last byte of the
+// first message is changed
+pub fn check_aug_scheme_vectors() -> Result<(), String> {
+    let mut a = Allocator::new();
+    for (i, v) in AUG_SCHEME_VECTORS.iter().enumerate() {
+        for tamper in [false, true] {
+            let mut args = vec![a
+                .new_atom(&decode(v.signature).expect("hex"))
+                .expect("new_atom")];
+            for (j, (pk, msg)) in v.pairs.iter().enumerate() {
+                let mut msg = msg.to_vec();
+                if tamper && j == 0 {
+                    *msg.last_mut().expect("empty message") ^= 1;
+                }
+                args.push(a.new_atom(&decode(pk).expect("hex")).expect("new_atom"));
+                args.push(a.new_atom(&msg).expect("new_atom"));
+            }
+            let args = make_list(&mut a, &args);
+            match (op_bls_verify(&mut a, args, Cost::MAX), tamper) {
+                (Ok(_), false) => {}
+                (Err(e), true) if e.1 == "bls_verify failed" => {}
+                (Ok(_), true) => return Err(format!("{i}: tampered message verified")),
+                (Err(e), _) => return Err(format!("{i}: {}", e.1)),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_bls_vectors() {
+    check_g1_map_vectors().unwrap();
+    check_g2_map_vectors().unwrap();
+    check_aug_scheme_vectors().unwrap();
+}
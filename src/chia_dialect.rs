@@ -1,20 +1,26 @@
-use crate::allocator::{Allocator, NodePtr};
+use crate::allocator::{fits_in_small_atom, Allocator, AtomKind, NodePtr};
 use crate::bls_ops::{
-    op_bls_g1_multiply, op_bls_g1_negate, op_bls_g1_subtract, op_bls_g2_add, op_bls_g2_multiply,
-    op_bls_g2_negate, op_bls_g2_subtract, op_bls_map_to_g1, op_bls_map_to_g2,
-    op_bls_pairing_identity, op_bls_verify,
+    op_bls_g1_multiply, op_bls_g1_negate, op_bls_g1_subtract, op_bls_g1_valid, op_bls_g2_add,
+    op_bls_g2_multiply, op_bls_g2_negate, op_bls_g2_subtract, op_bls_g2_valid, op_bls_map_to_g1,
+    op_bls_map_to_g2, op_bls_pairing_identity, op_bls_verify,
+};
+use crate::core_ops::{
+    op_cons, op_eq, op_first, op_if, op_listp, op_raise, op_rest, op_tree_depth, op_tree_size,
 };
-use crate::core_ops::{op_cons, op_eq, op_first, op_if, op_listp, op_raise, op_rest};
 use crate::cost::Cost;
 use crate::dialect::{Dialect, OperatorSet};
+use crate::ed25519_ops::op_ed25519_verify;
 use crate::err_utils::err;
+use crate::error_catalog::{NON_CANONICAL_OUTPUT, UNIMPLEMENTED_OPERATOR};
 use crate::more_ops::{
-    op_add, op_all, op_any, op_ash, op_coinid, op_concat, op_div, op_div_fixed, op_divmod, op_gr,
-    op_gr_bytes, op_logand, op_logior, op_lognot, op_logxor, op_lsh, op_mod, op_modpow,
-    op_multiply, op_not, op_point_add, op_pubkey_for_exp, op_sha256, op_strlen, op_substr,
-    op_subtract, op_unknown,
+    op_add, op_all, op_any, op_ash, op_bitlength, op_coinid, op_concat, op_div, op_div_fixed,
+    op_divexact, op_divmod, op_gcd, op_gr, op_gr_bytes, op_logand, op_logior, op_lognot, op_logxor,
+    op_lsh, op_mod, op_modpow, op_mul_div_floor, op_multiply, op_not, op_point_add, op_popcount,
+    op_pubkey_for_exp, op_sha256, op_sqrt_floor, op_strlen, op_substr, op_subtract, op_unknown,
 };
-use crate::reduction::Response;
+use crate::number::is_canonical_int;
+use crate::operator_info::operator_by_opcode;
+use crate::reduction::{EvalErr, Response};
 use crate::secp_ops::{op_secp256k1_verify, op_secp256r1_verify};
 
 // unknown operators are disallowed
@@ -22,7 +28,8 @@ use crate::secp_ops::{op_secp256k1_verify, op_secp256r1_verify};
 pub const NO_UNKNOWN_OPS: u32 = 0x0002;
 
 // When set, limits the number of atom-bytes allowed to be allocated, as well as
-// the number of pairs
+// the number of pairs. The limit is enforced by the Allocator, so the caller
+// has to create it with Allocator::new_with_flags()
 pub const LIMIT_HEAP: u32 = 0x0004;
 
 // enables the BLS ops extensions *outside* the softfork guard. This is a
@@ -33,6 +40,50 @@ pub const ENABLE_BLS_OPS_OUTSIDE_GUARD: u32 = 0x0020;
 // division operator
 pub const ENABLE_FIXED_DIV: u32 = 0x0080;
 
+// enables the g1_valid? and g2_valid? operators (62 and 63) as part of the BLS
+// operators. Since they return a value, they can't be soft-forked in as an
+// unknown operator, so enabling this is a hard fork
+pub const ENABLE_BLS_VALID_OPS: u32 = 0x0200;
+
+// enables the ed25519_verify operator. Without this flag, its opcode
+// (0x06ddcf00) is treated as an unknown operator, whose cost encoding
+// (multiplier 0x06ddcf, cost function 0) makes it cost a constant 450000. The
+// operator costs that plus a cost per message byte, so nodes with and without
+// the flag would disagree on the cost of a program using it (unlike the secp
+// operators, whose cost matches their unknown-op encoding). Enabling this is a
+// hard fork
+pub const ENABLE_ED25519_VERIFY: u32 = 0x0100;
+
+// enables the divexact and gcd operators. Without this flag, their opcodes are
+// treated as unknown operators. Since they return a value, enabling this is a
+// hard fork
+pub const ENABLE_DIVEXACT_GCD: u32 = 0x0400;
+
+// enables the fixed-point operators mul_div_floor and sqrt_floor. Like
+// ENABLE_DIVEXACT_GCD, enabling this is a hard fork
+pub const ENABLE_FIXED_POINT_OPS: u32 = 0x0800;
+
+// enables the popcount and bitlength operators. Like ENABLE_DIVEXACT_GCD,
+// enabling this is a hard fork
+pub const ENABLE_BIT_COUNT_OPS: u32 = 0x1000;
+
+// enables the tree_size and tree_depth operators. Like ENABLE_DIVEXACT_GCD,
+// enabling this is a hard fork
+pub const ENABLE_TREE_SIZE_OPS: u32 = 0x2000;
+
+// verifies that every integer returned by an operator is in the canonical
+// (minimal) encoding, failing with an internal error otherwise. This is a
+// self-check of the operator implementations, for tests and fuzzing. A
+// correct implementation never fails it, so it doesn't change consensus
+pub const STRICT_INT_OUTPUTS: u32 = 0x4000;
+
+// checks the number of arguments passed to every operator against the
+// operator's declared arity (see operator_info) before calling it, so that all
+// operators fail the same way when passed the wrong number of arguments. The
+// operators check their arguments themselves too, so this only changes error
+// messages, not which programs fail
+pub const STRICT_ARITY: u32 = 0x8000;
+
 // The default mode when running grnerators in mempool-mode (i.e. the stricter
 // mode)
 pub const MEMPOOL_MODE: u32 = NO_UNKNOWN_OPS | LIMIT_HEAP;
@@ -45,12 +96,26 @@ fn unknown_operator(
     max_cost: Cost,
 ) -> Response {
     if (flags & NO_UNKNOWN_OPS) != 0 {
-        err(o, "unimplemented operator")
+        err(o, UNIMPLEMENTED_OPERATOR)
     } else {
         op_unknown(allocator, o, args, max_cost)
     }
 }
 
+// integers are tagged with AtomKind::Int when they're created by
+// new_number(), so this checks exactly the atoms operators created as integers
+fn check_canonical_int(allocator: &Allocator, o: NodePtr, result: NodePtr) -> Result<(), EvalErr> {
+    if allocator.atom_kind(result) == Some(AtomKind::Int)
+        && !is_canonical_int(allocator.atom(result).as_ref())
+    {
+        err(o, NON_CANONICAL_OUTPUT)
+    } else {
+        Ok(())
+    }
+}
+
+type OpFn = fn(&mut Allocator, NodePtr, Cost) -> Response;
+
 pub struct ChiaDialect {
     flags: u32,
 }
@@ -59,19 +124,11 @@ impl ChiaDialect {
     pub fn new(flags: u32) -> ChiaDialect {
         ChiaDialect { flags }
     }
-}
 
-impl Dialect for ChiaDialect {
-    fn op(
-        &self,
-        allocator: &mut Allocator,
-        o: NodePtr,
-        argument_list: NodePtr,
-        max_cost: Cost,
-        extension: OperatorSet,
-    ) -> Response {
-        let op_len = allocator.atom_len(o);
-        if op_len == 4 {
+    // returns the implementation of the operator with the specified opcode,
+    // or None if it's an unknown operator
+    fn handler(&self, opcode: &[u8], extension: OperatorSet) -> Option<OpFn> {
+        if opcode.len() == 4 {
             // these are unknown operators with assigned cost
             // the formula is:
             // +---+---+---+------------+
@@ -83,27 +140,27 @@ impl Dialect for ChiaDialect {
             // (3 bytes)    + 2 bits
             //                cost_function
 
-            let b = allocator.atom(o);
-            let opcode = u32::from_be_bytes(b.as_ref().try_into().unwrap());
+            let opcode = u32::from_be_bytes(opcode.try_into().unwrap());
 
             // the secp operators have a fixed cost of 1850000 and 1300000,
             // which makes the multiplier 0x1c3a8f and 0x0cf84f (there is an
             // implied +1) and cost function 0
+            // ed25519_verify has a base cost of 450000, making the multiplier
+            // 0x06ddcf
             let f = match opcode {
                 0x13d61f00 => op_secp256k1_verify,
                 0x1c3a8f00 => op_secp256r1_verify,
+                0x06ddcf00 if (self.flags & ENABLE_ED25519_VERIFY) != 0 => op_ed25519_verify,
                 _ => {
-                    return unknown_operator(allocator, o, argument_list, self.flags, max_cost);
+                    return None;
                 }
             };
-            return f(allocator, argument_list, max_cost);
+            return Some(f);
         }
-        if op_len != 1 {
-            return unknown_operator(allocator, o, argument_list, self.flags, max_cost);
+        if opcode.len() != 1 {
+            return None;
         }
-        let Some(op) = allocator.small_number(o) else {
-            return unknown_operator(allocator, o, argument_list, self.flags, max_cost);
-        };
+        let op = fits_in_small_atom(opcode)?;
         let f = match op {
             // 1 = quote
             // 2 = apply
@@ -147,6 +204,14 @@ impl Dialect for ChiaDialect {
             34 => op_all,
             // 35 ---
             // 36 = softfork
+            37 if (self.flags & ENABLE_DIVEXACT_GCD) != 0 => op_divexact,
+            38 if (self.flags & ENABLE_DIVEXACT_GCD) != 0 => op_gcd,
+            39 if (self.flags & ENABLE_FIXED_POINT_OPS) != 0 => op_mul_div_floor,
+            40 if (self.flags & ENABLE_FIXED_POINT_OPS) != 0 => op_sqrt_floor,
+            41 if (self.flags & ENABLE_BIT_COUNT_OPS) != 0 => op_popcount,
+            42 if (self.flags & ENABLE_BIT_COUNT_OPS) != 0 => op_bitlength,
+            43 if (self.flags & ENABLE_TREE_SIZE_OPS) != 0 => op_tree_size,
+            44 if (self.flags & ENABLE_TREE_SIZE_OPS) != 0 => op_tree_depth,
             _ => {
                 if extension == OperatorSet::BLS || (self.flags & ENABLE_BLS_OPS_OUTSIDE_GUARD) != 0
                 {
@@ -165,22 +230,44 @@ impl Dialect for ChiaDialect {
                         59 => op_bls_verify,
                         60 => op_modpow,
                         61 => op_mod,
+                        62 if (self.flags & ENABLE_BLS_VALID_OPS) != 0 => op_bls_g1_valid,
+                        63 if (self.flags & ENABLE_BLS_VALID_OPS) != 0 => op_bls_g2_valid,
                         _ => {
-                            return unknown_operator(
-                                allocator,
-                                o,
-                                argument_list,
-                                self.flags,
-                                max_cost,
-                            );
+                            return None;
                         }
                     }
                 } else {
-                    return unknown_operator(allocator, o, argument_list, self.flags, max_cost);
+                    return None;
                 }
             }
         };
-        f(allocator, argument_list, max_cost)
+        Some(f)
+    }
+}
+
+impl Dialect for ChiaDialect {
+    fn op(
+        &self,
+        allocator: &mut Allocator,
+        o: NodePtr,
+        argument_list: NodePtr,
+        max_cost: Cost,
+        extension: OperatorSet,
+    ) -> Response {
+        let opcode = allocator.atom(o);
+        let Some(f) = self.handler(opcode.as_ref(), extension) else {
+            return unknown_operator(allocator, o, argument_list, self.flags, max_cost);
+        };
+        if (self.flags & STRICT_ARITY) != 0 {
+            if let Some(info) = operator_by_opcode(opcode.as_ref()) {
+                info.check_arity(allocator, argument_list)?;
+            }
+        }
+        let r = f(allocator, argument_list, max_cost)?;
+        if (self.flags & STRICT_INT_OUTPUTS) != 0 {
+            check_canonical_int(allocator, o, r.1)?;
+        }
+        Ok(r)
     }
 
     fn quote_kw(&self) -> u32 {
@@ -207,3 +294,22 @@ impl Dialect for ChiaDialect {
         (self.flags & NO_UNKNOWN_OPS) == 0
     }
 }
+
+#[test]
+fn test_check_canonical_int() {
+    let mut a = Allocator::new();
+    let o = a.nil();
+    let n = a.new_number((-129).into()).unwrap();
+    assert!(check_canonical_int(&a, o, n).is_ok());
+    // bytes aren't integers, they may have any encoding
+    let n = a.new_atom(&[0, 1]).unwrap();
+    assert!(check_canonical_int(&a, o, n).is_ok());
+    let n = a.new_pair(n, n).unwrap();
+    assert!(check_canonical_int(&a, o, n).is_ok());
+    // an operator building an integer with a redundant leading zero
+    let n = a.new_atom_with_kind(&[0, 1], AtomKind::Int).unwrap();
+    assert_eq!(
+        check_canonical_int(&a, o, n).unwrap_err().1,
+        "internal error: non-canonical integer output"
+    );
+}
@@ -1,20 +1,15 @@
 use crate::allocator::{Allocator, NodePtr, SExp};
-use crate::cost::Cost;
+use crate::cost::{Cost, CostAcc};
+use crate::cost_schedule_v1::{
+    CONS_COST, EQ_BASE_COST, EQ_COST_PER_BYTE, FIRST_COST, IF_COST, LISTP_COST,
+    MALLOC_COST_PER_BYTE, REST_COST, TREE_WALK_BASE_COST, TREE_WALK_COST_PER_NODE,
+    TREE_WALK_MAX_NODES,
+};
 use crate::err_utils::err;
+use crate::error_catalog::{fill, CLVM_RAISE, NODE_LIMIT, ON_LIST};
 use crate::op_utils::{first, get_args, nilp, rest};
 use crate::reduction::{EvalErr, Reduction, Response};
 
-const FIRST_COST: Cost = 30;
-const IF_COST: Cost = 33;
-// Cons cost lowered from 245. It only allocates a pair, which is small
-const CONS_COST: Cost = 50;
-// Rest cost lowered from 77 since it doesn't allocate anything and it should be
-// the same as first
-const REST_COST: Cost = 30;
-const LISTP_COST: Cost = 19;
-const EQ_BASE_COST: Cost = 117;
-const EQ_COST_PER_BYTE: Cost = 1;
-
 pub fn op_if(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
     let [cond, affirmative, negative] = get_args::<3>(a, input, "i")?;
     let chosen_node = if nilp(a, cond) { negative } else { affirmative };
@@ -59,14 +54,14 @@ pub fn op_raise(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response
         input
     };
 
-    err(throw_value, "clvm raise")
+    err(throw_value, CLVM_RAISE)
 }
 
 fn ensure_atom(a: &Allocator, n: NodePtr, op: &str) -> Result<(), EvalErr> {
     if let SExp::Atom = a.sexp(n) {
         Ok(())
     } else {
-        Err(EvalErr(n, format!("{op} on list")))
+        Err(EvalErr(n, fill(ON_LIST, &[&op])))
     }
 }
 
@@ -78,3 +73,90 @@ pub fn op_eq(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
     let cost = EQ_BASE_COST + (a.atom_len(s0) as Cost + a.atom_len(s1) as Cost) * EQ_COST_PER_BYTE;
     Ok(Reduction(cost, if eq { a.one() } else { a.nil() }))
 }
+
+// returns the number of nodes (pairs and atoms) and the depth of the tree. An
+// atom has depth 0 and a pair is one deeper than its deepest child
+fn walk_tree(
+    a: &Allocator,
+    root: NodePtr,
+    max_cost: Cost,
+    op: &str,
+) -> Result<(CostAcc, u64, u64), EvalErr> {
+    let mut cost = CostAcc::new(TREE_WALK_BASE_COST);
+    let mut nodes: u64 = 0;
+    let mut max_depth: u64 = 0;
+    let mut stack = vec![(root, 0_u64)];
+    while let Some((node, depth)) = stack.pop() {
+        nodes += 1;
+        if nodes > TREE_WALK_MAX_NODES {
+            return Err(EvalErr(root, fill(NODE_LIMIT, &[&op])));
+        }
+        cost.add(TREE_WALK_COST_PER_NODE);
+        cost.check(a, max_cost)?;
+        max_depth = max_depth.max(depth);
+        if let SExp::Pair(first, rest) = a.sexp(node) {
+            stack.push((rest, depth + 1));
+            stack.push((first, depth + 1));
+        }
+    }
+    Ok((cost, nodes, max_depth))
+}
+
+pub fn op_tree_size(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Response {
+    let [n] = get_args::<1>(a, input, "tree_size")?;
+    let (cost, nodes, _) = walk_tree(a, n, max_cost, "tree_size")?;
+    let r = a.new_number(nodes.into())?;
+    Ok(Reduction(
+        cost.cost() + a.atom_len(r) as Cost * MALLOC_COST_PER_BYTE,
+        r,
+    ))
+}
+
+pub fn op_tree_depth(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Response {
+    let [n] = get_args::<1>(a, input, "tree_depth")?;
+    let (cost, _, depth) = walk_tree(a, n, max_cost, "tree_depth")?;
+    let r = a.new_number(depth.into())?;
+    Ok(Reduction(
+        cost.cost() + a.atom_len(r) as Cost * MALLOC_COST_PER_BYTE,
+        r,
+    ))
+}
+
+#[cfg(test)]
+fn shared_tree(a: &mut Allocator, levels: usize) -> NodePtr {
+    // every level refers to the level below twice, so the tree has
+    // 2^(levels + 1) - 1 nodes, but the allocator only has levels + 1
+    let mut node = a.nil();
+    for _ in 0..levels {
+        node = a.new_pair(node, node).unwrap();
+    }
+    a.new_pair(node, a.nil()).unwrap()
+}
+
+#[test]
+fn test_tree_walk_shared() {
+    let mut a = Allocator::new();
+    let args = shared_tree(&mut a, 18);
+    let Reduction(cost, size) = op_tree_size(&mut a, args, 11000000000).unwrap();
+    assert_eq!(a.number(size), 524287.into());
+    assert_eq!(
+        cost,
+        TREE_WALK_BASE_COST + 524287 * TREE_WALK_COST_PER_NODE + 30
+    );
+    let Reduction(_, depth) = op_tree_depth(&mut a, args, 11000000000).unwrap();
+    assert_eq!(a.number(depth), 18.into());
+
+    // the walk fails once the cost limit is reached
+    let e = op_tree_size(&mut a, args, 1000000).unwrap_err();
+    assert_eq!(e.1, "cost exceeded");
+}
+
+#[test]
+fn test_tree_walk_node_limit() {
+    let mut a = Allocator::new();
+    let args = shared_tree(&mut a, 19);
+    let e = op_tree_size(&mut a, args, Cost::MAX).unwrap_err();
+    assert_eq!(e.1, "tree_size node limit exceeded");
+    let e = op_tree_depth(&mut a, args, Cost::MAX).unwrap_err();
+    assert_eq!(e.1, "tree_depth node limit exceeded");
+}
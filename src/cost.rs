@@ -1,12 +1,178 @@
-use crate::allocator::Allocator;
+use crate::allocator::{Allocator, NodePtr};
+use crate::error_catalog::COST_EXCEEDED;
 use crate::reduction::EvalErr;
 
 pub type Cost = u64;
 
 pub fn check_cost(a: &Allocator, cost: Cost, max_cost: Cost) -> Result<(), EvalErr> {
     if cost > max_cost {
-        Err(EvalErr(a.nil(), "cost exceeded".into()))
+        Err(EvalErr(a.nil(), COST_EXCEEDED.into()))
     } else {
         Ok(())
     }
 }
+
+// Accumulates the cost of an operator. All additions and multiplications
+// saturate at Cost::MAX instead of overflowing (or wrapping in release
+// builds). A saturated accumulator always fails check(), regardless of
+// max_cost, so pathological per-byte or per-argument costs are reported as
+// "cost exceeded".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct CostAcc(Cost);
+
+impl CostAcc {
+    pub const fn new(cost: Cost) -> Self {
+        Self(cost)
+    }
+
+    pub fn cost(self) -> Cost {
+        self.0
+    }
+
+    pub fn is_saturated(self) -> bool {
+        self.0 == Cost::MAX
+    }
+
+    pub fn add(&mut self, cost: Cost) {
+        self.0 = self.0.saturating_add(cost);
+    }
+
+    // adds count * per_unit, e.g. the number of bytes times the cost per byte
+    pub fn add_per(&mut self, count: usize, per_unit: Cost) {
+        self.add(per_unit.saturating_mul(Cost::try_from(count).unwrap_or(Cost::MAX)));
+    }
+
+    // returns the accumulated cost with cost added to it, without modifying
+    // this accumulator. This is useful for checking the cost including a
+    // pending term that's added at the end of an operator
+    pub fn plus(self, cost: Cost) -> Self {
+        let mut ret = self;
+        ret.add(cost);
+        ret
+    }
+
+    pub fn plus_per(self, count: usize, per_unit: Cost) -> Self {
+        let mut ret = self;
+        ret.add_per(count, per_unit);
+        ret
+    }
+
+    // the non-saturating version of add(). Returns None on overflow
+    pub fn checked_add(self, cost: Cost) -> Option<Self> {
+        self.0.checked_add(cost).map(Self)
+    }
+
+    pub fn check(self, a: &Allocator, max_cost: Cost) -> Result<(), EvalErr> {
+        if self.is_saturated() {
+            Err(EvalErr(a.nil(), COST_EXCEEDED.into()))
+        } else {
+            check_cost(a, self.0, max_cost)
+        }
+    }
+}
+
+// A cost limit shared by several sequential runs, e.g. running a generator
+// and then each of the puzzles it returns. Each run is limited to what's left
+// of the budget, and its cost is charged to it. Unlike the max_cost argument to
+// run_program(), a max_cost of 0 means nothing can be spent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CostBudget {
+    max_cost: Cost,
+    spent: Cost,
+}
+
+impl CostBudget {
+    pub const fn new(max_cost: Cost) -> Self {
+        Self { max_cost, spent: 0 }
+    }
+
+    pub fn max_cost(&self) -> Cost {
+        self.max_cost
+    }
+
+    pub fn spent(&self) -> Cost {
+        self.spent
+    }
+
+    pub fn remaining(&self) -> Cost {
+        self.max_cost - self.spent
+    }
+
+    // charges cost to the budget. If it exceeds what's left, the budget is
+    // exhausted and this fails with "cost exceeded"
+    pub fn charge(&mut self, cost: Cost) -> Result<(), EvalErr> {
+        if cost > self.remaining() {
+            self.spent = self.max_cost;
+            return Err(EvalErr(NodePtr::NIL, COST_EXCEEDED.into()));
+        }
+        self.spent += cost;
+        Ok(())
+    }
+
+    // marks the budget as exhausted, e.g. when a run failed with "cost
+    // exceeded"
+    pub fn exhaust(&mut self) {
+        self.spent = self.max_cost;
+    }
+}
+
+#[test]
+fn test_cost_acc() {
+    let a = Allocator::new();
+    let mut cost = CostAcc::new(100);
+    cost.add(20);
+    cost.add_per(3, 10);
+    assert_eq!(cost.cost(), 150);
+    assert_eq!(cost.plus(1).cost(), 151);
+    assert_eq!(cost.plus_per(2, 5).cost(), 160);
+    // plus() does not modify the accumulator
+    assert_eq!(cost.cost(), 150);
+    assert!(cost.check(&a, 150).is_ok());
+    assert_eq!(cost.check(&a, 149).unwrap_err().1, "cost exceeded");
+}
+
+#[test]
+fn test_cost_acc_saturate() {
+    let a = Allocator::new();
+    let mut cost = CostAcc::new(Cost::MAX - 10);
+    assert!(!cost.is_saturated());
+    assert!(cost.check(&a, Cost::MAX).is_ok());
+    cost.add(10);
+    assert!(cost.is_saturated());
+    cost.add(10);
+    assert_eq!(cost.cost(), Cost::MAX);
+    // even a max_cost of Cost::MAX does not accept a saturated cost
+    assert_eq!(cost.check(&a, Cost::MAX).unwrap_err().1, "cost exceeded");
+
+    let mut cost = CostAcc::new(1);
+    cost.add_per(usize::MAX, Cost::MAX / 2);
+    assert!(cost.is_saturated());
+
+    let cost = CostAcc::new(Cost::MAX - 1).plus_per(1, 2);
+    assert!(cost.is_saturated());
+}
+
+#[test]
+fn test_cost_acc_checked() {
+    let cost = CostAcc::new(Cost::MAX - 1);
+    assert_eq!(cost.checked_add(1), Some(CostAcc::new(Cost::MAX)));
+    assert_eq!(cost.checked_add(2), None);
+}
+
+#[test]
+fn test_cost_budget() {
+    let mut budget = CostBudget::new(100);
+    assert_eq!(budget.remaining(), 100);
+    assert!(budget.charge(60).is_ok());
+    assert!(budget.charge(40).is_ok());
+    assert_eq!((budget.spent(), budget.remaining()), (100, 0));
+    assert!(budget.charge(0).is_ok());
+    assert_eq!(budget.charge(1).unwrap_err().1, "cost exceeded");
+
+    let mut budget = CostBudget::new(100);
+    budget.charge(10).unwrap();
+    assert_eq!(budget.charge(91).unwrap_err().1, "cost exceeded");
+    // a failed charge exhausts the budget
+    assert_eq!(budget.remaining(), 0);
+    assert_eq!(budget.max_cost(), 100);
+}
@@ -0,0 +1,255 @@
+use crate::allocator::{Allocator, NodePtr};
+use crate::cost::Cost;
+use crate::dialect::Dialect;
+use crate::hex;
+use crate::operator_info::operator_by_opcode;
+use crate::reduction::Response;
+use crate::run_program::run_program_with_op_costs;
+use std::fmt;
+
+// A breakdown of what a run spent its cost on, per operator, for puzzle
+// developers to find out where the cost of a spend goes. The cost charged by
+// the interpreter itself (quoting, path lookups and applying operators) is
+// listed separately, as the evaluation cost.
+//
+// The report can be printed as a table (with Display), or exported as JSON
+// with to_json().
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpCost {
+    // the operator's name, or its opcode in hex for operators that aren't
+    // part of ChiaDialect
+    pub name: String,
+    pub calls: u64,
+    // the total cost the operator charged, not including the cost of
+    // evaluating its arguments
+    pub cost: Cost,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CostReport {
+    pub max_cost: Cost,
+    // the cost of the run. If it failed with "cost exceeded", this is the
+    // cost spent before the step that exceeded the limit. If it failed for
+    // any other reason, it's the cost of the operators only
+    pub cost: Cost,
+    // the error the run failed with, if any
+    pub error: Option<String>,
+    // most expensive first
+    pub ops: Vec<OpCost>,
+}
+
+impl CostReport {
+    // the part of the cost not charged by operators
+    pub fn evaluation_cost(&self) -> Cost {
+        let ops: Cost = self.ops.iter().map(|op| op.cost).sum();
+        self.cost.saturating_sub(ops)
+    }
+
+    // the share of max_cost (or of the total cost, if there was no limit), in
+    // percent
+    fn percent(&self, cost: Cost) -> f64 {
+        let budget = if self.max_cost == 0 {
+            self.cost
+        } else {
+            self.max_cost
+        };
+        if budget == 0 {
+            return 0.0;
+        }
+        cost as f64 * 100.0 / budget as f64
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut ret = format!(
+            "{{\"max_cost\":{},\"cost\":{},\"evaluation_cost\":{},\"error\":{},\"operators\":[",
+            self.max_cost,
+            self.cost,
+            self.evaluation_cost(),
+            self.error
+                .as_deref()
+                .map_or("null".to_string(), json_string),
+        );
+        for (i, op) in self.ops.iter().enumerate() {
+            if i > 0 {
+                ret += ",";
+            }
+            ret += &format!(
+                "{{\"name\":{},\"calls\":{},\"cost\":{}}}",
+                json_string(&op.name),
+                op.calls,
+                op.cost
+            );
+        }
+        ret + "]}"
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut ret = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => ret += "\\\"",
+            '\\' => ret += "\\\\",
+            c if (c as u32) < 0x20 => ret += &format!("\\u{:04x}", c as u32),
+            c => ret.push(c),
+        }
+    }
+    ret + "\""
+}
+
+impl fmt::Display for CostReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "operator                  calls            cost   budget"
+        )?;
+        for op in &self.ops {
+            writeln!(
+                f,
+                "{:20} {:10} {:15} {:7.2}%",
+                op.name,
+                op.calls,
+                op.cost,
+                self.percent(op.cost)
+            )?;
+        }
+        let evaluation = self.evaluation_cost();
+        writeln!(
+            f,
+            "{:20} {:>10} {:15} {:7.2}%",
+            "(evaluation)",
+            "-",
+            evaluation,
+            self.percent(evaluation)
+        )?;
+        writeln!(
+            f,
+            "{:20} {:>10} {:15} {:7.2}%",
+            "total",
+            "",
+            self.cost,
+            self.percent(self.cost)
+        )?;
+        if let Some(error) = &self.error {
+            writeln!(f, "failed: {error}")?;
+        }
+        Ok(())
+    }
+}
+
+// runs the program, and returns its result along with the cost report
+pub fn run_program_with_cost_report<D: Dialect>(
+    a: &mut Allocator,
+    dialect: &D,
+    program: NodePtr,
+    env: NodePtr,
+    max_cost: Cost,
+) -> (Response, CostReport) {
+    let (cost_exceeded, op_costs, r) =
+        run_program_with_op_costs(a, dialect, program, env, max_cost);
+    let mut ops: Vec<OpCost> = op_costs
+        .into_iter()
+        .map(|(opcode, (calls, cost))| OpCost {
+            name: operator_by_opcode(&opcode).map_or_else(
+                || format!("0x{}", hex::encode(&opcode)),
+                |info| info.name.to_string(),
+            ),
+            calls,
+            cost,
+        })
+        .collect();
+    ops.sort_by(|lhs, rhs| {
+        rhs.cost
+            .cmp(&lhs.cost)
+            .then(rhs.calls.cmp(&lhs.calls))
+            .then(lhs.name.cmp(&rhs.name))
+    });
+    let (cost, error) = match (&r, cost_exceeded) {
+        (Ok(reduction), _) => (reduction.0, None),
+        (Err(e), Some(ce)) => (ce.cost, Some(e.1.clone())),
+        (Err(e), None) => (ops.iter().map(|op| op.cost).sum(), Some(e.1.clone())),
+    };
+    let report = CostReport {
+        max_cost,
+        cost,
+        error,
+        ops,
+    };
+    (r, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chia_dialect::ChiaDialect;
+    use crate::test_ops::parse_exp;
+
+    fn report(prg: &str, max_cost: Cost) -> CostReport {
+        let mut a = Allocator::new();
+        let program = parse_exp(&mut a, prg).0;
+        run_program_with_cost_report(
+            &mut a,
+            &ChiaDialect::new(0),
+            program,
+            NodePtr::NIL,
+            max_cost,
+        )
+        .1
+    }
+
+    #[test]
+    fn test_cost_report() {
+        let r = report(
+            "(c (sha256 (q . 1)) (c (sha256 (q . 2)) (sha256 (q . 3))))",
+            1000000,
+        );
+        assert_eq!(r.error, None);
+        assert_eq!(r.ops.len(), 2);
+        assert_eq!(r.ops[0].name, "sha256");
+        assert_eq!(r.ops[0].calls, 3);
+        assert_eq!(r.ops[1].name, "c");
+        assert_eq!(r.ops[1].calls, 2);
+        assert_eq!(r.cost, r.ops[0].cost + r.ops[1].cost + r.evaluation_cost());
+        assert!(r.evaluation_cost() > 0);
+
+        let table = r.to_string();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[1].starts_with("sha256 "));
+        assert!(lines[3].starts_with("(evaluation) "));
+        assert!(lines[4].starts_with("total "));
+
+        assert_eq!(
+            r.to_json(),
+            format!(
+                "{{\"max_cost\":1000000,\"cost\":{},\"evaluation_cost\":{},\"error\":null,\"operators\":[\
+                {{\"name\":\"sha256\",\"calls\":3,\"cost\":{}}},\
+                {{\"name\":\"c\",\"calls\":2,\"cost\":{}}}]}}",
+                r.cost,
+                r.evaluation_cost(),
+                r.ops[0].cost,
+                r.ops[1].cost
+            )
+        );
+    }
+
+    #[test]
+    fn test_failed_run() {
+        // arguments are evaluated last to first
+        let r = report("(c (x (q . 1)) (sha256 (q . 1)))", 1000000);
+        assert_eq!(r.error.as_deref(), Some("clvm raise"));
+        assert_eq!(r.ops.len(), 1);
+        assert_eq!(r.ops[0].name, "sha256");
+        assert_eq!(r.cost, r.ops[0].cost);
+        assert!(r.to_string().ends_with("failed: clvm raise\n"));
+
+        // the cost spent before exceeding the limit
+        let r = report("(sha256 (sha256 (q . 1)))", 600);
+        assert_eq!(r.error.as_deref(), Some("cost exceeded"));
+        assert!(r.cost <= 600);
+        assert_eq!(r.ops[0].calls, 1);
+
+        assert_eq!(json_string("a\"b\\c\n"), "\"a\\\"b\\\\c\\u000a\"");
+    }
+}
@@ -0,0 +1,228 @@
+use crate::cost::Cost;
+
+// Version 1 of the cost schedule: the cost of every operator, and of the
+// interpreter itself. All prices live in this table, so that a future revision
+// of the schedule can be added as a separate table (cost_schedule_v2.rs),
+// rather than by editing constants spread across the operator modules.
+//
+// Changing any of these values is a consensus change. Existing values must
+// never be edited; bump the version instead.
+pub const COST_SCHEDULE_VERSION: u32 = 1;
+
+// interpreter
+
+// lowered from 46
+pub(crate) const QUOTE_COST: Cost = 20;
+// lowered from 138
+pub(crate) const APPLY_COST: Cost = 90;
+// the cost of entering a softfork guard
+pub(crate) const GUARD_COST: Cost = 140;
+// mandatory base cost for every operator we execute
+pub(crate) const OP_COST: Cost = 1;
+
+// path lookup
+
+// lowered from measured 147 per bit. It doesn't seem to take this long in
+// practice
+pub(crate) const TRAVERSE_BASE_COST: Cost = 40;
+pub(crate) const TRAVERSE_COST_PER_ZERO_BYTE: Cost = 4;
+pub(crate) const TRAVERSE_COST_PER_BIT: Cost = 4;
+
+// allocation
+
+// We ascribe some additional cost per byte for operations that allocate new atoms.
+// See the response helpers in op_utils for how it is charged
+pub const MALLOC_COST_PER_BYTE: Cost = 10;
+
+// core operators
+
+pub(crate) const FIRST_COST: Cost = 30;
+pub(crate) const IF_COST: Cost = 33;
+// Cons cost lowered from 245. It only allocates a pair, which is small
+pub(crate) const CONS_COST: Cost = 50;
+// Rest cost lowered from 77 since it doesn't allocate anything and it should be
+// the same as first
+pub(crate) const REST_COST: Cost = 30;
+pub(crate) const LISTP_COST: Cost = 19;
+pub(crate) const EQ_BASE_COST: Cost = 117;
+pub(crate) const EQ_COST_PER_BYTE: Cost = 1;
+
+// tree_size and tree_depth visit every node of their argument, as a tree. A
+// subtree that's referenced more than once is visited once per reference, so
+// the number of nodes visited may be exponential in the number of nodes in the
+// allocator. The walk charges for every node and also fails once it has
+// visited TREE_WALK_MAX_NODES nodes, regardless of the cost limit
+pub(crate) const TREE_WALK_BASE_COST: Cost = 100;
+pub(crate) const TREE_WALK_COST_PER_NODE: Cost = 20;
+pub(crate) const TREE_WALK_MAX_NODES: u64 = 1000000;
+
+// arithmetic, string and bitwise operators
+
+pub(crate) const ARITH_BASE_COST: Cost = 99;
+pub(crate) const ARITH_COST_PER_ARG: Cost = 320;
+pub(crate) const ARITH_COST_PER_BYTE: Cost = 3;
+
+pub(crate) const LOG_BASE_COST: Cost = 100;
+pub(crate) const LOG_COST_PER_ARG: Cost = 264;
+pub(crate) const LOG_COST_PER_BYTE: Cost = 3;
+
+pub(crate) const LOGNOT_BASE_COST: Cost = 331;
+pub(crate) const LOGNOT_COST_PER_BYTE: Cost = 3;
+
+pub(crate) const MUL_BASE_COST: Cost = 92;
+pub(crate) const MUL_COST_PER_OP: Cost = 885;
+pub(crate) const MUL_LINEAR_COST_PER_BYTE: Cost = 6;
+pub(crate) const MUL_SQUARE_COST_PER_BYTE_DIVIDER: Cost = 128;
+
+pub(crate) const GR_BASE_COST: Cost = 498;
+pub(crate) const GR_COST_PER_BYTE: Cost = 2;
+
+pub(crate) const GRS_BASE_COST: Cost = 117;
+pub(crate) const GRS_COST_PER_BYTE: Cost = 1;
+
+pub(crate) const STRLEN_BASE_COST: Cost = 173;
+pub(crate) const STRLEN_COST_PER_BYTE: Cost = 1;
+
+pub(crate) const CONCAT_BASE_COST: Cost = 142;
+pub(crate) const CONCAT_COST_PER_ARG: Cost = 135;
+pub(crate) const CONCAT_COST_PER_BYTE: Cost = 3;
+
+pub(crate) const DIVMOD_BASE_COST: Cost = 1116;
+pub(crate) const DIVMOD_COST_PER_BYTE: Cost = 6;
+
+pub(crate) const DIV_BASE_COST: Cost = 988;
+pub(crate) const DIV_COST_PER_BYTE: Cost = 4;
+
+// dividing is quadratic in the size of the operands too. Dividing an n byte
+// number by an m byte one with num-bigint took 1 - 5.5 times as long as
+// multiplying numbers whose sizes have the same product, n * m, so divexact
+// charges 4 times what * does for the product of the operand sizes
+pub(crate) const DIVEXACT_BASE_COST: Cost = 988;
+pub(crate) const DIVEXACT_COST_PER_BYTE: Cost = 4;
+pub(crate) const DIVEXACT_SQUARE_COST_PER_BYTE_DIVIDER: Cost = MUL_SQUARE_COST_PER_BYTE_DIVIDER / 4;
+
+// computing the gcd is quadratic in the size of the operands, so, like *, gcd
+// also charges for the product of the operand sizes
+pub(crate) const GCD_BASE_COST: Cost = 1200;
+pub(crate) const GCD_COST_PER_BYTE: Cost = 6;
+pub(crate) const GCD_SQUARE_COST_PER_BYTE_DIVIDER: Cost = 32;
+
+// mul_div_floor charges for the product of the sizes of the two factors, like
+// *, and for dividing their (double length) product by the divisor, like
+// divexact
+pub(crate) const MUL_DIV_FLOOR_BASE_COST: Cost = 1500;
+pub(crate) const MUL_DIV_FLOOR_COST_PER_BYTE: Cost = 8;
+pub(crate) const MUL_DIV_FLOOR_SQUARE_COST_PER_BYTE_DIVIDER: Cost =
+    DIVEXACT_SQUARE_COST_PER_BYTE_DIVIDER;
+
+pub(crate) const SQRT_FLOOR_BASE_COST: Cost = 1000;
+pub(crate) const SQRT_FLOOR_COST_PER_BYTE: Cost = 6;
+pub(crate) const SQRT_FLOOR_SQUARE_COST_PER_BYTE_DIVIDER: Cost = 64;
+
+// popcount and bitlength interpret their argument as an unsigned big-endian
+// number (i.e. a string of bits), not as a signed integer
+pub(crate) const POPCOUNT_BASE_COST: Cost = 200;
+pub(crate) const POPCOUNT_COST_PER_BYTE: Cost = 2;
+
+pub(crate) const BITLENGTH_BASE_COST: Cost = 173;
+pub(crate) const BITLENGTH_COST_PER_BYTE: Cost = 1;
+
+pub(crate) const SHA256_BASE_COST: Cost = 87;
+pub(crate) const SHA256_COST_PER_ARG: Cost = 134;
+pub(crate) const SHA256_COST_PER_BYTE: Cost = 2;
+
+pub(crate) const ASHIFT_BASE_COST: Cost = 596;
+pub(crate) const ASHIFT_COST_PER_BYTE: Cost = 3;
+
+pub(crate) const LSHIFT_BASE_COST: Cost = 277;
+pub(crate) const LSHIFT_COST_PER_BYTE: Cost = 3;
+
+pub(crate) const BOOL_BASE_COST: Cost = 200;
+pub(crate) const BOOL_COST_PER_ARG: Cost = 300;
+
+// Raspberry PI 4 is about 7.679960 / 1.201742 = 6.39 times slower
+// in the point_add benchmark
+
+// increased from 31592 to better model Raspberry PI
+pub(crate) const POINT_ADD_BASE_COST: Cost = 101094;
+// increased from 419994 to better model Raspberry PI
+pub(crate) const POINT_ADD_COST_PER_ARG: Cost = 1343980;
+
+// Raspberry PI 4 is about 2.833543 / 0.447859 = 6.32686 times slower
+// in the pubkey benchmark
+
+// increased from 419535 to better model Raspberry PI
+pub(crate) const PUBKEY_BASE_COST: Cost = 1325730;
+// increased from 12 to closer model Raspberry PI
+pub(crate) const PUBKEY_COST_PER_BYTE: Cost = 38;
+
+// the new coinid operator
+// we subtract 153 cost as a discount, to incentivize using this operator rather
+// than "naked" sha256
+pub(crate) const COINID_COST: Cost =
+    SHA256_BASE_COST + SHA256_COST_PER_ARG * 3 + SHA256_COST_PER_BYTE * (32 + 32 + 8) - 153;
+
+pub(crate) const MODPOW_BASE_COST: Cost = 17000;
+pub(crate) const MODPOW_COST_PER_BYTE_BASE_VALUE: Cost = 38;
+// the cost for exponent and modular scale by the square of the size of the
+// respective operands
+pub(crate) const MODPOW_COST_PER_BYTE_EXPONENT: Cost = 3;
+pub(crate) const MODPOW_COST_PER_BYTE_MOD: Cost = 21;
+
+// BLS operators
+
+// the same cost as point_add (aka g1_add)
+pub(crate) const BLS_G1_SUBTRACT_BASE_COST: Cost = 101094;
+pub(crate) const BLS_G1_SUBTRACT_COST_PER_ARG: Cost = 1343980;
+
+pub(crate) const BLS_G1_MULTIPLY_BASE_COST: Cost = 705500;
+pub(crate) const BLS_G1_MULTIPLY_COST_PER_BYTE: Cost = 10;
+
+// this is the same cost as XORing the top bit (minus the heap allocation of the
+// return value, which the operator is adding back)
+pub(crate) const BLS_G1_NEGATE_BASE_COST: Cost = 1396 - 480;
+
+// g2_add and g2_subtract have the same cost
+pub(crate) const BLS_G2_ADD_BASE_COST: Cost = 80000;
+pub(crate) const BLS_G2_ADD_COST_PER_ARG: Cost = 1950000;
+pub(crate) const BLS_G2_SUBTRACT_BASE_COST: Cost = 80000;
+pub(crate) const BLS_G2_SUBTRACT_COST_PER_ARG: Cost = 1950000;
+
+pub(crate) const BLS_G2_MULTIPLY_BASE_COST: Cost = 2100000;
+pub(crate) const BLS_G2_MULTIPLY_COST_PER_BYTE: Cost = 5;
+
+// this is the same cost as XORing the top bit (minus the heap allocation of the
+// return value, which the operator is adding back)
+pub(crate) const BLS_G2_NEGATE_BASE_COST: Cost = 2164 - 960;
+
+// validating the point is what dominates the cost of g1_negate and g2_negate,
+// so the predicates have the same cost (without any heap allocation)
+pub(crate) const BLS_G1_VALID_BASE_COST: Cost = BLS_G1_NEGATE_BASE_COST;
+pub(crate) const BLS_G2_VALID_BASE_COST: Cost = BLS_G2_NEGATE_BASE_COST;
+
+pub(crate) const BLS_MAP_TO_G1_BASE_COST: Cost = 195000;
+pub(crate) const BLS_MAP_TO_G1_COST_PER_BYTE: Cost = 4;
+pub(crate) const BLS_MAP_TO_G1_COST_PER_DST_BYTE: Cost = 4;
+
+pub(crate) const BLS_MAP_TO_G2_BASE_COST: Cost = 815000;
+pub(crate) const BLS_MAP_TO_G2_COST_PER_BYTE: Cost = 4;
+pub(crate) const BLS_MAP_TO_G2_COST_PER_DST_BYTE: Cost = 4;
+
+pub(crate) const BLS_PAIRING_BASE_COST: Cost = 3000000;
+pub(crate) const BLS_PAIRING_COST_PER_ARG: Cost = 1200000;
+
+// secp operators
+
+pub(crate) const SECP256R1_VERIFY_COST: Cost = 1850000;
+pub(crate) const SECP256K1_VERIFY_COST: Cost = 1300000;
+
+// ed25519
+
+// the base cost is the cost assigned to the opcode when it's treated as an
+// unknown operator (see chia_dialect.rs). Unlike the secp operators, ed25519
+// signs the message itself (not a digest of it) so the cost also scales with
+// the message length. benchmark-clvm-cost measured a base cost of 188000 -
+// 203000 and 40 - 51 per message byte (on the scale calibrated against
+// point_add), so the base cost leaves room for messages of a few kB
+pub(crate) const ED25519_VERIFY_BASE_COST: Cost = 450000;
+pub(crate) const ED25519_VERIFY_COST_PER_BYTE: Cost = 50;
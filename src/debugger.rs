@@ -0,0 +1,195 @@
+use crate::allocator::NodePtr;
+use crate::dialect::Dialect;
+use crate::op_utils::first;
+use crate::reduction::{EvalErr, Reduction};
+use crate::run_program::{NextStep, Vm, VmStep};
+use crate::sexp_store::SExpStore;
+
+// A debugger for CLVM programs, built on Vm. It runs a program until it's
+// about to take a step matching one of its breakpoints, and then lets the
+// caller inspect the state of the Vm and single-step through it.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    // stops before applying the operator with this opcode
+    Operator(u32),
+    // stops before evaluating this program node, either as the whole program,
+    // as an argument of an operator, or as the program run by apply
+    Node(NodePtr),
+}
+
+// why Debugger::resume() returned
+#[derive(Debug, PartialEq, Eq)]
+pub enum Stop {
+    // the next step matches the breakpoint
+    Breakpoint(Breakpoint),
+    Done(Reduction),
+}
+
+pub struct Debugger<'a, S: SExpStore, D> {
+    vm: Vm<'a, S, D>,
+    breakpoints: Vec<Breakpoint>,
+}
+
+impl<'a, S: SExpStore, D: Dialect<S>> Debugger<'a, S, D> {
+    pub fn new(vm: Vm<'a, S, D>) -> Self {
+        Self {
+            vm,
+            breakpoints: Vec::new(),
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, bp: Breakpoint) {
+        if !self.breakpoints.contains(&bp) {
+            self.breakpoints.push(bp);
+        }
+    }
+
+    pub fn remove_breakpoint(&mut self, bp: Breakpoint) {
+        self.breakpoints.retain(|b| *b != bp);
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    // the Vm, to inspect its next step and stacks
+    pub fn vm(&self) -> &Vm<'a, S, D> {
+        &self.vm
+    }
+
+    // takes a single step, regardless of breakpoints
+    pub fn step(&mut self) -> Result<VmStep, EvalErr> {
+        self.vm.step(1)
+    }
+
+    // runs until the next step matches a breakpoint, or the program completes.
+    // The first step is always taken, so resuming at a breakpoint continues
+    // past it
+    pub fn resume(&mut self) -> Result<Stop, EvalErr> {
+        loop {
+            if let VmStep::Done(reduction) = self.vm.step(1)? {
+                return Ok(Stop::Done(reduction));
+            }
+            if let Some(bp) = self.hit() {
+                return Ok(Stop::Breakpoint(bp));
+            }
+        }
+    }
+
+    // the breakpoint the next step matches, if any
+    pub fn hit(&self) -> Option<Breakpoint> {
+        let a = self.vm.allocator();
+        let (operator, program) = match self.vm.next_step()? {
+            NextStep::Eval { program, .. } => (None, Some(program)),
+            NextStep::Apply { operator, args } => {
+                let opcode = a.small_number(operator);
+                let program = if opcode == Some(self.vm.dialect().apply_kw()) {
+                    first(a, args).ok()
+                } else {
+                    None
+                };
+                (opcode, program)
+            }
+            _ => return None,
+        };
+        self.breakpoints.iter().copied().find(|bp| match bp {
+            Breakpoint::Operator(op) => operator == Some(*op),
+            Breakpoint::Node(node) => program == Some(*node),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::allocator::Allocator;
+    use crate::chia_dialect::ChiaDialect;
+    use crate::run_program::Operation;
+    use crate::test_ops::parse_exp;
+
+    #[test]
+    fn test_operator_breakpoint() {
+        let mut a = Allocator::new();
+        let dialect = ChiaDialect::new(0);
+        // (+ (q . 1) (* (q . 2) (q . 3)))
+        let program = parse_exp(&mut a, "(16 (q . 1) (18 (q . 2) (q . 3)))").0;
+        let vm = Vm::new(&mut a, &dialect, program, NodePtr::NIL, 0);
+        let mut dbg = Debugger::new(vm);
+        dbg.add_breakpoint(Breakpoint::Operator(18));
+        dbg.add_breakpoint(Breakpoint::Operator(18));
+        assert_eq!(dbg.breakpoints().len(), 1);
+
+        assert_eq!(
+            dbg.resume().unwrap(),
+            Stop::Breakpoint(Breakpoint::Operator(18))
+        );
+        let vm = dbg.vm();
+        let Some(NextStep::Apply { args, .. }) = vm.next_step() else {
+            panic!("expected apply");
+        };
+        let a = vm.allocator();
+        let (two, rest) = a.next(args).unwrap();
+        assert_eq!(a.number(two), 2.into());
+        assert_eq!(a.number(a.next(rest).unwrap().0), 3.into());
+        // the + is still to be applied, once its arguments are evaluated
+        assert_eq!(vm.op_stack()[0], Operation::Apply);
+        assert_eq!(vm.env_stack().len(), 2);
+
+        // single-step past the multiplication
+        assert_eq!(dbg.step().unwrap(), VmStep::Running);
+        let six = *dbg.vm().val_stack().last().unwrap();
+        assert_eq!(dbg.vm().allocator().number(six), 6.into());
+
+        let Stop::Done(Reduction(_, result)) = dbg.resume().unwrap() else {
+            panic!("expected done");
+        };
+        assert_eq!(small_number(&dbg, result), 7);
+        assert_eq!(dbg.step().unwrap_err().1, "vm finished");
+    }
+
+    fn small_number<D: Dialect<Allocator>>(dbg: &Debugger<'_, Allocator, D>, node: NodePtr) -> u32 {
+        dbg.vm().allocator().small_number(node).unwrap()
+    }
+
+    #[test]
+    fn test_node_breakpoint() {
+        let mut a = Allocator::new();
+        let dialect = ChiaDialect::new(0);
+        // runs the function in the environment twice, through apply
+        let function = parse_exp(&mut a, "(16 2 (q . 1))").0;
+        let program = parse_exp(&mut a, "(16 (a 2 (q 5)) (a 2 (q 7)))").0;
+        let env = a.new_pair(function, NodePtr::NIL).unwrap();
+        let vm = Vm::new(&mut a, &dialect, program, env, 0);
+        let mut dbg = Debugger::new(vm);
+
+        // the whole program is the first step
+        dbg.add_breakpoint(Breakpoint::Node(program));
+        assert_eq!(dbg.hit(), Some(Breakpoint::Node(program)));
+        dbg.remove_breakpoint(Breakpoint::Node(program));
+        assert_eq!(dbg.hit(), None);
+
+        dbg.add_breakpoint(Breakpoint::Node(function));
+        let mut envs = Vec::new();
+        loop {
+            match dbg.resume().unwrap() {
+                Stop::Breakpoint(bp) => {
+                    assert_eq!(bp, Breakpoint::Node(function));
+                    let Some(NextStep::Apply { args, .. }) = dbg.vm().next_step() else {
+                        panic!("expected apply");
+                    };
+                    let a = dbg.vm().allocator();
+                    let (_, rest) = a.next(args).unwrap();
+                    let (call_env, _) = a.next(rest).unwrap();
+                    envs.push(small_number(&dbg, a.next(call_env).unwrap().0));
+                }
+                Stop::Done(Reduction(_, result)) => {
+                    assert_eq!(small_number(&dbg, result), 14);
+                    break;
+                }
+            }
+        }
+        // arguments are evaluated last to first
+        assert_eq!(envs, [7, 5]);
+    }
+}
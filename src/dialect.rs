@@ -1,6 +1,8 @@
 use crate::allocator::{Allocator, NodePtr};
 use crate::cost::Cost;
+use crate::cost_schedule_v1::COST_SCHEDULE_VERSION;
 use crate::reduction::Response;
+use crate::sexp_store::SExpStore;
 
 #[repr(u32)]
 #[derive(Clone, Copy, Eq, PartialEq)]
@@ -9,18 +11,24 @@ pub enum OperatorSet {
     BLS,
 }
 
-pub trait Dialect {
+// The dialect defines the operators. It is parameterized by the node store the
+// operators work on, which is the Allocator by default
+pub trait Dialect<S: SExpStore = Allocator> {
     fn quote_kw(&self) -> u32;
     fn apply_kw(&self) -> u32;
     fn softfork_kw(&self) -> u32;
     fn softfork_extension(&self, ext: u32) -> OperatorSet;
     fn op(
         &self,
-        allocator: &mut Allocator,
+        allocator: &mut S,
         op: NodePtr,
         args: NodePtr,
         max_cost: Cost,
         extensions: OperatorSet,
     ) -> Response;
     fn allow_unknown_ops(&self) -> bool;
+    // the version of the cost schedule the operators charge by
+    fn cost_schedule_version(&self) -> u32 {
+        COST_SCHEDULE_VERSION
+    }
 }
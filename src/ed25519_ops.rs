@@ -0,0 +1,46 @@
+use crate::allocator::{Allocator, NodePtr};
+use crate::cost::{Cost, CostAcc};
+use crate::cost_schedule_v1::{ED25519_VERIFY_BASE_COST, ED25519_VERIFY_COST_PER_BYTE};
+use crate::err_utils::err;
+use crate::error_catalog::{
+    ED25519_FAILED, ED25519_INVALID_PUBKEY, ED25519_INVALID_SIG, ED25519_PUBKEY_SIZE,
+};
+use crate::op_utils::{atom, get_args};
+use crate::reduction::{Reduction, Response};
+use ed25519_dalek::{Signature, VerifyingKey};
+
+// expects: pubkey msg sig
+pub fn op_ed25519_verify(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Response {
+    let mut cost = CostAcc::new(ED25519_VERIFY_BASE_COST);
+    cost.check(a, max_cost)?;
+
+    let [pubkey, msg, sig] = get_args::<3>(a, input, "ed25519_verify")?;
+
+    // first argument is the 32 byte compressed edwards point
+    let pubkey = atom(a, pubkey, "ed25519_verify pubkey")?;
+    let Ok(pubkey) = <[u8; 32]>::try_from(pubkey.as_ref()) else {
+        return err(input, ED25519_PUBKEY_SIZE);
+    };
+    let verifier =
+        VerifyingKey::from_bytes(&pubkey).or_else(|_| err(input, ED25519_INVALID_PUBKEY))?;
+
+    // second arg is the message, of any length
+    let msg = atom(a, msg, "ed25519_verify msg")?;
+    cost.add_per(msg.as_ref().len(), ED25519_VERIFY_COST_PER_BYTE);
+    cost.check(a, max_cost)?;
+
+    // third arg is a fixed-size signature
+    let sig = atom(a, sig, "ed25519_verify sig")?;
+    let sig = Signature::from_slice(sig.as_ref()).or_else(|_| err(input, ED25519_INVALID_SIG))?;
+
+    // we use strict verification, which rejects weak public keys and
+    // malleable signatures. Any two implementations must agree on which
+    // signatures are valid
+    let result = verifier.verify_strict(msg.as_ref(), &sig);
+
+    if result.is_err() {
+        err(input, ED25519_FAILED)
+    } else {
+        Ok(Reduction(cost.cost(), a.nil()))
+    }
+}
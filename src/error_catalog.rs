@@ -0,0 +1,438 @@
+use std::fmt::{Display, Write};
+
+// The messages of the errors (EvalErr) the interpreter and the operators fail
+// with, and of the io::Errors of serialization and allocator images.
+// Downstream code matches on these strings, so they are part of the API, and
+// changing one is a breaking change. Every message has a stable code in
+// CATALOG, which test_catalog locks. New messages get new codes, and codes are
+// never reused.
+//
+// Messages with parameters are templates, where each {} is filled in by
+// fill(), in order. error_code() maps a message back to its code.
+
+// the interpreter
+pub const COST_EXCEEDED: &str = "cost exceeded";
+pub const BAD_OPERAND_LIST: &str = "bad operand list";
+pub const LONE_ATOM: &str = "in ((X)...) syntax X must be lone atom";
+pub const COST_MUST_BE_POSITIVE: &str = "cost must be > 0";
+pub const UNKNOWN_SOFTFORK_EXTENSION: &str = "unknown softfork extension";
+pub const SOFTFORK_COST_MISMATCH: &str = "softfork specified cost mismatch";
+pub const QUOTE_DEPTH_LIMIT: &str = "quote depth limit exceeded";
+pub const ENV_STACK_EMPTY: &str = "runtime error: env stack empty";
+pub const VALUE_STACK_EMPTY: &str = "runtime error: value stack empty";
+pub const ENV_STACK_LIMIT: &str = "environment stack limit reached";
+pub const VALUE_STACK_LIMIT: &str = "value stack limit reached";
+pub const VM_FINISHED: &str = "vm finished";
+pub const PATH_INTO_ATOM: &str = "path into atom";
+pub const PATH_MUST_BE_ATOM: &str = "path must be an atom";
+pub const UNIMPLEMENTED_OPERATOR: &str = "unimplemented operator";
+pub const RESERVED_OPERATOR: &str = "reserved operator";
+pub const INVALID_OPERATOR: &str = "invalid operator";
+pub const NON_CANONICAL_OUTPUT: &str = "internal error: non-canonical integer output";
+pub const CLVM_RAISE: &str = "clvm raise";
+
+// the allocator
+pub const OUT_OF_MEMORY: &str = "out of memory";
+pub const ATOM_TOO_LARGE: &str = "atom too large";
+pub const TOO_MANY_PAIRS: &str = "too many pairs";
+pub const TOO_MANY_ATOMS: &str = "too many atoms";
+pub const ATOM_OUT_OF_BOUNDS: &str = "(internal error) atom out of bounds";
+pub const SUBSTR_EXPECTED_ATOM: &str = "(internal error) substr expected atom, got pair";
+pub const CONCAT_EXPECTED_ATOM: &str = "(internal error) concat expected atom, got pair";
+pub const CONCAT_INVALID_SIZE: &str = "(internal error) concat passed invalid new_size";
+pub const SUBSTR_START_OUT_OF_BOUNDS: &str = "substr start out of bounds";
+pub const SUBSTR_END_OUT_OF_BOUNDS: &str = "substr end out of bounds";
+pub const SUBSTR_INVALID_BOUNDS: &str = "substr invalid bounds";
+pub const NOT_G1_SIZE: &str = "atom is not G1 size, 48 bytes";
+pub const NOT_G2_SIZE: &str = "atom is not G2 size, 96 bytes";
+pub const NOT_G1_POINT: &str = "atom is not a G1 point";
+pub const NOT_G2_POINT: &str = "atom is not a G2 point";
+pub const INVALID_G1_POINT: &str = "atom is not a valid G1 point";
+pub const INVALID_G2_POINT: &str = "atom is not a valid G2 point";
+pub const PAIR_NOT_G1: &str = "pair found, expected G1 point";
+pub const PAIR_NOT_G2: &str = "pair found, expected G2 point";
+
+// arguments. The first parameter is the name of the operator
+// the number of arguments, and "s" unless it's 1
+pub const TAKES_EXACTLY: &str = "{} takes exactly {} argument{}";
+pub const TAKES_NO_MORE_THAN: &str = "{} takes no more than {} argument{}";
+// the arity, as described by OperatorInfo
+pub const TAKES: &str = "{} takes {}";
+pub const FIRST_OF_NON_CONS: &str = "first of non-cons";
+pub const REST_OF_NON_CONS: &str = "rest of non-cons";
+pub const ON_LIST: &str = "{} on list";
+pub const REQUIRES_ATOM: &str = "{} requires an atom";
+pub const REQUIRES_INT_ARG: &str = "{} requires int arg";
+pub const REQUIRES_INT_ARGS: &str = "{} requires int args";
+pub const REQUIRES_INT32_ARGS: &str = "{} requires int32 args";
+pub const REQUIRES_CANONICAL_INT32_ARGS: &str = "{} requires int32 args (with no leading zeros)";
+pub const REQUIRES_POSITIVE_INT_ARG: &str = "{} requires positive int arg";
+// the number of bits
+pub const REQUIRES_UINT_ARG: &str = "{} requires u{} arg";
+pub const NODE_LIMIT: &str = "{} node limit exceeded";
+// the index of the argument, and the message of the error it failed with
+pub const ARGUMENT_ERROR: &str = "{} argument {}: {}";
+
+// operators
+pub const DIV_BY_ZERO: &str = "div with 0";
+pub const DIV_NEGATIVE: &str = "div operator with negative operands is deprecated";
+pub const DIVMOD_BY_ZERO: &str = "divmod with 0";
+pub const MOD_BY_ZERO: &str = "mod with 0";
+pub const DIVEXACT_BY_ZERO: &str = "divexact with 0";
+pub const DIVEXACT_REMAINDER: &str = "divexact with remainder";
+pub const MUL_DIV_FLOOR_BY_ZERO: &str = "mul_div_floor with 0";
+pub const SQRT_FLOOR_NEGATIVE: &str = "sqrt_floor of negative number";
+pub const MODPOW_NEGATIVE_EXPONENT: &str = "modpow with negative exponent";
+pub const MODPOW_ZERO_MODULUS: &str = "modpow with 0 modulus";
+pub const SUBSTR_ARG_COUNT: &str = "substr takes exactly 2 or 3 arguments";
+pub const SUBSTR_INVALID_INDICES: &str = "invalid indices for substr";
+pub const SHIFT_TOO_LARGE: &str = "shift too large";
+pub const COINID_PARENT_ID: &str = "coinid: invalid parent coin id (must be 32 bytes)";
+pub const COINID_PUZZLE_HASH: &str = "coinid: invalid puzzle hash (must be 32 bytes)";
+// the missing parenthesis is part of the message
+pub const COINID_NEGATIVE_AMOUNT: &str = "coinid: invalid amount (may not be negative";
+pub const COINID_LEADING_ZERO: &str =
+    "coinid: invalid amount (may not have redundant leading zero)";
+pub const COINID_AMOUNT_TOO_LARGE: &str = "coinid: invalid amount (may not exceed max coin amount)";
+pub const G1_MAP_ARG_COUNT: &str = "g1_map takes exactly 1 or 2 arguments";
+pub const G2_MAP_ARG_COUNT: &str = "g2_map takes exactly 1 or 2 arguments";
+pub const BLS_PAIRING_IDENTITY_FAILED: &str = "bls_pairing_identity failed";
+pub const BLS_VERIFY_FAILED: &str = "bls_verify failed";
+pub const SECP256K1_FAILED: &str = "secp256k1_verify failed";
+pub const SECP256K1_DIGEST_SIZE: &str = "secp256k1_verify message digest is not 32 bytes";
+pub const SECP256K1_INVALID_PUBKEY: &str = "secp256k1_verify pubkey is not valid";
+pub const SECP256K1_INVALID_SIG: &str = "secp256k1_verify sig is not valid";
+pub const SECP256R1_FAILED: &str = "secp256r1_verify failed";
+pub const SECP256R1_DIGEST_SIZE: &str = "secp256r1_verify message digest is not 32 bytes";
+pub const SECP256R1_INVALID_PUBKEY: &str = "secp256r1_verify pubkey is not valid";
+pub const SECP256R1_INVALID_SIG: &str = "secp256r1_verify sig is not valid";
+pub const ED25519_FAILED: &str = "ed25519_verify failed";
+pub const ED25519_PUBKEY_SIZE: &str = "ed25519_verify pubkey is not 32 bytes";
+pub const ED25519_INVALID_PUBKEY: &str = "ed25519_verify pubkey is not valid";
+pub const ED25519_INVALID_SIG: &str = "ed25519_verify sig is not valid";
+
+// serialization
+pub const BAD_ENCODING: &str = "bad encoding";
+pub const SERDE_INTERNAL_ERROR: &str = "internal error";
+pub const ATOM_TOO_BIG: &str = "atom too big";
+
+// allocator images
+// what's wrong with the image
+pub const INVALID_IMAGE: &str = "invalid allocator image: {}";
+pub const IMAGE_EXTERNAL_ATOMS: &str = "allocator image can't hold external atoms";
+
+// the test dialect
+// the arguments of fail_with
+pub const FAIL_WITH: &str = "fail_with: {}";
+
+pub const CATALOG: &[(u16, &str)] = &[
+    (1, COST_EXCEEDED),
+    (2, BAD_OPERAND_LIST),
+    (3, LONE_ATOM),
+    (4, COST_MUST_BE_POSITIVE),
+    (5, UNKNOWN_SOFTFORK_EXTENSION),
+    (6, SOFTFORK_COST_MISMATCH),
+    (7, QUOTE_DEPTH_LIMIT),
+    (8, ENV_STACK_EMPTY),
+    (9, VALUE_STACK_EMPTY),
+    (10, ENV_STACK_LIMIT),
+    (11, VALUE_STACK_LIMIT),
+    (12, VM_FINISHED),
+    (13, PATH_INTO_ATOM),
+    (14, PATH_MUST_BE_ATOM),
+    (15, UNIMPLEMENTED_OPERATOR),
+    (16, RESERVED_OPERATOR),
+    (17, INVALID_OPERATOR),
+    (18, NON_CANONICAL_OUTPUT),
+    (19, CLVM_RAISE),
+    (100, OUT_OF_MEMORY),
+    (101, ATOM_TOO_LARGE),
+    (102, TOO_MANY_PAIRS),
+    (103, TOO_MANY_ATOMS),
+    (104, ATOM_OUT_OF_BOUNDS),
+    (105, SUBSTR_EXPECTED_ATOM),
+    (106, CONCAT_EXPECTED_ATOM),
+    (107, CONCAT_INVALID_SIZE),
+    (108, SUBSTR_START_OUT_OF_BOUNDS),
+    (109, SUBSTR_END_OUT_OF_BOUNDS),
+    (110, SUBSTR_INVALID_BOUNDS),
+    (111, NOT_G1_SIZE),
+    (112, NOT_G2_SIZE),
+    (113, NOT_G1_POINT),
+    (114, NOT_G2_POINT),
+    (115, INVALID_G1_POINT),
+    (116, INVALID_G2_POINT),
+    (117, PAIR_NOT_G1),
+    (118, PAIR_NOT_G2),
+    (200, TAKES_EXACTLY),
+    (201, TAKES_NO_MORE_THAN),
+    (202, TAKES),
+    (203, FIRST_OF_NON_CONS),
+    (204, REST_OF_NON_CONS),
+    (205, ON_LIST),
+    (206, REQUIRES_ATOM),
+    (207, REQUIRES_INT_ARG),
+    (208, REQUIRES_INT_ARGS),
+    (209, REQUIRES_INT32_ARGS),
+    (210, REQUIRES_CANONICAL_INT32_ARGS),
+    (211, REQUIRES_POSITIVE_INT_ARG),
+    (212, REQUIRES_UINT_ARG),
+    (213, NODE_LIMIT),
+    (214, ARGUMENT_ERROR),
+    (300, DIV_BY_ZERO),
+    (301, DIV_NEGATIVE),
+    (302, DIVMOD_BY_ZERO),
+    (303, MOD_BY_ZERO),
+    (304, DIVEXACT_BY_ZERO),
+    (305, DIVEXACT_REMAINDER),
+    (306, MUL_DIV_FLOOR_BY_ZERO),
+    (307, SQRT_FLOOR_NEGATIVE),
+    (308, MODPOW_NEGATIVE_EXPONENT),
+    (309, MODPOW_ZERO_MODULUS),
+    (310, SUBSTR_ARG_COUNT),
+    (311, SUBSTR_INVALID_INDICES),
+    (312, SHIFT_TOO_LARGE),
+    (313, COINID_PARENT_ID),
+    (314, COINID_PUZZLE_HASH),
+    (315, COINID_NEGATIVE_AMOUNT),
+    (316, COINID_LEADING_ZERO),
+    (317, COINID_AMOUNT_TOO_LARGE),
+    (318, G1_MAP_ARG_COUNT),
+    (319, G2_MAP_ARG_COUNT),
+    (320, BLS_PAIRING_IDENTITY_FAILED),
+    (321, BLS_VERIFY_FAILED),
+    (322, SECP256K1_FAILED),
+    (323, SECP256K1_DIGEST_SIZE),
+    (324, SECP256K1_INVALID_PUBKEY),
+    (325, SECP256K1_INVALID_SIG),
+    (326, SECP256R1_FAILED),
+    (327, SECP256R1_DIGEST_SIZE),
+    (328, SECP256R1_INVALID_PUBKEY),
+    (329, SECP256R1_INVALID_SIG),
+    (330, ED25519_FAILED),
+    (331, ED25519_PUBKEY_SIZE),
+    (332, ED25519_INVALID_PUBKEY),
+    (333, ED25519_INVALID_SIG),
+    (400, BAD_ENCODING),
+    (401, SERDE_INTERNAL_ERROR),
+    (402, ATOM_TOO_BIG),
+    (410, INVALID_IMAGE),
+    (411, IMAGE_EXTERNAL_ATOMS),
+    (500, FAIL_WITH),
+];
+
+// fills in the {} of template with params, in order
+pub fn fill(template: &str, params: &[&dyn Display]) -> String {
+    let mut parts = template.split("{}");
+    let mut ret = parts.next().unwrap_or_default().to_string();
+    let mut params = params.iter();
+    for part in parts {
+        let param = params.next().expect("missing parameter for error message");
+        write!(ret, "{param}").expect("write to String");
+        ret.push_str(part);
+    }
+    debug_assert!(params.next().is_none(), "too many parameters");
+    ret
+}
+
+// the number of characters of msg matched by template, not counting the
+// parameters, or None if it doesn't match
+fn match_template(template: &str, msg: &str) -> Option<usize> {
+    let mut parts = template.split("{}");
+    let first = parts.next().unwrap_or_default();
+    let mut rest = msg.strip_prefix(first)?;
+    let mut matched = first.len();
+    let mut parts = parts.peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            // the last part has to match the end of the message
+            rest.strip_suffix(part)?;
+        } else {
+            rest = &rest[rest.find(part)? + part.len()..];
+        }
+        matched += part.len();
+    }
+    if !template.contains("{}") && msg != template {
+        return None;
+    }
+    Some(matched)
+}
+
+// returns the code of an error message. If several templates match it, the
+// most specific one (matching the most characters) is picked. Errors wrapping
+// another error, like ARGUMENT_ERROR, have the code of the wrapper
+pub fn error_code(msg: &str) -> Option<u16> {
+    CATALOG
+        .iter()
+        .filter_map(|(code, template)| Some((match_template(template, msg)?, *code)))
+        .max_by_key(|(matched, _)| *matched)
+        .map(|(_, code)| code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_fill() {
+        assert_eq!(fill(COST_EXCEEDED, &[]), "cost exceeded");
+        assert_eq!(
+            fill(TAKES_EXACTLY, &[&"substr", &1, &""]),
+            "substr takes exactly 1 argument"
+        );
+        assert_eq!(
+            fill(
+                ARGUMENT_ERROR,
+                &[&"bls_verify", &2, &"atom is not a valid G1 point"]
+            ),
+            "bls_verify argument 2: atom is not a valid G1 point"
+        );
+    }
+
+    #[test]
+    fn test_error_code() {
+        assert_eq!(error_code("cost exceeded"), Some(1));
+        assert_eq!(error_code("cost exceeded!"), None);
+        assert_eq!(error_code("+ requires int args"), Some(208));
+        assert_eq!(error_code("lsh requires int32 args"), Some(209));
+        assert_eq!(
+            error_code("lsh requires int32 args (with no leading zeros)"),
+            Some(210)
+        );
+        assert_eq!(error_code("path into atom"), Some(13));
+        // the static message is more specific than TAKES_EXACTLY and TAKES
+        assert_eq!(
+            error_code("substr takes exactly 2 or 3 arguments"),
+            Some(310)
+        );
+        assert_eq!(error_code("sha256 takes exactly 1 argument"), Some(200));
+        assert_eq!(error_code("sha256 takes at least 1 argument"), Some(202));
+        assert_eq!(error_code("concat on list"), Some(205));
+        assert_eq!(error_code("something else"), None);
+        assert_eq!(
+            error_code("invalid allocator image: too many atoms"),
+            Some(410)
+        );
+
+        // every message maps back to its own code
+        for (code, template) in CATALOG {
+            let params: [&dyn Display; 3] = [&"x"; 3];
+            let msg = fill(template, &params[..template.matches("{}").count()]);
+            assert_eq!(error_code(&msg), Some(*code), "{msg}");
+        }
+    }
+
+    // Changing this test means changing a message, or its code, which breaks
+    // code matching on it. Only add new entries
+    #[test]
+    fn test_catalog() {
+        let codes: HashSet<u16> = CATALOG.iter().map(|(code, _)| *code).collect();
+        assert_eq!(codes.len(), CATALOG.len());
+        let templates: HashSet<&str> = CATALOG.iter().map(|(_, t)| *t).collect();
+        assert_eq!(templates.len(), CATALOG.len());
+
+        let expected: &[(u16, &str)] = &[
+            (1, "cost exceeded"),
+            (2, "bad operand list"),
+            (3, "in ((X)...) syntax X must be lone atom"),
+            (4, "cost must be > 0"),
+            (5, "unknown softfork extension"),
+            (6, "softfork specified cost mismatch"),
+            (7, "quote depth limit exceeded"),
+            (8, "runtime error: env stack empty"),
+            (9, "runtime error: value stack empty"),
+            (10, "environment stack limit reached"),
+            (11, "value stack limit reached"),
+            (12, "vm finished"),
+            (13, "path into atom"),
+            (14, "path must be an atom"),
+            (15, "unimplemented operator"),
+            (16, "reserved operator"),
+            (17, "invalid operator"),
+            (18, "internal error: non-canonical integer output"),
+            (19, "clvm raise"),
+            (100, "out of memory"),
+            (101, "atom too large"),
+            (102, "too many pairs"),
+            (103, "too many atoms"),
+            (104, "(internal error) atom out of bounds"),
+            (105, "(internal error) substr expected atom, got pair"),
+            (106, "(internal error) concat expected atom, got pair"),
+            (107, "(internal error) concat passed invalid new_size"),
+            (108, "substr start out of bounds"),
+            (109, "substr end out of bounds"),
+            (110, "substr invalid bounds"),
+            (111, "atom is not G1 size, 48 bytes"),
+            (112, "atom is not G2 size, 96 bytes"),
+            (113, "atom is not a G1 point"),
+            (114, "atom is not a G2 point"),
+            (115, "atom is not a valid G1 point"),
+            (116, "atom is not a valid G2 point"),
+            (117, "pair found, expected G1 point"),
+            (118, "pair found, expected G2 point"),
+            (200, "{} takes exactly {} argument{}"),
+            (201, "{} takes no more than {} argument{}"),
+            (202, "{} takes {}"),
+            (203, "first of non-cons"),
+            (204, "rest of non-cons"),
+            (205, "{} on list"),
+            (206, "{} requires an atom"),
+            (207, "{} requires int arg"),
+            (208, "{} requires int args"),
+            (209, "{} requires int32 args"),
+            (210, "{} requires int32 args (with no leading zeros)"),
+            (211, "{} requires positive int arg"),
+            (212, "{} requires u{} arg"),
+            (213, "{} node limit exceeded"),
+            (214, "{} argument {}: {}"),
+            (300, "div with 0"),
+            (301, "div operator with negative operands is deprecated"),
+            (302, "divmod with 0"),
+            (303, "mod with 0"),
+            (304, "divexact with 0"),
+            (305, "divexact with remainder"),
+            (306, "mul_div_floor with 0"),
+            (307, "sqrt_floor of negative number"),
+            (308, "modpow with negative exponent"),
+            (309, "modpow with 0 modulus"),
+            (310, "substr takes exactly 2 or 3 arguments"),
+            (311, "invalid indices for substr"),
+            (312, "shift too large"),
+            (313, "coinid: invalid parent coin id (must be 32 bytes)"),
+            (314, "coinid: invalid puzzle hash (must be 32 bytes)"),
+            (315, "coinid: invalid amount (may not be negative"),
+            (
+                316,
+                "coinid: invalid amount (may not have redundant leading zero)",
+            ),
+            (
+                317,
+                "coinid: invalid amount (may not exceed max coin amount)",
+            ),
+            (318, "g1_map takes exactly 1 or 2 arguments"),
+            (319, "g2_map takes exactly 1 or 2 arguments"),
+            (320, "bls_pairing_identity failed"),
+            (321, "bls_verify failed"),
+            (322, "secp256k1_verify failed"),
+            (323, "secp256k1_verify message digest is not 32 bytes"),
+            (324, "secp256k1_verify pubkey is not valid"),
+            (325, "secp256k1_verify sig is not valid"),
+            (326, "secp256r1_verify failed"),
+            (327, "secp256r1_verify message digest is not 32 bytes"),
+            (328, "secp256r1_verify pubkey is not valid"),
+            (329, "secp256r1_verify sig is not valid"),
+            (330, "ed25519_verify failed"),
+            (331, "ed25519_verify pubkey is not 32 bytes"),
+            (332, "ed25519_verify pubkey is not valid"),
+            (333, "ed25519_verify sig is not valid"),
+            (400, "bad encoding"),
+            (401, "internal error"),
+            (402, "atom too big"),
+            (410, "invalid allocator image: {}"),
+            (411, "allocator image can't hold external atoms"),
+            (500, "fail_with: {}"),
+        ];
+        assert_eq!(CATALOG, expected);
+    }
+}
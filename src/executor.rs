@@ -0,0 +1,282 @@
+use crate::allocator::{Allocator, NodePtr};
+use crate::bls_ops::{bls_pairing_identity_check, bls_verify_check, DeferredCheck};
+use crate::chia_dialect::{ChiaDialect, ENABLE_BLS_OPS_OUTSIDE_GUARD, STRICT_ARITY};
+use crate::cost::Cost;
+use crate::dialect::{Dialect, OperatorSet};
+use crate::err_utils::err;
+use crate::operator_info::operator_by_opcode;
+use crate::reduction::{Reduction, Response};
+use crate::run_program::run_program;
+use std::cell::RefCell;
+use std::sync::mpsc::{channel, Receiver};
+
+// Offloads the expensive checks of bls_pairing_identity and bls_verify to a
+// host-provided executor (e.g. a thread pool), so the interpreter can continue
+// running the program while they're computed. This is possible because these
+// operators always return nil; the check only decides whether the program
+// fails. The operators' arguments are parsed, and their cost charged, up
+// front, like they would be otherwise.
+//
+// The results are the same as running the program with ChiaDialect. If a
+// deferred check fails, the program fails with the operator's error, even if
+// the program failed (for another reason) after the check was deferred, since
+// the check would have failed first. Checks are only deferred outside of
+// softfork guards, since the allocator state is restored when exiting a guard,
+// and the error would refer to a node that no longer exists.
+
+// runs jobs, possibly on other threads. Jobs may run in any order
+pub trait Executor {
+    fn spawn(&self, job: Box<dyn FnOnce() + Send>);
+}
+
+// runs every job immediately, on the calling thread
+pub struct InlineExecutor;
+
+impl Executor for InlineExecutor {
+    fn spawn(&self, job: Box<dyn FnOnce() + Send>) {
+        job();
+    }
+}
+
+struct PendingCheck {
+    // the argument list of the operator, which the error refers to
+    input: NodePtr,
+    msg: &'static str,
+    passed: Receiver<bool>,
+}
+
+// wraps ChiaDialect, and defers the checks of bls_pairing_identity and
+// bls_verify to the executor. See run_program_with_executor()
+pub struct ExecutorDialect<'a, E: Executor> {
+    dialect: ChiaDialect,
+    flags: u32,
+    executor: &'a E,
+    // the deferred checks, in the order the operators were called
+    pending: RefCell<Vec<PendingCheck>>,
+}
+
+impl<'a, E: Executor> ExecutorDialect<'a, E> {
+    pub fn new(flags: u32, executor: &'a E) -> Self {
+        Self {
+            dialect: ChiaDialect::new(flags),
+            flags,
+            executor,
+            pending: RefCell::new(Vec::new()),
+        }
+    }
+
+    // the number of checks deferred so far
+    pub fn pending_checks(&self) -> usize {
+        self.pending.borrow().len()
+    }
+
+    // waits for all deferred checks, and returns the error of the first one
+    // that failed, if any
+    pub fn wait(&self) -> Response {
+        for check in self.pending.borrow_mut().drain(..) {
+            // if the job was dropped without running, the check didn't pass
+            if !check.passed.recv().unwrap_or(false) {
+                return err(check.input, check.msg);
+            }
+        }
+        Ok(Reduction(0, NodePtr::NIL))
+    }
+
+    fn defer(&self, input: NodePtr, msg: &'static str, check: DeferredCheck) {
+        let (tx, rx) = channel();
+        self.pending.borrow_mut().push(PendingCheck {
+            input,
+            msg,
+            passed: rx,
+        });
+        self.executor.spawn(Box::new(move || {
+            // the receiver is gone if the run was abandoned
+            let _ = tx.send(check());
+        }));
+    }
+}
+
+impl<E: Executor> Dialect for ExecutorDialect<'_, E> {
+    fn op(
+        &self,
+        allocator: &mut Allocator,
+        o: NodePtr,
+        argument_list: NodePtr,
+        max_cost: Cost,
+        extension: OperatorSet,
+    ) -> Response {
+        let opcode = allocator.atom(o);
+        let opcode = opcode.as_ref();
+        let deferrable = extension == OperatorSet::Default
+            && (self.flags & ENABLE_BLS_OPS_OUTSIDE_GUARD) != 0
+            && (opcode == [58] || opcode == [59]);
+        if !deferrable {
+            return self
+                .dialect
+                .op(allocator, o, argument_list, max_cost, extension);
+        }
+        if (self.flags & STRICT_ARITY) != 0 {
+            if let Some(info) = operator_by_opcode(opcode) {
+                info.check_arity(allocator, argument_list)?;
+            }
+        }
+        let (cost, check, msg) = if opcode == [58] {
+            let (cost, check) = bls_pairing_identity_check(allocator, argument_list, max_cost)?;
+            (cost, check, "bls_pairing_identity failed")
+        } else {
+            let (cost, check) = bls_verify_check(allocator, argument_list, max_cost)?;
+            (cost, check, "bls_verify failed")
+        };
+        self.defer(argument_list, msg, check);
+        Ok(Reduction(cost, allocator.nil()))
+    }
+
+    fn quote_kw(&self) -> u32 {
+        self.dialect.quote_kw()
+    }
+    fn apply_kw(&self) -> u32 {
+        self.dialect.apply_kw()
+    }
+    fn softfork_kw(&self) -> u32 {
+        self.dialect.softfork_kw()
+    }
+    fn softfork_extension(&self, ext: u32) -> OperatorSet {
+        self.dialect.softfork_extension(ext)
+    }
+    fn allow_unknown_ops(&self) -> bool {
+        self.dialect.allow_unknown_ops()
+    }
+    fn cost_schedule_version(&self) -> u32 {
+        self.dialect.cost_schedule_version()
+    }
+}
+
+// like run_program() with ChiaDialect::new(flags), but with the BLS checks
+// run by the executor
+pub fn run_program_with_executor<E: Executor>(
+    allocator: &mut Allocator,
+    flags: u32,
+    executor: &E,
+    program: NodePtr,
+    env: NodePtr,
+    max_cost: Cost,
+) -> Response {
+    let dialect = ExecutorDialect::new(flags, executor);
+    let r = run_program(allocator, &dialect, program, env, max_cost);
+    dialect.wait()?;
+    r
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serde::node_to_bytes;
+    use crate::test_ops::parse_exp;
+    use std::thread;
+
+    struct ThreadExecutor;
+
+    impl Executor for ThreadExecutor {
+        fn spawn(&self, job: Box<dyn FnOnce() + Send>) {
+            thread::spawn(job);
+        }
+    }
+
+    // drops every job without running it
+    struct NullExecutor;
+
+    impl Executor for NullExecutor {
+        fn spawn(&self, _job: Box<dyn FnOnce() + Send>) {}
+    }
+
+    const G1_GEN: &str = "0x97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb";
+    const G2_GEN: &str = "0x93e02b6052719f607dacd3a088274f65596bd0d09920b61ab5da61bbdc7f5049334cf11213945d57e5ac7d055d042b7e024aa2b2f08f0a91260805272dc51051c6e47ad4fa403b02b4510b647ae3d1770bac0326a805bbefd48056c8c121bdb8";
+
+    // runs the program with ChiaDialect and each executor, and checks that
+    // the results are the same
+    fn check(prg: &str) -> Result<Cost, String> {
+        let flags = ENABLE_BLS_OPS_OUTSIDE_GUARD;
+        let run = |f: &dyn Fn(&mut Allocator, NodePtr) -> Response| {
+            let mut a = Allocator::new();
+            let (program, rest) = parse_exp(&mut a, prg);
+            assert_eq!(rest, "");
+            match f(&mut a, program) {
+                Ok(Reduction(cost, node)) => Ok((cost, node_to_bytes(&a, node).unwrap())),
+                Err(e) => Err((e.1, node_to_bytes(&a, e.0).unwrap())),
+            }
+        };
+        let expected =
+            run(&|a, program| run_program(a, &ChiaDialect::new(flags), program, NodePtr::NIL, 0));
+        let inline = run(&|a, program| {
+            run_program_with_executor(a, flags, &InlineExecutor, program, NodePtr::NIL, 0)
+        });
+        let threads = run(&|a, program| {
+            run_program_with_executor(a, flags, &ThreadExecutor, program, NodePtr::NIL, 0)
+        });
+        assert_eq!(inline, expected);
+        assert_eq!(threads, expected);
+        expected.map(|r| r.0).map_err(|e| e.0)
+    }
+
+    #[test]
+    fn test_executor() {
+        // e(g1, g2) * e(-g1, g2) is the identity
+        let identity = format!("(bls_pairing_identity (q . {G1_GEN}) (q . {G2_GEN}) (g1_negate (q . {G1_GEN})) (q . {G2_GEN}))");
+        let not_identity = format!("(bls_pairing_identity (q . {G1_GEN}) (q . {G2_GEN}))");
+
+        assert!(check(&identity).is_ok());
+        // an empty aggregate signature, for no public keys
+        let infinity = format!("0xc0{}", "00".repeat(95));
+        assert!(check(&format!("(bls_verify (q . {infinity}))")).is_ok());
+        assert_eq!(
+            check(&not_identity).unwrap_err(),
+            "bls_pairing_identity failed"
+        );
+        assert_eq!(
+            check(&format!("(bls_verify (q . {G2_GEN}))")).unwrap_err(),
+            "bls_verify failed"
+        );
+
+        // a failing check takes precedence over a later failure, since it would
+        // have failed the program first. Arguments are evaluated last to first
+        assert_eq!(
+            check(&format!("(c (x) {not_identity})")).unwrap_err(),
+            "bls_pairing_identity failed"
+        );
+        // but not over an earlier one
+        assert_eq!(
+            check(&format!("(c {not_identity} (x))")).unwrap_err(),
+            "clvm raise"
+        );
+
+        // invalid arguments fail right away
+        assert_eq!(
+            check("(bls_pairing_identity (q . 1))").unwrap_err(),
+            "bls_pairing_identity argument 0: atom is not G1 size, 48 bytes"
+        );
+
+        // checks aren't deferred inside softfork guards
+        assert_eq!(
+            check(&format!(
+                "(softfork (q . 10000000) (q . 0) (q . {not_identity}) (q . 0))"
+            ))
+            .unwrap_err(),
+            "bls_pairing_identity failed"
+        );
+    }
+
+    #[test]
+    fn test_dropped_job() {
+        let mut a = Allocator::new();
+        let dialect = ExecutorDialect::new(ENABLE_BLS_OPS_OUTSIDE_GUARD, &NullExecutor);
+        let program = parse_exp(
+            &mut a,
+            &format!("(bls_pairing_identity (q . {G1_GEN}) (q . {G2_GEN}))"),
+        )
+        .0;
+        run_program(&mut a, &dialect, program, NodePtr::NIL, 0).unwrap();
+        assert_eq!(dialect.pending_checks(), 1);
+        assert_eq!(dialect.wait().unwrap_err().1, "bls_pairing_identity failed");
+        assert_eq!(dialect.pending_checks(), 0);
+    }
+}
@@ -2,16 +2,20 @@ use std::collections::HashMap;
 
 use crate::allocator::{Allocator, NodePtr};
 use crate::bls_ops::{
-    op_bls_g1_multiply, op_bls_g1_negate, op_bls_g1_subtract, op_bls_g2_add, op_bls_g2_multiply,
-    op_bls_g2_negate, op_bls_g2_subtract, op_bls_map_to_g1, op_bls_map_to_g2,
-    op_bls_pairing_identity, op_bls_verify,
+    op_bls_g1_multiply, op_bls_g1_negate, op_bls_g1_subtract, op_bls_g1_valid, op_bls_g2_add,
+    op_bls_g2_multiply, op_bls_g2_negate, op_bls_g2_subtract, op_bls_g2_valid, op_bls_map_to_g1,
+    op_bls_map_to_g2, op_bls_pairing_identity, op_bls_verify,
+};
+use crate::core_ops::{
+    op_cons, op_eq, op_first, op_if, op_listp, op_raise, op_rest, op_tree_depth, op_tree_size,
 };
-use crate::core_ops::{op_cons, op_eq, op_first, op_if, op_listp, op_raise, op_rest};
 use crate::cost::Cost;
+use crate::ed25519_ops::op_ed25519_verify;
 use crate::more_ops::{
-    op_add, op_all, op_any, op_ash, op_concat, op_div, op_divmod, op_gr, op_gr_bytes, op_logand,
-    op_logior, op_lognot, op_logxor, op_lsh, op_mod, op_modpow, op_multiply, op_not, op_point_add,
-    op_pubkey_for_exp, op_sha256, op_strlen, op_substr, op_subtract,
+    op_add, op_all, op_any, op_ash, op_bitlength, op_concat, op_div, op_divexact, op_divmod,
+    op_gcd, op_gr, op_gr_bytes, op_logand, op_logior, op_lognot, op_logxor, op_lsh, op_mod,
+    op_modpow, op_mul_div_floor, op_multiply, op_not, op_point_add, op_popcount, op_pubkey_for_exp,
+    op_sha256, op_sqrt_floor, op_strlen, op_substr, op_subtract,
 };
 use crate::reduction::Response;
 use crate::secp_ops::{op_secp256k1_verify, op_secp256r1_verify};
@@ -21,7 +25,7 @@ type OpFn = fn(&mut Allocator, NodePtr, Cost) -> Response;
 pub type FLookup = [Option<OpFn>; 256];
 
 pub fn opcode_by_name(name: &str) -> Option<OpFn> {
-    let opcode_lookup: [(OpFn, &str); 44] = [
+    let opcode_lookup: [(OpFn, &str); 55] = [
         (op_if, "op_if"),
         (op_cons, "op_cons"),
         (op_first, "op_first"),
@@ -64,8 +68,19 @@ pub fn opcode_by_name(name: &str) -> Option<OpFn> {
         (op_bls_map_to_g2, "op_g2_map"),
         (op_bls_pairing_identity, "op_bls_pairing_identity"),
         (op_bls_verify, "op_bls_verify"),
+        (op_bls_g1_valid, "op_g1_valid"),
+        (op_bls_g2_valid, "op_g2_valid"),
         (op_secp256k1_verify, "op_secp256k1_verify"),
         (op_secp256r1_verify, "op_secp256r1_verify"),
+        (op_ed25519_verify, "op_ed25519_verify"),
+        (op_divexact, "op_divexact"),
+        (op_gcd, "op_gcd"),
+        (op_mul_div_floor, "op_mul_div_floor"),
+        (op_sqrt_floor, "op_sqrt_floor"),
+        (op_popcount, "op_popcount"),
+        (op_bitlength, "op_bitlength"),
+        (op_tree_size, "op_tree_size"),
+        (op_tree_depth, "op_tree_depth"),
     ];
     let name: &[u8] = name.as_ref();
     for (f, op) in opcode_lookup.iter() {
@@ -0,0 +1,257 @@
+use std::fmt;
+use std::io;
+use std::io::Write;
+
+// Hex encoding and decoding, intended for tooling and test fixtures that may
+// be edited by hand. The decoder accepts whitespace between bytes and an
+// optional "0x" prefix at the start of each whitespace-separated token, and
+// reports the offset of the first offending character.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HexErrorKind {
+    // a character that is not a hex digit, whitespace or part of a 0x prefix
+    InvalidCharacter(char),
+    // a byte was split by whitespace, or the input ended in the middle of a
+    // byte
+    OddDigits,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HexError {
+    // byte offset into the input
+    pub offset: usize,
+    pub kind: HexErrorKind,
+}
+
+impl fmt::Display for HexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            HexErrorKind::InvalidCharacter(c) => {
+                write!(f, "invalid hex character {c:?} at offset {}", self.offset)
+            }
+            HexErrorKind::OddDigits => {
+                write!(f, "odd number of hex digits at offset {}", self.offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HexError {}
+
+fn digit_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+// decodes hex input that may arrive in several chunks. Offsets in errors are
+// relative to the start of the first chunk.
+#[derive(Debug, Default)]
+pub struct HexDecoder {
+    // the number of bytes of input consumed so far
+    offset: usize,
+    // the high nibble of the byte being decoded, and the offset it was read at
+    pending: Option<(u8, usize)>,
+    // true if the next character starts a new token, i.e. we're at the start
+    // of the input or just after whitespace
+    token_start: bool,
+    // true if the pending nibble is the first character of a token, which
+    // means it may be the start of a 0x prefix
+    maybe_prefix: bool,
+}
+
+impl HexDecoder {
+    pub fn new() -> Self {
+        Self {
+            token_start: true,
+            ..Default::default()
+        }
+    }
+
+    // decodes the next chunk of input, appending the decoded bytes to out
+    pub fn feed(&mut self, input: &[u8], out: &mut Vec<u8>) -> Result<(), HexError> {
+        for c in input {
+            let offset = self.offset;
+            self.offset += 1;
+            let token_start = self.token_start;
+            self.token_start = false;
+            let maybe_prefix = self.maybe_prefix;
+            self.maybe_prefix = false;
+
+            if c.is_ascii_whitespace() {
+                if let Some((_, pos)) = self.pending {
+                    return Err(HexError {
+                        offset: pos,
+                        kind: HexErrorKind::OddDigits,
+                    });
+                }
+                self.token_start = true;
+                continue;
+            }
+
+            if (*c == b'x' || *c == b'X') && maybe_prefix {
+                self.pending = None;
+                continue;
+            }
+
+            let Some(value) = digit_value(*c) else {
+                return Err(HexError {
+                    offset,
+                    kind: HexErrorKind::InvalidCharacter(*c as char),
+                });
+            };
+
+            match self.pending.take() {
+                Some((high, _)) => out.push((high << 4) | value),
+                None => {
+                    self.pending = Some((value, offset));
+                    self.maybe_prefix = token_start && *c == b'0';
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // call once all input has been fed, to make sure it didn't end in the
+    // middle of a byte
+    pub fn finish(self) -> Result<(), HexError> {
+        match self.pending {
+            Some((_, offset)) => Err(HexError {
+                offset,
+                kind: HexErrorKind::OddDigits,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+pub fn decode<T: AsRef<[u8]>>(input: T) -> Result<Vec<u8>, HexError> {
+    let input = input.as_ref();
+    let mut ret = Vec::with_capacity(input.len() / 2);
+    let mut decoder = HexDecoder::new();
+    decoder.feed(input, &mut ret)?;
+    decoder.finish()?;
+    Ok(ret)
+}
+
+const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+// writes the lower-case hex encoding of buf to w
+pub fn encode_to<W: Write>(w: &mut W, buf: &[u8]) -> io::Result<()> {
+    let mut chunk = [0_u8; 128];
+    for input in buf.chunks(chunk.len() / 2) {
+        for (i, b) in input.iter().enumerate() {
+            chunk[i * 2] = DIGITS[(b >> 4) as usize];
+            chunk[i * 2 + 1] = DIGITS[(b & 0xf) as usize];
+        }
+        w.write_all(&chunk[..input.len() * 2])?;
+    }
+    Ok(())
+}
+
+pub fn encode<T: AsRef<[u8]>>(buf: T) -> String {
+    let mut ret = Vec::with_capacity(buf.as_ref().len() * 2);
+    encode_to(&mut ret, buf.as_ref()).expect("writing to Vec can't fail");
+    String::from_utf8(ret).expect("hex digits are ASCII")
+}
+
+#[cfg(test)]
+use rstest::rstest;
+
+#[cfg(test)]
+#[rstest]
+#[case("", &[])]
+#[case("00", &[0])]
+#[case("0x", &[])]
+#[case("0x00", &[0])]
+#[case("0X1337", &[0x13, 0x37])]
+#[case("deadBEEF", &[0xde, 0xad, 0xbe, 0xef])]
+#[case("  ff \n 0x01\t02 ", &[0xff, 0x01, 0x02])]
+#[case("0x00 0x0a", &[0x00, 0x0a])]
+fn test_decode(#[case] input: &str, #[case] expected: &[u8]) {
+    assert_eq!(decode(input).unwrap(), expected);
+}
+
+#[cfg(test)]
+#[rstest]
+#[case("0", 0, HexErrorKind::OddDigits)]
+#[case("123", 2, HexErrorKind::OddDigits)]
+#[case("1 23", 0, HexErrorKind::OddDigits)]
+#[case("0x1 2", 2, HexErrorKind::OddDigits)]
+#[case("12g4", 2, HexErrorKind::InvalidCharacter('g'))]
+#[case("12 x4", 3, HexErrorKind::InvalidCharacter('x'))]
+#[case("1x", 1, HexErrorKind::InvalidCharacter('x'))]
+#[case("000x", 3, HexErrorKind::InvalidCharacter('x'))]
+#[case("0x0x", 3, HexErrorKind::InvalidCharacter('x'))]
+#[case("00-", 2, HexErrorKind::InvalidCharacter('-'))]
+fn test_decode_error(#[case] input: &str, #[case] offset: usize, #[case] kind: HexErrorKind) {
+    assert_eq!(decode(input).unwrap_err(), HexError { offset, kind });
+}
+
+#[test]
+fn test_error_message() {
+    assert_eq!(
+        decode("0x12 3z").unwrap_err().to_string(),
+        "invalid hex character 'z' at offset 6"
+    );
+    assert_eq!(
+        decode("0x12 3").unwrap_err().to_string(),
+        "odd number of hex digits at offset 5"
+    );
+}
+
+#[test]
+fn test_streaming_decode() {
+    // split the input at every possible position, including in the middle of
+    // the 0x prefix and in the middle of a byte
+    let input = b"0xcafe 0x12\n34";
+    for split in 0..=input.len() {
+        let mut decoder = HexDecoder::new();
+        let mut out = Vec::new();
+        decoder.feed(&input[..split], &mut out).unwrap();
+        decoder.feed(&input[split..], &mut out).unwrap();
+        decoder.finish().unwrap();
+        assert_eq!(out, [0xca, 0xfe, 0x12, 0x34]);
+    }
+
+    // error offsets span chunks
+    let mut decoder = HexDecoder::new();
+    let mut out = Vec::new();
+    decoder.feed(b"0011", &mut out).unwrap();
+    assert_eq!(
+        decoder.feed(b"22q", &mut out).unwrap_err(),
+        HexError {
+            offset: 6,
+            kind: HexErrorKind::InvalidCharacter('q')
+        }
+    );
+
+    let mut decoder = HexDecoder::new();
+    decoder.feed(b"001", &mut out).unwrap();
+    assert_eq!(
+        decoder.finish().unwrap_err(),
+        HexError {
+            offset: 2,
+            kind: HexErrorKind::OddDigits
+        }
+    );
+}
+
+#[test]
+fn test_encode() {
+    assert_eq!(encode([]), "");
+    assert_eq!(encode([0x00, 0x0a, 0xff, 0x13, 0x37]), "000aff1337");
+
+    // larger than the internal chunk size
+    let buf: Vec<u8> = (0..=255).collect();
+    let encoded = encode(&buf);
+    assert_eq!(encoded.len(), 512);
+    assert_eq!(decode(&encoded).unwrap(), buf);
+
+    let mut out = Vec::new();
+    encode_to(&mut out, &[0xbe, 0xef]).unwrap();
+    assert_eq!(out, b"beef");
+}
@@ -1,28 +1,50 @@
 pub mod allocator;
+pub mod allocator_pool;
+#[cfg(feature = "fuzzing")]
+pub mod argument_generator;
 pub mod bls_ops;
+pub mod bls_test_vectors;
 pub mod chia_dialect;
 pub mod core_ops;
 pub mod cost;
+pub mod cost_report;
+pub mod cost_schedule_v1;
+pub mod debugger;
 pub mod dialect;
+pub mod ed25519_ops;
 pub mod err_utils;
+pub mod error_catalog;
+pub mod executor;
 pub mod f_table;
+pub mod hex;
 pub mod more_ops;
+pub mod node_map;
+mod node_storage;
 pub mod number;
 pub mod op_utils;
+pub mod operator_info;
+pub mod override_dialect;
 pub mod reduction;
+pub mod run_cache;
 pub mod run_program;
 pub mod runtime_dialect;
 pub mod secp_ops;
 pub mod serde;
+pub mod sexp_store;
 pub mod sha2;
+pub mod template;
+pub mod test_dialect;
 pub mod traverse_path;
 
-pub use allocator::{Allocator, Atom, NodePtr, SExp};
+pub use allocator::{Allocator, Atom, AtomKind, NodePtr, SExp};
 pub use chia_dialect::ChiaDialect;
+pub use node_map::NodeMap;
 pub use run_program::run_program;
 
 pub use chia_dialect::{
-    ENABLE_BLS_OPS_OUTSIDE_GUARD, ENABLE_FIXED_DIV, LIMIT_HEAP, MEMPOOL_MODE, NO_UNKNOWN_OPS,
+    ENABLE_BIT_COUNT_OPS, ENABLE_BLS_OPS_OUTSIDE_GUARD, ENABLE_BLS_VALID_OPS, ENABLE_DIVEXACT_GCD,
+    ENABLE_ED25519_VERIFY, ENABLE_FIXED_DIV, ENABLE_FIXED_POINT_OPS, ENABLE_TREE_SIZE_OPS,
+    LIMIT_HEAP, MEMPOOL_MODE, NO_UNKNOWN_OPS, STRICT_ARITY, STRICT_INT_OUTPUTS,
 };
 
 #[cfg(feature = "counters")]
@@ -6,94 +6,44 @@ use std::ops::BitOrAssign;
 use std::ops::BitXorAssign;
 
 use crate::allocator::{len_for_value, Allocator, NodePtr, NodeVisitor, SExp};
-use crate::cost::{check_cost, Cost};
+use crate::bls_ops::g1_arg;
+use crate::cost::{Cost, CostAcc};
+use crate::cost_schedule_v1::{
+    ARITH_BASE_COST, ARITH_COST_PER_ARG, ARITH_COST_PER_BYTE, ASHIFT_BASE_COST,
+    ASHIFT_COST_PER_BYTE, BITLENGTH_BASE_COST, BITLENGTH_COST_PER_BYTE, BOOL_BASE_COST,
+    BOOL_COST_PER_ARG, COINID_COST, CONCAT_BASE_COST, CONCAT_COST_PER_ARG, CONCAT_COST_PER_BYTE,
+    DIVEXACT_BASE_COST, DIVEXACT_COST_PER_BYTE, DIVEXACT_SQUARE_COST_PER_BYTE_DIVIDER,
+    DIVMOD_BASE_COST, DIVMOD_COST_PER_BYTE, DIV_BASE_COST, DIV_COST_PER_BYTE, GCD_BASE_COST,
+    GCD_COST_PER_BYTE, GCD_SQUARE_COST_PER_BYTE_DIVIDER, GRS_BASE_COST, GRS_COST_PER_BYTE,
+    GR_BASE_COST, GR_COST_PER_BYTE, LOGNOT_BASE_COST, LOGNOT_COST_PER_BYTE, LOG_BASE_COST,
+    LOG_COST_PER_ARG, LOG_COST_PER_BYTE, LSHIFT_BASE_COST, LSHIFT_COST_PER_BYTE,
+    MALLOC_COST_PER_BYTE, MODPOW_BASE_COST, MODPOW_COST_PER_BYTE_BASE_VALUE,
+    MODPOW_COST_PER_BYTE_EXPONENT, MODPOW_COST_PER_BYTE_MOD, MUL_BASE_COST, MUL_COST_PER_OP,
+    MUL_DIV_FLOOR_BASE_COST, MUL_DIV_FLOOR_COST_PER_BYTE,
+    MUL_DIV_FLOOR_SQUARE_COST_PER_BYTE_DIVIDER, MUL_LINEAR_COST_PER_BYTE,
+    MUL_SQUARE_COST_PER_BYTE_DIVIDER, POINT_ADD_BASE_COST, POINT_ADD_COST_PER_ARG,
+    POPCOUNT_BASE_COST, POPCOUNT_COST_PER_BYTE, PUBKEY_BASE_COST, PUBKEY_COST_PER_BYTE,
+    SHA256_BASE_COST, SHA256_COST_PER_ARG, SHA256_COST_PER_BYTE, SQRT_FLOOR_BASE_COST,
+    SQRT_FLOOR_COST_PER_BYTE, SQRT_FLOOR_SQUARE_COST_PER_BYTE_DIVIDER, STRLEN_BASE_COST,
+    STRLEN_COST_PER_BYTE,
+};
 use crate::err_utils::err;
+use crate::error_catalog::{
+    fill, COINID_AMOUNT_TOO_LARGE, COINID_LEADING_ZERO, COINID_NEGATIVE_AMOUNT, COINID_PARENT_ID,
+    COINID_PUZZLE_HASH, DIVEXACT_BY_ZERO, DIVEXACT_REMAINDER, DIVMOD_BY_ZERO, DIV_BY_ZERO,
+    DIV_NEGATIVE, INVALID_OPERATOR, MODPOW_NEGATIVE_EXPONENT, MODPOW_ZERO_MODULUS, MOD_BY_ZERO,
+    MUL_DIV_FLOOR_BY_ZERO, ON_LIST, REQUIRES_INT_ARGS, RESERVED_OPERATOR, SHIFT_TOO_LARGE,
+    SQRT_FLOOR_NEGATIVE, SUBSTR_ARG_COUNT, SUBSTR_INVALID_INDICES,
+};
 use crate::number::Number;
 use crate::op_utils::{
-    atom, atom_len, get_args, get_varargs, i32_atom, int_atom, match_args, mod_group_order,
-    new_atom_and_cost, nilp, u32_from_u8, MALLOC_COST_PER_BYTE,
+    atom, atom_len, bool_response, get_args, get_varargs, i32_atom, int_atom, int_op, malloc_cost,
+    match_args, mod_group_order, new_atom_and_cost, nilp, number_response, u32_from_u8,
 };
 use crate::reduction::{Reduction, Response};
 use crate::sha2::{Digest, Sha256};
 use chia_bls::G1Element;
 
-const ARITH_BASE_COST: Cost = 99;
-const ARITH_COST_PER_ARG: Cost = 320;
-const ARITH_COST_PER_BYTE: Cost = 3;
-
-const LOG_BASE_COST: Cost = 100;
-const LOG_COST_PER_ARG: Cost = 264;
-const LOG_COST_PER_BYTE: Cost = 3;
-
-const LOGNOT_BASE_COST: Cost = 331;
-const LOGNOT_COST_PER_BYTE: Cost = 3;
-
-const MUL_BASE_COST: Cost = 92;
-const MUL_COST_PER_OP: Cost = 885;
-const MUL_LINEAR_COST_PER_BYTE: Cost = 6;
-const MUL_SQUARE_COST_PER_BYTE_DIVIDER: Cost = 128;
-
-const GR_BASE_COST: Cost = 498;
-const GR_COST_PER_BYTE: Cost = 2;
-
-const GRS_BASE_COST: Cost = 117;
-const GRS_COST_PER_BYTE: Cost = 1;
-
-const STRLEN_BASE_COST: Cost = 173;
-const STRLEN_COST_PER_BYTE: Cost = 1;
-
-const CONCAT_BASE_COST: Cost = 142;
-const CONCAT_COST_PER_ARG: Cost = 135;
-const CONCAT_COST_PER_BYTE: Cost = 3;
-
-const DIVMOD_BASE_COST: Cost = 1116;
-const DIVMOD_COST_PER_BYTE: Cost = 6;
-
-const DIV_BASE_COST: Cost = 988;
-const DIV_COST_PER_BYTE: Cost = 4;
-
-const SHA256_BASE_COST: Cost = 87;
-const SHA256_COST_PER_ARG: Cost = 134;
-const SHA256_COST_PER_BYTE: Cost = 2;
-
-const ASHIFT_BASE_COST: Cost = 596;
-const ASHIFT_COST_PER_BYTE: Cost = 3;
-
-const LSHIFT_BASE_COST: Cost = 277;
-const LSHIFT_COST_PER_BYTE: Cost = 3;
-
-const BOOL_BASE_COST: Cost = 200;
-const BOOL_COST_PER_ARG: Cost = 300;
-
-// Raspberry PI 4 is about 7.679960 / 1.201742 = 6.39 times slower
-// in the point_add benchmark
-
-// increased from 31592 to better model Raspberry PI
-const POINT_ADD_BASE_COST: Cost = 101094;
-// increased from 419994 to better model Raspberry PI
-const POINT_ADD_COST_PER_ARG: Cost = 1343980;
-
-// Raspberry PI 4 is about 2.833543 / 0.447859 = 6.32686 times slower
-// in the pubkey benchmark
-
-// increased from 419535 to better model Raspberry PI
-const PUBKEY_BASE_COST: Cost = 1325730;
-// increased from 12 to closer model Raspberry PI
-const PUBKEY_COST_PER_BYTE: Cost = 38;
-
-// the new coinid operator
-// we subtract 153 cost as a discount, to incentivize using this operator rather
-// than "naked" sha256
-const COINID_COST: Cost =
-    SHA256_BASE_COST + SHA256_COST_PER_ARG * 3 + SHA256_COST_PER_BYTE * (32 + 32 + 8) - 153;
-
-const MODPOW_BASE_COST: Cost = 17000;
-const MODPOW_COST_PER_BYTE_BASE_VALUE: Cost = 38;
-// the cost for exponent and modular scale by the square of the size of the
-// respective operands
-const MODPOW_COST_PER_BYTE_EXPONENT: Cost = 3;
-const MODPOW_COST_PER_BYTE_MOD: Cost = 21;
-
 fn limbs_for_int(v: &Number) -> usize {
     ((v.bits() + 7) / 8) as usize
 }
@@ -149,11 +99,6 @@ fn test_limbs_for_int() {
     limb_test_helper(&[0x80, 0, 0, 0, 0, 0, 0, 0]);
 }
 
-fn malloc_cost(a: &Allocator, cost: Cost, ptr: NodePtr) -> Reduction {
-    let c = a.atom_len(ptr) as Cost * MALLOC_COST_PER_BYTE;
-    Reduction(cost + c, ptr)
-}
-
 pub fn op_unknown(
     allocator: &mut Allocator,
     o: NodePtr,
@@ -192,37 +137,34 @@ pub fn op_unknown(
     let op = op_atom.as_ref();
 
     if op.is_empty() || (op.len() >= 2 && op[0] == 0xff && op[1] == 0xff) {
-        return err(o, "reserved operator");
+        return err(o, RESERVED_OPERATOR);
     }
 
     let cost_function = (op[op.len() - 1] & 0b11000000) >> 6;
     let cost_multiplier: u64 = match u32_from_u8(&op[0..op.len() - 1]) {
         Some(v) => v as u64,
         None => {
-            return err(o, "invalid operator");
+            return err(o, INVALID_OPERATOR);
         }
     };
 
-    let mut cost = match cost_function {
-        0 => 1,
+    let cost = match cost_function {
+        0 => CostAcc::new(1),
         1 => {
-            let mut cost = ARITH_BASE_COST;
-            let mut byte_count: u64 = 0;
+            let mut cost = CostAcc::new(ARITH_BASE_COST);
+            let mut byte_count: usize = 0;
             while let Some((arg, rest)) = allocator.next(args) {
                 args = rest;
-                cost += ARITH_COST_PER_ARG;
+                cost.add(ARITH_COST_PER_ARG);
                 let len = atom_len(allocator, arg, "unknown op")?;
-                byte_count += len as u64;
-                check_cost(
-                    allocator,
-                    cost + (byte_count as Cost * ARITH_COST_PER_BYTE),
-                    max_cost,
-                )?;
+                byte_count = byte_count.saturating_add(len);
+                cost.plus_per(byte_count, ARITH_COST_PER_BYTE)
+                    .check(allocator, max_cost)?;
             }
-            cost + (byte_count * ARITH_COST_PER_BYTE)
+            cost.plus_per(byte_count, ARITH_COST_PER_BYTE)
         }
         2 => {
-            let mut cost = MUL_BASE_COST;
+            let mut cost = CostAcc::new(MUL_BASE_COST);
             let mut first_iter: bool = true;
             let mut l0: u64 = 0;
             while let Some((arg, rest)) = allocator.next(args) {
@@ -234,39 +176,39 @@ pub fn op_unknown(
                     continue;
                 }
                 let l1 = len as u64;
-                cost += MUL_COST_PER_OP;
-                cost += (l0 + l1) * MUL_LINEAR_COST_PER_BYTE;
-                cost += (l0 * l1) / MUL_SQUARE_COST_PER_BYTE_DIVIDER;
-                l0 += l1;
-                check_cost(allocator, cost, max_cost)?;
+                cost.add(MUL_COST_PER_OP);
+                cost.add(
+                    l0.saturating_add(l1)
+                        .saturating_mul(MUL_LINEAR_COST_PER_BYTE),
+                );
+                cost.add(l0.saturating_mul(l1) / MUL_SQUARE_COST_PER_BYTE_DIVIDER);
+                l0 = l0.saturating_add(l1);
+                cost.check(allocator, max_cost)?;
             }
             cost
         }
         3 => {
-            let mut cost = CONCAT_BASE_COST;
-            let mut total_size: u64 = 0;
+            let mut cost = CostAcc::new(CONCAT_BASE_COST);
+            let mut total_size: usize = 0;
             while let Some((arg, rest)) = allocator.next(args) {
                 args = rest;
-                cost += CONCAT_COST_PER_ARG;
+                cost.add(CONCAT_COST_PER_ARG);
                 let len = atom_len(allocator, arg, "unknown op")?;
-                total_size += len as u64;
-                check_cost(
-                    allocator,
-                    cost + total_size as Cost * CONCAT_COST_PER_BYTE,
-                    max_cost,
-                )?;
+                total_size = total_size.saturating_add(len);
+                cost.plus_per(total_size, CONCAT_COST_PER_BYTE)
+                    .check(allocator, max_cost)?;
             }
-            cost + total_size * CONCAT_COST_PER_BYTE
+            cost.plus_per(total_size, CONCAT_COST_PER_BYTE)
         }
-        _ => 1,
+        _ => CostAcc::new(1),
     };
 
-    assert!(cost > 0);
+    assert!(cost.cost() > 0);
 
-    check_cost(allocator, cost, max_cost)?;
-    cost *= cost_multiplier + 1;
+    cost.check(allocator, max_cost)?;
+    let cost = cost.cost().saturating_mul(cost_multiplier + 1);
     if cost > u32::MAX as u64 {
-        err(o, "invalid operator")
+        err(o, INVALID_OPERATOR)
     } else {
         Ok(Reduction(cost as Cost, allocator.nil()))
     }
@@ -381,7 +323,7 @@ pub const PRECOMPUTED_HASHES: [[u8; 32]; 37] = [
 ];
 
 pub fn op_sha256(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Response {
-    let mut cost = SHA256_BASE_COST;
+    let mut cost = CostAcc::new(SHA256_BASE_COST);
 
     if let Some([v0, v1]) = match_args::<2>(a, input) {
         if a.small_number(v0) == Some(1) {
@@ -390,8 +332,9 @@ pub fn op_sha256(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Respo
                 // integer, we may have a pre-computed hash for this
                 if (val as usize) < PRECOMPUTED_HASHES.len() {
                     let num_bytes = if val > 0 { 2 } else { 1 };
-                    cost += num_bytes * SHA256_COST_PER_BYTE + 2 as Cost * SHA256_COST_PER_ARG;
-                    return new_atom_and_cost(a, cost, &PRECOMPUTED_HASHES[val as usize]);
+                    cost.add_per(num_bytes, SHA256_COST_PER_BYTE);
+                    cost.add_per(2, SHA256_COST_PER_ARG);
+                    return new_atom_and_cost(a, cost.cost(), &PRECOMPUTED_HASHES[val as usize]);
                 }
             }
         }
@@ -401,32 +344,26 @@ pub fn op_sha256(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Respo
     let mut hasher = Sha256::new();
     while let Some((arg, rest)) = a.next(input) {
         input = rest;
-        cost += SHA256_COST_PER_ARG;
-        check_cost(
-            a,
-            cost + byte_count as Cost * SHA256_COST_PER_BYTE,
-            max_cost,
-        )?;
+        cost.add(SHA256_COST_PER_ARG);
+        cost.plus_per(byte_count, SHA256_COST_PER_BYTE)
+            .check(a, max_cost)?;
         let blob = atom(a, arg, "sha256")?;
         byte_count += blob.as_ref().len();
         hasher.update(blob);
     }
-    cost += byte_count as Cost * SHA256_COST_PER_BYTE;
-    new_atom_and_cost(a, cost, &hasher.finalize())
+    cost.add_per(byte_count, SHA256_COST_PER_BYTE);
+    new_atom_and_cost(a, cost.cost(), &hasher.finalize())
 }
 
 pub fn op_add(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Response {
-    let mut cost = ARITH_BASE_COST;
+    let mut cost = CostAcc::new(ARITH_BASE_COST);
     let mut byte_count: usize = 0;
     let mut total: Number = 0.into();
     while let Some((arg, rest)) = a.next(input) {
         input = rest;
-        cost += ARITH_COST_PER_ARG;
-        check_cost(
-            a,
-            cost + (byte_count as Cost * ARITH_COST_PER_BYTE),
-            max_cost,
-        )?;
+        cost.add(ARITH_COST_PER_ARG);
+        cost.plus_per(byte_count, ARITH_COST_PER_BYTE)
+            .check(a, max_cost)?;
 
         match a.node(arg) {
             NodeVisitor::Buffer(buf) => {
@@ -439,24 +376,25 @@ pub fn op_add(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Response
                 byte_count += len_for_value(val);
             }
             NodeVisitor::Pair(_, _) => {
-                return err(arg, "+ requires int args");
+                return err(arg, &fill(REQUIRES_INT_ARGS, &[&"+"]));
             }
         }
     }
     let total = a.new_number(total)?;
-    cost += byte_count as Cost * ARITH_COST_PER_BYTE;
-    Ok(malloc_cost(a, cost, total))
+    cost.add_per(byte_count, ARITH_COST_PER_BYTE);
+    Ok(malloc_cost(a, cost.cost(), total))
 }
 
 pub fn op_subtract(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Response {
-    let mut cost = ARITH_BASE_COST;
+    let mut cost = CostAcc::new(ARITH_BASE_COST);
     let mut byte_count: usize = 0;
     let mut total: Number = 0.into();
     let mut is_first = true;
     while let Some((arg, rest)) = a.next(input) {
         input = rest;
-        cost += ARITH_COST_PER_ARG;
-        check_cost(a, cost + byte_count as Cost * ARITH_COST_PER_BYTE, max_cost)?;
+        cost.add(ARITH_COST_PER_ARG);
+        cost.plus_per(byte_count, ARITH_COST_PER_BYTE)
+            .check(a, max_cost)?;
         if is_first {
             let (v, len) = int_atom(a, arg, "-")?;
             byte_count = len;
@@ -473,25 +411,25 @@ pub fn op_subtract(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Res
                     byte_count += len_for_value(val);
                 }
                 NodeVisitor::Pair(_, _) => {
-                    return err(arg, "- requires int args");
+                    return err(arg, &fill(REQUIRES_INT_ARGS, &[&"-"]));
                 }
             }
         };
         is_first = false;
     }
     let total = a.new_number(total)?;
-    cost += byte_count as Cost * ARITH_COST_PER_BYTE;
-    Ok(malloc_cost(a, cost, total))
+    cost.add_per(byte_count, ARITH_COST_PER_BYTE);
+    Ok(malloc_cost(a, cost.cost(), total))
 }
 
 pub fn op_multiply(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Response {
-    let mut cost: Cost = MUL_BASE_COST;
+    let mut cost = CostAcc::new(MUL_BASE_COST);
     let mut first_iter: bool = true;
     let mut total: Number = 1.into();
     let mut l0: usize = 0;
     while let Some((arg, rest)) = a.next(input) {
         input = rest;
-        check_cost(a, cost, max_cost)?;
+        cost.check(a, max_cost)?;
         if first_iter {
             (total, l0) = int_atom(a, arg, "*")?;
             first_iter = false;
@@ -509,17 +447,19 @@ pub fn op_multiply(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Res
                 len_for_value(val)
             }
             NodeVisitor::Pair(_, _) => {
-                return err(arg, "* requires int args");
+                return err(arg, &fill(REQUIRES_INT_ARGS, &[&"*"]));
             }
         };
 
-        cost += MUL_COST_PER_OP;
-        cost += (l0 + l1) as Cost * MUL_LINEAR_COST_PER_BYTE;
-        cost += (l0 * l1) as Cost / MUL_SQUARE_COST_PER_BYTE_DIVIDER;
+        // the product of the lengths is computed in Cost (u64), since it may
+        // not fit in a 32 bit usize
+        cost.add(MUL_COST_PER_OP);
+        cost.add_per(l0.saturating_add(l1), MUL_LINEAR_COST_PER_BYTE);
+        cost.add((l0 as Cost).saturating_mul(l1 as Cost) / MUL_SQUARE_COST_PER_BYTE_DIVIDER);
         l0 = limbs_for_int(&total);
     }
     let total = a.new_number(total)?;
-    Ok(malloc_cost(a, cost, total))
+    Ok(malloc_cost(a, cost.cost(), total))
 }
 
 pub fn op_div(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
@@ -528,10 +468,10 @@ pub fn op_div(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
     let (a1, a1_len) = int_atom(a, v1, "/")?;
     let cost = DIV_BASE_COST + ((a0_len + a1_len) as Cost) * DIV_COST_PER_BYTE;
     if a1.sign() == Sign::NoSign {
-        err(input, "div with 0")
+        err(input, DIV_BY_ZERO)
     } else {
         if a0.sign() == Sign::Minus || a1.sign() == Sign::Minus {
-            return err(input, "div operator with negative operands is deprecated");
+            return err(input, DIV_NEGATIVE);
         }
         let q = a0.div_floor(&a1);
         let q = a.new_number(q)?;
@@ -545,7 +485,7 @@ pub fn op_div_fixed(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Respo
     let (a1, a1_len) = int_atom(a, v1, "/")?;
     let cost = DIV_BASE_COST + ((a0_len + a1_len) as Cost) * DIV_COST_PER_BYTE;
     if a1.sign() == Sign::NoSign {
-        err(input, "div with 0")
+        err(input, DIV_BY_ZERO)
     } else {
         let q = a0.div_floor(&a1);
         let q = a.new_number(q)?;
@@ -559,7 +499,7 @@ pub fn op_divmod(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response
     let (a1, a1_len) = int_atom(a, v1, "divmod")?;
     let cost = DIVMOD_BASE_COST + ((a0_len + a1_len) as Cost) * DIVMOD_COST_PER_BYTE;
     if a1.sign() == Sign::NoSign {
-        err(input, "divmod with 0")
+        err(input, DIVMOD_BY_ZERO)
     } else {
         let (q, r) = a0.div_mod_floor(&a1);
         let q1 = a.new_number(q)?;
@@ -577,7 +517,7 @@ pub fn op_mod(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
     let (a1, a1_len) = int_atom(a, v1, "mod")?;
     let cost = DIV_BASE_COST + ((a0_len + a1_len) as Cost) * DIV_COST_PER_BYTE;
     if a1.sign() == Sign::NoSign {
-        err(input, "mod with 0")
+        err(input, MOD_BY_ZERO)
     } else {
         let q = a.new_number(a0.mod_floor(&a1))?;
         let c = a.atom_len(q) as Cost * MALLOC_COST_PER_BYTE;
@@ -585,6 +525,92 @@ pub fn op_mod(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
     }
 }
 
+// like /, but fails unless the divisor divides the dividend. Since the
+// division is exact, there is no rounding, and negative operands are allowed
+int_op!(
+    op_divexact,
+    "divexact",
+    |a, input, max_cost, [(a0, a0_len), (a1, a1_len)]| {
+        let mut cost = CostAcc::new(DIVEXACT_BASE_COST);
+        cost.add_per(a0_len.saturating_add(a1_len), DIVEXACT_COST_PER_BYTE);
+        cost.add(
+            (a0_len as Cost).saturating_mul(a1_len as Cost) / DIVEXACT_SQUARE_COST_PER_BYTE_DIVIDER,
+        );
+        // fail before doing the (potentially expensive) computation
+        cost.check(a, max_cost)?;
+        if a1.sign() == Sign::NoSign {
+            return err(input, DIVEXACT_BY_ZERO);
+        }
+        let (q, r) = a0.div_rem(&a1);
+        if r.sign() != Sign::NoSign {
+            return err(input, DIVEXACT_REMAINDER);
+        }
+        (cost.cost(), q)
+    }
+);
+
+// the result is never negative. gcd of 0 and 0 is 0
+int_op!(
+    op_gcd,
+    "gcd",
+    |a, _input, max_cost, [(a0, a0_len), (a1, a1_len)]| {
+        let mut cost = CostAcc::new(GCD_BASE_COST);
+        cost.add_per(a0_len.saturating_add(a1_len), GCD_COST_PER_BYTE);
+        cost.add(
+            (a0_len as Cost).saturating_mul(a1_len as Cost) / GCD_SQUARE_COST_PER_BYTE_DIVIDER,
+        );
+        // fail before doing the (potentially expensive) computation
+        cost.check(a, max_cost)?;
+        (cost.cost(), a0.gcd(&a1))
+    }
+);
+
+// computes floor(a * b / c) without loss of precision. The result is rounded
+// towards negative infinity, for any combination of signs, just like / (with
+// ENABLE_FIXED_DIV)
+int_op!(
+    op_mul_div_floor,
+    "mul_div_floor",
+    |a, input, max_cost, [(a0, a0_len), (a1, a1_len), (a2, a2_len)]| {
+        let mut cost = CostAcc::new(MUL_DIV_FLOOR_BASE_COST);
+        cost.add_per(
+            a0_len.saturating_add(a1_len).saturating_add(a2_len),
+            MUL_DIV_FLOOR_COST_PER_BYTE,
+        );
+        cost.add(
+            (a0_len as Cost).saturating_mul(a1_len as Cost) / MUL_SQUARE_COST_PER_BYTE_DIVIDER,
+        );
+        cost.add(
+            (a0_len.saturating_add(a1_len) as Cost).saturating_mul(a2_len as Cost)
+                / MUL_DIV_FLOOR_SQUARE_COST_PER_BYTE_DIVIDER,
+        );
+        cost.check(a, max_cost)?;
+        if a2.sign() == Sign::NoSign {
+            return err(input, MUL_DIV_FLOOR_BY_ZERO);
+        }
+        (cost.cost(), (a0 * a1).div_floor(&a2))
+    }
+);
+
+// computes floor(sqrt(n)), i.e. the largest integer whose square is not
+// greater than n. n may not be negative
+int_op!(
+    op_sqrt_floor,
+    "sqrt_floor",
+    |a, input, max_cost, [(n, n_len)]| {
+        let mut cost = CostAcc::new(SQRT_FLOOR_BASE_COST);
+        cost.add_per(n_len, SQRT_FLOOR_COST_PER_BYTE);
+        cost.add(
+            (n_len as Cost).saturating_mul(n_len as Cost) / SQRT_FLOOR_SQUARE_COST_PER_BYTE_DIVIDER,
+        );
+        cost.check(a, max_cost)?;
+        if n.sign() == Sign::Minus {
+            return err(input, SQRT_FLOOR_NEGATIVE);
+        }
+        (cost.cost(), n.sqrt())
+    }
+);
+
 pub fn op_gr(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
     let [v0, v1] = get_args::<2>(a, input, ">")?;
 
@@ -592,13 +618,13 @@ pub fn op_gr(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
         (Some(lhs), Some(rhs)) => {
             let cost =
                 GR_BASE_COST + (len_for_value(lhs) + len_for_value(rhs)) as Cost * GR_COST_PER_BYTE;
-            Ok(Reduction(cost, if lhs > rhs { a.one() } else { a.nil() }))
+            bool_response(a, cost, lhs > rhs)
         }
         _ => {
             let (v0, v0_len) = int_atom(a, v0, ">")?;
             let (v1, v1_len) = int_atom(a, v1, ">")?;
             let cost = GR_BASE_COST + (v0_len + v1_len) as Cost * GR_COST_PER_BYTE;
-            Ok(Reduction(cost, if v0 > v1 { a.one() } else { a.nil() }))
+            bool_response(a, cost, v0 > v1)
         }
     }
 }
@@ -610,21 +636,45 @@ pub fn op_gr_bytes(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Respon
     let v0 = v0_atom.as_ref();
     let v1 = v1_atom.as_ref();
     let cost = GRS_BASE_COST + (v0.len() + v1.len()) as Cost * GRS_COST_PER_BYTE;
-    Ok(Reduction(cost, if v0 > v1 { a.one() } else { a.nil() }))
+    bool_response(a, cost, v0 > v1)
 }
 
 pub fn op_strlen(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
     let [n] = get_args::<1>(a, input, "strlen")?;
     let size = atom_len(a, n, "strlen")?;
-    let size_node = a.new_number(size.into())?;
     let cost = STRLEN_BASE_COST + size as Cost * STRLEN_COST_PER_BYTE;
-    Ok(malloc_cost(a, cost, size_node))
+    number_response(a, cost, size.into())
+}
+
+// the number of bits set in the atom
+pub fn op_popcount(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+    let [n] = get_args::<1>(a, input, "popcount")?;
+    let buf = atom(a, n, "popcount")?;
+    let buf = buf.as_ref();
+    let count: u64 = buf.iter().map(|b| b.count_ones() as u64).sum();
+    let cost = POPCOUNT_BASE_COST + buf.len() as Cost * POPCOUNT_COST_PER_BYTE;
+    number_response(a, cost, count.into())
+}
+
+// the number of bits needed to represent the atom, i.e. its length in bits
+// excluding leading zero bits. The bitlength of nil (or any atom with only
+// zero bytes) is 0
+pub fn op_bitlength(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+    let [n] = get_args::<1>(a, input, "bitlength")?;
+    let buf = atom(a, n, "bitlength")?;
+    let buf = buf.as_ref();
+    let bits = match buf.iter().position(|b| *b != 0) {
+        Some(pos) => (buf.len() - pos) as u64 * 8 - buf[pos].leading_zeros() as u64,
+        None => 0,
+    };
+    let cost = BITLENGTH_BASE_COST + buf.len() as Cost * BITLENGTH_COST_PER_BYTE;
+    number_response(a, cost, bits.into())
 }
 
 pub fn op_substr(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
     let ([a0, start, end], argc) = get_varargs::<3>(a, input, "substr")?;
     if !(2..=3).contains(&argc) {
-        return err(input, "substr takes exactly 2 or 3 arguments");
+        return err(input, SUBSTR_ARG_COUNT);
     }
     let size = atom_len(a, a0, "substr")?;
     let start = i32_atom(a, start, "substr")?;
@@ -635,7 +685,7 @@ pub fn op_substr(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response
         size as i32
     };
     if end < 0 || start < 0 || end as usize > size || end < start {
-        err(input, "invalid indices for substr")
+        err(input, SUBSTR_INVALID_INDICES)
     } else {
         let r = a.new_substr(a0, start as u32, end as u32)?;
         let cost: Cost = 1;
@@ -644,29 +694,26 @@ pub fn op_substr(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response
 }
 
 pub fn op_concat(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Response {
-    let mut cost = CONCAT_BASE_COST;
+    let mut cost = CostAcc::new(CONCAT_BASE_COST);
     let mut total_size: usize = 0;
     let mut terms = Vec::<NodePtr>::new();
     while let Some((arg, rest)) = a.next(input) {
         input = rest;
-        cost += CONCAT_COST_PER_ARG;
-        check_cost(
-            a,
-            cost + total_size as Cost * CONCAT_COST_PER_BYTE,
-            max_cost,
-        )?;
+        cost.add(CONCAT_COST_PER_ARG);
+        cost.plus_per(total_size, CONCAT_COST_PER_BYTE)
+            .check(a, max_cost)?;
         match a.sexp(arg) {
-            SExp::Pair(_, _) => return err(arg, "concat on list"),
+            SExp::Pair(_, _) => return err(arg, &fill(ON_LIST, &[&"concat"])),
             SExp::Atom => total_size += a.atom_len(arg),
         };
         terms.push(arg);
     }
 
-    cost += total_size as Cost * CONCAT_COST_PER_BYTE;
-    cost += total_size as Cost * MALLOC_COST_PER_BYTE;
-    check_cost(a, cost, max_cost)?;
+    cost.add_per(total_size, CONCAT_COST_PER_BYTE);
+    cost.add_per(total_size, MALLOC_COST_PER_BYTE);
+    cost.check(a, max_cost)?;
     let new_atom = a.new_concat(total_size, &terms)?;
-    Ok(Reduction(cost, new_atom))
+    Ok(Reduction(cost.cost(), new_atom))
 }
 
 pub fn op_ash(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
@@ -674,7 +721,7 @@ pub fn op_ash(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
     let (i0, l0) = int_atom(a, n0, "ash")?;
     let a1 = i32_atom(a, n1, "ash")?;
     if !(-65535..=65535).contains(&a1) {
-        return err(n1, "shift too large");
+        return err(n1, SHIFT_TOO_LARGE);
     }
 
     let v: Number = if a1 > 0 { i0 << a1 } else { i0 >> -a1 };
@@ -742,13 +789,36 @@ fn test_op_ash() {
     assert_eq!(node_bytes.len(), 4065);
 }
 
+#[test]
+fn test_atom_limit() {
+    let mut a = Allocator::new();
+    a.set_atom_limit(1000);
+
+    assert_eq!(
+        test_shift(op_lsh, &mut a, &[1], &[0x1f, 0x40])
+            .unwrap_err()
+            .1,
+        "atom too large"
+    );
+    let node = test_shift(op_lsh, &mut a, &[1], &[0x1f, 0x38]).unwrap().1;
+    assert_eq!(a.atom_len(node), 1000);
+
+    let args = a.nil();
+    let args = a.new_pair(node, args).unwrap();
+    let args = a.new_pair(node, args).unwrap();
+    assert_eq!(
+        op_concat(&mut a, args, 10000000).unwrap_err().1,
+        "atom too large"
+    );
+}
+
 pub fn op_lsh(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
     let [n0, n1] = get_args::<2>(a, input, "lsh")?;
     let b0_atom = atom(a, n0, "lsh")?;
     let b0 = b0_atom.as_ref();
     let a1 = i32_atom(a, n1, "lsh")?;
     if !(-65535..=65535).contains(&a1) {
-        return err(n1, "shift too large");
+        return err(n1, SHIFT_TOO_LARGE);
     }
     let i0 = BigUint::from_bytes_be(b0);
     let l0 = b0.len();
@@ -815,18 +885,19 @@ fn binop_reduction(
 ) -> Response {
     let mut total = initial_value;
     let mut arg_size: usize = 0;
-    let mut cost = LOG_BASE_COST;
+    let mut cost = CostAcc::new(LOG_BASE_COST);
     while let Some((arg, rest)) = a.next(input) {
         input = rest;
         let (n0, len) = int_atom(a, arg, op_name)?;
         op_f(&mut total, &n0);
         arg_size += len;
-        cost += LOG_COST_PER_ARG;
-        check_cost(a, cost + (arg_size as Cost * LOG_COST_PER_BYTE), max_cost)?;
+        cost.add(LOG_COST_PER_ARG);
+        cost.plus_per(arg_size, LOG_COST_PER_BYTE)
+            .check(a, max_cost)?;
     }
-    cost += arg_size as Cost * LOG_COST_PER_BYTE;
+    cost.add_per(arg_size, LOG_COST_PER_BYTE);
     let total = a.new_number(total)?;
-    Ok(malloc_cost(a, cost, total))
+    Ok(malloc_cost(a, cost.cost(), total))
 }
 
 fn logand_op(a: &mut Number, b: &Number) {
@@ -856,44 +927,38 @@ pub fn op_logxor(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Response
     binop_reduction("logxor", a, v, input, max_cost, logxor_op)
 }
 
-pub fn op_lognot(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
-    let [n] = get_args::<1>(a, input, "lognot")?;
-    let (mut n, len) = int_atom(a, n, "lognot")?;
-    n = !n;
+int_op!(op_lognot, "lognot", |_a, _input, _max_cost, [(n, len)]| {
     let cost = LOGNOT_BASE_COST + ((len as Cost) * LOGNOT_COST_PER_BYTE);
-    let r = a.new_number(n)?;
-    Ok(malloc_cost(a, cost, r))
-}
+    (cost, !n)
+});
 
 pub fn op_not(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
     let [n] = get_args::<1>(a, input, "not")?;
-    let r = if nilp(a, n) { a.one() } else { a.nil() };
-    let cost = BOOL_BASE_COST;
-    Ok(Reduction(cost, r))
+    bool_response(a, BOOL_BASE_COST, nilp(a, n))
 }
 
 pub fn op_any(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Response {
-    let mut cost = BOOL_BASE_COST;
+    let mut cost = CostAcc::new(BOOL_BASE_COST);
     let mut is_any = false;
     while let Some((arg, rest)) = a.next(input) {
         input = rest;
-        cost += BOOL_COST_PER_ARG;
-        check_cost(a, cost, max_cost)?;
+        cost.add(BOOL_COST_PER_ARG);
+        cost.check(a, max_cost)?;
         is_any = is_any || !nilp(a, arg);
     }
-    Ok(Reduction(cost, if is_any { a.one() } else { a.nil() }))
+    bool_response(a, cost.cost(), is_any)
 }
 
 pub fn op_all(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Response {
-    let mut cost = BOOL_BASE_COST;
+    let mut cost = CostAcc::new(BOOL_BASE_COST);
     let mut is_all = true;
     while let Some((arg, rest)) = a.next(input) {
         input = rest;
-        cost += BOOL_COST_PER_ARG;
-        check_cost(a, cost, max_cost)?;
+        cost.add(BOOL_COST_PER_ARG);
+        cost.check(a, max_cost)?;
         is_all = is_all && !nilp(a, arg);
     }
-    Ok(Reduction(cost, if is_all { a.one() } else { a.nil() }))
+    bool_response(a, cost.cost(), is_all)
 }
 
 pub fn op_pubkey_for_exp(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
@@ -911,17 +976,19 @@ pub fn op_pubkey_for_exp(a: &mut Allocator, input: NodePtr, _max_cost: Cost) ->
 }
 
 pub fn op_point_add(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Response {
-    let mut cost = POINT_ADD_BASE_COST;
+    let mut cost = CostAcc::new(POINT_ADD_BASE_COST);
     let mut total = G1Element::default();
+    let mut idx = 0;
     while let Some((arg, rest)) = a.next(input) {
         input = rest;
-        let point = a.g1(arg)?;
-        cost += POINT_ADD_COST_PER_ARG;
-        check_cost(a, cost, max_cost)?;
+        let point = g1_arg(a, arg, "point_add", idx)?;
+        cost.add(POINT_ADD_COST_PER_ARG);
+        cost.check(a, max_cost)?;
         total += &point;
+        idx += 1;
     }
     Ok(Reduction(
-        cost + 48 * MALLOC_COST_PER_BYTE,
+        cost.plus_per(48, MALLOC_COST_PER_BYTE).cost(),
         a.new_g1(total)?,
     ))
 }
@@ -931,31 +998,25 @@ pub fn op_coinid(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response
 
     let parent_coin = atom(a, parent_coin, "coinid")?;
     if parent_coin.as_ref().len() != 32 {
-        return err(input, "coinid: invalid parent coin id (must be 32 bytes)");
+        return err(input, COINID_PARENT_ID);
     }
     let puzzle_hash = atom(a, puzzle_hash, "coinid")?;
     if puzzle_hash.as_ref().len() != 32 {
-        return err(input, "coinid: invalid puzzle hash (must be 32 bytes)");
+        return err(input, COINID_PUZZLE_HASH);
     }
     let amount_atom = atom(a, amount, "coinid")?;
     let amount = amount_atom.as_ref();
     if !amount.is_empty() {
         if (amount[0] & 0x80) != 0 {
-            return err(input, "coinid: invalid amount (may not be negative");
+            return err(input, COINID_NEGATIVE_AMOUNT);
         }
         if amount == [0_u8] || (amount.len() > 1 && amount[0] == 0 && (amount[1] & 0x80) == 0) {
-            return err(
-                input,
-                "coinid: invalid amount (may not have redundant leading zero)",
-            );
+            return err(input, COINID_LEADING_ZERO);
         }
         // the only valid coin value that's 9 bytes is when a leading zero is
         // required to not have the value interpreted as negative
         if amount.len() > 9 || (amount.len() == 9 && amount[0] != 0) {
-            return err(
-                input,
-                "coinid: invalid amount (may not exceed max coin amount)",
-            );
+            return err(input, COINID_AMOUNT_TOO_LARGE);
         }
     }
 
@@ -975,27 +1036,39 @@ pub fn op_coinid(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response
 pub fn op_modpow(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Response {
     let [base, exponent, modulus] = get_args::<3>(a, input, "modpow")?;
 
-    let mut cost = MODPOW_BASE_COST;
+    // the squares of the sizes are computed in Cost (u64), since they may not
+    // fit in a 32 bit usize
+    let mut cost = CostAcc::new(MODPOW_BASE_COST);
     let (base, bsize) = int_atom(a, base, "modpow")?;
-    cost += bsize as Cost * MODPOW_COST_PER_BYTE_BASE_VALUE;
+    cost.add_per(bsize, MODPOW_COST_PER_BYTE_BASE_VALUE);
     let (exponent, esize) = int_atom(a, exponent, "modpow")?;
-    cost += (esize * esize) as Cost * MODPOW_COST_PER_BYTE_EXPONENT;
-    check_cost(a, cost, max_cost)?;
+    let esize = esize as Cost;
+    cost.add(
+        esize
+            .saturating_mul(esize)
+            .saturating_mul(MODPOW_COST_PER_BYTE_EXPONENT),
+    );
+    cost.check(a, max_cost)?;
     let (modulus, msize) = int_atom(a, modulus, "modpow")?;
-    cost += (msize * msize) as Cost * MODPOW_COST_PER_BYTE_MOD;
-    check_cost(a, cost, max_cost)?;
+    let msize = msize as Cost;
+    cost.add(
+        msize
+            .saturating_mul(msize)
+            .saturating_mul(MODPOW_COST_PER_BYTE_MOD),
+    );
+    cost.check(a, max_cost)?;
 
     if exponent.sign() == Sign::Minus {
-        return err(input, "modpow with negative exponent");
+        return err(input, MODPOW_NEGATIVE_EXPONENT);
     }
 
     if modulus.sign() == Sign::NoSign {
-        return err(input, "modpow with 0 modulus");
+        return err(input, MODPOW_ZERO_MODULUS);
     }
 
     let ret = base.modpow(&exponent, &modulus);
     let ret = a.new_number(ret)?;
-    Ok(malloc_cost(a, cost, ret))
+    Ok(malloc_cost(a, cost.cost(), ret))
 }
 
 #[cfg(test)]
@@ -0,0 +1,239 @@
+use crate::allocator::{Checkpoint, NodePtr, ObjectType};
+use std::collections::HashMap;
+
+// A map keyed by NodePtr, for caching values computed for nodes in an
+// allocator, like tree hashes. Pairs and atoms are looked up by their index,
+// in vectors parallel to the allocator's, rather than by hashing the NodePtr.
+// The values themselves are kept in a separate, dense vector, so a map with
+// few entries for a large allocator costs 4 bytes per node up to the highest
+// index in it, not the size of a value. Small atoms (whose index is the atom
+// itself) are kept in a HashMap.
+//
+// Restoring a checkpoint in the allocator invalidates the NodePtrs allocated
+// after it, and their indices will be reused. To keep a map in sync, pass the
+// same checkpoint to NodeMap::restore_checkpoint().
+
+// the value of a slot with no entry
+const EMPTY: u32 = u32::MAX;
+
+#[derive(Clone, Debug)]
+pub struct NodeMap<V> {
+    // for each pair (and atom) index, the position of its entry in entries,
+    // or EMPTY
+    pairs: Vec<u32>,
+    atoms: Vec<u32>,
+    small_atoms: HashMap<u32, u32>,
+    entries: Vec<(NodePtr, V)>,
+}
+
+impl<V> Default for NodeMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> NodeMap<V> {
+    pub fn new() -> Self {
+        Self {
+            pairs: Vec::new(),
+            atoms: Vec::new(),
+            small_atoms: HashMap::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    fn slot(&self, node: NodePtr) -> Option<usize> {
+        let idx = node.index();
+        let slot = match node.object_type() {
+            ObjectType::Pair => *self.pairs.get(idx as usize)?,
+            ObjectType::Bytes => *self.atoms.get(idx as usize)?,
+            ObjectType::SmallAtom => *self.small_atoms.get(&idx)?,
+        };
+        if slot == EMPTY {
+            None
+        } else {
+            Some(slot as usize)
+        }
+    }
+
+    fn set_slot(&mut self, node: NodePtr, slot: u32) {
+        let idx = node.index();
+        let vec = match node.object_type() {
+            ObjectType::Pair => &mut self.pairs,
+            ObjectType::Bytes => &mut self.atoms,
+            ObjectType::SmallAtom => {
+                if slot == EMPTY {
+                    self.small_atoms.remove(&idx);
+                } else {
+                    self.small_atoms.insert(idx, slot);
+                }
+                return;
+            }
+        };
+        let idx = idx as usize;
+        if vec.len() <= idx {
+            vec.resize(idx + 1, EMPTY);
+        }
+        vec[idx] = slot;
+    }
+
+    pub fn get(&self, node: NodePtr) -> Option<&V> {
+        self.slot(node).map(|slot| &self.entries[slot].1)
+    }
+
+    pub fn get_mut(&mut self, node: NodePtr) -> Option<&mut V> {
+        self.slot(node).map(|slot| &mut self.entries[slot].1)
+    }
+
+    pub fn contains_key(&self, node: NodePtr) -> bool {
+        self.slot(node).is_some()
+    }
+
+    // returns the value previously associated with the node, if any
+    pub fn insert(&mut self, node: NodePtr, value: V) -> Option<V> {
+        if let Some(slot) = self.slot(node) {
+            return Some(std::mem::replace(&mut self.entries[slot].1, value));
+        }
+        self.set_slot(node, self.entries.len() as u32);
+        self.entries.push((node, value));
+        None
+    }
+
+    pub fn remove(&mut self, node: NodePtr) -> Option<V> {
+        let slot = self.slot(node)?;
+        self.set_slot(node, EMPTY);
+        let (_, value) = self.entries.swap_remove(slot);
+        if let Some((moved, _)) = self.entries.get(slot) {
+            self.set_slot(*moved, slot as u32);
+        }
+        Some(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.pairs.clear();
+        self.atoms.clear();
+        self.small_atoms.clear();
+        self.entries.clear();
+    }
+
+    // the entries, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (NodePtr, &V)> {
+        self.entries.iter().map(|(node, value)| (*node, value))
+    }
+
+    // drops the entries for the pairs and atoms allocated after the
+    // checkpoint was created. Call this along with
+    // Allocator::restore_checkpoint(), before allocating any new nodes
+    pub fn restore_checkpoint(&mut self, cp: &Checkpoint) {
+        let (pairs, atoms) = cp.node_counts();
+        if self.pairs.len() <= pairs && self.atoms.len() <= atoms {
+            return;
+        }
+        self.pairs.truncate(pairs);
+        self.atoms.truncate(atoms);
+        self.entries.retain(|(node, _)| match node.object_type() {
+            ObjectType::Pair => (node.index() as usize) < pairs,
+            ObjectType::Bytes => (node.index() as usize) < atoms,
+            ObjectType::SmallAtom => true,
+        });
+        for (slot, (node, _)) in self.entries.iter().enumerate() {
+            let idx = node.index();
+            match node.object_type() {
+                ObjectType::Pair => self.pairs[idx as usize] = slot as u32,
+                ObjectType::Bytes => self.atoms[idx as usize] = slot as u32,
+                ObjectType::SmallAtom => {
+                    self.small_atoms.insert(idx, slot as u32);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::allocator::Allocator;
+
+    #[test]
+    fn test_node_map() {
+        let mut a = Allocator::new();
+        let mut map = NodeMap::<u32>::new();
+        let atom = a.new_atom(&[1; 20]).unwrap();
+        let small = a.new_small_number(1000).unwrap();
+        let pair = a.new_pair(atom, small).unwrap();
+
+        assert!(map.is_empty());
+        assert_eq!(map.insert(atom, 1), None);
+        assert_eq!(map.insert(small, 2), None);
+        assert_eq!(map.insert(pair, 3), None);
+        assert_eq!(map.insert(NodePtr::NIL, 4), None);
+        assert_eq!(map.len(), 4);
+        assert_eq!(map.get(atom), Some(&1));
+        assert_eq!(map.get(small), Some(&2));
+        assert_eq!(map.get(pair), Some(&3));
+        assert_eq!(map.get(NodePtr::NIL), Some(&4));
+        assert!(!map.contains_key(a.one()));
+
+        assert_eq!(map.insert(atom, 10), Some(1));
+        *map.get_mut(pair).unwrap() += 10;
+        assert_eq!(map.get(pair), Some(&13));
+        assert_eq!(map.len(), 4);
+
+        // removing an entry moves the last one into its place
+        assert_eq!(map.remove(atom), Some(10));
+        assert_eq!(map.remove(atom), None);
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get(small), Some(&2));
+        assert_eq!(map.get(pair), Some(&13));
+        assert_eq!(map.get(NodePtr::NIL), Some(&4));
+        let mut entries: Vec<(NodePtr, u32)> = map.iter().map(|(n, v)| (n, *v)).collect();
+        entries.sort();
+        let mut expected = vec![(pair, 13), (NodePtr::NIL, 4), (small, 2)];
+        expected.sort();
+        assert_eq!(entries, expected);
+
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.get(pair), None);
+    }
+
+    #[test]
+    fn test_restore_checkpoint() {
+        let mut a = Allocator::new();
+        let mut map = NodeMap::<&str>::new();
+        let atom = a.new_atom(&[1; 20]).unwrap();
+        let pair = a.new_pair(atom, atom).unwrap();
+        map.insert(atom, "atom");
+        map.insert(pair, "pair");
+
+        let cp = a.checkpoint();
+        let atom2 = a.new_atom(&[2; 20]).unwrap();
+        let pair2 = a.new_pair(atom2, atom2).unwrap();
+        let small = a.new_small_number(1000).unwrap();
+        map.insert(atom2, "atom2");
+        map.insert(pair2, "pair2");
+        map.insert(small, "small");
+        a.restore_checkpoint(&cp);
+        map.restore_checkpoint(&cp);
+
+        // small atoms stay valid
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get(atom), Some(&"atom"));
+        assert_eq!(map.get(pair), Some(&"pair"));
+        assert_eq!(map.get(small), Some(&"small"));
+
+        // the indices of the freed nodes are reused (unless they're poisoned)
+        let atom3 = a.new_atom(&[3; 20]).unwrap();
+        let pair3 = a.new_pair(atom3, atom3).unwrap();
+        assert_eq!(map.get(atom3), None);
+        assert_eq!(map.get(pair3), None);
+    }
+}
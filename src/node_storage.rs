@@ -0,0 +1,190 @@
+use std::ops::{Index, IndexMut};
+
+// The storage of the allocator's pair and atom tables. Both are grow-only
+// stacks, indexed by NodePtr, that are only ever shrunk by restoring a
+// checkpoint. Atom bytes are not stored this way, since they have to be
+// contiguous.
+//
+// Vec is the default. With the "arena-allocator" feature, the allocator uses
+// ArenaVec instead. Vec's inherent methods take precedence over these, so
+// without it, only entries() is used.
+#[cfg_attr(not(feature = "arena-allocator"), allow(dead_code))]
+pub(crate) trait NodeStorage<T>:
+    Index<usize, Output = T> + IndexMut<usize> + Default
+{
+    fn len(&self) -> usize;
+    fn capacity(&self) -> usize;
+    fn push(&mut self, value: T);
+    // like Vec::truncate(), this keeps the memory for reuse. The
+    // poison-allocator never truncates, it overwrites freed entries instead
+    #[cfg_attr(feature = "poison-allocator", allow(dead_code))]
+    fn truncate(&mut self, len: usize);
+    fn reserve(&mut self, additional: usize);
+    // all entries, in order. This isn't named iter(), since that would
+    // shadow the slice's iter() on every Vec
+    fn entries<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a;
+}
+
+impl<T> NodeStorage<T> for Vec<T> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+    fn capacity(&self) -> usize {
+        Vec::capacity(self)
+    }
+    fn push(&mut self, value: T) {
+        Vec::push(self, value)
+    }
+    fn truncate(&mut self, len: usize) {
+        Vec::truncate(self, len)
+    }
+    fn reserve(&mut self, additional: usize) {
+        Vec::reserve(self, additional)
+    }
+    fn entries<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        self.as_slice().iter()
+    }
+}
+
+// the number of entries in each ArenaVec chunk
+const CHUNK_SIZE: usize = 1 << 12;
+
+// A bump arena of fixed size chunks. Unlike a Vec, growing never moves the
+// existing entries, so there is no copy, and the old and new buffers are never
+// allocated at the same time. This lowers the peak memory use of large
+// allocators, and the memory wasted by a Vec's doubling, at the cost of an
+// extra indirection for every lookup.
+#[cfg_attr(not(feature = "arena-allocator"), allow(dead_code))]
+#[derive(Clone, Debug)]
+pub(crate) struct ArenaVec<T> {
+    // every chunk is allocated with a capacity of CHUNK_SIZE, and never grows
+    // past it. Chunks past the end are kept (empty) when truncating
+    chunks: Vec<Vec<T>>,
+    len: usize,
+}
+
+impl<T> Default for ArenaVec<T> {
+    fn default() -> Self {
+        Self {
+            chunks: Vec::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<T> NodeStorage<T> for ArenaVec<T> {
+    fn len(&self) -> usize {
+        self.len
+    }
+    fn capacity(&self) -> usize {
+        self.chunks.len() * CHUNK_SIZE
+    }
+    fn push(&mut self, value: T) {
+        let chunk = self.len / CHUNK_SIZE;
+        if chunk == self.chunks.len() {
+            self.chunks.push(Vec::with_capacity(CHUNK_SIZE));
+        }
+        self.chunks[chunk].push(value);
+        self.len += 1;
+    }
+    fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        let first = len / CHUNK_SIZE;
+        self.chunks[first].truncate(len % CHUNK_SIZE);
+        for chunk in &mut self.chunks[first + 1..] {
+            chunk.clear();
+        }
+        self.len = len;
+    }
+    fn reserve(&mut self, additional: usize) {
+        while self.capacity() < self.len + additional {
+            self.chunks.push(Vec::with_capacity(CHUNK_SIZE));
+        }
+    }
+    fn entries<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        // the chunks past the end are empty
+        self.chunks.iter().flatten()
+    }
+}
+
+impl<T> Index<usize> for ArenaVec<T> {
+    type Output = T;
+    fn index(&self, idx: usize) -> &T {
+        &self.chunks[idx / CHUNK_SIZE][idx % CHUNK_SIZE]
+    }
+}
+
+impl<T> IndexMut<usize> for ArenaVec<T> {
+    fn index_mut(&mut self, idx: usize) -> &mut T {
+        &mut self.chunks[idx / CHUNK_SIZE][idx % CHUNK_SIZE]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_storage<S: NodeStorage<u32>>(mut s: S) {
+        assert_eq!(s.len(), 0);
+        let count = CHUNK_SIZE as u32 * 2 + 10;
+        for i in 0..count {
+            s.push(i);
+        }
+        assert_eq!(s.len(), count as usize);
+        assert_eq!(s[CHUNK_SIZE], CHUNK_SIZE as u32);
+        s[5] = 1000;
+        assert_eq!(s[5], 1000);
+        assert!(s
+            .entries()
+            .copied()
+            .eq((0..count).map(|i| if i == 5 { 1000 } else { i })));
+
+        // truncate into the first chunk, and grow again
+        let capacity = s.capacity();
+        s.truncate(CHUNK_SIZE - 1);
+        assert_eq!(s.len(), CHUNK_SIZE - 1);
+        assert_eq!(s.entries().count(), CHUNK_SIZE - 1);
+        assert_eq!(s.capacity(), capacity);
+        s.push(1);
+        s.push(2);
+        assert_eq!(s[CHUNK_SIZE], 2);
+        assert_eq!(s.entries().count(), CHUNK_SIZE + 1);
+
+        // truncating to a longer length does nothing
+        s.truncate(count as usize);
+        assert_eq!(s.len(), CHUNK_SIZE + 1);
+        s.truncate(0);
+        assert_eq!(s.len(), 0);
+        s.reserve(CHUNK_SIZE * 3);
+        assert!(s.capacity() >= CHUNK_SIZE * 3);
+    }
+
+    #[test]
+    fn test_vec() {
+        check_storage(Vec::new());
+    }
+
+    #[test]
+    fn test_arena_vec() {
+        check_storage(ArenaVec::default());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_arena_vec_out_of_bounds() {
+        let mut s = ArenaVec::default();
+        s.push(1);
+        s.truncate(0);
+        let _ = s[0];
+    }
+}
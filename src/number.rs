@@ -1,9 +1,28 @@
-use crate::allocator::{Allocator, NodePtr};
+use crate::allocator::{Allocator, AtomKind, NodePtr};
 use crate::reduction::EvalErr;
 
 use num_bigint::BigInt;
 pub type Number = BigInt;
 
+// Integers are stored in atoms as big-endian two's complement. The canonical
+// encoding is the minimal one: 0 is the empty atom, and there is no redundant
+// leading 0x00 (or 0xff) byte, i.e. one whose following byte has the same sign
+// bit. new_number() and new_small_number() always produce the canonical
+// encoding, and operators returning integers must build them through those
+// (or number_response()), never from bytes they assemble themselves.
+// number() accepts any encoding, since inputs may come from anywhere.
+
+// returns true if v is the canonical encoding of the integer it holds
+pub fn is_canonical_int(v: &[u8]) -> bool {
+    match v {
+        [] => true,
+        [0] => false,
+        [0x00, next, ..] => (next & 0x80) != 0,
+        [0xff, next, ..] => (next & 0x80) == 0,
+        _ => true,
+    }
+}
+
 // This low-level conversion function is meant to be used by the Allocator, for
 // logic interacting with the CLVM heap/allocator, use new_number() and number()
 // instead.
@@ -18,7 +37,7 @@ pub fn node_from_number(allocator: &mut Allocator, item: &Number) -> Result<Node
         }
         slice = &slice[1..];
     }
-    allocator.new_atom(slice)
+    allocator.new_atom_with_kind(slice, AtomKind::Int)
 }
 
 // This low-level conversion function is meant to be used by the Allocator, for
@@ -93,6 +112,45 @@ fn test_node_from_number() {
     assert_eq!(&[0x40, 0x00], &a.atom(ptr).as_ref());
 }
 
+#[test]
+fn test_is_canonical_int() {
+    assert!(is_canonical_int(&[]));
+    assert!(is_canonical_int(&[1]));
+    assert!(is_canonical_int(&[0x7f]));
+    assert!(is_canonical_int(&[0x80]));
+    assert!(is_canonical_int(&[0xff]));
+    assert!(is_canonical_int(&[0x00, 0x80]));
+    assert!(is_canonical_int(&[0xff, 0x7f]));
+    assert!(is_canonical_int(&[0x01, 0x00]));
+
+    // redundant leading bytes
+    assert!(!is_canonical_int(&[0]));
+    assert!(!is_canonical_int(&[0x00, 0x7f]));
+    assert!(!is_canonical_int(&[0x00, 0x00, 0x80]));
+    assert!(!is_canonical_int(&[0xff, 0x80]));
+    assert!(!is_canonical_int(&[0xff, 0xff]));
+
+    // everything node_from_number() produces is canonical
+    let mut a = Allocator::new();
+    for v in [
+        0_i64,
+        1,
+        -1,
+        127,
+        128,
+        -128,
+        -129,
+        255,
+        0x8000,
+        -0x8000,
+        i64::MAX,
+        i64::MIN,
+    ] {
+        let ptr = node_from_number(&mut a, &v.into()).unwrap();
+        assert!(is_canonical_int(a.atom(ptr).as_ref()));
+    }
+}
+
 #[cfg(test)]
 use num_bigint::{BigUint, Sign};
 
@@ -1,33 +1,34 @@
 use crate::allocator::{Allocator, Atom, NodePtr, NodeVisitor, SExp};
 use crate::cost::Cost;
+pub use crate::cost_schedule_v1::MALLOC_COST_PER_BYTE;
 use crate::err_utils::err;
+use crate::error_catalog::{
+    fill, FIRST_OF_NON_CONS, ON_LIST, REQUIRES_ATOM, REQUIRES_CANONICAL_INT32_ARGS,
+    REQUIRES_INT32_ARGS, REQUIRES_INT_ARG, REQUIRES_INT_ARGS, REQUIRES_POSITIVE_INT_ARG,
+    REQUIRES_UINT_ARG, REST_OF_NON_CONS, TAKES_EXACTLY, TAKES_NO_MORE_THAN,
+};
 use crate::number::Number;
 use crate::reduction::EvalErr;
 use crate::reduction::{Reduction, Response};
+use crate::sexp_store::SExpStore;
 use lazy_static::lazy_static;
 use num_bigint::{BigUint, Sign};
 use num_integer::Integer;
 
-// We ascribe some additional cost per byte for operations that allocate new atoms
-pub const MALLOC_COST_PER_BYTE: Cost = 10;
-
 pub fn get_args<const N: usize>(
-    a: &Allocator,
+    a: &impl SExpStore,
     args: NodePtr,
     name: &str,
 ) -> Result<[NodePtr; N], EvalErr> {
     match_args::<N>(a, args).ok_or_else(|| {
         EvalErr(
             args,
-            format!(
-                "{name} takes exactly {N} argument{}",
-                if N == 1 { "" } else { "s" }
-            ),
+            fill(TAKES_EXACTLY, &[&name, &N, &if N == 1 { "" } else { "s" }]),
         )
     })
 }
 
-pub fn match_args<const N: usize>(a: &Allocator, args: NodePtr) -> Option<[NodePtr; N]> {
+pub fn match_args<const N: usize>(a: &impl SExpStore, args: NodePtr) -> Option<[NodePtr; N]> {
     let mut next = args;
     let mut counter = 0;
     let mut ret = [NodePtr::NIL; N];
@@ -98,9 +99,9 @@ pub fn get_varargs<const N: usize>(
         if counter == N {
             return err(
                 args,
-                &format!(
-                    "{name} takes no more than {N} argument{}",
-                    if N == 1 { "" } else { "s" }
+                &fill(
+                    TAKES_NO_MORE_THAN,
+                    &[&name, &N, &if N == 1 { "" } else { "s" }],
                 ),
             );
         }
@@ -179,10 +180,10 @@ fn test_nilp() {
     assert!(nilp(&a, a5));
 }
 
-pub fn first(a: &Allocator, n: NodePtr) -> Result<NodePtr, EvalErr> {
+pub fn first(a: &impl SExpStore, n: NodePtr) -> Result<NodePtr, EvalErr> {
     match a.sexp(n) {
         SExp::Pair(first, _) => Ok(first),
-        _ => err(n, "first of non-cons"),
+        _ => err(n, FIRST_OF_NON_CONS),
     }
 }
 
@@ -202,7 +203,7 @@ fn test_first() {
 pub fn rest(a: &Allocator, n: NodePtr) -> Result<NodePtr, EvalErr> {
     match a.sexp(n) {
         SExp::Pair(_, rest) => Ok(rest),
-        _ => err(n, "rest of non-cons"),
+        _ => err(n, REST_OF_NON_CONS),
     }
 }
 
@@ -222,7 +223,7 @@ fn test_rest() {
 pub fn int_atom(a: &Allocator, args: NodePtr, op_name: &str) -> Result<(Number, usize), EvalErr> {
     match a.sexp(args) {
         SExp::Atom => Ok((a.number(args), a.atom_len(args))),
-        _ => err(args, &format!("{op_name} requires int args")),
+        _ => err(args, &fill(REQUIRES_INT_ARGS, &[&op_name])),
     }
 }
 
@@ -254,7 +255,7 @@ fn test_int_atom_failure() {
 pub fn atom_len(a: &Allocator, args: NodePtr, op_name: &str) -> Result<usize, EvalErr> {
     match a.sexp(args) {
         SExp::Atom => Ok(a.atom_len(args)),
-        _ => err(args, &format!("{op_name} requires an atom")),
+        _ => err(args, &fill(REQUIRES_ATOM, &[&op_name])),
     }
 }
 
@@ -275,7 +276,7 @@ fn test_atom_len() {
 }
 
 pub fn uint_atom<const SIZE: usize>(
-    a: &Allocator,
+    a: &impl SExpStore,
     args: NodePtr,
     op_name: &str,
 ) -> Result<u64, EvalErr> {
@@ -286,7 +287,7 @@ pub fn uint_atom<const SIZE: usize>(
             }
 
             if (bytes[0] & 0x80) != 0 {
-                return err(args, &format!("{op_name} requires positive int arg"));
+                return err(args, &fill(REQUIRES_POSITIVE_INT_ARG, &[&op_name]));
             }
 
             // strip leading zeros
@@ -296,7 +297,7 @@ pub fn uint_atom<const SIZE: usize>(
             }
 
             if buf.len() > SIZE {
-                return err(args, &format!("{op_name} requires u{} arg", SIZE * 8));
+                return err(args, &fill(REQUIRES_UINT_ARG, &[&op_name, &(SIZE * 8)]));
             }
 
             let mut ret = 0;
@@ -307,7 +308,7 @@ pub fn uint_atom<const SIZE: usize>(
             Ok(ret)
         }
         NodeVisitor::U32(val) => Ok(val as u64),
-        NodeVisitor::Pair(_, _) => err(args, &format!("{op_name} requires int arg")),
+        NodeVisitor::Pair(_, _) => err(args, &fill(REQUIRES_INT_ARG, &[&op_name])),
     }
 }
 
@@ -407,7 +408,7 @@ fn test_uint_atom_8_pair() {
 
 pub fn atom<'a>(a: &'a Allocator, n: NodePtr, op_name: &str) -> Result<Atom<'a>, EvalErr> {
     if n.is_pair() {
-        return err(n, &format!("{op_name} on list"));
+        return err(n, &fill(ON_LIST, &[&op_name]));
     }
     Ok(a.atom(n))
 }
@@ -534,13 +535,10 @@ pub fn i32_atom(a: &Allocator, args: NodePtr, op_name: &str) -> Result<i32, Eval
     match a.node(args) {
         NodeVisitor::Buffer(buf) => match i32_from_u8(buf) {
             Some(v) => Ok(v),
-            _ => err(
-                args,
-                &format!("{op_name} requires int32 args (with no leading zeros)"),
-            ),
+            _ => err(args, &fill(REQUIRES_CANONICAL_INT32_ARGS, &[&op_name])),
         },
         NodeVisitor::U32(val) => Ok(val as i32),
-        NodeVisitor::Pair(_, _) => err(args, &format!("{op_name} requires int32 args")),
+        NodeVisitor::Pair(_, _) => err(args, &fill(REQUIRES_INT32_ARGS, &[&op_name])),
     }
 }
 
@@ -571,11 +569,118 @@ fn test_i32_atom() {
     assert_eq!(r.1, "test requires int32 args (with no leading zeros)");
 }
 
-pub fn new_atom_and_cost(a: &mut Allocator, cost: Cost, buf: &[u8]) -> Response {
-    let c = buf.len() as Cost * MALLOC_COST_PER_BYTE;
+// The following helpers build the Response of an operator, and are meant to
+// be used by dialects outside this crate as well, to charge for memory the same
+// way the built-in operators do. The formula is:
+//
+//   cost + len * MALLOC_COST_PER_BYTE
+//
+// where cost is the operator's own cost and len is the length of the atom the
+// operator returns, in bytes. The allocation cost is only charged for atoms the
+// operator allocates (including small atoms, which don't use the heap but are
+// charged by their length all the same). Returning an argument, or one of the
+// preallocated atoms nil and 1, is free. Pairs aren't charged for
+
+// returns the cost of allocating an atom of len bytes
+pub fn malloc_cost_for(len: usize) -> Cost {
+    len as Cost * MALLOC_COST_PER_BYTE
+}
+
+// adds the cost of allocating the atom node points to
+pub fn malloc_cost(a: &Allocator, cost: Cost, node: NodePtr) -> Reduction {
+    Reduction(cost + malloc_cost_for(a.atom_len(node)), node)
+}
+
+// allocates a new atom
+pub fn atom_response(a: &mut Allocator, cost: Cost, buf: &[u8]) -> Response {
+    let c = malloc_cost_for(buf.len());
     Ok(Reduction(cost + c, a.new_atom(buf)?))
 }
 
+// the same as atom_response()
+pub fn new_atom_and_cost(a: &mut Allocator, cost: Cost, buf: &[u8]) -> Response {
+    atom_response(a, cost, buf)
+}
+
+// allocates a new atom holding the number
+pub fn number_response(a: &mut Allocator, cost: Cost, n: Number) -> Response {
+    let node = a.new_number(n)?;
+    Ok(malloc_cost(a, cost, node))
+}
+
+// returns an atom that was already allocated by the operator
+pub fn node_response(a: &Allocator, cost: Cost, node: NodePtr) -> Response {
+    Ok(malloc_cost(a, cost, node))
+}
+
+// 1 or nil. These are preallocated, so there's no allocation cost
+pub fn bool_response(a: &Allocator, cost: Cost, value: bool) -> Response {
+    Ok(Reduction(cost, if value { a.one() } else { a.nil() }))
+}
+
+pub fn nil_response(a: &Allocator, cost: Cost) -> Response {
+    Ok(Reduction(cost, a.nil()))
+}
+
+// Defines an operator that takes a fixed number of integer arguments and
+// returns an integer. Each argument is bound to its value (a Number) and its
+// length in bytes. The body evaluates to (cost, result), or returns an error.
+// The result is allocated and charged for with number_response(). For
+// example:
+//
+// int_op!(op_double, "double", |a, input, _max_cost, [(n, n_len)]| {
+//     (DOUBLE_BASE_COST + n_len as Cost * DOUBLE_COST_PER_BYTE, n * 2)
+// });
+macro_rules! int_op {
+    ($name:ident, $op:literal, |$a:ident, $input:ident, $max_cost:ident, [$(($arg:ident, $len:pat)),+]| $body:block) => {
+        pub fn $name($a: &mut Allocator, $input: NodePtr, $max_cost: Cost) -> Response {
+            let [$($arg),+] = get_args($a, $input, $op)?;
+            $(let ($arg, $len) = int_atom($a, $arg, $op)?;)+
+            let (cost, result): (Cost, Number) = $body;
+            number_response($a, cost, result)
+        }
+    };
+}
+pub(crate) use int_op;
+
+#[test]
+fn test_response_helpers() {
+    let mut a = Allocator::new();
+
+    let Reduction(cost, node) = atom_response(&mut a, 100, b"foobar").unwrap();
+    assert_eq!(cost, 100 + 6 * MALLOC_COST_PER_BYTE);
+    assert_eq!(a.atom(node).as_ref(), b"foobar");
+
+    let Reduction(cost, node) = number_response(&mut a, 100, 0x1337.into()).unwrap();
+    assert_eq!(cost, 100 + 2 * MALLOC_COST_PER_BYTE);
+    assert_eq!(a.number(node), 0x1337.into());
+
+    assert_eq!(
+        node_response(&a, 100, node).unwrap(),
+        Reduction(100 + 2 * MALLOC_COST_PER_BYTE, node)
+    );
+
+    assert_eq!(malloc_cost_for(0), 0);
+    assert_eq!(malloc_cost_for(48), 48 * MALLOC_COST_PER_BYTE);
+    assert_eq!(
+        malloc_cost(&a, 100, node),
+        Reduction(100 + 2 * MALLOC_COST_PER_BYTE, node)
+    );
+    let Reduction(cost, node) = new_atom_and_cost(&mut a, 100, b"foo").unwrap();
+    assert_eq!(cost, 100 + 3 * MALLOC_COST_PER_BYTE);
+    assert_eq!(a.atom(node).as_ref(), b"foo");
+
+    assert_eq!(
+        bool_response(&a, 100, true).unwrap(),
+        Reduction(100, a.one())
+    );
+    assert_eq!(
+        bool_response(&a, 100, false).unwrap(),
+        Reduction(100, a.nil())
+    );
+    assert_eq!(nil_response(&a, 100).unwrap(), Reduction(100, a.nil()));
+}
+
 pub fn mod_group_order(n: Number) -> Number {
     let order = GROUP_ORDER.clone();
     let mut remainder = n.mod_floor(&order);
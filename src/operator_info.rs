@@ -0,0 +1,702 @@
+use crate::chia_dialect::{
+    ENABLE_BIT_COUNT_OPS, ENABLE_BLS_OPS_OUTSIDE_GUARD, ENABLE_BLS_VALID_OPS, ENABLE_DIVEXACT_GCD,
+    ENABLE_ED25519_VERIFY, ENABLE_FIXED_POINT_OPS, ENABLE_TREE_SIZE_OPS,
+};
+
+use crate::allocator::{Allocator, NodePtr};
+use crate::cost::Cost;
+use crate::cost_schedule_v1::{
+    ARITH_BASE_COST, ARITH_COST_PER_ARG, ARITH_COST_PER_BYTE, ASHIFT_BASE_COST,
+    ASHIFT_COST_PER_BYTE, BITLENGTH_BASE_COST, BITLENGTH_COST_PER_BYTE, BLS_G1_MULTIPLY_BASE_COST,
+    BLS_G1_MULTIPLY_COST_PER_BYTE, BLS_G1_NEGATE_BASE_COST, BLS_G1_SUBTRACT_BASE_COST,
+    BLS_G1_SUBTRACT_COST_PER_ARG, BLS_G1_VALID_BASE_COST, BLS_G2_ADD_BASE_COST,
+    BLS_G2_ADD_COST_PER_ARG, BLS_G2_MULTIPLY_BASE_COST, BLS_G2_MULTIPLY_COST_PER_BYTE,
+    BLS_G2_NEGATE_BASE_COST, BLS_G2_SUBTRACT_BASE_COST, BLS_G2_SUBTRACT_COST_PER_ARG,
+    BLS_G2_VALID_BASE_COST, BLS_MAP_TO_G1_BASE_COST, BLS_MAP_TO_G1_COST_PER_BYTE,
+    BLS_MAP_TO_G2_BASE_COST, BLS_MAP_TO_G2_COST_PER_BYTE, BLS_PAIRING_BASE_COST,
+    BLS_PAIRING_COST_PER_ARG, BOOL_BASE_COST, BOOL_COST_PER_ARG, COINID_COST, CONCAT_BASE_COST,
+    CONCAT_COST_PER_ARG, CONCAT_COST_PER_BYTE, CONS_COST, DIVEXACT_BASE_COST,
+    DIVEXACT_COST_PER_BYTE, DIVMOD_BASE_COST, DIVMOD_COST_PER_BYTE, DIV_BASE_COST,
+    DIV_COST_PER_BYTE, ED25519_VERIFY_BASE_COST, ED25519_VERIFY_COST_PER_BYTE, EQ_BASE_COST,
+    EQ_COST_PER_BYTE, FIRST_COST, GCD_BASE_COST, GCD_COST_PER_BYTE, GRS_BASE_COST,
+    GRS_COST_PER_BYTE, GR_BASE_COST, GR_COST_PER_BYTE, IF_COST, LISTP_COST, LOGNOT_BASE_COST,
+    LOGNOT_COST_PER_BYTE, LOG_BASE_COST, LOG_COST_PER_ARG, LOG_COST_PER_BYTE, LSHIFT_BASE_COST,
+    LSHIFT_COST_PER_BYTE, MODPOW_BASE_COST, MODPOW_COST_PER_BYTE_BASE_VALUE, MUL_BASE_COST,
+    MUL_COST_PER_OP, MUL_DIV_FLOOR_BASE_COST, MUL_DIV_FLOOR_COST_PER_BYTE,
+    MUL_LINEAR_COST_PER_BYTE, POINT_ADD_BASE_COST, POINT_ADD_COST_PER_ARG, POPCOUNT_BASE_COST,
+    POPCOUNT_COST_PER_BYTE, PUBKEY_BASE_COST, PUBKEY_COST_PER_BYTE, REST_COST,
+    SECP256K1_VERIFY_COST, SECP256R1_VERIFY_COST, SHA256_BASE_COST, SHA256_COST_PER_ARG,
+    SHA256_COST_PER_BYTE, SQRT_FLOOR_BASE_COST, SQRT_FLOOR_COST_PER_BYTE, STRLEN_BASE_COST,
+    STRLEN_COST_PER_BYTE, TREE_WALK_BASE_COST,
+};
+use crate::dialect::OperatorSet;
+use crate::err_utils::err;
+use crate::error_catalog::{fill, TAKES};
+use crate::reduction::EvalErr;
+
+// A description of an operator in ChiaDialect, meant for generating
+// documentation and for tools (like IDE plugins) that need to know which
+// operators exist. The costs are taken from the same constants the operators
+// use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OperatorInfo {
+    pub opcode: &'static [u8],
+    pub name: &'static str,
+
+    // the number of arguments the operator accepts. max_args is None for
+    // operators taking any number of arguments
+    pub min_args: usize,
+    pub max_args: Option<usize>,
+
+    // the linear terms of the operator's cost. Some operators have additional
+    // non-linear terms (*, modpow, divexact, gcd, mul_div_floor, sqrt_floor)
+    // or charge per node (tree_size, tree_depth) or charge per byte of
+    // specific arguments only. Operators allocating a new atom also charge
+    // MALLOC_COST_PER_BYTE for the size of the result. For the pairing
+    // operators, the cost per argument is charged per pair of arguments.
+    pub base_cost: Cost,
+    pub cost_per_arg: Cost,
+    pub cost_per_byte: Cost,
+
+    // the ChiaDialect flags that must be set for the operator to be available
+    pub flags: u32,
+
+    // true if the operator is only available in the BLS softfork extension,
+    // or outside of the guard with ENABLE_BLS_OPS_OUTSIDE_GUARD
+    pub bls_extension: bool,
+}
+
+const fn op(
+    opcode: &'static [u8],
+    name: &'static str,
+    args: (usize, Option<usize>),
+    cost: (Cost, Cost, Cost),
+) -> OperatorInfo {
+    OperatorInfo {
+        opcode,
+        name,
+        min_args: args.0,
+        max_args: args.1,
+        base_cost: cost.0,
+        cost_per_arg: cost.1,
+        cost_per_byte: cost.2,
+        flags: 0,
+        bls_extension: false,
+    }
+}
+
+const fn bls(info: OperatorInfo) -> OperatorInfo {
+    OperatorInfo {
+        bls_extension: true,
+        ..info
+    }
+}
+
+const fn flag(flags: u32, info: OperatorInfo) -> OperatorInfo {
+    OperatorInfo { flags, ..info }
+}
+
+const fn exactly(n: usize) -> (usize, Option<usize>) {
+    (n, Some(n))
+}
+
+const ANY: (usize, Option<usize>) = (0, None);
+
+pub const CHIA_OPERATORS: [OperatorInfo; 58] = [
+    op(&[3], "i", exactly(3), (IF_COST, 0, 0)),
+    op(&[4], "c", exactly(2), (CONS_COST, 0, 0)),
+    op(&[5], "f", exactly(1), (FIRST_COST, 0, 0)),
+    op(&[6], "r", exactly(1), (REST_COST, 0, 0)),
+    op(&[7], "l", exactly(1), (LISTP_COST, 0, 0)),
+    op(&[8], "x", ANY, (0, 0, 0)),
+    op(&[9], "=", exactly(2), (EQ_BASE_COST, 0, EQ_COST_PER_BYTE)),
+    op(
+        &[10],
+        ">s",
+        exactly(2),
+        (GRS_BASE_COST, 0, GRS_COST_PER_BYTE),
+    ),
+    op(
+        &[11],
+        "sha256",
+        ANY,
+        (SHA256_BASE_COST, SHA256_COST_PER_ARG, SHA256_COST_PER_BYTE),
+    ),
+    op(&[12], "substr", (2, Some(3)), (1, 0, 0)),
+    op(
+        &[13],
+        "strlen",
+        exactly(1),
+        (STRLEN_BASE_COST, 0, STRLEN_COST_PER_BYTE),
+    ),
+    op(
+        &[14],
+        "concat",
+        ANY,
+        (CONCAT_BASE_COST, CONCAT_COST_PER_ARG, CONCAT_COST_PER_BYTE),
+    ),
+    op(
+        &[16],
+        "+",
+        ANY,
+        (ARITH_BASE_COST, ARITH_COST_PER_ARG, ARITH_COST_PER_BYTE),
+    ),
+    op(
+        &[17],
+        "-",
+        ANY,
+        (ARITH_BASE_COST, ARITH_COST_PER_ARG, ARITH_COST_PER_BYTE),
+    ),
+    op(
+        &[18],
+        "*",
+        ANY,
+        (MUL_BASE_COST, MUL_COST_PER_OP, MUL_LINEAR_COST_PER_BYTE),
+    ),
+    op(
+        &[19],
+        "/",
+        exactly(2),
+        (DIV_BASE_COST, 0, DIV_COST_PER_BYTE),
+    ),
+    op(
+        &[20],
+        "divmod",
+        exactly(2),
+        (DIVMOD_BASE_COST, 0, DIVMOD_COST_PER_BYTE),
+    ),
+    op(&[21], ">", exactly(2), (GR_BASE_COST, 0, GR_COST_PER_BYTE)),
+    op(
+        &[22],
+        "ash",
+        exactly(2),
+        (ASHIFT_BASE_COST, 0, ASHIFT_COST_PER_BYTE),
+    ),
+    op(
+        &[23],
+        "lsh",
+        exactly(2),
+        (LSHIFT_BASE_COST, 0, LSHIFT_COST_PER_BYTE),
+    ),
+    op(
+        &[24],
+        "logand",
+        ANY,
+        (LOG_BASE_COST, LOG_COST_PER_ARG, LOG_COST_PER_BYTE),
+    ),
+    op(
+        &[25],
+        "logior",
+        ANY,
+        (LOG_BASE_COST, LOG_COST_PER_ARG, LOG_COST_PER_BYTE),
+    ),
+    op(
+        &[26],
+        "logxor",
+        ANY,
+        (LOG_BASE_COST, LOG_COST_PER_ARG, LOG_COST_PER_BYTE),
+    ),
+    op(
+        &[27],
+        "lognot",
+        exactly(1),
+        (LOGNOT_BASE_COST, 0, LOGNOT_COST_PER_BYTE),
+    ),
+    op(
+        &[29],
+        "point_add",
+        ANY,
+        (POINT_ADD_BASE_COST, POINT_ADD_COST_PER_ARG, 0),
+    ),
+    op(
+        &[30],
+        "pubkey_for_exp",
+        exactly(1),
+        (PUBKEY_BASE_COST, 0, PUBKEY_COST_PER_BYTE),
+    ),
+    op(&[32], "not", exactly(1), (BOOL_BASE_COST, 0, 0)),
+    op(&[33], "any", ANY, (BOOL_BASE_COST, BOOL_COST_PER_ARG, 0)),
+    op(&[34], "all", ANY, (BOOL_BASE_COST, BOOL_COST_PER_ARG, 0)),
+    flag(
+        ENABLE_DIVEXACT_GCD,
+        op(
+            &[37],
+            "divexact",
+            exactly(2),
+            (DIVEXACT_BASE_COST, 0, DIVEXACT_COST_PER_BYTE),
+        ),
+    ),
+    flag(
+        ENABLE_DIVEXACT_GCD,
+        op(
+            &[38],
+            "gcd",
+            exactly(2),
+            (GCD_BASE_COST, 0, GCD_COST_PER_BYTE),
+        ),
+    ),
+    flag(
+        ENABLE_FIXED_POINT_OPS,
+        op(
+            &[39],
+            "mul_div_floor",
+            exactly(3),
+            (MUL_DIV_FLOOR_BASE_COST, 0, MUL_DIV_FLOOR_COST_PER_BYTE),
+        ),
+    ),
+    flag(
+        ENABLE_FIXED_POINT_OPS,
+        op(
+            &[40],
+            "sqrt_floor",
+            exactly(1),
+            (SQRT_FLOOR_BASE_COST, 0, SQRT_FLOOR_COST_PER_BYTE),
+        ),
+    ),
+    flag(
+        ENABLE_BIT_COUNT_OPS,
+        op(
+            &[41],
+            "popcount",
+            exactly(1),
+            (POPCOUNT_BASE_COST, 0, POPCOUNT_COST_PER_BYTE),
+        ),
+    ),
+    flag(
+        ENABLE_BIT_COUNT_OPS,
+        op(
+            &[42],
+            "bitlength",
+            exactly(1),
+            (BITLENGTH_BASE_COST, 0, BITLENGTH_COST_PER_BYTE),
+        ),
+    ),
+    flag(
+        ENABLE_TREE_SIZE_OPS,
+        op(&[43], "tree_size", exactly(1), (TREE_WALK_BASE_COST, 0, 0)),
+    ),
+    flag(
+        ENABLE_TREE_SIZE_OPS,
+        op(&[44], "tree_depth", exactly(1), (TREE_WALK_BASE_COST, 0, 0)),
+    ),
+    bls(op(&[48], "coinid", exactly(3), (COINID_COST, 0, 0))),
+    bls(op(
+        &[49],
+        "g1_subtract",
+        ANY,
+        (BLS_G1_SUBTRACT_BASE_COST, BLS_G1_SUBTRACT_COST_PER_ARG, 0),
+    )),
+    bls(op(
+        &[50],
+        "g1_multiply",
+        exactly(2),
+        (BLS_G1_MULTIPLY_BASE_COST, 0, BLS_G1_MULTIPLY_COST_PER_BYTE),
+    )),
+    bls(op(
+        &[51],
+        "g1_negate",
+        exactly(1),
+        (BLS_G1_NEGATE_BASE_COST, 0, 0),
+    )),
+    bls(op(
+        &[52],
+        "g2_add",
+        ANY,
+        (BLS_G2_ADD_BASE_COST, BLS_G2_ADD_COST_PER_ARG, 0),
+    )),
+    bls(op(
+        &[53],
+        "g2_subtract",
+        ANY,
+        (BLS_G2_SUBTRACT_BASE_COST, BLS_G2_SUBTRACT_COST_PER_ARG, 0),
+    )),
+    bls(op(
+        &[54],
+        "g2_multiply",
+        exactly(2),
+        (BLS_G2_MULTIPLY_BASE_COST, 0, BLS_G2_MULTIPLY_COST_PER_BYTE),
+    )),
+    bls(op(
+        &[55],
+        "g2_negate",
+        exactly(1),
+        (BLS_G2_NEGATE_BASE_COST, 0, 0),
+    )),
+    bls(op(
+        &[56],
+        "g1_map",
+        (1, Some(2)),
+        (BLS_MAP_TO_G1_BASE_COST, 0, BLS_MAP_TO_G1_COST_PER_BYTE),
+    )),
+    bls(op(
+        &[57],
+        "g2_map",
+        (1, Some(2)),
+        (BLS_MAP_TO_G2_BASE_COST, 0, BLS_MAP_TO_G2_COST_PER_BYTE),
+    )),
+    bls(op(
+        &[58],
+        "bls_pairing_identity",
+        ANY,
+        (BLS_PAIRING_BASE_COST, BLS_PAIRING_COST_PER_ARG, 0),
+    )),
+    bls(op(
+        &[59],
+        "bls_verify",
+        (1, None),
+        (
+            BLS_PAIRING_BASE_COST,
+            BLS_PAIRING_COST_PER_ARG,
+            BLS_MAP_TO_G2_COST_PER_BYTE,
+        ),
+    )),
+    bls(op(
+        &[60],
+        "modpow",
+        exactly(3),
+        (MODPOW_BASE_COST, 0, MODPOW_COST_PER_BYTE_BASE_VALUE),
+    )),
+    bls(op(
+        &[61],
+        "%",
+        exactly(2),
+        (DIV_BASE_COST, 0, DIV_COST_PER_BYTE),
+    )),
+    flag(
+        ENABLE_BLS_VALID_OPS,
+        bls(op(
+            &[62],
+            "g1_valid?",
+            exactly(1),
+            (BLS_G1_VALID_BASE_COST, 0, 0),
+        )),
+    ),
+    flag(
+        ENABLE_BLS_VALID_OPS,
+        bls(op(
+            &[63],
+            "g2_valid?",
+            exactly(1),
+            (BLS_G2_VALID_BASE_COST, 0, 0),
+        )),
+    ),
+    op(
+        &[0x13, 0xd6, 0x1f, 0x00],
+        "secp256k1_verify",
+        exactly(3),
+        (SECP256K1_VERIFY_COST, 0, 0),
+    ),
+    op(
+        &[0x1c, 0x3a, 0x8f, 0x00],
+        "secp256r1_verify",
+        exactly(3),
+        (SECP256R1_VERIFY_COST, 0, 0),
+    ),
+    flag(
+        ENABLE_ED25519_VERIFY,
+        op(
+            &[0x06, 0xdd, 0xcf, 0x00],
+            "ed25519_verify",
+            exactly(3),
+            (ED25519_VERIFY_BASE_COST, 0, ED25519_VERIFY_COST_PER_BYTE),
+        ),
+    ),
+    // these are handled by the interpreter rather than the dialect, but are
+    // included for completeness
+    op(&[1], "q", ANY, (0, 0, 0)),
+    op(&[2], "a", exactly(2), (0, 0, 0)),
+];
+
+impl OperatorInfo {
+    // returns true if the operator is available when running with the
+    // specified ChiaDialect flags, in the specified operator set (i.e. inside
+    // or outside the softfork guard)
+    pub fn is_available(&self, flags: u32, extension: OperatorSet) -> bool {
+        if (flags & self.flags) != self.flags {
+            return false;
+        }
+        !self.bls_extension
+            || extension == OperatorSet::BLS
+            || (flags & ENABLE_BLS_OPS_OUTSIDE_GUARD) != 0
+    }
+}
+
+impl OperatorInfo {
+    // a description of the number of arguments the operator takes, e.g.
+    // "exactly 2 arguments". The phrasing matches the errors of get_args()
+    pub fn arity(&self) -> String {
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        match (self.min_args, self.max_args) {
+            (min, Some(max)) if min == max => format!("exactly {min} argument{}", plural(min)),
+            (min, Some(max)) if min + 1 == max => format!("exactly {min} or {max} arguments"),
+            (min, Some(max)) => format!("{min} to {max} arguments"),
+            (min, None) => format!("at least {min} argument{}", plural(min)),
+        }
+    }
+
+    // fails if args has fewer than min_args or more than max_args elements,
+    // with the same error regardless of which operator it is. Like get_args(),
+    // the atom terminating the list is ignored
+    pub fn check_arity(&self, a: &Allocator, args: NodePtr) -> Result<(), EvalErr> {
+        let mut count = 0;
+        let mut next = args;
+        while let Some((_, rest)) = a.next(next) {
+            count += 1;
+            next = rest;
+            if self.max_args.is_some_and(|max| count > max) {
+                break;
+            }
+        }
+        if count < self.min_args || self.max_args.is_some_and(|max| count > max) {
+            err(args, &fill(TAKES, &[&self.name, &self.arity()]))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// returns the operator with the specified opcode, regardless of whether it's
+// available with any particular flags
+pub fn operator_by_opcode(opcode: &[u8]) -> Option<&'static OperatorInfo> {
+    CHIA_OPERATORS.iter().find(|op| op.opcode == opcode)
+}
+
+// returns all operators available in ChiaDialect with the specified flags and
+// operator set
+pub fn chia_operators(
+    flags: u32,
+    extension: OperatorSet,
+) -> impl Iterator<Item = &'static OperatorInfo> {
+    CHIA_OPERATORS
+        .iter()
+        .filter(move |op| op.is_available(flags, extension))
+}
+
+// An alternative name for an operator in CHIA_OPERATORS (e.g. g1_add, the name
+// point_add has in the BLS extension), for tools that compile or disassemble
+// programs. Aliases only affect name lookups. ChiaDialect dispatches on the
+// opcode, which is the target's, so an alias has the target's implementation
+// and cost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OperatorAlias {
+    pub name: &'static str,
+
+    // the opcode of the operator this is an alias of
+    pub target: &'static [u8],
+}
+
+const fn alias(name: &'static str, target: &'static [u8]) -> OperatorAlias {
+    OperatorAlias { name, target }
+}
+
+pub const CHIA_OPERATOR_ALIASES: [OperatorAlias; 1] = [alias("g1_add", &[29])];
+
+impl OperatorAlias {
+    // returns the operator this is an alias of
+    pub fn target(&self) -> &'static OperatorInfo {
+        CHIA_OPERATORS
+            .iter()
+            .find(|op| op.opcode == self.target)
+            .expect("alias of unknown operator")
+    }
+
+    // returns true if the alias is available when running with the specified
+    // ChiaDialect flags, in the specified operator set. This is when its
+    // target is
+    pub fn is_available(&self, flags: u32, extension: OperatorSet) -> bool {
+        self.target().is_available(flags, extension)
+    }
+}
+
+// returns the aliases of op available in ChiaDialect with the specified flags
+// and operator set
+pub fn aliases_of(
+    op: &OperatorInfo,
+    flags: u32,
+    extension: OperatorSet,
+) -> impl Iterator<Item = &'static OperatorAlias> + '_ {
+    CHIA_OPERATOR_ALIASES
+        .iter()
+        .filter(move |alias| alias.target == op.opcode && alias.is_available(flags, extension))
+}
+
+// looks up an operator by its name or the name of one of its aliases.
+// Returns the opcode to use along with the operator it resolves to
+pub fn operator_by_name(
+    name: &str,
+    flags: u32,
+    extension: OperatorSet,
+) -> Option<(&'static [u8], &'static OperatorInfo)> {
+    if let Some(op) = chia_operators(flags, extension).find(|op| op.name == name) {
+        return Some((op.opcode, op));
+    }
+    CHIA_OPERATOR_ALIASES
+        .iter()
+        .find(|alias| alias.name == name && alias.is_available(flags, extension))
+        .map(|alias| (alias.target, alias.target()))
+}
+
+#[cfg(test)]
+use crate::chia_dialect::{ChiaDialect, NO_UNKNOWN_OPS};
+#[cfg(test)]
+use crate::dialect::Dialect;
+
+// ensures the table is in sync with the dispatch in ChiaDialect, by checking
+// which opcodes the dialect considers unimplemented
+#[cfg(test)]
+fn is_implemented(opcode: &[u8], flags: u32, extension: OperatorSet) -> bool {
+    let mut a = Allocator::new();
+    let op = a.new_atom(opcode).unwrap();
+    let args = a.nil();
+    let dialect = ChiaDialect::new(flags | NO_UNKNOWN_OPS);
+    match dialect.op(&mut a, op, args, 11000000000, extension) {
+        Ok(_) => true,
+        Err(e) => e.1 != "unimplemented operator",
+    }
+}
+
+#[test]
+fn test_table_matches_dialect() {
+    let all_flags = ENABLE_BLS_VALID_OPS
+        | ENABLE_ED25519_VERIFY
+        | ENABLE_DIVEXACT_GCD
+        | ENABLE_FIXED_POINT_OPS
+        | ENABLE_BIT_COUNT_OPS
+        | ENABLE_TREE_SIZE_OPS;
+    for flags in [0, ENABLE_BLS_OPS_OUTSIDE_GUARD, all_flags] {
+        for extension in [OperatorSet::Default, OperatorSet::BLS] {
+            // all 1 byte opcodes, except quote, apply and softfork, which are
+            // handled by the interpreter
+            for opcode in 0..=255_u8 {
+                if [1, 2, 36].contains(&opcode) {
+                    continue;
+                }
+                let info = CHIA_OPERATORS.iter().find(|op| op.opcode == [opcode]);
+                assert_eq!(
+                    info.is_some_and(|op| op.is_available(flags, extension)),
+                    is_implemented(&[opcode], flags, extension),
+                    "opcode {opcode} flags {flags:x}"
+                );
+            }
+            for info in CHIA_OPERATORS.iter().filter(|op| op.opcode.len() == 4) {
+                assert_eq!(
+                    info.is_available(flags, extension),
+                    is_implemented(info.opcode, flags, extension),
+                    "operator {} flags {flags:x}",
+                    info.name
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_chia_operators() {
+    let names = |flags, extension| -> Vec<&str> {
+        chia_operators(flags, extension).map(|op| op.name).collect()
+    };
+    let default = names(0, OperatorSet::Default);
+    assert!(default.contains(&"sha256"));
+    assert!(default.contains(&"secp256k1_verify"));
+    assert!(!default.contains(&"coinid"));
+    assert!(!default.contains(&"g1_valid?"));
+    assert!(!default.contains(&"ed25519_verify"));
+    assert!(!default.contains(&"gcd"));
+    assert!(!default.contains(&"sqrt_floor"));
+    assert!(!default.contains(&"popcount"));
+    assert!(!default.contains(&"tree_size"));
+
+    let bls = names(0, OperatorSet::BLS);
+    assert!(bls.contains(&"coinid"));
+    assert!(bls.contains(&"bls_verify"));
+    assert!(!bls.contains(&"g1_valid?"));
+    assert_eq!(
+        bls,
+        names(ENABLE_BLS_OPS_OUTSIDE_GUARD, OperatorSet::Default)
+    );
+
+    let all = names(
+        ENABLE_BLS_VALID_OPS
+            | ENABLE_ED25519_VERIFY
+            | ENABLE_DIVEXACT_GCD
+            | ENABLE_FIXED_POINT_OPS
+            | ENABLE_BIT_COUNT_OPS
+            | ENABLE_TREE_SIZE_OPS,
+        OperatorSet::BLS,
+    );
+    assert_eq!(all.len(), CHIA_OPERATORS.len());
+
+    // opcodes and names are unique
+    for (i, lhs) in CHIA_OPERATORS.iter().enumerate() {
+        for rhs in &CHIA_OPERATORS[i + 1..] {
+            assert_ne!(lhs.opcode, rhs.opcode);
+            assert_ne!(lhs.name, rhs.name);
+        }
+    }
+}
+
+#[test]
+fn test_aliases() {
+    // every alias refers to an operator, and doesn't shadow one
+    for alias in &CHIA_OPERATOR_ALIASES {
+        assert_eq!(alias.target().opcode, alias.target);
+        assert!(CHIA_OPERATORS.iter().all(|op| op.name != alias.name));
+    }
+
+    let (opcode, op) = operator_by_name("g1_add", 0, OperatorSet::Default).unwrap();
+    assert_eq!(opcode, &[29]);
+    assert_eq!(op.name, "point_add");
+    let (opcode, op) = operator_by_name("point_add", 0, OperatorSet::Default).unwrap();
+    assert_eq!(opcode, &[29]);
+    assert_eq!(op.name, "point_add");
+    assert!(operator_by_name("coinid", 0, OperatorSet::Default).is_none());
+    assert!(operator_by_name("coinid", 0, OperatorSet::BLS).is_some());
+    assert!(operator_by_name("foobar", 0, OperatorSet::BLS).is_none());
+
+    let point_add = operator_by_name("point_add", 0, OperatorSet::Default)
+        .unwrap()
+        .1;
+    let names: Vec<&str> = aliases_of(point_add, 0, OperatorSet::Default)
+        .map(|alias| alias.name)
+        .collect();
+    assert_eq!(names, ["g1_add"]);
+}
+
+#[test]
+fn test_check_arity() {
+    let mut a = Allocator::new();
+    let one = a.one();
+    let mut lists = vec![a.nil()];
+    for _ in 0..4 {
+        let list = a.new_pair(one, *lists.last().unwrap()).unwrap();
+        lists.push(list);
+    }
+    let check = |name: &str, argc: usize| {
+        let op = CHIA_OPERATORS.iter().find(|op| op.name == name).unwrap();
+        op.check_arity(&a, lists[argc]).map_err(|e| e.1)
+    };
+    assert_eq!(check("f", 1), Ok(()));
+    assert_eq!(check("f", 0).unwrap_err(), "f takes exactly 1 argument");
+    assert_eq!(check("f", 2).unwrap_err(), "f takes exactly 1 argument");
+    assert_eq!(check("i", 4).unwrap_err(), "i takes exactly 3 arguments");
+    assert_eq!(check("substr", 2), Ok(()));
+    assert_eq!(check("substr", 3), Ok(()));
+    assert_eq!(
+        check("substr", 4).unwrap_err(),
+        "substr takes exactly 2 or 3 arguments"
+    );
+    assert_eq!(check("bls_verify", 1), Ok(()));
+    assert_eq!(check("bls_verify", 4), Ok(()));
+    assert_eq!(
+        check("bls_verify", 0).unwrap_err(),
+        "bls_verify takes at least 1 argument"
+    );
+    assert_eq!(check("concat", 0), Ok(()));
+    assert_eq!(check("concat", 4), Ok(()));
+
+    // the terminator of the list is ignored
+    let improper = a.new_pair(one, one).unwrap();
+    let op = operator_by_opcode(&[5]).unwrap();
+    assert!(op.check_arity(&a, improper).is_ok());
+    assert!(operator_by_opcode(&[15]).is_none());
+}
@@ -0,0 +1,240 @@
+use crate::allocator::{Allocator, NodePtr};
+use crate::cost::Cost;
+use crate::dialect::{Dialect, OperatorSet};
+use crate::reduction::Response;
+use std::collections::HashMap;
+
+// An override handler is called instead of the operator it's registered for.
+// It's passed the argument list, the max cost and a Next object, which can be
+// used to forward the call to the next handler in the chain (or the operator's
+// original implementation, once all handlers have been called).
+pub type OverrideHandler<'a> = Box<dyn Fn(&mut Allocator, NodePtr, Cost, &Next) -> Response + 'a>;
+
+// represents the rest of the handler chain for an operator invocation
+pub struct Next<'a, 'b> {
+    dialect: &'b dyn Dialect,
+    handlers: &'b [OverrideHandler<'a>],
+    op: NodePtr,
+    extension: OperatorSet,
+}
+
+impl Next<'_, '_> {
+    pub fn call(&self, allocator: &mut Allocator, args: NodePtr, max_cost: Cost) -> Response {
+        match self.handlers.split_last() {
+            Some((handler, rest)) => {
+                let next = Next {
+                    dialect: self.dialect,
+                    handlers: rest,
+                    op: self.op,
+                    extension: self.extension,
+                };
+                handler(allocator, args, max_cost, &next)
+            }
+            None => self
+                .dialect
+                .op(allocator, self.op, args, max_cost, self.extension),
+        }
+    }
+
+    // the opcode being invoked
+    pub fn op(&self) -> NodePtr {
+        self.op
+    }
+}
+
+// wraps a dialect and replaces (or intercepts) selected operators with
+// handlers, for the purpose of testing programs. e.g. stubbing out bls_verify
+// to always succeed, or recording the inputs to sha256. Opcodes without
+// handlers behave exactly like in the wrapped dialect.
+pub struct OverrideDialect<'a, D: Dialect> {
+    dialect: D,
+    overrides: HashMap<Vec<u8>, Vec<OverrideHandler<'a>>>,
+}
+
+impl<'a, D: Dialect> OverrideDialect<'a, D> {
+    pub fn new(dialect: D) -> Self {
+        Self {
+            dialect,
+            overrides: HashMap::new(),
+        }
+    }
+
+    // registers a handler for the specified opcode. If there already are
+    // handlers for the opcode, the new handler is called first, and the
+    // previously registered handler is the next one in its chain.
+    pub fn override_op(&mut self, opcode: &[u8], handler: OverrideHandler<'a>) {
+        self.overrides
+            .entry(opcode.to_vec())
+            .or_default()
+            .push(handler);
+    }
+
+    pub fn inner(&self) -> &D {
+        &self.dialect
+    }
+}
+
+impl<D: Dialect> Dialect for OverrideDialect<'_, D> {
+    fn op(
+        &self,
+        allocator: &mut Allocator,
+        o: NodePtr,
+        argument_list: NodePtr,
+        max_cost: Cost,
+        extension: OperatorSet,
+    ) -> Response {
+        let Some(handlers) = self.overrides.get(allocator.atom(o).as_ref()) else {
+            return self
+                .dialect
+                .op(allocator, o, argument_list, max_cost, extension);
+        };
+        let next = Next {
+            dialect: &self.dialect,
+            handlers,
+            op: o,
+            extension,
+        };
+        next.call(allocator, argument_list, max_cost)
+    }
+
+    fn quote_kw(&self) -> u32 {
+        self.dialect.quote_kw()
+    }
+    fn apply_kw(&self) -> u32 {
+        self.dialect.apply_kw()
+    }
+    fn softfork_kw(&self) -> u32 {
+        self.dialect.softfork_kw()
+    }
+
+    fn softfork_extension(&self, ext: u32) -> OperatorSet {
+        self.dialect.softfork_extension(ext)
+    }
+
+    fn allow_unknown_ops(&self) -> bool {
+        self.dialect.allow_unknown_ops()
+    }
+    fn cost_schedule_version(&self) -> u32 {
+        self.dialect.cost_schedule_version()
+    }
+}
+
+#[cfg(test)]
+use crate::chia_dialect::{ChiaDialect, ENABLE_BLS_OPS_OUTSIDE_GUARD};
+#[cfg(test)]
+use crate::cost_schedule_v1::COST_SCHEDULE_VERSION;
+#[cfg(test)]
+use crate::reduction::Reduction;
+#[cfg(test)]
+use crate::run_program::run_program;
+#[cfg(test)]
+use crate::test_ops::parse_exp;
+#[cfg(test)]
+use std::cell::RefCell;
+
+#[cfg(test)]
+fn run(dialect: &impl Dialect, prg: &str) -> Result<(Cost, Vec<u8>), String> {
+    let mut a = Allocator::new();
+    let (prg, rest) = parse_exp(&mut a, prg);
+    assert_eq!(rest, "");
+    let env = a.nil();
+    match run_program(&mut a, dialect, prg, env, 11000000000) {
+        Ok(Reduction(cost, node)) => Ok((cost, a.atom(node).as_ref().to_vec())),
+        Err(e) => Err(e.1),
+    }
+}
+
+#[test]
+fn test_cost_schedule_version() {
+    let dialect = ChiaDialect::new(0);
+    assert_eq!(dialect.cost_schedule_version(), COST_SCHEDULE_VERSION);
+    let dialect = OverrideDialect::new(dialect);
+    assert_eq!(dialect.cost_schedule_version(), 1);
+}
+
+#[test]
+fn test_stub_operator() {
+    // bls_verify with an invalid signature
+    let prg = "(bls_verify (q . 0xc00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000) (q . 0x97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb) (q . 0x1337))";
+
+    let dialect = ChiaDialect::new(ENABLE_BLS_OPS_OUTSIDE_GUARD);
+    assert_eq!(run(&dialect, prg).unwrap_err(), "bls_verify failed");
+
+    let mut dialect = OverrideDialect::new(ChiaDialect::new(ENABLE_BLS_OPS_OUTSIDE_GUARD));
+    dialect.override_op(
+        &[59],
+        Box::new(|a, _args, _max_cost, _next| Ok(Reduction(1000, a.nil()))),
+    );
+    // 3 quotes, the operator and the stub's cost
+    assert_eq!(run(&dialect, prg).unwrap(), (1061, vec![]));
+}
+
+#[test]
+fn test_intercept_operator() {
+    let inputs = RefCell::new(Vec::<Vec<u8>>::new());
+
+    let mut dialect = OverrideDialect::new(ChiaDialect::new(0));
+    dialect.override_op(
+        &[11],
+        Box::new(|a, args, max_cost, next| {
+            let mut input = Vec::new();
+            let mut iter = args;
+            while let Some((arg, rest)) = a.next(iter) {
+                input.extend_from_slice(a.atom(arg).as_ref());
+                iter = rest;
+            }
+            inputs.borrow_mut().push(input);
+            next.call(a, args, max_cost)
+        }),
+    );
+
+    let prg = "(sha256 (sha256 (q . 0x1337)) (q . 0x42))";
+    let expected = run(&ChiaDialect::new(0), prg).unwrap();
+    assert_eq!(run(&dialect, prg).unwrap(), expected);
+
+    // the inner sha256 call is evaluated first
+    let mut inner = run(&ChiaDialect::new(0), "(sha256 (q . 0x1337))")
+        .unwrap()
+        .1;
+    inner.push(0x42);
+    drop(dialect);
+    assert_eq!(inputs.into_inner(), vec![vec![0x13, 0x37], inner]);
+}
+
+#[test]
+fn test_handler_chain() {
+    let calls = RefCell::new(Vec::<&str>::new());
+
+    let mut dialect = OverrideDialect::new(ChiaDialect::new(0));
+    dialect.override_op(
+        &[16],
+        Box::new(|a, args, max_cost, next| {
+            calls.borrow_mut().push("first");
+            next.call(a, args, max_cost)
+        }),
+    );
+    dialect.override_op(
+        &[16],
+        Box::new(|a, args, max_cost, next| {
+            calls.borrow_mut().push("second");
+            assert_eq!(a.atom(next.op()).as_ref(), &[16]);
+            let Reduction(cost, node) = next.call(a, args, max_cost)?;
+            Ok(Reduction(cost + 1, node))
+        }),
+    );
+
+    // other operators are not affected
+    assert_eq!(
+        run(&dialect, "(- (q . 5) (q . 3))").unwrap(),
+        (796, vec![2])
+    );
+    assert!(calls.borrow().is_empty());
+
+    // the cost of (+ (q . 5) (q . 3)) is 796, the second handler adds 1
+    assert_eq!(
+        run(&dialect, "(+ (q . 5) (q . 3))").unwrap(),
+        (797, vec![8])
+    );
+    drop(dialect);
+    assert_eq!(calls.into_inner(), vec!["second", "first"]);
+}
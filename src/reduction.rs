@@ -2,6 +2,7 @@ use std::{fmt, io};
 
 use crate::allocator::NodePtr;
 use crate::cost::Cost;
+use crate::error_catalog::COST_EXCEEDED;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct EvalErr(pub NodePtr, pub String);
@@ -11,6 +12,14 @@ pub struct Reduction(pub Cost, pub NodePtr);
 
 pub type Response = Result<Reduction, EvalErr>;
 
+impl EvalErr {
+    // operators fail with COST_EXCEEDED whenever they run out of cost. This
+    // is the one place that tells those failures apart from other errors
+    pub fn is_cost_exceeded(&self) -> bool {
+        self.1 == COST_EXCEEDED
+    }
+}
+
 impl fmt::Display for EvalErr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Error at {:?}: {}", self.0, self.1)
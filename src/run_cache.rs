@@ -0,0 +1,309 @@
+use crate::allocator::{Allocator, NodePtr};
+use crate::cost::Cost;
+use crate::dialect::Dialect;
+use crate::reduction::{Reduction, Response};
+use crate::run_program::run_program;
+use crate::serde::{node_from_bytes, node_to_bytes, tree_hash};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+// A cache of the results of running programs, for callers that run the same
+// program with the same environment repeatedly, like re-validating the mempool
+// after a reorg. Entries are keyed by the tree hashes of the program and the
+// environment, along with the flags of the dialect it ran with, and hold the
+// serialized result and its cost. Only successful runs are cached, since
+// errors refer to nodes in the allocator the program ran in.
+//
+// The cache is bounded by the total size of the cached results. When it's
+// full, the least recently used entries are evicted first. Entries also expire
+// after a fixed time-to-live.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RunCacheKey {
+    pub program: [u8; 32],
+    pub env: [u8; 32],
+    pub flags: u32,
+}
+
+impl RunCacheKey {
+    pub fn new(a: &Allocator, program: NodePtr, env: NodePtr, flags: u32) -> Self {
+        Self {
+            program: tree_hash(a, program),
+            env: tree_hash(a, env),
+            flags,
+        }
+    }
+}
+
+struct Entry {
+    cost: Cost,
+    result: Vec<u8>,
+    expires: Instant,
+    // the position in RunCache::lru
+    seq: u64,
+}
+
+pub struct RunCache {
+    entries: HashMap<RunCacheKey, Entry>,
+    // the keys of all entries, least recently used first
+    lru: BTreeMap<u64, RunCacheKey>,
+    next_seq: u64,
+    max_bytes: usize,
+    used_bytes: usize,
+    ttl: Duration,
+    hits: u64,
+    misses: u64,
+}
+
+impl RunCache {
+    // max_bytes is the limit of the total size of the serialized results in
+    // the cache. Entries expire ttl after they were inserted
+    pub fn new(max_bytes: usize, ttl: Duration) -> Self {
+        Self {
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            next_seq: 0,
+            max_bytes,
+            used_bytes: 0,
+            ttl,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    // returns the cost and serialized result of the entry, unless it has
+    // expired or it cost more than max_cost
+    pub fn get(&mut self, key: &RunCacheKey, max_cost: Cost) -> Option<(Cost, &[u8])> {
+        let now = Instant::now();
+        match self.entries.get(key) {
+            None => {
+                self.misses += 1;
+                return None;
+            }
+            Some(entry) if entry.expires <= now => {
+                self.remove(key);
+                self.misses += 1;
+                return None;
+            }
+            Some(entry) if entry.cost > max_cost => {
+                self.misses += 1;
+                return None;
+            }
+            Some(_) => {}
+        }
+        let entry = self.entries.get_mut(key).expect("entry exists");
+        self.lru.remove(&entry.seq);
+        entry.seq = self.next_seq;
+        self.next_seq += 1;
+        self.lru.insert(entry.seq, *key);
+        self.hits += 1;
+        Some((entry.cost, &entry.result))
+    }
+
+    // adds an entry, replacing any existing one with the same key. Results
+    // larger than the whole cache aren't inserted
+    pub fn insert(&mut self, key: RunCacheKey, cost: Cost, result: Vec<u8>) {
+        self.remove(&key);
+        if result.len() > self.max_bytes {
+            return;
+        }
+        while self.used_bytes + result.len() > self.max_bytes {
+            let (_, oldest) = self.lru.pop_first().expect("cache accounting");
+            let entry = self.entries.remove(&oldest).expect("cache accounting");
+            self.used_bytes -= entry.result.len();
+        }
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.used_bytes += result.len();
+        self.lru.insert(seq, key);
+        self.entries.insert(
+            key,
+            Entry {
+                cost,
+                result,
+                expires: Instant::now() + self.ttl,
+                seq,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, key: &RunCacheKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.lru.remove(&entry.seq);
+            self.used_bytes -= entry.result.len();
+        }
+    }
+
+    // keeps only the entries for which f returns true
+    pub fn retain(&mut self, mut f: impl FnMut(&RunCacheKey) -> bool) {
+        let keys: Vec<RunCacheKey> = self.entries.keys().filter(|k| !f(k)).copied().collect();
+        for key in &keys {
+            self.remove(key);
+        }
+    }
+
+    // removes all entries for programs run with the specified flags. This is
+    // meant to be called when the flags in effect change, e.g. when a
+    // hard-fork activates, and the results are no longer of interest
+    pub fn invalidate_flags(&mut self, flags: u32) {
+        self.retain(|key| key.flags != flags);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.lru.clear();
+        self.used_bytes = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // the total size of the serialized results in the cache
+    pub fn used_bytes(&self) -> usize {
+        self.used_bytes
+    }
+
+    // the number of lookups that found (and didn't find) an entry
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+}
+
+// like run_program(), but returns the result from the cache if the same
+// program has been run with the same environment and flags before. flags must
+// be the flags the dialect was created with, since they're part of the key.
+// On a hit, the cached result is deserialized into the allocator
+pub fn run_program_cached<D: Dialect>(
+    cache: &mut RunCache,
+    a: &mut Allocator,
+    dialect: &D,
+    flags: u32,
+    program: NodePtr,
+    env: NodePtr,
+    max_cost: Cost,
+) -> Response {
+    let key = RunCacheKey::new(a, program, env, flags);
+    if let Some((cost, result)) = cache.get(&key, max_cost) {
+        // if the result doesn't fit in the allocator, run the program, to fail
+        // the same way it would have without the cache
+        if let Ok(node) = node_from_bytes(a, result) {
+            return Ok(Reduction(cost, node));
+        }
+    }
+    let r = run_program(a, dialect, program, env, max_cost)?;
+    if let Ok(result) = node_to_bytes(a, r.1) {
+        cache.insert(key, r.0, result);
+    }
+    Ok(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chia_dialect::{ChiaDialect, NO_UNKNOWN_OPS};
+    use crate::test_ops::parse_exp;
+
+    fn key(n: u8, flags: u32) -> RunCacheKey {
+        RunCacheKey {
+            program: [n; 32],
+            env: [0; 32],
+            flags,
+        }
+    }
+
+    #[test]
+    fn test_run_program_cached() {
+        let mut cache = RunCache::new(1000, Duration::from_secs(3600));
+        let mut a = Allocator::new();
+        let program = parse_exp(&mut a, "(+ 2 5)").0;
+        let env = parse_exp(&mut a, "(1 2)").0;
+        let dialect = ChiaDialect::new(0);
+
+        let first =
+            run_program_cached(&mut cache, &mut a, &dialect, 0, program, env, 100000).unwrap();
+        assert_eq!((cache.hits(), cache.misses()), (0, 1));
+        assert_eq!(cache.len(), 1);
+
+        let second =
+            run_program_cached(&mut cache, &mut a, &dialect, 0, program, env, 100000).unwrap();
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+        assert_eq!(first.0, second.0);
+        assert_eq!(a.number(second.1), 3.into());
+
+        // the key is the tree hash, not the NodePtr
+        let program = parse_exp(&mut a, "(+ 2 5)").0;
+        run_program_cached(&mut cache, &mut a, &dialect, 0, program, env, 100000).unwrap();
+        assert_eq!(cache.hits(), 2);
+
+        // an entry that cost more than max_cost is a miss, and the program
+        // fails the same way it would without the cache
+        let err = run_program_cached(&mut cache, &mut a, &dialect, 0, program, env, first.0 - 1)
+            .unwrap_err();
+        assert_eq!(err.1, "cost exceeded");
+
+        // errors aren't cached
+        let program = parse_exp(&mut a, "(x)").0;
+        for _ in 0..2 {
+            run_program_cached(&mut cache, &mut a, &dialect, 0, program, env, 100000).unwrap_err();
+        }
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_ttl() {
+        let mut cache = RunCache::new(1000, Duration::ZERO);
+        cache.insert(key(1, 0), 10, vec![1]);
+        assert!(cache.get(&key(1, 0), 100).is_none());
+        assert!(cache.is_empty());
+        assert_eq!(cache.used_bytes(), 0);
+    }
+
+    #[test]
+    fn test_eviction() {
+        let mut cache = RunCache::new(10, Duration::from_secs(3600));
+        cache.insert(key(1, 0), 10, vec![0; 4]);
+        cache.insert(key(2, 0), 10, vec![0; 4]);
+        // key 1 is now the most recently used
+        assert!(cache.get(&key(1, 0), 100).is_some());
+        cache.insert(key(3, 0), 10, vec![0; 4]);
+        assert!(cache.get(&key(2, 0), 100).is_none());
+        assert!(cache.get(&key(1, 0), 100).is_some());
+        assert!(cache.get(&key(3, 0), 100).is_some());
+        assert_eq!(cache.used_bytes(), 8);
+
+        // replacing an entry frees the old one
+        cache.insert(key(3, 0), 10, vec![0; 6]);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.used_bytes(), 10);
+
+        // too large to cache at all
+        cache.insert(key(4, 0), 10, vec![0; 11]);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&key(4, 0), 100).is_none());
+    }
+
+    #[test]
+    fn test_invalidate_flags() {
+        let mut cache = RunCache::new(1000, Duration::from_secs(3600));
+        cache.insert(key(1, 0), 10, vec![1]);
+        cache.insert(key(1, NO_UNKNOWN_OPS), 10, vec![1]);
+        cache.insert(key(2, NO_UNKNOWN_OPS), 10, vec![1]);
+        cache.invalidate_flags(NO_UNKNOWN_OPS);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get(&key(1, 0), 100).is_some());
+        assert!(cache.get(&key(1, NO_UNKNOWN_OPS), 100).is_none());
+
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.used_bytes(), 0);
+    }
+}
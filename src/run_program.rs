@@ -1,36 +1,54 @@
 use super::traverse_path::{traverse_path, traverse_path_fast};
-use crate::allocator::{Allocator, Checkpoint, NodePtr, NodeVisitor, SExp};
-use crate::cost::Cost;
+use crate::allocator::Allocator;
+use crate::allocator::{len_for_value, NodePtr, NodeVisitor, SExp};
+use crate::cost::{Cost, CostBudget};
+use crate::cost_schedule_v1::{APPLY_COST, GUARD_COST, OP_COST, QUOTE_COST};
 use crate::dialect::{Dialect, OperatorSet};
 use crate::err_utils::err;
+use crate::error_catalog::{
+    BAD_OPERAND_LIST, COST_EXCEEDED, COST_MUST_BE_POSITIVE, ENV_STACK_EMPTY, ENV_STACK_LIMIT,
+    LONE_ATOM, QUOTE_DEPTH_LIMIT, SOFTFORK_COST_MISMATCH, UNKNOWN_SOFTFORK_EXTENSION,
+    VALUE_STACK_EMPTY, VALUE_STACK_LIMIT, VM_FINISHED,
+};
+use crate::node_map::NodeMap;
 use crate::op_utils::{first, get_args, uint_atom};
 use crate::reduction::{EvalErr, Reduction, Response};
-
-// lowered from 46
-const QUOTE_COST: Cost = 20;
-// lowered from 138
-const APPLY_COST: Cost = 90;
-// the cost of entering a softfork guard
-const GUARD_COST: Cost = 140;
-// mandatory base cost for every operator we execute
-const OP_COST: Cost = 1;
+use crate::sexp_store::SExpStore;
+use std::collections::HashMap;
 
 // The max number of elements allowed on the stack. The program fails if this is
 // exceeded
 const STACK_SIZE_LIMIT: usize = 20000000;
 
 #[cfg(feature = "pre-eval")]
-pub type PreEval =
-    Box<dyn Fn(&mut Allocator, NodePtr, NodePtr) -> Result<Option<Box<PostEval>>, EvalErr>>;
+pub type PreEval<S = Allocator> =
+    Box<dyn Fn(&mut S, NodePtr, NodePtr) -> Result<Option<Box<PostEval>>, EvalErr>>;
 
 #[cfg(feature = "pre-eval")]
 pub type PostEval = dyn Fn(Option<NodePtr>);
 
+// callbacks around every operator the dialect executes, for tracing and
+// profiling. See run_program_with_hooks(). apply and softfork are not
+// dialect operators, and don't invoke the hooks. If an operator fails, the run
+// fails and after_op() is not called
+pub trait RunHooks<S = Allocator> {
+    fn before_op(&mut self, _allocator: &S, _operator: NodePtr, _args: NodePtr) {}
+    fn after_op(&mut self, _allocator: &S, _operator: NodePtr, _result: NodePtr, _cost: Cost) {}
+}
+
+// the operations on the op stack. See Vm::op_stack()
 #[repr(u8)]
-enum Operation {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    // applies the operator below the top of the value stack to the argument
+    // list on top of it
     Apply,
+    // adds the value on top of the value stack to the argument list below it
     Cons,
+    // checks the cost of a softfork guard, and frees its allocations
     ExitGuard,
+    // evaluates the program below the top of the value stack, in the
+    // environment on top of the env stack
     SwapEval,
 
     #[cfg(feature = "pre-eval")]
@@ -64,10 +82,36 @@ impl Counters {
     }
 }
 
+// describes how a run failed with "cost exceeded", to tell how much more cost
+// the program would have needed without re-running it with increasing
+// max_cost. See run_program_with_cost_info()
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CostExceeded {
+    // the limit that was exceeded. Inside a softfork guard, this is the
+    // guard's expected cost rather than the max_cost passed to run_program()
+    pub max_cost: Cost,
+    // the cost spent before the step that exceeded the limit
+    pub cost: Cost,
+    // a lower bound of the cost the program needed to complete the step. If
+    // the step's cost is known, this is exact. If an operator gave up early,
+    // this is max_cost + 1
+    pub required: Cost,
+    // the operator being applied in the step that exceeded the limit, or None
+    // if the step didn't apply an operator (e.g. quoting or a path lookup)
+    pub operator: Option<NodePtr>,
+}
+
+impl CostExceeded {
+    // the budget that was left before the step that exceeded the limit
+    pub fn remaining(&self) -> Cost {
+        self.max_cost - self.cost
+    }
+}
+
 // this represents the state we were in before entering a soft-fork guard. We
 // may need this to long-jump out of the guard, and also to validate the cost
 // when exiting the guard
-struct SoftforkGuard {
+struct SoftforkGuard<C> {
     // This is the expected cost of the program when exiting the guard. i.e. the
     // current_cost + the first argument to the operator
     expected_cost: Cost,
@@ -75,7 +119,7 @@ struct SoftforkGuard {
     // When exiting a softfork guard, all values used inside it are zapped. This
     // was the state of the allocator before entering. We restore to this state
     // on exit.
-    allocator_state: Checkpoint,
+    allocator_state: C,
 
     // this specifies which new operators are available
     operator_set: OperatorSet,
@@ -90,33 +134,53 @@ struct SoftforkGuard {
 // 3. the environment stack (points to the environment for the current
 //    operation). env_stack
 
-struct RunProgramContext<'a, D> {
-    allocator: &'a mut Allocator,
+// the state of the interpreter loop between calls to run_steps()
+#[derive(Default, Clone, Copy)]
+struct LoopState {
+    max_cost: Cost,
+    max_cost_ptr: NodePtr,
+    cost: Cost,
+    // the cost and operator of the last step, in case it exceeded the cost
+    step_cost: Cost,
+    operator: Option<NodePtr>,
+}
+
+struct RunProgramContext<'a, S: SExpStore, D> {
+    allocator: &'a mut S,
     dialect: &'a D,
     val_stack: Vec<NodePtr>,
     env_stack: Vec<NodePtr>,
     op_stack: Vec<Operation>,
-    softfork_stack: Vec<SoftforkGuard>,
+    softfork_stack: Vec<SoftforkGuard<S::Checkpoint>>,
+    // set when the run fails with "cost exceeded"
+    cost_exceeded: Option<CostExceeded>,
+    // the deepest quoted value the program may push onto the value stack,
+    // see run_program_with_quote_depth_limit(). u32::MAX means no limit
+    max_quote_depth: u32,
+    // the depths of the pairs in quoted values checked so far
+    quote_depths: NodeMap<u32>,
+    hooks: Option<&'a mut dyn RunHooks<S>>,
+    loop_state: LoopState,
     #[cfg(feature = "counters")]
     pub counters: Counters,
 
     #[cfg(feature = "pre-eval")]
-    pre_eval: Option<PreEval>,
+    pre_eval: Option<PreEval<S>>,
     #[cfg(feature = "pre-eval")]
     posteval_stack: Vec<Box<PostEval>>,
 }
 
 fn augment_cost_errors(r: Result<Cost, EvalErr>, max_cost: NodePtr) -> Result<Cost, EvalErr> {
     r.map_err(|e| {
-        if &e.1 != "cost exceeded" {
-            e
-        } else {
+        if e.is_cost_exceeded() {
             EvalErr(max_cost, e.1)
+        } else {
+            e
         }
     })
 }
 
-impl<'a, D: Dialect> RunProgramContext<'a, D> {
+impl<'a, S: SExpStore, D: Dialect<S>> RunProgramContext<'a, S, D> {
     #[cfg(feature = "counters")]
     #[inline(always)]
     fn account_val_push(&mut self) {
@@ -155,14 +219,14 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
         match v {
             None => {
                 let node: NodePtr = self.allocator.nil();
-                err(node, "runtime error: value stack empty")
+                err(node, VALUE_STACK_EMPTY)
             }
             Some(k) => Ok(k),
         }
     }
     pub fn push(&mut self, node: NodePtr) -> Result<(), EvalErr> {
         if self.val_stack.len() == STACK_SIZE_LIMIT {
-            return err(node, "value stack limit reached");
+            return err(node, VALUE_STACK_LIMIT);
         }
         self.val_stack.push(node);
         self.account_val_push();
@@ -171,7 +235,7 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
 
     pub fn push_env(&mut self, env: NodePtr) -> Result<(), EvalErr> {
         if self.env_stack.len() == STACK_SIZE_LIMIT {
-            return err(env, "environment stack limit reached");
+            return err(env, ENV_STACK_LIMIT);
         }
         self.env_stack.push(env);
         self.account_env_push();
@@ -180,9 +244,9 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
 
     #[cfg(feature = "pre-eval")]
     fn new_with_pre_eval(
-        allocator: &'a mut Allocator,
+        allocator: &'a mut S,
         dialect: &'a D,
-        pre_eval: Option<PreEval>,
+        pre_eval: Option<PreEval<S>>,
     ) -> Self {
         RunProgramContext {
             allocator,
@@ -191,6 +255,11 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
             env_stack: Vec::new(),
             op_stack: Vec::new(),
             softfork_stack: Vec::new(),
+            cost_exceeded: None,
+            max_quote_depth: u32::MAX,
+            quote_depths: NodeMap::new(),
+            hooks: None,
+            loop_state: LoopState::default(),
             #[cfg(feature = "counters")]
             counters: Counters::new(),
             pre_eval,
@@ -198,7 +267,7 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
         }
     }
 
-    fn new(allocator: &'a mut Allocator, dialect: &'a D) -> Self {
+    fn new(allocator: &'a mut S, dialect: &'a D) -> Self {
         RunProgramContext {
             allocator,
             dialect,
@@ -206,6 +275,11 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
             env_stack: Vec::new(),
             op_stack: Vec::new(),
             softfork_stack: Vec::new(),
+            cost_exceeded: None,
+            max_quote_depth: u32::MAX,
+            quote_depths: NodeMap::new(),
+            hooks: None,
+            loop_state: LoopState::default(),
             #[cfg(feature = "counters")]
             counters: Counters::new(),
             #[cfg(feature = "pre-eval")]
@@ -224,6 +298,41 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
         Ok(0)
     }
 
+    // returns the depth of the tree, like the tree_depth operator. The depths
+    // of the pairs are remembered, so every pair is only visited once per run,
+    // no matter how many times it's quoted
+    fn quoted_depth(&mut self, root: NodePtr) -> u32 {
+        let depth = |rpc: &Self, node: NodePtr| match rpc.allocator.sexp(node) {
+            SExp::Atom => Some(0),
+            SExp::Pair(..) => rpc.quote_depths.get(node).copied(),
+        };
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            let SExp::Pair(first, rest) = self.allocator.sexp(node) else {
+                continue;
+            };
+            if self.quote_depths.contains_key(node) {
+                continue;
+            }
+            match (depth(self, first), depth(self, rest)) {
+                (Some(f), Some(r)) => {
+                    self.quote_depths.insert(node, f.max(r).saturating_add(1));
+                }
+                (f, r) => {
+                    // visit the node again once its children are done
+                    stack.push(node);
+                    if f.is_none() {
+                        stack.push(first);
+                    }
+                    if r.is_none() {
+                        stack.push(rest);
+                    }
+                }
+            }
+        }
+        depth(self, root).expect("depth of quoted value")
+    }
+
     fn eval_op_atom(
         &mut self,
         operator_node: NodePtr,
@@ -232,6 +341,11 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
     ) -> Result<Cost, EvalErr> {
         // special case check for quote
         if self.allocator.small_number(operator_node) == Some(self.dialect.quote_kw()) {
+            if self.max_quote_depth != u32::MAX
+                && self.quoted_depth(operand_list) > self.max_quote_depth
+            {
+                return err(operand_list, QUOTE_DEPTH_LIMIT);
+            }
             self.push(operand_list)?;
             Ok(QUOTE_COST)
         } else {
@@ -259,7 +373,7 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
             }
             // ensure a correct nil terminator
             if self.allocator.atom_len(operands) != 0 {
-                err(operand_list, "bad operand list")
+                err(operand_list, BAD_OPERAND_LIST)
             } else {
                 self.push(self.allocator.nil())?;
                 Ok(OP_COST)
@@ -298,7 +412,7 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
                     "in the ((X)...) syntax, the inner list",
                 )?;
                 if let SExp::Pair(_, _) = self.allocator.sexp(inner) {
-                    return err(program, "in ((X)...) syntax X must be lone atom");
+                    return err(program, LONE_ATOM);
                 }
                 self.push_env(env)?;
                 self.push(new_operator)?;
@@ -317,7 +431,7 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
         let env: NodePtr = *self
             .env_stack
             .last()
-            .ok_or_else(|| EvalErr(program, "runtime error: env stack empty".into()))?;
+            .ok_or_else(|| EvalErr(program, ENV_STACK_EMPTY.into()))?;
         self.push(v2)?;
 
         // on the way back, build a list from the values
@@ -337,7 +451,7 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
             self.dialect
                 .softfork_extension(uint_atom::<4>(self.allocator, extension, "softfork")? as u32);
         if extension == OperatorSet::Default {
-            err(args, "unknown softfork extension")
+            err(args, UNKNOWN_SOFTFORK_EXTENSION)
         } else {
             Ok((extension, program, env))
         }
@@ -347,7 +461,7 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
         let operand_list = self.pop()?;
         let operator = self.pop()?;
         if self.env_stack.pop().is_none() {
-            return err(operator, "runtime error: env stack empty");
+            return err(operator, ENV_STACK_EMPTY);
         }
         let op_atom = self.allocator.small_number(operator);
 
@@ -361,10 +475,10 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
                 "softfork",
             )?;
             if expected_cost > max_cost {
-                return err(operand_list, "cost exceeded");
+                return err(operand_list, COST_EXCEEDED);
             }
             if expected_cost == 0 {
-                return err(operand_list, "cost must be > 0");
+                return err(operand_list, COST_MUST_BE_POSITIVE);
             }
 
             // we can't blindly propagate errors here, since we handle errors
@@ -404,6 +518,9 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
                 OperatorSet::Default
             };
 
+            if let Some(hooks) = &mut self.hooks {
+                hooks.before_op(self.allocator, operator, operand_list);
+            }
             let r = self.dialect.op(
                 self.allocator,
                 operator,
@@ -411,6 +528,9 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
                 max_cost,
                 current_extensions,
             )?;
+            if let Some(hooks) = &mut self.hooks {
+                hooks.after_op(self.allocator, operator, r.1, r.0);
+            }
             self.push(r.1)?;
             Ok(r.0)
         }
@@ -431,7 +551,7 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
                 current_cost - guard.start_cost,
                 guard.expected_cost - guard.start_cost
             );
-            return err(self.allocator.nil(), "softfork specified cost mismatch");
+            return err(self.allocator.nil(), SOFTFORK_COST_MISMATCH);
         }
 
         // restore the allocator to the state when we entered the softfork guard
@@ -440,6 +560,8 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
         // escape the softfork program, and it's therefore safe to restore the
         // heap
         self.allocator.restore_checkpoint(&guard.allocator_state);
+        // the indices of the freed pairs will be reused
+        self.quote_depths.clear();
 
         // the softfork always returns nil, pop the value pushed by the
         // evaluation of the program and push nil instead
@@ -452,6 +574,15 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
     }
 
     pub fn run_program(&mut self, program: NodePtr, env: NodePtr, max_cost: Cost) -> Response {
+        self.start(program, env, max_cost)?;
+        loop {
+            if let Some(reduction) = self.run_steps(u64::MAX, None)? {
+                return Ok(reduction);
+            }
+        }
+    }
+
+    fn start(&mut self, program: NodePtr, env: NodePtr, max_cost: Cost) -> Result<(), EvalErr> {
         self.val_stack = vec![];
         self.op_stack = vec![];
 
@@ -460,9 +591,33 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
         let max_cost = if max_cost == 0 { Cost::MAX } else { max_cost };
         let max_cost_ptr = self.allocator.new_number(max_cost.into())?;
 
-        let mut cost: Cost = 0;
+        let step_cost = self.eval_pair(program, env)?;
+        self.loop_state = LoopState {
+            max_cost,
+            max_cost_ptr,
+            cost: step_cost,
+            step_cost,
+            operator: None,
+        };
+        Ok(())
+    }
 
-        cost += self.eval_pair(program, env)?;
+    // runs at most max_steps operations of a program set up by start(), and
+    // returns its result if it completed. It also stops once the cost reaches
+    // pause_cost, if set. Otherwise, the state is saved in loop_state, to
+    // continue in the next call
+    fn run_steps(
+        &mut self,
+        mut max_steps: u64,
+        pause_cost: Option<Cost>,
+    ) -> Result<Option<Reduction>, EvalErr> {
+        let LoopState {
+            max_cost,
+            max_cost_ptr,
+            mut cost,
+            mut step_cost,
+            mut operator,
+        } = self.loop_state;
 
         loop {
             // if we are in a softfork guard, temporarily use the guard's
@@ -476,36 +631,77 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
             };
 
             if cost > effective_max_cost {
-                return err(max_cost_ptr, "cost exceeded");
+                self.cost_exceeded = Some(CostExceeded {
+                    max_cost: effective_max_cost,
+                    cost: cost - step_cost,
+                    required: cost,
+                    operator,
+                });
+                return err(max_cost_ptr, COST_EXCEEDED);
             }
+            if max_steps == 0 || pause_cost.is_some_and(|c| cost >= c) {
+                self.loop_state = LoopState {
+                    max_cost,
+                    max_cost_ptr,
+                    cost,
+                    step_cost,
+                    operator,
+                };
+                return Ok(None);
+            }
+            max_steps -= 1;
             let top = self.op_stack.pop();
             let op = match top {
                 Some(f) => f,
                 None => break,
             };
-            cost += match op {
+            // apply_op() pops the operand list and then the operator
+            operator = match op {
+                Operation::Apply => self
+                    .val_stack
+                    .len()
+                    .checked_sub(2)
+                    .map(|idx| self.val_stack[idx]),
+                _ => None,
+            };
+            let r = match op {
                 Operation::Apply => augment_cost_errors(
                     self.apply_op(cost, effective_max_cost - cost),
                     max_cost_ptr,
-                )?,
-                Operation::ExitGuard => self.exit_guard(cost)?,
-                Operation::Cons => self.cons_op()?,
-                Operation::SwapEval => augment_cost_errors(self.swap_eval_op(), max_cost_ptr)?,
+                ),
+                Operation::ExitGuard => self.exit_guard(cost),
+                Operation::Cons => self.cons_op(),
+                Operation::SwapEval => augment_cost_errors(self.swap_eval_op(), max_cost_ptr),
                 #[cfg(feature = "pre-eval")]
                 Operation::PostEval => {
                     let f = self.posteval_stack.pop().unwrap();
                     let peek: Option<NodePtr> = self.val_stack.last().copied();
                     f(peek);
-                    0
+                    Ok(0)
                 }
             };
+            step_cost = match r {
+                Ok(c) => c,
+                Err(e) => {
+                    if e.is_cost_exceeded() {
+                        self.cost_exceeded = Some(CostExceeded {
+                            max_cost: effective_max_cost,
+                            cost,
+                            required: effective_max_cost.saturating_add(1),
+                            operator,
+                        });
+                    }
+                    return Err(e);
+                }
+            };
+            cost += step_cost;
         }
-        Ok(Reduction(cost, self.pop()?))
+        Ok(Some(Reduction(cost, self.pop()?)))
     }
 }
 
-pub fn run_program<'a, D: Dialect>(
-    allocator: &'a mut Allocator,
+pub fn run_program<'a, S: SExpStore, D: Dialect<S>>(
+    allocator: &'a mut S,
     dialect: &'a D,
     program: NodePtr,
     env: NodePtr,
@@ -515,14 +711,270 @@ pub fn run_program<'a, D: Dialect>(
     rpc.run_program(program, env, max_cost)
 }
 
+// like run_program(), but if the program fails with "cost exceeded", also
+// returns how it exceeded the cost
+pub fn run_program_with_cost_info<'a, S: SExpStore, D: Dialect<S>>(
+    allocator: &'a mut S,
+    dialect: &'a D,
+    program: NodePtr,
+    env: NodePtr,
+    max_cost: Cost,
+) -> (Option<CostExceeded>, Response) {
+    let mut rpc = RunProgramContext::new(allocator, dialect);
+    let ret = rpc.run_program(program, env, max_cost);
+    (rpc.cost_exceeded, ret)
+}
+
+// like run_program(), but limited to what's left of budget, and charging the
+// cost of the run to it. This lets several sequential runs share one limit.
+// If the run fails with "cost exceeded", the budget is exhausted. Other
+// failures don't charge the budget
+pub fn run_program_with_budget<'a, S: SExpStore, D: Dialect<S>>(
+    allocator: &'a mut S,
+    dialect: &'a D,
+    program: NodePtr,
+    env: NodePtr,
+    budget: &mut CostBudget,
+) -> Response {
+    // a max_cost of 0 means no limit to run_program()
+    if budget.remaining() == 0 {
+        return err(NodePtr::NIL, COST_EXCEEDED);
+    }
+    let mut rpc = RunProgramContext::new(allocator, dialect);
+    match rpc.run_program(program, env, budget.remaining()) {
+        Ok(reduction) => {
+            budget.charge(reduction.0)?;
+            Ok(reduction)
+        }
+        Err(e) => {
+            if rpc.cost_exceeded.is_some() {
+                budget.exhaust();
+            }
+            Err(e)
+        }
+    }
+}
+
+// like run_program(), but quoting a value nested deeper than max_quote_depth
+// (with the depth defined like the tree_depth operator) fails with "quote
+// depth limit exceeded". This is a policy limit, independent of cost, to
+// reject quote bombs, which are cheap to run but produce values that are slow
+// to process for tools that walk them, like hashing and serialization
+pub fn run_program_with_quote_depth_limit<'a, S: SExpStore, D: Dialect<S>>(
+    allocator: &'a mut S,
+    dialect: &'a D,
+    program: NodePtr,
+    env: NodePtr,
+    max_cost: Cost,
+    max_quote_depth: u32,
+) -> Response {
+    let mut rpc = RunProgramContext::new(allocator, dialect);
+    rpc.max_quote_depth = max_quote_depth;
+    rpc.run_program(program, env, max_cost)
+}
+
+// like run_program(), but calls hooks before and after every operator
+pub fn run_program_with_hooks<'a, S: SExpStore, D: Dialect<S>>(
+    allocator: &'a mut S,
+    dialect: &'a D,
+    program: NodePtr,
+    env: NodePtr,
+    max_cost: Cost,
+    hooks: &'a mut dyn RunHooks<S>,
+) -> Response {
+    let mut rpc = RunProgramContext::new(allocator, dialect);
+    rpc.hooks = Some(hooks);
+    rpc.run_program(program, env, max_cost)
+}
+
+// the number of calls to each operator, and the total cost it charged, keyed
+// by opcode. See run_program_with_op_costs()
+pub type OpCosts = HashMap<Vec<u8>, (u64, Cost)>;
+
+impl<S: SExpStore> RunHooks<S> for OpCosts {
+    fn after_op(&mut self, allocator: &S, operator: NodePtr, _result: NodePtr, cost: Cost) {
+        let opcode = match allocator.node(operator) {
+            NodeVisitor::Buffer(buf) => buf.to_vec(),
+            NodeVisitor::U32(val) => val.to_be_bytes()[4 - len_for_value(val)..].to_vec(),
+            NodeVisitor::Pair(_, _) => return,
+        };
+        let entry = self.entry(opcode).or_default();
+        entry.0 += 1;
+        entry.1 += cost;
+    }
+}
+
+// like run_program_with_cost_info(), but also returns the number of calls to,
+// and the cost of, every operator the program ran, to find out where the cost
+// of a puzzle goes. An operator's cost doesn't include evaluating its
+// arguments, and the cost charged by the interpreter itself (quote, apply and
+// path lookups) isn't part of any operator. If the run fails, only the
+// operators that completed are counted. See also CostReport, which presents
+// this as a table
+pub fn run_program_with_op_costs<'a, S: SExpStore, D: Dialect<S>>(
+    allocator: &'a mut S,
+    dialect: &'a D,
+    program: NodePtr,
+    env: NodePtr,
+    max_cost: Cost,
+) -> (Option<CostExceeded>, OpCosts, Response) {
+    let mut op_costs = OpCosts::new();
+    let mut rpc = RunProgramContext::new(allocator, dialect);
+    rpc.hooks = Some(&mut op_costs);
+    let ret = rpc.run_program(program, env, max_cost);
+    (rpc.cost_exceeded, op_costs, ret)
+}
+
+// the outcome of Vm::step()
+#[derive(Debug, PartialEq, Eq)]
+pub enum VmStep {
+    // the program hasn't completed yet
+    Running,
+    Done(Reduction),
+}
+
+// what the next step of a Vm does. See Vm::next_step()
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NextStep {
+    // evaluates program in env. This is the first step, and the step
+    // evaluating each argument of an operator. Programs run by apply are
+    // evaluated as part of the Apply step
+    Eval { program: NodePtr, env: NodePtr },
+    // applies operator to args, the list of its evaluated arguments
+    Apply { operator: NodePtr, args: NodePtr },
+    // returns the value on top of the value stack, completing the program
+    Return,
+    Other(Operation),
+}
+
+// runs a program a limited number of steps at a time, to interleave it with
+// other work or to stop it after some wall-clock time. A step is one operation
+// of the interpreter (like applying an operator or building an argument list),
+// not a unit of cost. The result, cost and errors are the same as
+// run_program()'s
+pub struct Vm<'a, S: SExpStore, D> {
+    rpc: RunProgramContext<'a, S, D>,
+    // the arguments to start the program with, until the first step
+    start: Option<(NodePtr, NodePtr, Cost)>,
+    finished: bool,
+}
+
+impl<'a, S: SExpStore, D: Dialect<S>> Vm<'a, S, D> {
+    pub fn new(
+        allocator: &'a mut S,
+        dialect: &'a D,
+        program: NodePtr,
+        env: NodePtr,
+        max_cost: Cost,
+    ) -> Self {
+        Self {
+            rpc: RunProgramContext::new(allocator, dialect),
+            start: Some((program, env, max_cost)),
+            finished: false,
+        }
+    }
+
+    // runs at most max_steps steps. Once the program has completed or failed,
+    // this fails with "vm finished"
+    pub fn step(&mut self, max_steps: u64) -> Result<VmStep, EvalErr> {
+        self.advance(max_steps, None)
+    }
+
+    // runs until the program has spent (at least) another installment of
+    // cost, and can be resumed with another installment. This lets a
+    // scheduler share time between several programs by cost. The last step
+    // may take the cost past the installment, since a step can't be undone.
+    // max_cost passed to new() still limits the total cost
+    pub fn run_installment(&mut self, installment: Cost) -> Result<VmStep, EvalErr> {
+        let pause_cost = self.cost().saturating_add(installment);
+        self.advance(u64::MAX, Some(pause_cost))
+    }
+
+    fn advance(&mut self, max_steps: u64, pause_cost: Option<Cost>) -> Result<VmStep, EvalErr> {
+        if self.finished {
+            return err(NodePtr::NIL, VM_FINISHED);
+        }
+        if max_steps == 0 {
+            return Ok(VmStep::Running);
+        }
+        // any error ends the run
+        self.finished = true;
+        let mut max_steps = max_steps;
+        if let Some((program, env, max_cost)) = self.start.take() {
+            // evaluating the program is the first step
+            self.rpc.start(program, env, max_cost)?;
+            max_steps -= 1;
+        }
+        match self.rpc.run_steps(max_steps, pause_cost)? {
+            Some(reduction) => Ok(VmStep::Done(reduction)),
+            None => {
+                self.finished = false;
+                Ok(VmStep::Running)
+            }
+        }
+    }
+
+    // the cost spent so far
+    pub fn cost(&self) -> Cost {
+        self.rpc.loop_state.cost
+    }
+
+    // what the next call to step(1) does, or None if the program has
+    // completed or failed
+    pub fn next_step(&self) -> Option<NextStep> {
+        if self.finished {
+            return None;
+        }
+        if let Some((program, env, _)) = self.start {
+            return Some(NextStep::Eval { program, env });
+        }
+        let vals = &self.rpc.val_stack;
+        let next = match self.rpc.op_stack.last() {
+            None => NextStep::Return,
+            Some(Operation::Apply) => NextStep::Apply {
+                operator: vals[vals.len() - 2],
+                args: vals[vals.len() - 1],
+            },
+            Some(Operation::SwapEval) => NextStep::Eval {
+                program: vals[vals.len() - 2],
+                env: *self.rpc.env_stack.last()?,
+            },
+            Some(op) => NextStep::Other(*op),
+        };
+        Some(next)
+    }
+
+    // the stacks of the interpreter, with the top last. See
+    // RunProgramContext
+    pub fn val_stack(&self) -> &[NodePtr] {
+        &self.rpc.val_stack
+    }
+
+    pub fn env_stack(&self) -> &[NodePtr] {
+        &self.rpc.env_stack
+    }
+
+    pub fn op_stack(&self) -> &[Operation] {
+        &self.rpc.op_stack
+    }
+
+    pub fn allocator(&self) -> &S {
+        self.rpc.allocator
+    }
+
+    pub fn dialect(&self) -> &D {
+        self.rpc.dialect
+    }
+}
+
 #[cfg(feature = "pre-eval")]
-pub fn run_program_with_pre_eval<'a, D: Dialect>(
-    allocator: &'a mut Allocator,
+pub fn run_program_with_pre_eval<'a, S: SExpStore, D: Dialect<S>>(
+    allocator: &'a mut S,
     dialect: &'a D,
     program: NodePtr,
     env: NodePtr,
     max_cost: Cost,
-    pre_eval: Option<PreEval>,
+    pre_eval: Option<PreEval<S>>,
 ) -> Response {
     let mut rpc = RunProgramContext::new_with_pre_eval(allocator, dialect, pre_eval);
     rpc.run_program(program, env, max_cost)
@@ -559,7 +1011,11 @@ struct RunProgramTest {
 use crate::test_ops::parse_exp;
 
 #[cfg(test)]
-use crate::chia_dialect::{ENABLE_BLS_OPS_OUTSIDE_GUARD, ENABLE_FIXED_DIV, NO_UNKNOWN_OPS};
+use crate::chia_dialect::{
+    ENABLE_BIT_COUNT_OPS, ENABLE_BLS_OPS_OUTSIDE_GUARD, ENABLE_BLS_VALID_OPS, ENABLE_DIVEXACT_GCD,
+    ENABLE_ED25519_VERIFY, ENABLE_FIXED_DIV, ENABLE_FIXED_POINT_OPS, ENABLE_TREE_SIZE_OPS,
+    NO_UNKNOWN_OPS, STRICT_ARITY, STRICT_INT_OUTPUTS,
+};
 
 #[cfg(test)]
 const TEST_CASES: &[RunProgramTest] = &[
@@ -1242,6 +1698,50 @@ const TEST_CASES: &[RunProgramTest] = &[
         err: "unimplemented operator",
     },
 
+    // g1_valid? and g2_valid? require the ENABLE_BLS_VALID_OPS flag
+    RunProgramTest {
+        prg: "(g1_valid? (q . 0x97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb))",
+        args: "()",
+        flags: ENABLE_BLS_OPS_OUTSIDE_GUARD | ENABLE_BLS_VALID_OPS,
+        result: Some("1"),
+        cost: 937,
+        err: "",
+    },
+    RunProgramTest {
+        prg: "(g2_valid? (q . 0x97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb))",
+        args: "()",
+        flags: ENABLE_BLS_OPS_OUTSIDE_GUARD | ENABLE_BLS_VALID_OPS,
+        result: Some("0"),
+        cost: 1225,
+        err: "",
+    },
+    RunProgramTest {
+        prg: "(g1_valid? (q . 0x97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb))",
+        args: "()",
+        flags: ENABLE_BLS_OPS_OUTSIDE_GUARD | NO_UNKNOWN_OPS,
+        result: None,
+        cost: 0,
+        err: "unimplemented operator",
+    },
+
+    // errors from BLS operators say which argument was not a valid point
+    RunProgramTest {
+        prg: "(g1_subtract (q . 0x97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb) (q . 0x97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb) (q . 0x1337))",
+        args: "()",
+        flags: ENABLE_BLS_OPS_OUTSIDE_GUARD,
+        result: None,
+        cost: 0,
+        err: "g1_subtract argument 2: atom is not G1 size, 48 bytes",
+    },
+    RunProgramTest {
+        prg: "(bls_pairing_identity (q . 0x97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb) (q . 0x97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb))",
+        args: "()",
+        flags: ENABLE_BLS_OPS_OUTSIDE_GUARD,
+        result: None,
+        cost: 0,
+        err: "bls_pairing_identity argument 1: atom is not G2 size, 96 bytes",
+    },
+
     // secp261k1
 
     RunProgramTest {
@@ -1281,6 +1781,178 @@ const TEST_CASES: &[RunProgramTest] = &[
         cost: 0,
         err: "secp256r1_verify failed",
     },
+
+    // ed25519
+
+    RunProgramTest {
+        prg: "(ed25519_verify (q . 0x3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c) (q . 0x72) (q . 0x92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00))",
+        args: "()",
+        flags: ENABLE_ED25519_VERIFY,
+        result: Some("0"),
+        cost: 450111,
+        err: "",
+    },
+    // invalid signature
+    RunProgramTest {
+        prg: "(ed25519_verify (q . 0x3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c) (q . 0x72) (q . 0x92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c01))",
+        args: "()",
+        flags: ENABLE_ED25519_VERIFY,
+        result: None,
+        cost: 0,
+        err: "ed25519_verify failed",
+    },
+    // without the flag, ed25519_verify is an unknown operator. It doesn't
+    // validate the signature and has the fixed base cost
+    RunProgramTest {
+        prg: "(ed25519_verify (q . 0x3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c) (q . 0x72) (q . 0x92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c01))",
+        args: "()",
+        flags: 0,
+        result: Some("0"),
+        cost: 450061,
+        err: "",
+    },
+    RunProgramTest {
+        prg: "(ed25519_verify (q . 0x3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c) (q . 0x72) (q . 0x92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00))",
+        args: "()",
+        flags: NO_UNKNOWN_OPS,
+        result: None,
+        cost: 0,
+        err: "unimplemented operator",
+    },
+
+    // divexact and gcd
+
+    RunProgramTest {
+        prg: "(divexact (q . -12) (q . 4))",
+        args: "()",
+        flags: ENABLE_DIVEXACT_GCD,
+        result: Some("-3"),
+        cost: 1047,
+        err: "",
+    },
+    RunProgramTest {
+        prg: "(divexact (q . 12) (q . 5))",
+        args: "()",
+        flags: ENABLE_DIVEXACT_GCD,
+        result: None,
+        cost: 0,
+        err: "divexact with remainder",
+    },
+    RunProgramTest {
+        prg: "(gcd (q . -12) (q . 18))",
+        args: "()",
+        flags: ENABLE_DIVEXACT_GCD,
+        result: Some("6"),
+        cost: 1263,
+        err: "",
+    },
+    // without the flag, divexact and gcd are unknown operators
+    RunProgramTest {
+        prg: "(gcd (q . -12) (q . 18))",
+        args: "()",
+        flags: 0,
+        result: Some("0"),
+        cost: 42,
+        err: "",
+    },
+    RunProgramTest {
+        prg: "(divexact (q . 12) (q . 5))",
+        args: "()",
+        flags: NO_UNKNOWN_OPS,
+        result: None,
+        cost: 0,
+        err: "unimplemented operator",
+    },
+
+    // fixed-point operators
+
+    RunProgramTest {
+        prg: "(mul_div_floor (q . 1500000) (q . 3) (q . 7))",
+        args: "()",
+        flags: ENABLE_FIXED_POINT_OPS,
+        result: Some("642857"),
+        cost: 1631,
+        err: "",
+    },
+    RunProgramTest {
+        prg: "(sqrt_floor (q . 1000000))",
+        args: "()",
+        flags: ENABLE_FIXED_POINT_OPS,
+        result: Some("1000"),
+        cost: 1059,
+        err: "",
+    },
+    RunProgramTest {
+        prg: "(sqrt_floor (q . -1))",
+        args: "()",
+        flags: ENABLE_FIXED_POINT_OPS,
+        result: None,
+        cost: 0,
+        err: "sqrt_floor of negative number",
+    },
+    // without the flag, they are unknown operators
+    RunProgramTest {
+        prg: "(sqrt_floor (q . 1000000))",
+        args: "()",
+        flags: 0,
+        result: Some("0"),
+        cost: 22,
+        err: "",
+    },
+
+    // bit counting operators
+
+    RunProgramTest {
+        prg: "(popcount (q . 0x0f0f))",
+        args: "()",
+        flags: ENABLE_BIT_COUNT_OPS,
+        result: Some("8"),
+        cost: 235,
+        err: "",
+    },
+    RunProgramTest {
+        prg: "(bitlength (q . 0x0f0f))",
+        args: "()",
+        flags: ENABLE_BIT_COUNT_OPS,
+        result: Some("12"),
+        cost: 206,
+        err: "",
+    },
+    RunProgramTest {
+        prg: "(popcount (q . 0x0f0f))",
+        args: "()",
+        flags: NO_UNKNOWN_OPS,
+        result: None,
+        cost: 0,
+        err: "unimplemented operator",
+    },
+
+    // tree introspection operators
+
+    RunProgramTest {
+        prg: "(tree_size 1)",
+        args: "(1 2 3)",
+        flags: ENABLE_TREE_SIZE_OPS,
+        result: Some("7"),
+        cost: 295,
+        err: "",
+    },
+    RunProgramTest {
+        prg: "(tree_depth 1)",
+        args: "(1 2 3)",
+        flags: ENABLE_TREE_SIZE_OPS,
+        result: Some("3"),
+        cost: 295,
+        err: "",
+    },
+    RunProgramTest {
+        prg: "(tree_size 1)",
+        args: "(1 2 3)",
+        flags: NO_UNKNOWN_OPS,
+        result: None,
+        cost: 0,
+        err: "unimplemented operator",
+    },
 ];
 
 #[cfg(test)]
@@ -1295,29 +1967,40 @@ fn test_run_program() {
     use crate::test_ops::node_eq;
 
     for t in TEST_CASES {
-        let mut allocator = Allocator::new();
-
-        let program = check(parse_exp(&mut allocator, t.prg));
-        let args = check(parse_exp(&mut allocator, t.args));
-        let expected_result = &t.result.map(|v| check(parse_exp(&mut allocator, v)));
-
-        let dialect = ChiaDialect::new(t.flags);
-        println!("prg: {}", t.prg);
-        match run_program(&mut allocator, &dialect, program, args, t.cost) {
-            Ok(Reduction(cost, prg_result)) => {
-                assert!(node_eq(&allocator, prg_result, expected_result.unwrap()));
-                assert_eq!(cost, t.cost);
-
-                // now, run the same program again but with the cost limit 1 too low, to
-                // ensure it fails with the correct error
-                let expected_cost_exceeded =
-                    run_program(&mut allocator, &dialect, program, args, t.cost - 1).unwrap_err();
-                assert_eq!(expected_cost_exceeded.1, "cost exceeded");
-            }
-            Err(err) => {
-                println!("FAILED: {}", err.1);
-                assert_eq!(err.1, t.err);
-                assert!(expected_result.is_none());
+        // every integer the operators return must be canonical, and every
+        // operator must check its arguments the way its declared arity says, so
+        // the STRICT_INT_OUTPUTS and STRICT_ARITY checks must not change any
+        // result
+        for flags in [
+            t.flags,
+            t.flags | STRICT_INT_OUTPUTS,
+            t.flags | STRICT_ARITY,
+        ] {
+            let mut allocator = Allocator::new();
+
+            let program = check(parse_exp(&mut allocator, t.prg));
+            let args = check(parse_exp(&mut allocator, t.args));
+            let expected_result = &t.result.map(|v| check(parse_exp(&mut allocator, v)));
+
+            let dialect = ChiaDialect::new(flags);
+            println!("prg: {} flags: {flags:x}", t.prg);
+            match run_program(&mut allocator, &dialect, program, args, t.cost) {
+                Ok(Reduction(cost, prg_result)) => {
+                    assert!(node_eq(&allocator, prg_result, expected_result.unwrap()));
+                    assert_eq!(cost, t.cost);
+
+                    // now, run the same program again but with the cost limit 1 too low, to
+                    // ensure it fails with the correct error
+                    let expected_cost_exceeded =
+                        run_program(&mut allocator, &dialect, program, args, t.cost - 1)
+                            .unwrap_err();
+                    assert_eq!(expected_cost_exceeded.1, "cost exceeded");
+                }
+                Err(err) => {
+                    println!("FAILED: {}", err.1);
+                    assert_eq!(err.1, t.err);
+                    assert!(expected_result.is_none());
+                }
             }
         }
     }
@@ -1347,3 +2030,296 @@ fn test_counters() {
 
     assert_eq!(result.unwrap().0, cost);
 }
+
+#[test]
+fn test_cost_info() {
+    use crate::chia_dialect::ChiaDialect;
+
+    let mut a = Allocator::new();
+    let dialect = ChiaDialect::new(0);
+    // (sha256 (q . "foobar") (q . "foobar"))
+    let program = check(parse_exp(
+        &mut a,
+        "(11 (q . 0x666f6f626172) (q . 0x666f6f626172))",
+    ));
+    let args = a.nil();
+
+    let (info, result) = run_program_with_cost_info(&mut a, &dialect, program, args, 0);
+    assert_eq!(info, None);
+    let total = result.unwrap().0;
+
+    // the operator is the step that exceeds the cost
+    for max_cost in [total - 1, total - 50] {
+        let (info, result) = run_program_with_cost_info(&mut a, &dialect, program, args, max_cost);
+        assert_eq!(result.unwrap_err().1, "cost exceeded");
+        let info = info.unwrap();
+        assert_eq!(info.max_cost, max_cost);
+        assert!(info.required > max_cost && info.required <= total);
+        assert_eq!(
+            info.operator.map(|op| a.atom(op).as_ref().to_vec()),
+            Some(vec![11])
+        );
+        assert_eq!(info.remaining(), max_cost - info.cost);
+    }
+
+    // quoting isn't an operator
+    let program = check(parse_exp(&mut a, "(q . 1)"));
+    let (info, result) = run_program_with_cost_info(&mut a, &dialect, program, args, 1);
+    assert_eq!(result.unwrap_err().1, "cost exceeded");
+    let info = info.unwrap();
+    assert_eq!((info.cost, info.required, info.operator), (0, 20, None));
+    assert_eq!(info.remaining(), 1);
+
+    // other errors don't report cost info
+    let program = check(parse_exp(&mut a, "(x)"));
+    let (info, result) = run_program_with_cost_info(&mut a, &dialect, program, args, 0);
+    assert_eq!(result.unwrap_err().1, "clvm raise");
+    assert_eq!(info, None);
+}
+
+#[test]
+fn test_budget() {
+    use crate::chia_dialect::ChiaDialect;
+
+    let mut a = Allocator::new();
+    let dialect = ChiaDialect::new(0);
+    let program = check(parse_exp(&mut a, "(+ (q . 1) (q . 2))"));
+    let args = a.nil();
+    let cost = run_program(&mut a, &dialect, program, args, 0).unwrap().0;
+
+    // two runs fit exactly, the third exceeds the budget
+    let mut budget = CostBudget::new(cost * 2);
+    for _ in 0..2 {
+        let result = run_program_with_budget(&mut a, &dialect, program, args, &mut budget);
+        assert_eq!(result.unwrap().0, cost);
+    }
+    assert_eq!((budget.spent(), budget.remaining()), (cost * 2, 0));
+    let result = run_program_with_budget(&mut a, &dialect, program, args, &mut budget);
+    assert_eq!(result.unwrap_err().1, "cost exceeded");
+
+    // exceeding the budget exhausts it
+    let mut budget = CostBudget::new(cost + cost / 2);
+    run_program_with_budget(&mut a, &dialect, program, args, &mut budget).unwrap();
+    let result = run_program_with_budget(&mut a, &dialect, program, args, &mut budget);
+    assert_eq!(result.unwrap_err().1, "cost exceeded");
+    assert_eq!(budget.remaining(), 0);
+
+    // other failures don't charge the budget
+    let mut budget = CostBudget::new(cost);
+    let failing = check(parse_exp(&mut a, "(x)"));
+    let result = run_program_with_budget(&mut a, &dialect, failing, args, &mut budget);
+    assert_eq!(result.unwrap_err().1, "clvm raise");
+    assert_eq!(budget.remaining(), cost);
+}
+
+#[test]
+fn test_hooks() {
+    use crate::chia_dialect::ChiaDialect;
+
+    #[derive(Default)]
+    struct Trace {
+        ops: Vec<String>,
+        cost: Cost,
+    }
+
+    impl RunHooks for Trace {
+        fn before_op(&mut self, a: &Allocator, operator: NodePtr, args: NodePtr) {
+            let op = a.small_number(operator).unwrap();
+            let args = crate::serde::node_to_bytes(a, args).unwrap();
+            self.ops.push(format!("{op} {}", hex::encode(args)));
+        }
+        fn after_op(&mut self, a: &Allocator, operator: NodePtr, result: NodePtr, cost: Cost) {
+            let op = a.small_number(operator).unwrap();
+            let result = a.small_number(result).unwrap();
+            self.ops.push(format!("{op} -> {result}"));
+            self.cost += cost;
+        }
+    }
+
+    let mut a = Allocator::new();
+    let dialect = ChiaDialect::new(0);
+    // (+ (q . 1) (* (q . 2) (q . 3)))
+    let program = check(parse_exp(&mut a, "(16 (q . 1) (18 (q . 2) (q . 3)))"));
+    let args = a.nil();
+    let mut trace = Trace::default();
+    let result = run_program_with_hooks(&mut a, &dialect, program, args, 0, &mut trace);
+    let total = result.unwrap().0;
+    assert_eq!(
+        trace.ops,
+        ["18 ff02ff0380", "18 -> 6", "16 ff01ff0680", "16 -> 7"]
+    );
+    assert!(trace.cost > 0 && trace.cost < total);
+
+    // a failing operator doesn't call after_op()
+    let program = check(parse_exp(&mut a, "(x (q . 1))"));
+    let mut trace = Trace::default();
+    let result = run_program_with_hooks(&mut a, &dialect, program, args, 0, &mut trace);
+    assert_eq!(result.unwrap_err().1, "clvm raise");
+    assert_eq!(trace.ops, ["8 ff0180"]);
+}
+
+#[test]
+fn test_op_costs() {
+    use crate::chia_dialect::ChiaDialect;
+
+    let mut a = Allocator::new();
+    let dialect = ChiaDialect::new(0);
+    // (+ (q . 1) (+ (q . 2) (q . 3)) (sha256 (q . "foo")))
+    let program = check(parse_exp(
+        &mut a,
+        "(16 (q . 1) (16 (q . 2) (q . 3)) (11 (q . \"foo\")))",
+    ));
+    let args = a.nil();
+    let (_, op_costs, result) = run_program_with_op_costs(&mut a, &dialect, program, args, 0);
+    let total = result.unwrap().0;
+    assert_eq!(op_costs.len(), 2);
+    let (add_calls, add_cost) = op_costs[&vec![16_u8]];
+    let (sha_calls, sha_cost) = op_costs[&vec![11_u8]];
+    assert_eq!(add_calls, 2);
+    assert_eq!(sha_calls, 1);
+    assert!(add_cost > 0 && sha_cost > 0);
+    assert!(add_cost + sha_cost < total);
+
+    // an operator that fails isn't counted
+    let program = check(parse_exp(&mut a, "(x (16 (q . 1) (q . 2)))"));
+    let (_, op_costs, result) = run_program_with_op_costs(&mut a, &dialect, program, args, 0);
+    assert_eq!(result.unwrap_err().1, "clvm raise");
+    assert_eq!(op_costs.keys().collect::<Vec<_>>(), [&vec![16_u8]]);
+}
+
+#[test]
+fn test_vm() {
+    use crate::chia_dialect::ChiaDialect;
+
+    let mut a = Allocator::new();
+    let dialect = ChiaDialect::new(0);
+    // (a (q 2 2 (c 2 (c 5 ()))) (c (q 16 5 (q . 1)) 1)), adds 1 to the argument
+    let program = check(parse_exp(
+        &mut a,
+        "(2 (q 2 2 (c 2 (c 5 ()))) (c (q 16 5 (q . 1)) 1))",
+    ));
+    let args = check(parse_exp(&mut a, "(41)"));
+    let expected = run_program(&mut a, &dialect, program, args, 0).unwrap();
+
+    let mut vm = Vm::new(&mut a, &dialect, program, args, 0);
+    assert_eq!(vm.step(0).unwrap(), VmStep::Running);
+    assert_eq!(vm.next_step(), Some(NextStep::Eval { program, env: args }));
+    let mut steps = 0;
+    let result = loop {
+        let cost = vm.cost();
+        match vm.step(1).unwrap() {
+            VmStep::Running => assert!(vm.cost() >= cost),
+            VmStep::Done(reduction) => break reduction,
+        }
+        steps += 1;
+    };
+    assert!(steps > 5);
+    assert_eq!(vm.next_step(), None);
+    assert_eq!(vm.step(1).unwrap_err().1, "vm finished");
+    assert_eq!(result.0, expected.0);
+    assert_eq!(a.number(result.1), 42.into());
+
+    // the cost limit applies across steps
+    let mut vm = Vm::new(&mut a, &dialect, program, args, expected.0 - 1);
+    assert_eq!(vm.step(3).unwrap(), VmStep::Running);
+    assert_eq!(vm.step(u64::MAX).unwrap_err().1, "cost exceeded");
+    assert_eq!(vm.step(1).unwrap_err().1, "vm finished");
+
+    // errors before the first operation
+    let bad_path = check(parse_exp(&mut a, "5"));
+    let mut vm = Vm::new(&mut a, &dialect, bad_path, args, 0);
+    assert!(vm.step(1).unwrap_err().1.starts_with("path into atom"));
+}
+
+#[test]
+fn test_vm_installments() {
+    use crate::chia_dialect::ChiaDialect;
+
+    let mut a = Allocator::new();
+    let dialect = ChiaDialect::new(0);
+    // sums the numbers from 1 to 100 recursively
+    let program = check(parse_exp(
+        &mut a,
+        "(2 (q 2 2 (c 2 (c 5 ()))) (c (q 2 (i 5 (q 16 5 (2 2 (c 2 (c (17 5 (q . 1)) ())))) ()) 1) 1))",
+    ));
+    let args = check(parse_exp(&mut a, "(100)"));
+    let expected = run_program(&mut a, &dialect, program, args, 0).unwrap();
+
+    let installment = 1000;
+    let mut vm = Vm::new(&mut a, &dialect, program, args, 0);
+    let mut installments = 0;
+    let result = loop {
+        let cost = vm.cost();
+        match vm.run_installment(installment).unwrap() {
+            VmStep::Running => assert!(vm.cost() >= cost + installment),
+            VmStep::Done(reduction) => break reduction,
+        }
+        installments += 1;
+    };
+    assert!(installments > 10);
+    assert_eq!(result.0, expected.0);
+    assert_eq!(a.number(result.1), 5050.into());
+
+    // max_cost limits the total cost, regardless of the installments
+    let mut vm = Vm::new(&mut a, &dialect, program, args, expected.0 / 2);
+    let result = loop {
+        match vm.run_installment(installment) {
+            Ok(VmStep::Running) => {}
+            result => break result,
+        }
+    };
+    assert_eq!(result.unwrap_err().1, "cost exceeded");
+
+    // an installment of 0 makes no progress
+    let mut vm = Vm::new(&mut a, &dialect, program, args, 0);
+    vm.step(1).unwrap();
+    let cost = vm.cost();
+    assert_eq!(vm.run_installment(0).unwrap(), VmStep::Running);
+    assert_eq!(vm.cost(), cost);
+}
+
+#[test]
+fn test_quote_depth_limit() {
+    use crate::chia_dialect::ChiaDialect;
+
+    let mut a = Allocator::new();
+    let dialect = ChiaDialect::new(0);
+    let args = a.nil();
+    let run = |a: &mut Allocator, prg: &str, max_quote_depth: u32| {
+        let program = check(parse_exp(a, prg));
+        run_program_with_quote_depth_limit(a, &dialect, program, args, 0, max_quote_depth)
+            .map(|r| r.0)
+            .map_err(|e| e.1)
+    };
+
+    // (q . ((1 2) 3)) has depth 3, since (1 2) is (1 . (2 . ()))
+    assert!(run(&mut a, "(q (1 2) 3)", 3).is_ok());
+    assert_eq!(
+        run(&mut a, "(q (1 2) 3)", 2).unwrap_err(),
+        "quote depth limit exceeded"
+    );
+    assert!(run(&mut a, "(q . 1)", 0).is_ok());
+
+    // the limit applies to every quote, not just the program, and doesn't
+    // affect cost
+    let prg = "(c (q . 1) (q (((1)))))";
+    let program = check(parse_exp(&mut a, prg));
+    let cost = run_program(&mut a, &dialect, program, args, 0).unwrap().0;
+    assert_eq!(run(&mut a, prg, 4), Ok(cost));
+    assert_eq!(
+        run(&mut a, prg, 3).unwrap_err(),
+        "quote depth limit exceeded"
+    );
+
+    // a deep tree sharing its nodes is only walked once
+    let mut node = a.nil();
+    for _ in 0..100000 {
+        node = a.new_pair(node, node).unwrap();
+    }
+    let quote = a.new_small_number(1).unwrap();
+    let program = a.new_pair(quote, node).unwrap();
+    let r = run_program_with_quote_depth_limit(&mut a, &dialect, program, args, 0, 100000);
+    assert!(r.is_ok());
+    let r = run_program_with_quote_depth_limit(&mut a, &dialect, program, args, 0, 99999);
+    assert_eq!(r.unwrap_err().0, node);
+}
@@ -1,15 +1,17 @@
 use crate::allocator::{Allocator, NodePtr};
 use crate::cost::{check_cost, Cost};
+use crate::cost_schedule_v1::{SECP256K1_VERIFY_COST, SECP256R1_VERIFY_COST};
 use crate::err_utils::err;
+use crate::error_catalog::{
+    SECP256K1_DIGEST_SIZE, SECP256K1_FAILED, SECP256K1_INVALID_PUBKEY, SECP256K1_INVALID_SIG,
+    SECP256R1_DIGEST_SIZE, SECP256R1_FAILED, SECP256R1_INVALID_PUBKEY, SECP256R1_INVALID_SIG,
+};
 use crate::op_utils::{atom, get_args};
 use crate::reduction::{Reduction, Response};
 use k256::ecdsa::{Signature as K1Signature, VerifyingKey as K1VerifyingKey};
 use p256::ecdsa::signature::hazmat::PrehashVerifier;
 use p256::ecdsa::{Signature as P1Signature, VerifyingKey as P1VerifyingKey};
 
-const SECP256R1_VERIFY_COST: Cost = 1850000;
-const SECP256K1_VERIFY_COST: Cost = 1300000;
-
 // expects: pubkey msg sig
 pub fn op_secp256r1_verify(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Response {
     let cost = SECP256R1_VERIFY_COST;
@@ -20,24 +22,24 @@ pub fn op_secp256r1_verify(a: &mut Allocator, input: NodePtr, max_cost: Cost) ->
     // first argument is sec1 encoded pubkey
     let pubkey = atom(a, pubkey, "secp256r1_verify pubkey")?;
     let verifier = P1VerifyingKey::from_sec1_bytes(pubkey.as_ref())
-        .or_else(|_| err(input, "secp256r1_verify pubkey is not valid"))?;
+        .or_else(|_| err(input, SECP256R1_INVALID_PUBKEY))?;
 
     // second arg is sha256 hash of message
     let msg = atom(a, msg, "secp256r1_verify msg")?;
     if msg.as_ref().len() != 32 {
-        return err(input, "secp256r1_verify message digest is not 32 bytes");
+        return err(input, SECP256R1_DIGEST_SIZE);
     }
 
     // third arg is a fixed-size signature
     let sig = atom(a, sig, "secp256r1_verify sig")?;
-    let sig = P1Signature::from_slice(sig.as_ref())
-        .or_else(|_| err(input, "secp256r1_verify sig is not valid"))?;
+    let sig =
+        P1Signature::from_slice(sig.as_ref()).or_else(|_| err(input, SECP256R1_INVALID_SIG))?;
 
     // verify signature
     let result = verifier.verify_prehash(msg.as_ref(), &sig);
 
     if result.is_err() {
-        err(input, "secp256r1_verify failed")
+        err(input, SECP256R1_FAILED)
     } else {
         Ok(Reduction(cost, a.nil()))
     }
@@ -53,24 +55,24 @@ pub fn op_secp256k1_verify(a: &mut Allocator, input: NodePtr, max_cost: Cost) ->
     // first argument is sec1 encoded pubkey
     let pubkey = atom(a, pubkey, "secp256k1_verify pubkey")?;
     let verifier = K1VerifyingKey::from_sec1_bytes(pubkey.as_ref())
-        .or_else(|_| err(input, "secp256k1_verify pubkey is not valid"))?;
+        .or_else(|_| err(input, SECP256K1_INVALID_PUBKEY))?;
 
     // second arg is message
     let msg = atom(a, msg, "secp256k1_verify msg")?;
     if msg.as_ref().len() != 32 {
-        return err(input, "secp256k1_verify message digest is not 32 bytes");
+        return err(input, SECP256K1_DIGEST_SIZE);
     }
 
     // third arg is a fixed-size signature
     let sig = atom(a, sig, "secp256k1_verify sig")?;
-    let sig = K1Signature::from_slice(sig.as_ref())
-        .or_else(|_| err(input, "secp256k1_verify sig is not valid"))?;
+    let sig =
+        K1Signature::from_slice(sig.as_ref()).or_else(|_| err(input, SECP256K1_INVALID_SIG))?;
 
     // verify signature
     let result = verifier.verify_prehash(msg.as_ref(), &sig);
 
     if result.is_err() {
-        err(input, "secp256k1_verify failed")
+        err(input, SECP256K1_FAILED)
     } else {
         Ok(Reduction(cost, a.nil()))
     }
@@ -1,11 +1,14 @@
 use std::io;
 use std::io::{Cursor, Read};
+use std::sync::Arc;
 
 use crate::allocator::{Allocator, NodePtr};
 
-use super::parse_atom::parse_atom;
+use super::errors::bad_encoding;
+use super::parse_atom::{decode_size, parse_atom};
 
 const CONS_BOX_MARKER: u8 = 0xff;
+const MAX_SINGLE_BYTE: u8 = 0x7f;
 
 #[repr(u8)]
 enum ParseOp {
@@ -46,3 +49,116 @@ pub fn node_from_bytes(allocator: &mut Allocator, b: &[u8]) -> io::Result<NodePt
     let mut buffer = Cursor::new(b);
     node_from_stream(allocator, &mut buffer)
 }
+
+/// like `node_from_bytes()`, but `b` may continue past the serialized node.
+/// Returns the node along with the number of bytes it took up, so
+/// concatenated nodes can be parsed one after the other
+pub fn node_from_bytes_partial(
+    allocator: &mut Allocator,
+    b: &[u8],
+) -> io::Result<(NodePtr, usize)> {
+    let mut buffer = Cursor::new(b);
+    let node = node_from_stream(allocator, &mut buffer)?;
+    Ok((node, buffer.position() as usize))
+}
+
+/// like `node_from_bytes()`, but atoms larger than a few bytes refer to `b`
+/// instead of being copied into the allocator. This saves copying large
+/// inputs, like block generators. The allocator keeps a reference to `b`
+pub fn node_from_shared_bytes(allocator: &mut Allocator, b: &Arc<[u8]>) -> io::Result<NodePtr> {
+    let mut f = Cursor::new(&b[..]);
+    let mut values: Vec<NodePtr> = Vec::new();
+    let mut ops = vec![ParseOp::SExp];
+
+    let mut first = [0; 1];
+    while let Some(op) = ops.pop() {
+        match op {
+            ParseOp::SExp => {
+                f.read_exact(&mut first)?;
+                if first[0] == CONS_BOX_MARKER {
+                    ops.push(ParseOp::Cons);
+                    ops.push(ParseOp::SExp);
+                    ops.push(ParseOp::SExp);
+                } else if first[0] == 0x80 {
+                    values.push(allocator.nil());
+                } else if first[0] <= MAX_SINGLE_BYTE {
+                    values.push(allocator.new_atom(&first)?);
+                } else {
+                    let size = decode_size(&mut f, first[0])?;
+                    let start = f.position() as usize;
+                    if ((b.len() - start) as u64) < size {
+                        return Err(bad_encoding());
+                    }
+                    let end = start + size as usize;
+                    f.set_position(end as u64);
+                    values.push(allocator.new_atom_shared(b, start, end)?);
+                }
+            }
+            ParseOp::Cons => {
+                let v2 = values.pop();
+                let v1 = values.pop();
+                values.push(allocator.new_pair(v1.unwrap(), v2.unwrap())?);
+            }
+        }
+    }
+    Ok(values.pop().unwrap())
+}
+
+// returns the byte at pos, or an EOF error, matching read_exact()
+fn byte_at(b: &[u8], pos: usize) -> io::Result<u8> {
+    b.get(pos)
+        .copied()
+        .ok_or_else(|| io::ErrorKind::UnexpectedEof.into())
+}
+
+/// deserialize a clvm node from bytes produced by this crate (e.g. by
+/// `node_to_bytes()`, and kept in a cache). Unlike `node_from_bytes()`, the
+/// encoding of length prefixes and the allocator limits are only validated in
+/// debug builds, where invalid input panics. Truncated input is still an
+/// error.
+pub fn node_from_bytes_trusted(allocator: &mut Allocator, b: &[u8]) -> io::Result<NodePtr> {
+    let mut values: Vec<NodePtr> = Vec::new();
+    let mut ops = vec![ParseOp::SExp];
+    let mut pos = 0;
+
+    while let Some(op) = ops.pop() {
+        match op {
+            ParseOp::SExp => {
+                let first = byte_at(b, pos)?;
+                pos += 1;
+                if first == CONS_BOX_MARKER {
+                    ops.push(ParseOp::Cons);
+                    ops.push(ParseOp::SExp);
+                    ops.push(ParseOp::SExp);
+                } else if first == 0x80 {
+                    values.push(allocator.nil());
+                } else if first <= MAX_SINGLE_BYTE {
+                    values.push(allocator.new_atom_trusted(&b[pos - 1..pos]));
+                } else {
+                    // the number of leading 1-bits is the length of the size
+                    // prefix
+                    let prefix_len = first.leading_ones() as usize;
+                    debug_assert!(prefix_len <= 6, "trusted atom size prefix too long");
+                    let mut size = (first & (0xff >> prefix_len)) as u64;
+                    for _ in 1..prefix_len {
+                        size = (size << 8) | byte_at(b, pos)? as u64;
+                        pos += 1;
+                    }
+                    debug_assert!(size < 0x400000000, "trusted atom too large");
+                    if ((b.len() - pos) as u64) < size {
+                        return Err(bad_encoding());
+                    }
+                    let atom = &b[pos..pos + size as usize];
+                    pos += size as usize;
+                    values.push(allocator.new_atom_trusted(atom));
+                }
+            }
+            ParseOp::Cons => {
+                let v2 = values.pop();
+                let v1 = values.pop();
+                values.push(allocator.new_pair_trusted(v1.unwrap(), v2.unwrap()));
+            }
+        }
+    }
+    Ok(values.pop().unwrap())
+}
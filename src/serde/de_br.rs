@@ -67,6 +67,18 @@ pub fn node_from_bytes_backrefs(allocator: &mut Allocator, b: &[u8]) -> io::Resu
     node_from_stream_backrefs(allocator, &mut buffer, |_node| {})
 }
 
+/// like `node_from_bytes_backrefs()`, but `b` may continue past the
+/// serialized node. Returns the node along with the number of bytes it took
+/// up. Back references can only refer to the node itself
+pub fn node_from_bytes_backrefs_partial(
+    allocator: &mut Allocator,
+    b: &[u8],
+) -> io::Result<(NodePtr, usize)> {
+    let mut buffer = Cursor::new(b);
+    let node = node_from_stream_backrefs(allocator, &mut buffer, |_node| {})?;
+    Ok((node, buffer.position() as usize))
+}
+
 pub fn node_from_bytes_backrefs_record(
     allocator: &mut Allocator,
     b: &[u8],
@@ -1,9 +1,10 @@
+use crate::error_catalog::{BAD_ENCODING, SERDE_INTERNAL_ERROR};
 use std::io::{Error, ErrorKind};
 
 pub fn bad_encoding() -> Error {
-    Error::new(ErrorKind::InvalidInput, "bad encoding")
+    Error::new(ErrorKind::InvalidInput, BAD_ENCODING)
 }
 
 pub fn internal_error() -> Error {
-    Error::new(ErrorKind::InvalidInput, "internal error")
+    Error::new(ErrorKind::InvalidInput, SERDE_INTERNAL_ERROR)
 }
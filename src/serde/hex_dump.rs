@@ -0,0 +1,301 @@
+// An annotated hex dump of a serialized CLVM object, for debugging the
+// serializer and corrupt test fixtures. Understands both the plain and the
+// back-reference format (the plain format never uses the 0xfe prefix)
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::io;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+use super::errors::bad_encoding;
+use super::parse_atom::{decode_size, parse_path};
+use crate::allocator::{Allocator, NodePtr, SExp};
+use crate::traverse_path::traverse_path;
+
+const MAX_SINGLE_BYTE: u8 = 0x7f;
+const BACK_REFERENCE: u8 = 0xfe;
+const CONS_BOX_MARKER: u8 = 0xff;
+
+// the number of bytes printed per line
+const BYTES_PER_LINE: usize = 8;
+
+enum ParseOp {
+    // the depth of the node to parse
+    SExp(usize),
+    // the offset of the cons box marker
+    Cons(usize),
+}
+
+enum Token {
+    Cons,
+    Atom(u64),
+    // the offset of the node the back reference resolves to, or None if it
+    // refers to (part of) the parse stack rather than a node
+    BackRef(Option<usize>),
+}
+
+struct Entry {
+    offset: usize,
+    len: usize,
+    depth: usize,
+    token: Token,
+}
+
+/// returns an annotated hex dump of the serialized CLVM object in `b`. Each
+/// line starts with an offset into `b`, followed by the bytes of one cons
+/// box marker, atom or back-reference, and a description of it. Elements of
+/// a list are printed at the same depth, their first elements indented one
+/// level further. Nodes that back-references resolve to are annotated with
+/// the offsets of those back-references.
+///
+/// Parsing stops at the first error, which is printed after the part of the
+/// dump that could be parsed, as is any trailing garbage.
+pub fn hex_dump(b: &[u8]) -> String {
+    let mut entries = Vec::<Entry>::new();
+    let result = parse_entries(b, &mut entries);
+
+    // the offsets of all back-references to each node
+    let mut referenced_by = HashMap::<usize, Vec<usize>>::new();
+    for e in &entries {
+        if let Token::BackRef(Some(target)) = e.token {
+            referenced_by.entry(target).or_default().push(e.offset);
+        }
+    }
+
+    let mut ret = String::new();
+    for e in &entries {
+        let mut desc = "  ".repeat(e.depth);
+        match e.token {
+            Token::Cons => desc.push_str("cons"),
+            Token::Atom(0) => desc.push_str("nil"),
+            Token::Atom(1) => desc.push_str("atom, 1 byte"),
+            Token::Atom(size) => write!(desc, "atom, {size} bytes").unwrap(),
+            Token::BackRef(Some(target)) => write!(desc, "backref -> {target:08x}").unwrap(),
+            Token::BackRef(None) => desc.push_str("backref -> (parse stack)"),
+        }
+        if let Some(refs) = referenced_by.get(&e.offset) {
+            let refs: Vec<String> = refs.iter().map(|r| format!("{r:08x}")).collect();
+            write!(desc, "  [referenced by {}]", refs.join(", ")).unwrap();
+        }
+
+        let bytes = &b[e.offset..e.offset + e.len];
+        for (i, chunk) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+            let hex = hex.join(" ");
+            let offset = e.offset + i * BYTES_PER_LINE;
+            let desc = if i == 0 { desc.as_str() } else { "" };
+            let line = format!(
+                "{offset:08x}  {hex:width$}  {desc}",
+                width = BYTES_PER_LINE * 3 - 1
+            );
+            writeln!(ret, "{}", line.trim_end()).unwrap();
+        }
+    }
+
+    match result {
+        Err(err) => {
+            let offset = entries.last().map_or(0, |e| e.offset + e.len);
+            writeln!(ret, "{offset:08x}  error: {err}").unwrap();
+        }
+        Ok(end) if end < b.len() => {
+            writeln!(
+                ret,
+                "{end:08x}  {} bytes of trailing garbage",
+                b.len() - end
+            )
+            .unwrap();
+        }
+        Ok(_) => {}
+    }
+    ret
+}
+
+// parses b into entries, returning the length of the serialization. On
+// failure, entries holds everything up to the error
+fn parse_entries(b: &[u8], entries: &mut Vec<Entry>) -> io::Result<usize> {
+    let mut f = Cursor::new(b);
+
+    // like serialized_length_from_bytes(), this uses an allocator to track the
+    // tree structure, to resolve back-references. Each atom is represented by
+    // (one plus) its offset, and the offsets of cons boxes are kept in
+    // cons_offsets. Pairs not in there are part of the parse stack
+    let mut allocator = Allocator::new();
+    let mut cons_offsets = HashMap::<NodePtr, usize>::new();
+    let mut values = allocator.nil();
+    let mut ops = vec![ParseOp::SExp(0)];
+
+    let mut byte = [0; 1];
+    while let Some(op) = ops.pop() {
+        match op {
+            ParseOp::SExp(depth) => {
+                let offset = f.position() as usize;
+                f.read_exact(&mut byte)?;
+                let (node, token) = if byte[0] == CONS_BOX_MARKER {
+                    ops.push(ParseOp::Cons(offset));
+                    ops.push(ParseOp::SExp(depth));
+                    ops.push(ParseOp::SExp(depth + 1));
+                    entries.push(Entry {
+                        offset,
+                        len: 1,
+                        depth,
+                        token: Token::Cons,
+                    });
+                    continue;
+                } else if byte[0] == BACK_REFERENCE {
+                    let path = parse_path(&mut f)?;
+                    let node = traverse_path(&allocator, path, values)?.1;
+                    let target = match allocator.sexp(node) {
+                        SExp::Pair(..) => cons_offsets.get(&node).copied(),
+                        SExp::Atom => match allocator.small_number(node) {
+                            Some(0) | None => None,
+                            Some(v) => Some(v as usize - 1),
+                        },
+                    };
+                    (node, Token::BackRef(target))
+                } else {
+                    let size = if byte[0] == 0x80 {
+                        0
+                    } else if byte[0] <= MAX_SINGLE_BYTE {
+                        1
+                    } else {
+                        let size = decode_size(&mut f, byte[0])?;
+                        f.seek(SeekFrom::Current(size as i64))?;
+                        if (b.len() as u64) < f.position() {
+                            return Err(bad_encoding());
+                        }
+                        size
+                    };
+                    let node = allocator.new_number((offset + 1).into())?;
+                    (node, Token::Atom(size))
+                };
+                entries.push(Entry {
+                    offset,
+                    len: f.position() as usize - offset,
+                    depth,
+                    token,
+                });
+                values = allocator.new_pair(node, values)?;
+            }
+            ParseOp::Cons(offset) => {
+                let SExp::Pair(v1, v2) = allocator.sexp(values) else {
+                    return Err(bad_encoding());
+                };
+                let SExp::Pair(v3, v4) = allocator.sexp(v2) else {
+                    return Err(bad_encoding());
+                };
+                let new_root = allocator.new_pair(v3, v1)?;
+                cons_offsets.insert(new_root, offset);
+                values = allocator.new_pair(new_root, v4)?;
+            }
+        }
+    }
+    Ok(f.position() as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    // nil
+    #[case("80", &["00000000  80                       nil"])]
+    // ("foobar" "foobar")
+    #[case(
+        "ff86666f6f626172ff86666f6f62617280",
+        &[
+            "00000000  ff                       cons",
+            "00000001  86 66 6f 6f 62 61 72       atom, 6 bytes",
+            "00000008  ff                       cons",
+            "00000009  86 66 6f 6f 62 61 72       atom, 6 bytes",
+            "00000010  80                       nil",
+        ]
+    )]
+    // ("foobar" . "foobar"), with a back-reference
+    #[case(
+        "ff86666f6f626172fe02",
+        &[
+            "00000000  ff                       cons",
+            "00000001  86 66 6f 6f 62 61 72       atom, 6 bytes  [referenced by 00000008]",
+            "00000008  fe 02                    backref -> 00000001",
+        ]
+    )]
+    // ((1 2) 1 2), the back-reference is to a cons box
+    #[case(
+        "ffff01ff0280fe02",
+        &[
+            "00000000  ff                       cons",
+            "00000001  ff                         cons  [referenced by 00000006]",
+            "00000002  01                           atom, 1 byte",
+            "00000003  ff                         cons",
+            "00000004  02                           atom, 1 byte",
+            "00000005  80                         nil",
+            "00000006  fe 02                    backref -> 00000001",
+        ]
+    )]
+    // a 9 byte atom, which spans two lines
+    #[case(
+        "89010203040506070809",
+        &[
+            "00000000  89 01 02 03 04 05 06 07  atom, 9 bytes",
+            "00000008  08 09",
+        ]
+    )]
+    // ("foobar" "foobar"), the back-reference is to the list ("foobar") on
+    // the parse stack
+    #[case(
+        "ff86666f6f626172fe01",
+        &[
+            "00000000  ff                       cons",
+            "00000001  86 66 6f 6f 62 61 72       atom, 6 bytes",
+            "00000008  fe 01                    backref -> (parse stack)",
+        ]
+    )]
+    // a back-reference to the parse stack
+    #[case(
+        "ff01fe01",
+        &[
+            "00000000  ff                       cons",
+            "00000001  01                         atom, 1 byte",
+            "00000002  fe 01                    backref -> (parse stack)",
+        ]
+    )]
+    // truncated
+    #[case(
+        "ff8601",
+        &[
+            "00000000  ff                       cons",
+            "00000001  error: bad encoding",
+        ]
+    )]
+    #[case(
+        "ff01",
+        &[
+            "00000000  ff                       cons",
+            "00000001  01                         atom, 1 byte",
+            "00000002  error: failed to fill whole buffer",
+        ]
+    )]
+    // a back-reference into an atom
+    #[case(
+        "ff01fe0b",
+        &[
+            "00000000  ff                       cons",
+            "00000001  01                         atom, 1 byte",
+            "00000002  error: path into atom",
+        ]
+    )]
+    // trailing garbage
+    #[case(
+        "0102",
+        &[
+            "00000000  01                       atom, 1 byte",
+            "00000001  1 bytes of trailing garbage",
+        ]
+    )]
+    fn test_hex_dump(#[case] input: &str, #[case] expected: &[&str]) {
+        let input = hex::decode(input).unwrap();
+        let dump = hex_dump(&input);
+        assert_eq!(dump.lines().collect::<Vec<_>>(), expected);
+    }
+}
@@ -3,6 +3,7 @@ mod de;
 mod de_br;
 mod de_tree;
 mod errors;
+mod hex_dump;
 mod object_cache;
 mod parse_atom;
 mod read_cache_lookup;
@@ -15,11 +16,17 @@ pub mod write_atom;
 #[cfg(test)]
 mod test;
 
-pub use de::node_from_bytes;
-pub use de_br::{node_from_bytes_backrefs, node_from_bytes_backrefs_record};
+pub use de::{
+    node_from_bytes, node_from_bytes_partial, node_from_bytes_trusted, node_from_shared_bytes,
+};
+pub use de_br::{
+    node_from_bytes_backrefs, node_from_bytes_backrefs_partial, node_from_bytes_backrefs_record,
+};
 pub use de_tree::{parse_triples, ParsedTriple};
+pub use hex_dump::hex_dump;
 pub use ser::node_to_bytes;
 pub use ser_br::node_to_bytes_backrefs;
 pub use tools::{
-    serialized_length_from_bytes, serialized_length_from_bytes_trusted, tree_hash_from_stream,
+    serialized_length_from_bytes, serialized_length_from_bytes_trusted, tree_hash,
+    tree_hash_from_stream,
 };
@@ -7,12 +7,12 @@
 /// have a non-recursive implementation (as it keeps a stack of uncached
 /// objects locally).
 use crate::allocator::{Allocator, NodePtr, SExp};
-use std::collections::HashMap;
+use crate::node_map::NodeMap;
 type CachedFunction<T> = fn(&mut ObjectCache<T>, &Allocator, NodePtr) -> Option<T>;
 use super::bytes32::{hash_blobs, Bytes32};
 
 pub struct ObjectCache<'a, T> {
-    cache: HashMap<NodePtr, T>,
+    cache: NodeMap<T>,
     allocator: &'a Allocator,
 
     /// The function `f` is expected to calculate its T value recursively based
@@ -28,7 +28,7 @@ pub struct ObjectCache<'a, T> {
 impl<'a, T: Clone> ObjectCache<'a, T> {
     pub fn new(allocator: &'a Allocator, f: CachedFunction<T>) -> Self {
         Self {
-            cache: HashMap::new(),
+            cache: NodeMap::new(),
             allocator,
             f,
         }
@@ -43,7 +43,7 @@ impl<'a, T: Clone> ObjectCache<'a, T> {
 
     /// return the cached value for this node, or `None`
     fn get_from_cache(&self, node: &NodePtr) -> Option<&T> {
-        self.cache.get(node)
+        self.cache.get(*node)
     }
 
     /// set the cached value for a node
@@ -2,10 +2,13 @@ use hex::FromHex;
 
 use crate::allocator::Allocator;
 
-use crate::serde::de::node_from_bytes;
-use crate::serde::de_br::node_from_bytes_backrefs;
+use crate::serde::de::{
+    node_from_bytes, node_from_bytes_partial, node_from_bytes_trusted, node_from_shared_bytes,
+};
+use crate::serde::de_br::{node_from_bytes_backrefs, node_from_bytes_backrefs_partial};
 use crate::serde::ser::node_to_bytes;
 use crate::serde::ser_br::node_to_bytes_backrefs;
+use std::sync::Arc;
 
 fn check_round_trip(obj_ser_br_hex: &str) {
     // serialized with br => obj => serialized no br =(allow_br)=> obj => serialized w br
@@ -62,3 +65,127 @@ fn test_round_trip() {
 
     check("ff83666f6ffffe01fffe01fffe01fffe01fffe01fffe0180");
 }
+
+#[test]
+fn test_trusted() {
+    for hex in [
+        "01",
+        "80",
+        "00",
+        "ff83666f6f83626172",
+        "ff83666f6fff8362617280",
+        "ffff0102ff0304",
+        "ff01ff02ff03ff04ff05ff0680",
+        "ff8400010203ff820080ff8180ff8401020304ff850102030405ff820100ff85fffffffffeff8900112233445566778880",
+    ] {
+        let buf = <Vec<u8>>::from_hex(hex).unwrap();
+        let mut a = Allocator::new();
+        let expected = node_from_bytes(&mut a, &buf).unwrap();
+        let mut b = Allocator::new();
+        let node = node_from_bytes_trusted(&mut b, &buf).unwrap();
+
+        // the same nodes are allocated, in the same order
+        assert_eq!(node, expected);
+        assert_eq!(node_to_bytes(&b, node).unwrap(), buf);
+    }
+
+    // long atoms, with 2 and 3 byte size prefixes
+    for len in [0x40, 0x2000, 0x10000] {
+        let mut a = Allocator::new();
+        let atom = a.new_atom(&vec![0x55; len]).unwrap();
+        let buf = node_to_bytes(&a, atom).unwrap();
+        let mut b = Allocator::new();
+        let node = node_from_bytes_trusted(&mut b, &buf).unwrap();
+        assert_eq!(b.atom(node).as_ref(), a.atom(atom).as_ref());
+    }
+
+    // truncated input is still an error
+    for hex in ["", "ff01", "ff0183", "8301", "c0"] {
+        let buf = <Vec<u8>>::from_hex(hex).unwrap();
+        let mut a = Allocator::new();
+        assert!(node_from_bytes_trusted(&mut a, &buf).is_err());
+    }
+}
+
+#[test]
+fn test_shared() {
+    for hex in [
+        "01",
+        "80",
+        "ff83666f6fff8362617280",
+        "ff01ff02ff03ff04ff05ff0680",
+        "ff8400010203ff820080ff8180ff8401020304ff850102030405ff820100ff85fffffffffeff8900112233445566778880",
+        "ff996c6f6e6720656e6f75676820746f2062652073686172656420ff8a30313233343536373839ff8a3031323334353637383980",
+    ] {
+        let buf: Arc<[u8]> = <Vec<u8>>::from_hex(hex).unwrap().into();
+        let mut a = Allocator::new();
+        let expected = node_from_bytes(&mut a, &buf).unwrap();
+        let mut b = Allocator::new();
+        let node = node_from_shared_bytes(&mut b, &buf).unwrap();
+
+        // the same nodes are allocated, in the same order
+        assert_eq!(node, expected);
+        assert_eq!(node_to_bytes(&b, node).unwrap(), &buf[..]);
+    }
+
+    // invalid and truncated input is an error
+    for hex in ["", "ff01", "ff0183", "8301", "c0", "ff"] {
+        let buf: Arc<[u8]> = <Vec<u8>>::from_hex(hex).unwrap().into();
+        let mut a = Allocator::new();
+        assert!(node_from_shared_bytes(&mut a, &buf).is_err());
+    }
+}
+
+#[test]
+fn test_partial() {
+    // 1, (foo . bar), (foo . foo) with a back reference, nil
+    let buf = <Vec<u8>>::from_hex("01ff83666f6f83626172ff83666f6ffe0280").unwrap();
+
+    let mut a = Allocator::new();
+    let mut nodes = Vec::new();
+    let mut rest = &buf[..];
+    while !rest.is_empty() {
+        let (node, len) = node_from_bytes_backrefs_partial(&mut a, rest).unwrap();
+        nodes.push(node_to_bytes(&a, node).unwrap());
+        rest = &rest[len..];
+    }
+    let expected = ["01", "ff83666f6f83626172", "ff83666f6f83666f6f", "80"];
+    assert_eq!(nodes.len(), expected.len());
+    for (node, hex) in nodes.iter().zip(expected) {
+        assert_eq!(node, &<Vec<u8>>::from_hex(hex).unwrap());
+    }
+
+    // without back references
+    let buf = <Vec<u8>>::from_hex("01ff83666f6f8362617280").unwrap();
+    let mut rest = &buf[..];
+    for hex in ["01", "ff83666f6f83626172", "80"] {
+        let (node, len) = node_from_bytes_partial(&mut a, rest).unwrap();
+        assert_eq!(
+            node_to_bytes(&a, node).unwrap(),
+            <Vec<u8>>::from_hex(hex).unwrap()
+        );
+        assert_eq!(len, hex.len() / 2);
+        rest = &rest[len..];
+    }
+    assert!(rest.is_empty());
+
+    // a truncated node is still an error
+    assert!(node_from_bytes_partial(&mut a, &[0xff, 0x01]).is_err());
+    assert!(node_from_bytes_backrefs_partial(&mut a, &[0xff, 0x01]).is_err());
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "trusted atom size prefix too long")]
+fn test_trusted_invalid_prefix() {
+    let mut a = Allocator::new();
+    let _ = node_from_bytes_trusted(&mut a, &[0xfe, 0, 0, 0, 0, 0, 0, 0]);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "trusted atom exceeds heap limit")]
+fn test_trusted_heap_limit() {
+    let mut a = Allocator::new_limited(10);
+    let _ = node_from_bytes_trusted(&mut a, &[0x8a, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+}
@@ -2,7 +2,9 @@ use std::io;
 use std::io::{Cursor, Read, Seek, SeekFrom};
 
 use super::errors::bad_encoding;
+use super::object_cache::{treehash, ObjectCache};
 use super::parse_atom::decode_size;
+use crate::allocator::{Allocator, NodePtr};
 
 const MAX_SINGLE_BYTE: u8 = 0x7f;
 const BACK_REFERENCE: u8 = 0xfe;
@@ -107,6 +109,15 @@ pub fn tree_hash_from_stream(f: &mut Cursor<&[u8]>) -> io::Result<[u8; 32]> {
     Ok(values.pop().unwrap())
 }
 
+// computes the tree-hash of a CLVM structure in the allocator. Shared subtrees
+// are only hashed once
+pub fn tree_hash(a: &Allocator, node: NodePtr) -> [u8; 32] {
+    let mut cache = ObjectCache::new(a, treehash);
+    *cache
+        .get_or_calculate(&node)
+        .expect("treehash always succeeds")
+}
+
 /// validate that a buffer is a valid CLVM serialization, and return the length
 /// of the CLVM object. This may fail if the serialization contains an invalid
 /// back-reference or if the buffer is truncated.
@@ -525,3 +536,22 @@ ae5c3c40c50832a7aecc0b3ba4646568a00c01289c45e1f03b2b488080808080"
         assert_eq!(len, buf.len() as u64);
     }
 }
+
+#[test]
+fn test_tree_hash_matches_stream() {
+    use super::node_from_bytes;
+
+    for h in [
+        "80",
+        "01",
+        "ff0180",
+        "ff8300ffffff86666f6f626172ff0280",
+        "ffff0102ff0304",
+    ] {
+        let buf = hex::decode(h).unwrap();
+        let mut a = Allocator::new();
+        let node = node_from_bytes(&mut a, &buf).unwrap();
+        let mut f = Cursor::new(buf.as_slice());
+        assert_eq!(tree_hash(&a, node), tree_hash_from_stream(&mut f).unwrap());
+    }
+}
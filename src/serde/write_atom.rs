@@ -1,3 +1,4 @@
+use crate::error_catalog::ATOM_TOO_BIG;
 use std::io;
 use std::io::ErrorKind;
 
@@ -41,7 +42,7 @@ fn write_atom_encoding_prefix_with_size<W: io::Write>(
             ((size) & 0xff) as u8,
         ])
     } else {
-        Err(io::Error::new(ErrorKind::InvalidData, "atom too big"))
+        Err(io::Error::new(ErrorKind::InvalidData, ATOM_TOO_BIG))
     }
 }
 
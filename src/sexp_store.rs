@@ -0,0 +1,210 @@
+use crate::allocator::{Allocator, Checkpoint, NodePtr, NodeVisitor, SExp};
+use crate::number::Number;
+use crate::reduction::EvalErr;
+
+// The subset of node storage operations the interpreter itself needs. The
+// interpreter (run_program) is generic over this trait, so programs can be run
+// against alternative node stores (e.g. memory mapped, persistent or
+// instrumented ones) without first copying them into an Allocator. Operators
+// are implemented by the Dialect, which is parameterized by the store type as
+// well.
+pub trait SExpStore {
+    // a snapshot of the store's state, which can be restored to. This is used
+    // to free all nodes allocated inside a softfork guard when exiting it
+    type Checkpoint;
+
+    fn nil(&self) -> NodePtr;
+    fn sexp(&self, node: NodePtr) -> SExp;
+    fn node(&self, node: NodePtr) -> NodeVisitor<'_>;
+    fn atom_len(&self, node: NodePtr) -> usize;
+
+    // returns the value of the atom, if it's a canonical, positive integer
+    // that fits in a u32
+    fn small_number(&self, node: NodePtr) -> Option<u32>;
+
+    fn new_pair(&mut self, first: NodePtr, rest: NodePtr) -> Result<NodePtr, EvalErr>;
+    fn new_number(&mut self, v: Number) -> Result<NodePtr, EvalErr>;
+
+    fn checkpoint(&self) -> Self::Checkpoint;
+    fn restore_checkpoint(&mut self, cp: &Self::Checkpoint);
+
+    fn next(&self, n: NodePtr) -> Option<(NodePtr, NodePtr)> {
+        match self.sexp(n) {
+            SExp::Pair(first, rest) => Some((first, rest)),
+            SExp::Atom => None,
+        }
+    }
+}
+
+impl SExpStore for Allocator {
+    type Checkpoint = Checkpoint;
+
+    #[inline(always)]
+    fn nil(&self) -> NodePtr {
+        Allocator::nil(self)
+    }
+
+    #[inline(always)]
+    fn sexp(&self, node: NodePtr) -> SExp {
+        Allocator::sexp(self, node)
+    }
+
+    #[inline(always)]
+    fn node(&self, node: NodePtr) -> NodeVisitor<'_> {
+        Allocator::node(self, node)
+    }
+
+    #[inline(always)]
+    fn atom_len(&self, node: NodePtr) -> usize {
+        Allocator::atom_len(self, node)
+    }
+
+    #[inline(always)]
+    fn small_number(&self, node: NodePtr) -> Option<u32> {
+        Allocator::small_number(self, node)
+    }
+
+    #[inline(always)]
+    fn new_pair(&mut self, first: NodePtr, rest: NodePtr) -> Result<NodePtr, EvalErr> {
+        Allocator::new_pair(self, first, rest)
+    }
+
+    #[inline(always)]
+    fn new_number(&mut self, v: Number) -> Result<NodePtr, EvalErr> {
+        Allocator::new_number(self, v)
+    }
+
+    #[inline(always)]
+    fn checkpoint(&self) -> Checkpoint {
+        Allocator::checkpoint(self)
+    }
+
+    #[inline(always)]
+    fn restore_checkpoint(&mut self, cp: &Checkpoint) {
+        Allocator::restore_checkpoint(self, cp)
+    }
+
+    #[inline(always)]
+    fn next(&self, n: NodePtr) -> Option<(NodePtr, NodePtr)> {
+        Allocator::next(self, n)
+    }
+}
+
+#[cfg(test)]
+use crate::chia_dialect::ChiaDialect;
+#[cfg(test)]
+use crate::cost::Cost;
+#[cfg(test)]
+use crate::dialect::{Dialect, OperatorSet};
+#[cfg(test)]
+use crate::reduction::{Reduction, Response};
+#[cfg(test)]
+use crate::run_program::run_program;
+#[cfg(test)]
+use crate::test_ops::parse_exp;
+
+// an instrumented store, counting the number of pairs the interpreter
+// allocates
+#[cfg(test)]
+struct CountingStore {
+    a: Allocator,
+    pairs: usize,
+}
+
+#[cfg(test)]
+impl SExpStore for CountingStore {
+    type Checkpoint = Checkpoint;
+
+    fn nil(&self) -> NodePtr {
+        self.a.nil()
+    }
+    fn sexp(&self, node: NodePtr) -> SExp {
+        self.a.sexp(node)
+    }
+    fn node(&self, node: NodePtr) -> NodeVisitor<'_> {
+        self.a.node(node)
+    }
+    fn atom_len(&self, node: NodePtr) -> usize {
+        self.a.atom_len(node)
+    }
+    fn small_number(&self, node: NodePtr) -> Option<u32> {
+        self.a.small_number(node)
+    }
+    fn new_pair(&mut self, first: NodePtr, rest: NodePtr) -> Result<NodePtr, EvalErr> {
+        self.pairs += 1;
+        self.a.new_pair(first, rest)
+    }
+    fn new_number(&mut self, v: Number) -> Result<NodePtr, EvalErr> {
+        self.a.new_number(v)
+    }
+    fn checkpoint(&self) -> Checkpoint {
+        self.a.checkpoint()
+    }
+    fn restore_checkpoint(&mut self, cp: &Checkpoint) {
+        self.a.restore_checkpoint(cp)
+    }
+}
+
+// operators are executed against the wrapped allocator
+#[cfg(test)]
+struct CountingDialect(ChiaDialect);
+
+#[cfg(test)]
+impl Dialect<CountingStore> for CountingDialect {
+    fn quote_kw(&self) -> u32 {
+        self.0.quote_kw()
+    }
+    fn apply_kw(&self) -> u32 {
+        self.0.apply_kw()
+    }
+    fn softfork_kw(&self) -> u32 {
+        self.0.softfork_kw()
+    }
+    fn softfork_extension(&self, ext: u32) -> OperatorSet {
+        self.0.softfork_extension(ext)
+    }
+    fn op(
+        &self,
+        store: &mut CountingStore,
+        op: NodePtr,
+        args: NodePtr,
+        max_cost: Cost,
+        extensions: OperatorSet,
+    ) -> Response {
+        self.0.op(&mut store.a, op, args, max_cost, extensions)
+    }
+    fn allow_unknown_ops(&self) -> bool {
+        self.0.allow_unknown_ops()
+    }
+}
+
+#[test]
+fn test_alternative_store() {
+    let mut a = Allocator::new();
+    let (prg, rest) = parse_exp(&mut a, "(c (q . 1) (+ (q . 2) (q . 3)))");
+    assert_eq!(rest, "");
+    let env = a.nil();
+    let expected = run_program(&mut a, &ChiaDialect::new(0), prg, env, 11000000000).unwrap();
+
+    let mut store = CountingStore { a, pairs: 0 };
+    let Reduction(cost, node) = run_program(
+        &mut store,
+        &CountingDialect(ChiaDialect::new(0)),
+        prg,
+        env,
+        11000000000,
+    )
+    .unwrap();
+    assert_eq!(cost, expected.0);
+
+    // the interpreter builds the argument lists for "c" (2 arguments) and
+    // "+" (2 arguments). The pair returned by "c" is allocated by the
+    // operator, which uses the wrapped allocator directly
+    assert_eq!(store.pairs, 4);
+
+    let SExp::Pair(first, rest) = store.sexp(node) else {
+        panic!("expected pair");
+    };
+    assert_eq!(store.small_number(first), Some(1));
+    assert_eq!(store.small_number(rest), Some(5));
+}
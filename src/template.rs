@@ -0,0 +1,278 @@
+use crate::allocator::{Allocator, NodePtr, SExp};
+use crate::reduction::EvalErr;
+use crate::serde::node_from_bytes_backrefs;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io;
+
+// Instantiates puzzle (or solution) templates. A template is a program where
+// some atoms are placeholders, distinguished by starting with a marker prefix
+// (e.g. b"$placeholder:"). Instantiating a template replaces every
+// placeholder with the value it's mapped to. A template is parsed once and
+// can then be instantiated any number of times, in the same allocator.
+//
+// Subtrees that don't contain any placeholders are not copied; the
+// instantiated program refers to the template's own nodes. Subtrees that are
+// shared in the template are also shared in the instantiated program.
+
+#[derive(Debug)]
+pub enum TemplateError {
+    // the template failed to parse
+    Io(io::Error),
+    // allocating the instantiated program failed
+    Eval(EvalErr),
+    // placeholders in the template that were not given a value
+    Unfilled(Vec<Vec<u8>>),
+    // values given for placeholders that don't exist in the template
+    UnknownPlaceholder(Vec<Vec<u8>>),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |keys: &[Vec<u8>]| {
+            keys.iter()
+                .map(|k| String::from_utf8_lossy(k).into_owned())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            TemplateError::Io(e) => write!(f, "invalid template: {e}"),
+            TemplateError::Eval(e) => write!(f, "{e}"),
+            TemplateError::Unfilled(keys) => write!(f, "unfilled placeholders: {}", list(keys)),
+            TemplateError::UnknownPlaceholder(keys) => {
+                write!(f, "unknown placeholders: {}", list(keys))
+            }
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl From<io::Error> for TemplateError {
+    fn from(e: io::Error) -> Self {
+        TemplateError::Io(e)
+    }
+}
+
+impl From<EvalErr> for TemplateError {
+    fn from(e: EvalErr) -> Self {
+        TemplateError::Eval(e)
+    }
+}
+
+pub struct Template {
+    root: NodePtr,
+    // the placeholder atoms, and their keys (the full atom)
+    placeholders: Vec<(NodePtr, Vec<u8>)>,
+    // the pairs that contain placeholders, directly or indirectly. Children
+    // come before their parents, which is the order they're rebuilt in
+    pairs: Vec<NodePtr>,
+    keys: HashSet<Vec<u8>>,
+}
+
+impl Template {
+    // a placeholder is any atom starting with marker (and longer than it)
+    pub fn new(a: &Allocator, root: NodePtr, marker: &[u8]) -> Self {
+        let mut placeholders = Vec::new();
+        let mut pairs = Vec::new();
+        // whether each visited node contains a placeholder
+        let mut visited = HashMap::<NodePtr, bool>::new();
+        let mut stack = vec![root];
+        while let Some(node) = stack.last().copied() {
+            if visited.contains_key(&node) {
+                stack.pop();
+                continue;
+            }
+            match a.sexp(node) {
+                SExp::Atom => {
+                    let atom = a.atom(node);
+                    let is_placeholder =
+                        atom.as_ref().len() > marker.len() && atom.as_ref().starts_with(marker);
+                    if is_placeholder {
+                        placeholders.push((node, atom.as_ref().to_vec()));
+                    }
+                    visited.insert(node, is_placeholder);
+                    stack.pop();
+                }
+                SExp::Pair(first, rest) => match (visited.get(&first), visited.get(&rest)) {
+                    (Some(f), Some(r)) => {
+                        let dirty = *f || *r;
+                        if dirty {
+                            pairs.push(node);
+                        }
+                        visited.insert(node, dirty);
+                        stack.pop();
+                    }
+                    _ => {
+                        stack.push(first);
+                        stack.push(rest);
+                    }
+                },
+            }
+        }
+        let keys = placeholders.iter().map(|(_, k)| k.clone()).collect();
+        Self {
+            root,
+            placeholders,
+            pairs,
+            keys,
+        }
+    }
+
+    // parses a serialized template (with or without back references)
+    pub fn from_bytes(a: &mut Allocator, buf: &[u8], marker: &[u8]) -> io::Result<Self> {
+        let root = node_from_bytes_backrefs(a, buf)?;
+        Ok(Self::new(a, root, marker))
+    }
+
+    // the distinct placeholders in the template, sorted
+    pub fn placeholders(&self) -> Vec<Vec<u8>> {
+        let mut ret: Vec<_> = self.keys.iter().cloned().collect();
+        ret.sort();
+        ret
+    }
+
+    // returns the program with every placeholder replaced by its value. Every
+    // placeholder must have a value, and every value must correspond to a
+    // placeholder
+    pub fn instantiate(
+        &self,
+        a: &mut Allocator,
+        values: &HashMap<Vec<u8>, NodePtr>,
+    ) -> Result<NodePtr, TemplateError> {
+        let mut unfilled: Vec<_> = self
+            .keys
+            .iter()
+            .filter(|k| !values.contains_key(*k))
+            .cloned()
+            .collect();
+        if !unfilled.is_empty() {
+            unfilled.sort();
+            return Err(TemplateError::Unfilled(unfilled));
+        }
+        let mut unknown: Vec<_> = values
+            .keys()
+            .filter(|k| !self.keys.contains(*k))
+            .cloned()
+            .collect();
+        if !unknown.is_empty() {
+            unknown.sort();
+            return Err(TemplateError::UnknownPlaceholder(unknown));
+        }
+
+        let mut replaced = HashMap::<NodePtr, NodePtr>::new();
+        for (node, key) in &self.placeholders {
+            replaced.insert(*node, values[key]);
+        }
+        for node in &self.pairs {
+            let SExp::Pair(first, rest) = a.sexp(*node) else {
+                unreachable!();
+            };
+            let first = *replaced.get(&first).unwrap_or(&first);
+            let rest = *replaced.get(&rest).unwrap_or(&rest);
+            let new_node = a.new_pair(first, rest)?;
+            replaced.insert(*node, new_node);
+        }
+        Ok(*replaced.get(&self.root).unwrap_or(&self.root))
+    }
+}
+
+#[cfg(test)]
+use crate::serde::node_to_bytes;
+#[cfg(test)]
+use crate::test_ops::parse_exp;
+
+#[cfg(test)]
+fn values(a: &mut Allocator, pairs: &[(&str, &str)]) -> HashMap<Vec<u8>, NodePtr> {
+    let mut ret = HashMap::new();
+    for (k, v) in pairs {
+        let (node, rest) = parse_exp(a, v);
+        assert_eq!(rest, "");
+        ret.insert(k.as_bytes().to_vec(), node);
+    }
+    ret
+}
+
+#[cfg(test)]
+fn check(a: &mut Allocator, node: NodePtr, expected: &str) {
+    let (expected, rest) = parse_exp(a, expected);
+    assert_eq!(rest, "");
+    assert_eq!(
+        node_to_bytes(a, node).unwrap(),
+        node_to_bytes(a, expected).unwrap()
+    );
+}
+
+#[test]
+fn test_instantiate() {
+    let mut a = Allocator::new();
+    let shared = a.new_atom(b"$amount").unwrap();
+    let shared = a.new_pair(shared, a.nil()).unwrap();
+    let (prefix, _) = parse_exp(&mut a, "(q . 1)");
+    let inner = a.new_pair(prefix, shared).unwrap();
+    let puzzle_hash = a.new_atom(b"$puzzle_hash").unwrap();
+    let root = a.new_pair(puzzle_hash, shared).unwrap();
+    let root = a.new_pair(inner, root).unwrap();
+
+    let t = Template::new(&a, root, b"$");
+    assert_eq!(
+        t.placeholders(),
+        vec![b"$amount".to_vec(), b"$puzzle_hash".to_vec()]
+    );
+
+    let v = values(&mut a, &[("$amount", "1000"), ("$puzzle_hash", "0xcafe")]);
+    let p1 = t.instantiate(&mut a, &v).unwrap();
+    check(&mut a, p1, "(((q . 1) 1000) 0xcafe 1000)");
+
+    // subtrees without placeholders are not copied, and shared subtrees stay
+    // shared
+    let SExp::Pair(new_inner, new_rest) = a.sexp(p1) else {
+        panic!("expected pair");
+    };
+    let SExp::Pair(new_prefix, new_shared1) = a.sexp(new_inner) else {
+        panic!("expected pair");
+    };
+    let SExp::Pair(_, new_shared2) = a.sexp(new_rest) else {
+        panic!("expected pair");
+    };
+    assert_eq!(new_prefix, prefix);
+    assert_eq!(new_shared1, new_shared2);
+
+    // the template can be instantiated again
+    let v = values(&mut a, &[("$amount", "42"), ("$puzzle_hash", "(1 2)")]);
+    let p2 = t.instantiate(&mut a, &v).unwrap();
+    check(&mut a, p2, "(((q . 1) 42) (1 2) 42)");
+}
+
+#[test]
+fn test_instantiate_errors() {
+    let mut a = Allocator::new();
+    let template = {
+        let (node, _) = parse_exp(&mut a, "(c (q . 1) 2)");
+        node_to_bytes(&a, node).unwrap()
+    };
+    // without placeholders, the template is the program itself
+    let t = Template::from_bytes(&mut a, &template, b"$").unwrap();
+    assert!(t.placeholders().is_empty());
+    let p = t.instantiate(&mut a, &HashMap::new()).unwrap();
+    check(&mut a, p, "(c (q . 1) 2)");
+
+    let v = values(&mut a, &[("$foo", "1"), ("$bar", "2")]);
+    let err = t.instantiate(&mut a, &v).unwrap_err();
+    assert_eq!(err.to_string(), "unknown placeholders: $bar, $foo");
+
+    let foo = a.new_atom(b"$foo").unwrap();
+    let bar = a.new_atom(b"$bar").unwrap();
+    let root = a.new_pair(foo, bar).unwrap();
+    let t = Template::new(&a, root, b"$");
+    let v = values(&mut a, &[("$foo", "1")]);
+    let err = t.instantiate(&mut a, &v).unwrap_err();
+    assert_eq!(err.to_string(), "unfilled placeholders: $bar");
+
+    // the marker itself is not a placeholder
+    let root = a.new_atom(b"$").unwrap();
+    let t = Template::new(&a, root, b"$");
+    assert!(t.placeholders().is_empty());
+
+    assert!(Template::from_bytes(&mut a, &[0xff, 0x01], b"$").is_err());
+}
@@ -0,0 +1,337 @@
+use crate::allocator::{Allocator, NodePtr, SExp};
+use crate::cost::Cost;
+use crate::dialect::{Dialect, OperatorSet};
+use crate::err_utils::err;
+use crate::error_catalog::{fill, FAIL_WITH};
+use crate::op_utils::get_args;
+use crate::reduction::{Reduction, Response};
+use std::cell::RefCell;
+
+// The test dialect wraps another dialect and adds operators meant for unit
+// tests of CLVM programs. They all use opcodes with the 0xffff prefix, which
+// ChiaDialect considers reserved and always fails on (regardless of flags). So
+// no consensus flag can enable these, and a program using them can never pass
+// on chain.
+//
+// The operators don't have any cost, so adding them to a program doesn't
+// change its cost.
+
+// (assert_equal A B) fails if A and B are not the same tree
+pub const ASSERT_EQUAL_OPCODE: [u8; 3] = [0xff, 0xff, 0x01];
+
+// (print ...) appends all its arguments to the dialect's log, and returns the
+// last one (or nil if there are no arguments)
+pub const PRINT_OPCODE: [u8; 3] = [0xff, 0xff, 0x02];
+
+// (fail_with ...) fails, with an error message containing all its arguments
+pub const FAIL_WITH_OPCODE: [u8; 3] = [0xff, 0xff, 0x03];
+
+pub struct TestDialect<D: Dialect> {
+    dialect: D,
+    log: RefCell<Vec<String>>,
+}
+
+impl<D: Dialect> TestDialect<D> {
+    pub fn new(dialect: D) -> Self {
+        Self {
+            dialect,
+            log: RefCell::new(Vec::new()),
+        }
+    }
+
+    // returns the lines printed so far, and clears the log
+    pub fn take_log(&self) -> Vec<String> {
+        self.log.take()
+    }
+}
+
+fn format_atom(buf: &[u8]) -> String {
+    if buf.is_empty() {
+        "()".to_string()
+    } else if buf.len() <= 4
+        && (buf[0] & 0x80) == 0
+        && (buf[0] != 0 || (buf.len() > 1 && (buf[1] & 0x80) != 0))
+    {
+        let mut val: u32 = 0;
+        for b in buf {
+            val = (val << 8) | *b as u32;
+        }
+        val.to_string()
+    } else if buf.iter().all(|b| (0x20..0x7f).contains(b)) {
+        format!("\"{}\"", std::str::from_utf8(buf).unwrap())
+    } else {
+        let mut ret = "0x".to_string();
+        for b in buf {
+            ret += &format!("{b:02x}");
+        }
+        ret
+    }
+}
+
+enum FormatOp {
+    // formats a node
+    Node(NodePtr),
+    // formats the rest of a list, after its first item, and closes it
+    Rest(NodePtr),
+}
+
+// formats a tree in a human readable form. Atoms are printed as integers if
+// they're small, canonical, positive integers, as strings if they only contain
+// printable ASCII characters and as hex otherwise
+pub fn format_node(a: &Allocator, node: NodePtr) -> String {
+    let mut ret = String::new();
+    let mut ops = vec![FormatOp::Node(node)];
+    while let Some(op) = ops.pop() {
+        match op {
+            FormatOp::Node(node) => match a.sexp(node) {
+                SExp::Atom => ret += &format_atom(a.atom(node).as_ref()),
+                SExp::Pair(first, rest) => {
+                    ret += "(";
+                    ops.push(FormatOp::Rest(rest));
+                    ops.push(FormatOp::Node(first));
+                }
+            },
+            FormatOp::Rest(rest) => match a.sexp(rest) {
+                SExp::Pair(first, rest) => {
+                    ret += " ";
+                    ops.push(FormatOp::Rest(rest));
+                    ops.push(FormatOp::Node(first));
+                }
+                SExp::Atom => {
+                    if !a.atom(rest).as_ref().is_empty() {
+                        ret += " . ";
+                        ret += &format_atom(a.atom(rest).as_ref());
+                    }
+                    ret += ")";
+                }
+            },
+        }
+    }
+    ret
+}
+
+fn format_args(a: &Allocator, mut args: NodePtr) -> String {
+    let mut items = Vec::<String>::new();
+    while let Some((arg, rest)) = a.next(args) {
+        items.push(format_node(a, arg));
+        args = rest;
+    }
+    items.join(" ")
+}
+
+fn tree_eq(a: &Allocator, lhs: NodePtr, rhs: NodePtr) -> bool {
+    let mut stack = vec![(lhs, rhs)];
+    while let Some((lhs, rhs)) = stack.pop() {
+        match (a.sexp(lhs), a.sexp(rhs)) {
+            (SExp::Pair(l0, l1), SExp::Pair(r0, r1)) => {
+                stack.push((l1, r1));
+                stack.push((l0, r0));
+            }
+            (SExp::Atom, SExp::Atom) => {
+                if !a.atom_eq(lhs, rhs) {
+                    return false;
+                }
+            }
+            _ => return false,
+        }
+    }
+    true
+}
+
+impl<D: Dialect> Dialect for TestDialect<D> {
+    fn op(
+        &self,
+        allocator: &mut Allocator,
+        o: NodePtr,
+        argument_list: NodePtr,
+        max_cost: Cost,
+        extension: OperatorSet,
+    ) -> Response {
+        let op = allocator.atom(o);
+        if op.as_ref() == ASSERT_EQUAL_OPCODE {
+            let [lhs, rhs] = get_args::<2>(allocator, argument_list, "assert_equal")?;
+            if tree_eq(allocator, lhs, rhs) {
+                Ok(Reduction(0, allocator.nil()))
+            } else {
+                err(
+                    argument_list,
+                    &format!(
+                        "assert_equal failed: {} != {}",
+                        format_node(allocator, lhs),
+                        format_node(allocator, rhs)
+                    ),
+                )
+            }
+        } else if op.as_ref() == PRINT_OPCODE {
+            self.log
+                .borrow_mut()
+                .push(format_args(allocator, argument_list));
+            let mut ret = allocator.nil();
+            let mut args = argument_list;
+            while let Some((arg, rest)) = allocator.next(args) {
+                ret = arg;
+                args = rest;
+            }
+            Ok(Reduction(0, ret))
+        } else if op.as_ref() == FAIL_WITH_OPCODE {
+            err(
+                argument_list,
+                &fill(FAIL_WITH, &[&format_args(allocator, argument_list)]),
+            )
+        } else {
+            self.dialect
+                .op(allocator, o, argument_list, max_cost, extension)
+        }
+    }
+
+    fn quote_kw(&self) -> u32 {
+        self.dialect.quote_kw()
+    }
+    fn apply_kw(&self) -> u32 {
+        self.dialect.apply_kw()
+    }
+    fn softfork_kw(&self) -> u32 {
+        self.dialect.softfork_kw()
+    }
+
+    fn softfork_extension(&self, ext: u32) -> OperatorSet {
+        self.dialect.softfork_extension(ext)
+    }
+
+    fn allow_unknown_ops(&self) -> bool {
+        self.dialect.allow_unknown_ops()
+    }
+    fn cost_schedule_version(&self) -> u32 {
+        self.dialect.cost_schedule_version()
+    }
+}
+
+#[cfg(test)]
+use crate::chia_dialect::{ChiaDialect, MEMPOOL_MODE};
+#[cfg(test)]
+use crate::run_program::run_program;
+#[cfg(test)]
+use crate::test_ops::parse_exp;
+#[cfg(test)]
+use rstest::rstest;
+
+#[cfg(test)]
+fn run(dialect: &impl Dialect, prg: &str) -> Result<(Cost, String), String> {
+    let mut a = Allocator::new();
+    let (prg, rest) = parse_exp(&mut a, prg);
+    assert_eq!(rest, "");
+    let env = a.nil();
+    match run_program(&mut a, dialect, prg, env, 11000000000) {
+        Ok(Reduction(cost, node)) => Ok((cost, format_node(&a, node))),
+        Err(e) => Err(e.1),
+    }
+}
+
+#[cfg(test)]
+#[rstest]
+#[case("()", "()")]
+#[case("1", "1")]
+#[case("0x00", "0x00")]
+#[case("0x0080", "128")]
+#[case("0x7fffffff", "2147483647")]
+#[case("0x80", "0x80")]
+#[case("0x0100000000", "0x0100000000")]
+#[case("\"foobar\"", "\"foobar\"")]
+#[case("(1 2 3)", "(1 2 3)")]
+#[case("(1 (2 \"a b c\") . 3)", "(1 (2 \"a b c\") . 3)")]
+#[case("((1 . 2) . (3 . ()))", "((1 . 2) 3)")]
+fn test_format_node(#[case] input: &str, #[case] expected: &str) {
+    let mut a = Allocator::new();
+    let (node, rest) = parse_exp(&mut a, input);
+    assert_eq!(rest, "");
+    assert_eq!(format_node(&a, node), expected);
+}
+
+#[test]
+fn test_assert_equal() {
+    let dialect = TestDialect::new(ChiaDialect::new(0));
+    // the only cost is the two quotes and the operator invocation
+    assert_eq!(
+        run(&dialect, "(0xffff01 (q . 1) (q . 1))").unwrap(),
+        (41, "()".to_string())
+    );
+    assert_eq!(
+        run(&dialect, "(0xffff01 (c (q . 1) (q . 2)) (q 1 . 2))")
+            .unwrap()
+            .1,
+        "()"
+    );
+    assert_eq!(
+        run(&dialect, "(0xffff01 (q 1 2) (q 1 3))").unwrap_err(),
+        "assert_equal failed: (1 2) != (1 3)"
+    );
+    assert_eq!(
+        run(&dialect, "(0xffff01 (q . 1))").unwrap_err(),
+        "assert_equal takes exactly 2 arguments"
+    );
+}
+
+#[test]
+fn test_deep_tree() {
+    // format_node() and tree_eq() don't recurse, so deep trees don't overflow
+    // the stack
+    let mut a = Allocator::new();
+    let mut lhs = a.nil();
+    let mut rhs = a.nil();
+    for _ in 0..1000000 {
+        lhs = a.new_pair(lhs, a.nil()).unwrap();
+        rhs = a.new_pair(rhs, a.nil()).unwrap();
+    }
+    assert!(tree_eq(&a, lhs, rhs));
+    let one = a.one();
+    let other = a.new_pair(one, rhs).unwrap();
+    assert!(!tree_eq(&a, lhs, other));
+    let expected = "(".repeat(1000000) + "()" + &")".repeat(1000000);
+    assert!(format_node(&a, lhs) == expected);
+}
+
+#[test]
+fn test_print() {
+    let dialect = TestDialect::new(ChiaDialect::new(0));
+    assert_eq!(
+        run(
+            &dialect,
+            "(+ (0xffff02 (q . \"first\") (q . 1)) (0xffff02 (q . \"second\") (q . 0x1337)))"
+        )
+        .unwrap()
+        .1,
+        "4920"
+    );
+    // the inner calls are evaluated right-to-left
+    assert_eq!(dialect.take_log(), vec!["\"second\" 4919", "\"first\" 1"]);
+    assert!(dialect.take_log().is_empty());
+
+    assert_eq!(run(&dialect, "(0xffff02)").unwrap().1, "()");
+    assert_eq!(dialect.take_log(), vec![""]);
+}
+
+#[test]
+fn test_fail_with() {
+    let dialect = TestDialect::new(ChiaDialect::new(0));
+    assert_eq!(
+        run(&dialect, "(0xffff03 (q . \"unexpected value\") (q 1 2))").unwrap_err(),
+        "fail_with: \"unexpected value\" (1 2)"
+    );
+}
+
+#[test]
+fn test_consensus_dialect() {
+    // none of the test operators are available in the consensus dialect,
+    // regardless of flags
+    for flags in [0, MEMPOOL_MODE, u32::MAX] {
+        let dialect = ChiaDialect::new(flags);
+        for prg in [
+            "(0xffff01 (q . 1) (q . 1))",
+            "(0xffff02 (q . 1))",
+            "(0xffff03 (q . 1))",
+        ] {
+            let e = run(&dialect, prg).unwrap_err();
+            assert!(e == "reserved operator" || e == "unimplemented operator");
+        }
+    }
+}
@@ -1,21 +1,25 @@
 use crate::allocator::{Allocator, NodePtr, SExp};
 use crate::bls_ops::{
-    op_bls_g1_multiply, op_bls_g1_negate, op_bls_g1_subtract, op_bls_g2_add, op_bls_g2_multiply,
-    op_bls_g2_negate, op_bls_g2_subtract, op_bls_map_to_g1, op_bls_map_to_g2,
-    op_bls_pairing_identity, op_bls_verify,
+    op_bls_g1_multiply, op_bls_g1_negate, op_bls_g1_subtract, op_bls_g1_valid, op_bls_g2_add,
+    op_bls_g2_multiply, op_bls_g2_negate, op_bls_g2_subtract, op_bls_g2_valid, op_bls_map_to_g1,
+    op_bls_map_to_g2, op_bls_pairing_identity, op_bls_verify,
+};
+use crate::core_ops::{
+    op_cons, op_eq, op_first, op_if, op_listp, op_raise, op_rest, op_tree_depth, op_tree_size,
 };
-use crate::core_ops::{op_cons, op_eq, op_first, op_if, op_listp, op_raise, op_rest};
 use crate::cost::Cost;
+use crate::ed25519_ops::op_ed25519_verify;
+use crate::hex;
 use crate::more_ops::{
-    op_add, op_all, op_any, op_ash, op_coinid, op_concat, op_div, op_divmod, op_gr, op_gr_bytes,
-    op_logand, op_logior, op_lognot, op_logxor, op_lsh, op_mod, op_modpow, op_multiply, op_not,
-    op_point_add, op_pubkey_for_exp, op_sha256, op_strlen, op_substr, op_subtract,
+    op_add, op_all, op_any, op_ash, op_bitlength, op_coinid, op_concat, op_div, op_divexact,
+    op_divmod, op_gcd, op_gr, op_gr_bytes, op_logand, op_logior, op_lognot, op_logxor, op_lsh,
+    op_mod, op_modpow, op_mul_div_floor, op_multiply, op_not, op_point_add, op_popcount,
+    op_pubkey_for_exp, op_sha256, op_sqrt_floor, op_strlen, op_substr, op_subtract,
 };
 use crate::number::Number;
 use crate::reduction::{EvalErr, Reduction, Response};
 use crate::secp_ops::{op_secp256k1_verify, op_secp256r1_verify};
 
-use hex::FromHex;
 use num_traits::Num;
 use std::cmp::min;
 use std::collections::HashMap;
@@ -28,7 +32,7 @@ fn parse_atom(a: &mut Allocator, v: &str) -> NodePtr {
     assert!(!v.is_empty());
 
     if v.starts_with("0x") {
-        let buf = Vec::from_hex(v.strip_prefix("0x").unwrap()).unwrap();
+        let buf = hex::decode(v).unwrap_or_else(|e| panic!("{v}: {e}"));
         return a.new_atom(&buf).unwrap();
     }
 
@@ -99,8 +103,19 @@ fn parse_atom(a: &mut Allocator, v: &str) -> NodePtr {
             "g2_map" => a.new_atom(&[57]).unwrap(),
             "bls_pairing_identity" => a.new_atom(&[58]).unwrap(),
             "bls_verify" => a.new_atom(&[59]).unwrap(),
+            "g1_valid?" => a.new_atom(&[62]).unwrap(),
+            "g2_valid?" => a.new_atom(&[63]).unwrap(),
             "secp256k1_verify" => a.new_atom(&[0x13, 0xd6, 0x1f, 0x00]).unwrap(),
             "secp256r1_verify" => a.new_atom(&[0x1c, 0x3a, 0x8f, 0x00]).unwrap(),
+            "ed25519_verify" => a.new_atom(&[0x06, 0xdd, 0xcf, 0x00]).unwrap(),
+            "divexact" => a.new_atom(&[37]).unwrap(),
+            "gcd" => a.new_atom(&[38]).unwrap(),
+            "mul_div_floor" => a.new_atom(&[39]).unwrap(),
+            "sqrt_floor" => a.new_atom(&[40]).unwrap(),
+            "popcount" => a.new_atom(&[41]).unwrap(),
+            "bitlength" => a.new_atom(&[42]).unwrap(),
+            "tree_size" => a.new_atom(&[43]).unwrap(),
+            "tree_depth" => a.new_atom(&[44]).unwrap(),
             _ => {
                 panic!("atom not supported \"{}\"", v);
             }
@@ -222,9 +237,16 @@ use rstest::rstest;
 #[case("test-blspy-pairing")]
 #[case("test-blspy-verify")]
 #[case("test-bls-zk")]
+#[case("test-bls-valid")]
 #[case("test-secp-verify")]
 #[case("test-secp256k1")]
 #[case("test-secp256r1")]
+#[case("test-ed25519")]
+#[case("test-ed25519-verify")]
+#[case("test-divexact-gcd")]
+#[case("test-fixed-point")]
+#[case("test-bit-count")]
+#[case("test-tree-size")]
 #[case("test-modpow")]
 #[case("test-sha256")]
 fn test_ops(#[case] filename: &str) {
@@ -277,8 +299,19 @@ fn test_ops(#[case] filename: &str) {
         ("g2_map", op_bls_map_to_g2 as Opf),
         ("bls_pairing_identity", op_bls_pairing_identity as Opf),
         ("bls_verify", op_bls_verify as Opf),
+        ("g1_valid?", op_bls_g1_valid as Opf),
+        ("g2_valid?", op_bls_g2_valid as Opf),
         ("secp256k1_verify", op_secp256k1_verify as Opf),
         ("secp256r1_verify", op_secp256r1_verify as Opf),
+        ("ed25519_verify", op_ed25519_verify as Opf),
+        ("divexact", op_divexact as Opf),
+        ("gcd", op_gcd as Opf),
+        ("mul_div_floor", op_mul_div_floor as Opf),
+        ("sqrt_floor", op_sqrt_floor as Opf),
+        ("popcount", op_popcount as Opf),
+        ("bitlength", op_bitlength as Opf),
+        ("tree_size", op_tree_size as Opf),
+        ("tree_depth", op_tree_depth as Opf),
         ("modpow", op_modpow as Opf),
     ]);
 
@@ -364,7 +397,7 @@ struct EvalFTracker {
 #[cfg(feature = "pre-eval")]
 use crate::chia_dialect::{ChiaDialect, NO_UNKNOWN_OPS};
 #[cfg(feature = "pre-eval")]
-use crate::run_program::run_program_with_pre_eval;
+use crate::run_program::{run_program_with_pre_eval, PreEval};
 #[cfg(feature = "pre-eval")]
 use std::cell::RefCell;
 #[cfg(feature = "pre-eval")]
@@ -408,13 +441,7 @@ fn test_pre_eval_and_post_eval() {
 
     let tracking = Rc::new(RefCell::new(HashMap::new()));
     let pre_eval_tracking = tracking.clone();
-    let pre_eval_f: Box<
-        dyn Fn(
-            &mut Allocator,
-            NodePtr,
-            NodePtr,
-        ) -> Result<Option<Box<(dyn Fn(Option<NodePtr>))>>, EvalErr>,
-    > = Box::new(move |_allocator, prog, args| {
+    let pre_eval_f: PreEval = Box::new(move |_allocator, prog, args| {
         let tracking_key = pre_eval_tracking.borrow().len();
         // Ensure lifetime of mutable borrow is contained.
         // It must end before the lifetime of the following closure.
@@ -468,14 +495,16 @@ fn test_pre_eval_and_post_eval() {
     // args consed
     let args_consed = allocator.new_pair(a99, a101).unwrap();
 
-    let mut desired_outcomes = Vec::new(); // Not in order.
-    desired_outcomes.push((args, NodePtr::NIL, arg_mid));
-    desired_outcomes.push((f_quoted, NodePtr::NIL, f_expr));
-    desired_outcomes.push((a2, arg_mid, a99));
-    desired_outcomes.push((a5, arg_mid, a101));
-    desired_outcomes.push((cons_expr, arg_mid, args_consed));
-    desired_outcomes.push((f_expr, arg_mid, a99));
-    desired_outcomes.push((program, NodePtr::NIL, a99));
+    // Not in order.
+    let desired_outcomes = [
+        (args, NodePtr::NIL, arg_mid),
+        (f_quoted, NodePtr::NIL, f_expr),
+        (a2, arg_mid, a99),
+        (a5, arg_mid, a101),
+        (cons_expr, arg_mid, args_consed),
+        (f_expr, arg_mid, a99),
+        (program, NodePtr::NIL, a99),
+    ];
 
     let mut found_outcomes = HashSet::new();
     let tracking_examine = tracking.borrow();
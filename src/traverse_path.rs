@@ -1,12 +1,14 @@
-use crate::allocator::{Allocator, NodePtr, SExp};
+use crate::allocator::{NodePtr, NodeVisitor, SExp};
 use crate::cost::Cost;
+use crate::cost_schedule_v1::{
+    TRAVERSE_BASE_COST, TRAVERSE_COST_PER_BIT, TRAVERSE_COST_PER_ZERO_BYTE,
+};
+use crate::error_catalog::{PATH_INTO_ATOM, PATH_MUST_BE_ATOM};
+use crate::number::Number;
 use crate::reduction::{EvalErr, Reduction, Response};
-
-// lowered from measured 147 per bit. It doesn't seem to take this long in
-// practice
-const TRAVERSE_BASE_COST: Cost = 40;
-const TRAVERSE_COST_PER_ZERO_BYTE: Cost = 4;
-const TRAVERSE_COST_PER_BIT: Cost = 4;
+use crate::sexp_store::SExpStore;
+use num_bigint::Sign;
+use std::fmt;
 
 // `run_program` has two stacks: the operand stack (of `Node` objects) and the
 // operator stack (of Operation)
@@ -31,7 +33,62 @@ const fn first_non_zero(buf: &[u8]) -> usize {
     c
 }
 
-pub fn traverse_path(allocator: &Allocator, node_index: &[u8], args: NodePtr) -> Response {
+// why validate_path() failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathError {
+    // the path is a pair
+    NotAtom(NodePtr),
+    // the path runs into an atom after following the first bits of it. reached
+    // is the (shorter) path leading to that atom, to help locate where a
+    // program and the environment it's run with disagree
+    IntoAtom {
+        atom: NodePtr,
+        reached: Number,
+        bits: u64,
+    },
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAtom(_) => f.write_str(PATH_MUST_BE_ATOM),
+            Self::IntoAtom { reached, bits, .. } => write!(
+                f,
+                "{PATH_INTO_ATOM} (reached atom at path {reached} after {bits} bits)"
+            ),
+        }
+    }
+}
+
+// the error the interpreter fails with
+impl From<PathError> for EvalErr {
+    fn from(e: PathError) -> Self {
+        match e {
+            PathError::NotAtom(node) => EvalErr(node, PATH_MUST_BE_ATOM.into()),
+            PathError::IntoAtom { atom, .. } => EvalErr(atom, PATH_INTO_ATOM.into()),
+        }
+    }
+}
+
+// the atom a path ran into, and the number of bits of the path followed to
+// reach it
+struct IntoAtom(NodePtr, u64);
+
+impl From<IntoAtom> for EvalErr {
+    fn from(e: IntoAtom) -> Self {
+        EvalErr(e.0, PATH_INTO_ATOM.into())
+    }
+}
+
+pub fn traverse_path(allocator: &impl SExpStore, node_index: &[u8], args: NodePtr) -> Response {
+    Ok(follow_path(allocator, node_index, args)?)
+}
+
+fn follow_path(
+    allocator: &impl SExpStore,
+    node_index: &[u8],
+    args: NodePtr,
+) -> Result<Reduction, IntoAtom> {
     let mut arg_list: NodePtr = args;
 
     // find first non-zero byte
@@ -51,11 +108,12 @@ pub fn traverse_path(allocator: &Allocator, node_index: &[u8], args: NodePtr) ->
     // follow through the bits, moving left and right
     let mut byte_idx = node_index.len() - 1;
     let mut bitmask = 0x01;
+    let mut bits = 0;
     while byte_idx > first_bit_byte_index || bitmask < last_bitmask {
         let is_bit_set: bool = (node_index[byte_idx] & bitmask) != 0;
         match allocator.sexp(arg_list) {
             SExp::Atom => {
-                return Err(EvalErr(arg_list, "path into atom".into()));
+                return Err(IntoAtom(arg_list, bits));
             }
             SExp::Pair(left, right) => {
                 arg_list = if is_bit_set { right } else { left };
@@ -67,6 +125,7 @@ pub fn traverse_path(allocator: &Allocator, node_index: &[u8], args: NodePtr) ->
         } else {
             bitmask <<= 1;
         }
+        bits += 1;
         cost += TRAVERSE_COST_PER_BIT;
     }
     Ok(Reduction(cost, arg_list))
@@ -75,7 +134,15 @@ pub fn traverse_path(allocator: &Allocator, node_index: &[u8], args: NodePtr) ->
 // The cost calculation for this version of traverse_path assumes the node_index has the canonical
 // integer representation (which is true for SmallAtom in the allocator). If there are any
 // redundant leading zeros, the slow path must be used
-pub fn traverse_path_fast(allocator: &Allocator, mut node_index: u32, args: NodePtr) -> Response {
+pub fn traverse_path_fast(allocator: &impl SExpStore, node_index: u32, args: NodePtr) -> Response {
+    Ok(follow_path_fast(allocator, node_index, args)?)
+}
+
+fn follow_path_fast(
+    allocator: &impl SExpStore,
+    mut node_index: u32,
+    args: NodePtr,
+) -> Result<Reduction, IntoAtom> {
     if node_index == 0 {
         return Ok(Reduction(
             TRAVERSE_BASE_COST + TRAVERSE_COST_PER_BIT,
@@ -89,7 +156,7 @@ pub fn traverse_path_fast(allocator: &Allocator, mut node_index: u32, args: Node
     let mut num_bits = 0;
     while node_index != 1 {
         let SExp::Pair(left, right) = allocator.sexp(arg_list) else {
-            return Err(EvalErr(arg_list, "path into atom".into()));
+            return Err(IntoAtom(arg_list, num_bits));
         };
 
         let is_bit_set: bool = (node_index & 0x01) != 0;
@@ -108,6 +175,35 @@ pub fn traverse_path_fast(allocator: &Allocator, mut node_index: u32, args: Node
     Ok(Reduction(cost, arg_list))
 }
 
+// returns the node the path (an atom) refers to in env. No cost is charged.
+// This can be used to check the paths a program refers to against the
+// environment it's (e.g.) curried with, before running it. Unlike the
+// interpreter's error, PathError tells where the path runs into an atom
+pub fn validate_path(
+    allocator: &impl SExpStore,
+    path: NodePtr,
+    env: NodePtr,
+) -> Result<NodePtr, PathError> {
+    let (r, path) = match allocator.node(path) {
+        NodeVisitor::Buffer(buf) => (
+            follow_path(allocator, buf, env),
+            Number::from_bytes_be(Sign::Plus, buf),
+        ),
+        NodeVisitor::U32(val) => (follow_path_fast(allocator, val, env), val.into()),
+        NodeVisitor::Pair(_, _) => {
+            return Err(PathError::NotAtom(path));
+        }
+    };
+    r.map(|r| r.1).map_err(|IntoAtom(atom, bits)| {
+        let sentinel = Number::from(1) << bits;
+        PathError::IntoAtom {
+            atom,
+            reached: (path & (&sentinel - 1)) | sentinel,
+            bits,
+        }
+    })
+}
+
 #[test]
 fn test_msb_mask() {
     assert_eq!(msb_mask(0x0), 0x0);
@@ -260,3 +356,53 @@ fn test_traverse_path_fast_fast() {
         EvalErr(n2, "path into atom".to_string())
     );
 }
+
+#[test]
+fn test_validate_path() {
+    use crate::allocator::Allocator;
+
+    let mut a = Allocator::new();
+    let nul = a.nil();
+    let n1 = a.new_atom(&[0, 1, 2]).unwrap();
+    let n2 = a.new_atom(&[4, 5, 6]).unwrap();
+    let list = a.new_pair(n1, nul).unwrap();
+    let list = a.new_pair(n2, list).unwrap();
+
+    let path = a.new_small_number(0b101).unwrap();
+    assert_eq!(validate_path(&a, path, list).unwrap(), n1);
+
+    // paths with redundant leading zeros take the slow path
+    let path = a.new_atom(&[0, 0, 0b101]).unwrap();
+    assert_eq!(validate_path(&a, path, list).unwrap(), n1);
+
+    let path = a.new_small_number(0b1110).unwrap();
+    let e = validate_path(&a, path, list).unwrap_err();
+    assert_eq!(
+        e,
+        PathError::IntoAtom {
+            atom: n2,
+            reached: 2.into(),
+            bits: 1
+        }
+    );
+    assert_eq!(
+        e.to_string(),
+        "path into atom (reached atom at path 2 after 1 bits)"
+    );
+    assert_eq!(EvalErr::from(e), EvalErr(n2, "path into atom".to_string()));
+
+    // a long path, running into an atom early
+    let path = a.new_atom(&[0x01, 0, 0, 0, 0, 0b1011]).unwrap();
+    assert_eq!(
+        validate_path(&a, path, list).unwrap_err(),
+        PathError::IntoAtom {
+            atom: nul,
+            reached: 7.into(),
+            bits: 2
+        }
+    );
+
+    let e = validate_path(&a, list, list).unwrap_err();
+    assert_eq!(e, PathError::NotAtom(list));
+    assert_eq!(EvalErr::from(e), EvalErr(list, PATH_MUST_BE_ATOM.into()));
+}
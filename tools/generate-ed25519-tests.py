@@ -0,0 +1,49 @@
+from cryptography.hazmat.primitives.asymmetric.ed25519 import Ed25519PrivateKey
+from cryptography.hazmat.primitives.serialization import Encoding, PublicFormat
+from random import randbytes, randint, seed, sample
+
+def flip_bit(b: bytes) -> bytearray:
+    idx = randint(0, len(b) - 1)
+    bit = 1 << randint(0, 7)
+    ret = bytearray(b)
+    ret[idx] ^= bit
+    return ret
+
+def print_validation_test_case(f, num_cases, filter_pk, filter_msg, filter_sig, expect: str):
+    sks = sample(secret_keys, num_cases)
+
+    for sk in sks:
+        pk = sk.public_key().public_bytes(Encoding.Raw, PublicFormat.Raw)
+        msg = randbytes(randint(1,100))
+        sig = sk.sign(msg)
+        cost = 450000 + 50 * len(msg)
+        f.write(f"ed25519_verify 0x{bytes(filter_pk(pk)).hex()} 0x{bytes(filter_msg(msg)).hex()} 0x{bytes(filter_sig(sig)).hex()}")
+
+        f.write(f" => {expect}")
+        if expect != "FAIL":
+            f.write(f" | {cost}")
+        f.write("\n")
+
+
+seed(1337)
+
+SIZE = 30
+
+# generate a bunch of keys
+secret_keys = []
+for i in range(SIZE):
+    secret_keys.append(Ed25519PrivateKey.from_private_bytes(randbytes(32)))
+
+with open("../op-tests/test-ed25519.txt", "w+") as f:
+    f.write("; This file was generated by tools/generate-ed25519-tests.py\n\n")
+
+    print_validation_test_case(f, SIZE, lambda pk: pk, lambda msg: msg, lambda sig: sig, "0")
+
+    # negative tests (alter public key)
+    print_validation_test_case(f, 3, flip_bit, lambda msg: msg, lambda sig: sig, "FAIL")
+
+    # negative tests (alter message)
+    print_validation_test_case(f, 3, lambda pk: pk, flip_bit, lambda sig: sig, "FAIL")
+
+    # negative tests (alter signature)
+    print_validation_test_case(f, 3, lambda pk: pk, lambda msg: msg, flip_bit, "FAIL")
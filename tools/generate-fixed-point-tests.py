@@ -0,0 +1,113 @@
+from math import isqrt
+from random import randint, seed
+
+# the costs, as defined in src/more_ops.rs
+MALLOC_COST_PER_BYTE = 10
+MUL_SQUARE_COST_PER_BYTE_DIVIDER = 128
+MUL_DIV_FLOOR_BASE_COST = 1500
+MUL_DIV_FLOOR_COST_PER_BYTE = 8
+MUL_DIV_FLOOR_SQUARE_COST_PER_BYTE_DIVIDER = 32
+SQRT_FLOOR_BASE_COST = 1000
+SQRT_FLOOR_COST_PER_BYTE = 6
+SQRT_FLOOR_SQUARE_COST_PER_BYTE_DIVIDER = 64
+
+# the length of the (minimal) CLVM encoding of n
+def int_len(n: int) -> int:
+    if n == 0:
+        return 0
+    # the number of bits, excluding the sign bit
+    bits = n.bit_length() if n > 0 else (~n).bit_length()
+    return (bits + 8) // 8
+
+def mul_div_floor(f, a: int, b: int, c: int):
+    f.write(f"mul_div_floor {a} {b} {c} => ")
+    if c == 0:
+        f.write("FAIL\n")
+        return
+    # python's // rounds towards negative infinity
+    r = (a * b) // c
+    la, lb, lc = int_len(a), int_len(b), int_len(c)
+    cost = MUL_DIV_FLOOR_BASE_COST
+    cost += (la + lb + lc) * MUL_DIV_FLOOR_COST_PER_BYTE
+    cost += (la * lb) // MUL_SQUARE_COST_PER_BYTE_DIVIDER
+    cost += ((la + lb) * lc) // MUL_DIV_FLOOR_SQUARE_COST_PER_BYTE_DIVIDER
+    cost += int_len(r) * MALLOC_COST_PER_BYTE
+    f.write(f"{r} | {cost}\n")
+
+def sqrt_floor(f, n: int):
+    f.write(f"sqrt_floor {n} => ")
+    if n < 0:
+        f.write("FAIL\n")
+        return
+    r = isqrt(n)
+    ln = int_len(n)
+    cost = SQRT_FLOOR_BASE_COST
+    cost += ln * SQRT_FLOOR_COST_PER_BYTE
+    cost += (ln * ln) // SQRT_FLOOR_SQUARE_COST_PER_BYTE_DIVIDER
+    cost += int_len(r) * MALLOC_COST_PER_BYTE
+    f.write(f"{r} | {cost}\n")
+
+def rand_int(num_bytes: int) -> int:
+    return randint(-(1 << (num_bytes * 8 - 1)), (1 << (num_bytes * 8 - 1)) - 1)
+
+seed(1337)
+
+# 18 decimals, as commonly used for fixed-point prices
+ONE = 10**18
+
+with open("../op-tests/test-fixed-point.txt", "w+") as f:
+    f.write("; This file was generated by tools/generate-fixed-point-tests.py\n\n")
+
+    f.write("; the wrong number of arguments, or arguments that aren't integers\n")
+    f.write("mul_div_floor => FAIL\n")
+    f.write("mul_div_floor 1 2 => FAIL\n")
+    f.write("mul_div_floor 1 2 3 4 => FAIL\n")
+    f.write("mul_div_floor ( 1 2 ) 2 3 => FAIL\n")
+    f.write("mul_div_floor 1 2 ( 1 2 ) => FAIL\n")
+    f.write("sqrt_floor => FAIL\n")
+    f.write("sqrt_floor 1 2 => FAIL\n")
+    f.write("sqrt_floor ( 1 2 ) => FAIL\n")
+
+    f.write("\n; division by zero\n")
+    mul_div_floor(f, 0, 0, 0)
+    mul_div_floor(f, 1, 1, 0)
+
+    f.write("\n; the result is rounded towards negative infinity, for all signs\n")
+    for a, b, c in [(7, 1, 2), (-7, 1, 2), (7, -1, 2), (7, 1, -2), (-7, -1, 2),
+                    (-7, 1, -2), (7, -1, -2), (-7, -1, -2), (6, 1, 2), (-6, 1, 2),
+                    (0, 5, 3), (5, 0, -3), (1, 1, 3), (-1, 1, 3), (1, -1, -3)]:
+        mul_div_floor(f, a, b, c)
+
+    f.write("\n; the intermediate product doesn't overflow\n")
+    mul_div_floor(f, 2**255, 2**255, 2**256)
+    mul_div_floor(f, 3 * ONE, 7 * ONE, ONE)
+    mul_div_floor(f, ONE // 3, ONE, 2 * ONE)
+    mul_div_floor(f, -ONE, 10**30, 10**30 + 1)
+
+    f.write("\n; random operands of various sizes\n")
+    for _ in range(30):
+        a = rand_int(randint(1, 40))
+        b = rand_int(randint(1, 40))
+        c = rand_int(randint(1, 40))
+        if c == 0:
+            c = 1
+        mul_div_floor(f, a, b, c)
+
+    f.write("\n; negative numbers have no square root\n")
+    sqrt_floor(f, -1)
+    sqrt_floor(f, -4)
+
+    f.write("\n; perfect squares, and their neighbours\n")
+    for n in [0, 1, 2, 3, 4, 15, 16, 17, 255, 256, 65535, 65536, 2**64 - 1, 2**64, 2**64 + 1]:
+        sqrt_floor(f, n)
+    for r in [3, 127, 128, 2**32 - 1, 10**18, 2**127]:
+        sqrt_floor(f, r * r - 1)
+        sqrt_floor(f, r * r)
+        sqrt_floor(f, r * r + 1)
+
+    f.write("\n; the square root of a fixed-point number with 18 decimals\n")
+    sqrt_floor(f, 2 * ONE * ONE)
+
+    f.write("\n; random operands of various sizes\n")
+    for _ in range(30):
+        sqrt_floor(f, abs(rand_int(randint(1, 64))))
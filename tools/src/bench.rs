@@ -0,0 +1,113 @@
+use clvmr::allocator::{Allocator, AllocatorStats};
+use clvmr::cost::Cost;
+use clvmr::dialect::Dialect;
+use clvmr::run_program::run_program;
+use clvmr::serde::node_from_bytes_backrefs;
+use std::fmt;
+use std::io;
+use std::time::{Duration, Instant};
+
+// Measures the wall time of running a program, to compare the performance of
+// builds (or implementations) of the interpreter on the same inputs. Every
+// iteration runs in a fresh allocator, and only run_program() itself is timed,
+// not deserializing the program and environment.
+
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub iterations: usize,
+    pub median: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    pub cost: Cost,
+    // the allocator's high-water marks, which are the same for every iteration
+    pub peak: AllocatorStats,
+}
+
+impl BenchResult {
+    pub fn cost_per_us(&self) -> f64 {
+        self.cost as f64 / (self.median.as_nanos().max(1) as f64 / 1000.0)
+    }
+}
+
+impl fmt::Display for BenchResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "iterations: {}", self.iterations)?;
+        writeln!(
+            f,
+            "wall time: {:?} median, {:?} min, {:?} max",
+            self.median, self.min, self.max
+        )?;
+        writeln!(f, "cost: {}", self.cost)?;
+        writeln!(f, "cost per microsecond: {:.1}", self.cost_per_us())?;
+        writeln!(
+            f,
+            "peak allocator usage: {} atom bytes, {} atoms, {} pairs",
+            self.peak.max_atom_bytes, self.peak.max_atoms, self.peak.max_pairs
+        )
+    }
+}
+
+// runs the serialized program with the serialized environment iterations
+// times. Fails if the program or environment can't be deserialized, or the
+// program fails
+pub fn bench_program<D: Dialect>(
+    program: &[u8],
+    env: &[u8],
+    dialect: &D,
+    max_cost: Cost,
+    iterations: usize,
+) -> io::Result<BenchResult> {
+    assert!(iterations > 0, "iterations must be > 0");
+    let mut times = Vec::with_capacity(iterations);
+    let mut cost = 0;
+    let mut peak = AllocatorStats::default();
+    for _ in 0..iterations {
+        let mut a = Allocator::new();
+        let program = node_from_bytes_backrefs(&mut a, program)?;
+        let env = node_from_bytes_backrefs(&mut a, env)?;
+        let start = Instant::now();
+        let reduction = run_program(&mut a, dialect, program, env, max_cost)?;
+        times.push(start.elapsed());
+        cost = reduction.0;
+        peak = a.stats();
+    }
+    times.sort();
+    Ok(BenchResult {
+        iterations,
+        median: times[times.len() / 2],
+        min: times[0],
+        max: times[times.len() - 1],
+        cost,
+        peak,
+    })
+}
+
+#[cfg(test)]
+use clvmr::allocator::NodePtr;
+#[cfg(test)]
+use clvmr::chia_dialect::ChiaDialect;
+
+#[test]
+fn test_bench_program() {
+    // (c (q . 1) (sha256 (q . "foobar")))
+    let program = clvmr::hex::decode("ff04ffff0101ffff0bffff0186666f6f6261728080").unwrap();
+    let dialect = ChiaDialect::new(0);
+    let expected = {
+        let mut a = Allocator::new();
+        let program = node_from_bytes_backrefs(&mut a, &program).unwrap();
+        run_program(&mut a, &dialect, program, NodePtr::NIL, 0)
+            .unwrap()
+            .0
+    };
+
+    let r = bench_program(&program, &[0x80], &dialect, 0, 5).unwrap();
+    assert_eq!(r.iterations, 5);
+    assert_eq!(r.cost, expected);
+    assert!(r.min <= r.median && r.median <= r.max);
+    assert_eq!(r.peak.max_pairs, r.peak.pairs);
+    assert!(r.peak.max_pairs > 0);
+    assert!(r.to_string().contains(&format!("cost: {expected}\n")));
+
+    let e = bench_program(&program, &[0x80], &dialect, 1, 5).unwrap_err();
+    assert_eq!(e.to_string(), "cost exceeded");
+}
@@ -1,6 +1,10 @@
 use clap::Parser;
 use clvmr::allocator::{Allocator, NodePtr};
-use clvmr::chia_dialect::{ChiaDialect, ENABLE_BLS_OPS_OUTSIDE_GUARD};
+use clvmr::chia_dialect::{
+    ChiaDialect, ENABLE_BIT_COUNT_OPS, ENABLE_BLS_OPS_OUTSIDE_GUARD, ENABLE_DIVEXACT_GCD,
+    ENABLE_ED25519_VERIFY, ENABLE_FIXED_POINT_OPS,
+};
+use clvmr::hex;
 use clvmr::run_program::run_program;
 use linreg::linear_regression_of;
 use std::fs::{create_dir_all, File};
@@ -118,7 +122,13 @@ fn substitute(args: Placeholder, s: NodePtr) -> OpArgs {
 fn time_invocation(a: &mut Allocator, op: u32, arg: OpArgs, flags: u32) -> f64 {
     let call = build_call(a, op, arg, 1, None);
     //println!("{:x?}", &Node::new(a, call));
-    let dialect = ChiaDialect::new(ENABLE_BLS_OPS_OUTSIDE_GUARD);
+    let dialect = ChiaDialect::new(
+        ENABLE_BLS_OPS_OUTSIDE_GUARD
+            | ENABLE_ED25519_VERIFY
+            | ENABLE_DIVEXACT_GCD
+            | ENABLE_FIXED_POINT_OPS
+            | ENABLE_BIT_COUNT_OPS,
+    );
     let start = Instant::now();
     let r = run_program(a, &dialect, call, a.nil(), 11000000000);
     if (flags & ALLOW_FAILURE) == 0 {
@@ -169,7 +179,13 @@ fn time_per_byte(a: &mut Allocator, op: &Operator, output: &mut dyn Write) -> f6
 // establish how much time each additional argument contributes
 fn time_per_arg(a: &mut Allocator, op: &Operator, output: &mut dyn Write) -> f64 {
     let mut samples = Vec::<(f64, f64)>::new();
-    let dialect = ChiaDialect::new(ENABLE_BLS_OPS_OUTSIDE_GUARD);
+    let dialect = ChiaDialect::new(
+        ENABLE_BLS_OPS_OUTSIDE_GUARD
+            | ENABLE_ED25519_VERIFY
+            | ENABLE_DIVEXACT_GCD
+            | ENABLE_FIXED_POINT_OPS
+            | ENABLE_BIT_COUNT_OPS,
+    );
 
     let subst = a
         .new_atom(
@@ -212,7 +228,13 @@ fn base_call_time(
     output: &mut dyn Write,
 ) -> f64 {
     let mut samples = Vec::<(f64, f64)>::new();
-    let dialect = ChiaDialect::new(ENABLE_BLS_OPS_OUTSIDE_GUARD);
+    let dialect = ChiaDialect::new(
+        ENABLE_BLS_OPS_OUTSIDE_GUARD
+            | ENABLE_ED25519_VERIFY
+            | ENABLE_DIVEXACT_GCD
+            | ENABLE_FIXED_POINT_OPS
+            | ENABLE_BIT_COUNT_OPS,
+    );
 
     let subst = a
         .new_atom(
@@ -372,6 +394,19 @@ pub fn main() {
     let r1_sig = a.new_atom(&hex::decode("e8de121f4cceca12d97527cc957cca64a4bcfc685cffdee051b38ee81cb22d7e2c187fec82c731018ed2d56f08a4a5cbc40c5bfe9ae18c02295bb65e7f605ffc").unwrap()).unwrap();
     let r1_sig = quote(&mut a, r1_sig);
 
+    // for ed25519_verify (test vector 2 from RFC 8032)
+    let ed_pk = a
+        .new_atom(
+            &hex::decode("3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c")
+                .unwrap(),
+        )
+        .unwrap();
+    let ed_pk = quote(&mut a, ed_pk);
+    let ed_msg = a.new_atom(&[0x72]).unwrap();
+    let ed_msg = quote(&mut a, ed_msg);
+    let ed_sig = a.new_atom(&hex::decode("92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00").unwrap()).unwrap();
+    let ed_sig = quote(&mut a, ed_sig);
+
     let number = a
         .new_atom(
             &hex::decode("123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef0")
@@ -380,7 +415,7 @@ pub fn main() {
         .unwrap();
     let number = quote(&mut a, number);
 
-    let ops: [Operator; 17] = [
+    let ops: [Operator; 25] = [
         Operator {
             opcode: 60,
             name: "modpow (modulus cost)",
@@ -500,6 +535,62 @@ pub fn main() {
             extra: None,
             flags: ALLOW_FAILURE,
         },
+        Operator {
+            opcode: 0x06ddcf00,
+            name: "ed25519_verify",
+            arg: Placeholder::ThreeArgs(Some(ed_pk), Some(ed_msg), Some(ed_sig)),
+            extra: None,
+            flags: ALLOW_FAILURE,
+        },
+        Operator {
+            opcode: 0x06ddcf00,
+            name: "ed25519_verify (message cost)",
+            arg: Placeholder::ThreeArgs(Some(ed_pk), None, Some(ed_sig)),
+            extra: None,
+            flags: PER_BYTE_COST | ALLOW_FAILURE,
+        },
+        Operator {
+            opcode: 37,
+            name: "divexact",
+            arg: Placeholder::TwoArgs(None, Some(number)),
+            extra: None,
+            flags: PER_BYTE_COST | ALLOW_FAILURE,
+        },
+        Operator {
+            opcode: 38,
+            name: "gcd",
+            arg: Placeholder::TwoArgs(None, Some(number)),
+            extra: None,
+            flags: PER_BYTE_COST,
+        },
+        Operator {
+            opcode: 39,
+            name: "mul_div_floor",
+            arg: Placeholder::ThreeArgs(None, Some(number), Some(number)),
+            extra: None,
+            flags: PER_BYTE_COST,
+        },
+        Operator {
+            opcode: 40,
+            name: "sqrt_floor",
+            arg: Placeholder::SingleArg(None),
+            extra: None,
+            flags: PER_BYTE_COST | ALLOW_FAILURE,
+        },
+        Operator {
+            opcode: 41,
+            name: "popcount",
+            arg: Placeholder::SingleArg(None),
+            extra: None,
+            flags: PER_BYTE_COST,
+        },
+        Operator {
+            opcode: 42,
+            name: "bitlength",
+            arg: Placeholder::SingleArg(None),
+            extra: None,
+            flags: PER_BYTE_COST,
+        },
     ];
 
     // this "magic" scaling depends on the computer you run the tests on.
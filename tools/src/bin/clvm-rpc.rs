@@ -0,0 +1,16 @@
+use clvm_rs_test_tools::rpc::handle_line;
+use std::io::{stdin, stdout, BufRead, Write};
+
+// Runs the JSON-RPC service over stdin/stdout. See rpc.rs for the supported
+// methods.
+pub fn main() {
+    let mut out = stdout().lock();
+    for line in stdin().lock().lines() {
+        let line = line.expect("failed to read stdin");
+        if line.trim().is_empty() {
+            continue;
+        }
+        writeln!(out, "{}", handle_line(&line)).expect("failed to write stdout");
+        out.flush().expect("failed to write stdout");
+    }
+}
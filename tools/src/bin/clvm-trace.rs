@@ -0,0 +1,170 @@
+use clap::{Parser, Subcommand};
+use clvm_rs_test_tools::bench::bench_program;
+use clvm_rs_test_tools::cost_listing::{cost_listing, profile_program};
+use clvm_rs_test_tools::trace::{compare_traces, trace_program, Trace};
+use clvmr::allocator::{Allocator, NodePtr};
+use clvmr::chia_dialect::ChiaDialect;
+use clvmr::hex;
+use clvmr::serde::node_from_bytes_backrefs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::process::ExitCode;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// run a program and write its trace file
+    Run {
+        /// hex encoded, serialized program
+        program: String,
+
+        /// hex encoded, serialized environment
+        #[arg(default_value = "80")]
+        env: String,
+
+        /// the file to write the trace to
+        #[arg(short, long)]
+        output: String,
+
+        /// ChiaDialect flags
+        #[arg(long, default_value_t = 0)]
+        flags: u32,
+
+        #[arg(long, default_value_t = 11000000000)]
+        max_cost: u64,
+    },
+    /// compare two trace files and print the first difference
+    Compare { left: String, right: String },
+    /// run a program and print its disassembly, annotated with the cost of
+    /// each operator call site
+    Listing {
+        /// hex encoded, serialized program
+        program: String,
+
+        /// hex encoded, serialized environment
+        #[arg(default_value = "80")]
+        env: String,
+
+        /// ChiaDialect flags
+        #[arg(long, default_value_t = 0)]
+        flags: u32,
+
+        #[arg(long, default_value_t = 11000000000)]
+        max_cost: u64,
+    },
+    /// run a program a number of times and print its median wall time, cost
+    /// and peak allocator usage, to compare the performance of builds on the
+    /// same inputs
+    Bench {
+        /// hex encoded, serialized program
+        program: String,
+
+        /// hex encoded, serialized environment
+        #[arg(default_value = "80")]
+        env: String,
+
+        #[arg(short, long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+        iterations: u32,
+
+        /// ChiaDialect flags
+        #[arg(long, default_value_t = 0)]
+        flags: u32,
+
+        #[arg(long, default_value_t = 11000000000)]
+        max_cost: u64,
+    },
+}
+
+fn parse(a: &mut Allocator, hex: &str, what: &str) -> NodePtr {
+    let buf = hex::decode(hex).unwrap_or_else(|_| panic!("invalid {what} hex"));
+    node_from_bytes_backrefs(a, &buf).unwrap_or_else(|_| panic!("invalid {what}"))
+}
+
+fn read_trace(path: &str) -> Trace {
+    let f = File::open(path).expect("failed to open trace file");
+    Trace::read(&mut BufReader::new(f)).expect("failed to read trace file")
+}
+
+// Writes and compares trace files, and prints cost listings and benchmarks.
+// See trace.rs for the format.
+pub fn main() -> ExitCode {
+    match Args::parse().command {
+        Command::Run {
+            program,
+            env,
+            output,
+            flags,
+            max_cost,
+        } => {
+            let mut a = Allocator::new();
+            let program = parse(&mut a, &program, "program");
+            let env = parse(&mut a, &env, "env");
+            let trace = trace_program(&mut a, &ChiaDialect::new(flags), program, env, max_cost);
+            let mut f = BufWriter::new(File::create(output).expect("failed to create file"));
+            trace.write(&mut f).expect("failed to write trace");
+            ExitCode::SUCCESS
+        }
+        Command::Compare { left, right } => {
+            match compare_traces(&read_trace(&left), &read_trace(&right)) {
+                None => {
+                    println!("traces are identical");
+                    ExitCode::SUCCESS
+                }
+                Some(d) => {
+                    println!("{d}");
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Command::Listing {
+            program,
+            env,
+            flags,
+            max_cost,
+        } => {
+            let mut a = Allocator::new();
+            let program = parse(&mut a, &program, "program");
+            let env = parse(&mut a, &env, "env");
+            let (r, profile) =
+                profile_program(&mut a, &ChiaDialect::new(flags), program, env, max_cost);
+            print!("{}", cost_listing(&a, program, flags, Some(&profile)));
+            match r {
+                Ok(r) => {
+                    println!("total cost: {}", r.0);
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    println!("error: {}", e.1);
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Command::Bench {
+            program,
+            env,
+            iterations,
+            flags,
+            max_cost,
+        } => {
+            let program = hex::decode(program).expect("invalid program hex");
+            let env = hex::decode(env).expect("invalid env hex");
+            let dialect = ChiaDialect::new(flags);
+            match bench_program(&program, &env, &dialect, max_cost, iterations as usize) {
+                Ok(r) => {
+                    print!("{r}");
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    println!("error: {e}");
+                    ExitCode::FAILURE
+                }
+            }
+        }
+    }
+}
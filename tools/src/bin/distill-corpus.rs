@@ -0,0 +1,69 @@
+use clap::{Parser, ValueEnum};
+use clvm_rs_test_tools::corpus::{distill, CorpusTarget};
+use clvmr::chia_dialect::{ENABLE_BLS_OPS_OUTSIDE_GUARD, MEMPOOL_MODE};
+use std::fs;
+use std::path::Path;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Target {
+    RunProgram,
+    Sandbox,
+}
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// the directory of the corpus to distill
+    input: String,
+
+    /// the directory to copy the distilled corpus to
+    output: String,
+
+    /// the fuzz target the corpus belongs to
+    #[arg(short, long, value_enum, default_value_t = Target::RunProgram)]
+    target: Target,
+
+    /// ChiaDialect flags to run each input with. Defaults to the flags the
+    /// fuzz targets use
+    #[arg(long)]
+    flags: Vec<u32>,
+}
+
+// Copies the inputs of a fuzz corpus that add coverage to a new directory. See
+// corpus.rs for how coverage is measured.
+pub fn main() {
+    let args = Args::parse();
+    let target = match args.target {
+        Target::RunProgram => CorpusTarget::RunProgram,
+        Target::Sandbox => CorpusTarget::Sandbox,
+    };
+    let flags = if args.flags.is_empty() {
+        vec![0, ENABLE_BLS_OPS_OUTSIDE_GUARD, MEMPOOL_MODE]
+    } else {
+        args.flags
+    };
+
+    let mut names = Vec::new();
+    let mut inputs = Vec::new();
+    for entry in fs::read_dir(&args.input).expect("failed to list corpus directory") {
+        let path = entry.expect("failed to list corpus directory").path();
+        if !path.is_file() {
+            continue;
+        }
+        inputs.push(fs::read(&path).expect("failed to read file"));
+        names.push(path.file_name().unwrap().to_owned());
+    }
+
+    let (kept, features) = distill(target, &inputs, &flags);
+
+    fs::create_dir_all(&args.output).expect("failed to create directory");
+    for idx in &kept {
+        fs::write(Path::new(&args.output).join(&names[*idx]), &inputs[*idx])
+            .expect("failed to write file");
+    }
+    println!(
+        "kept {} of {} inputs, covering {features} features",
+        kept.len(),
+        inputs.len()
+    );
+}
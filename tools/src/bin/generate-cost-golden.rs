@@ -0,0 +1,15 @@
+use clvm_rs_test_tools::cost_golden::all_golden_entries;
+use std::fs::File;
+use std::io::Write;
+
+// Regenerates the golden file of (cost, result hash) for the programs produced
+// by the argument generator. The file is checked in and verified by
+// test_cost_golden on every platform, to catch architecture dependent
+// divergence in the interpreter, serializer or generator.
+pub fn main() {
+    let mut out = File::create(concat!(env!("CARGO_MANIFEST_DIR"), "/cost-golden.txt"))
+        .expect("failed to open file");
+    for line in all_golden_entries() {
+        writeln!(out, "{line}").expect("failed to write file");
+    }
+}
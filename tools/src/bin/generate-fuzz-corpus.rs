@@ -1,3 +1,4 @@
+use clvmr::hex;
 use clvmr::serde::write_atom::write_atom;
 use hex_literal::hex;
 use rand::rngs::StdRng;
@@ -57,7 +58,7 @@ const fn op(opcode: u32, operands: &'static [Type], result: Type) -> OperatorInf
     }
 }
 
-const OPERATORS: [OperatorInfo; 83] = [
+const OPERATORS: [OperatorInfo; 96] = [
     // apply
     op(2, &[Type::Program, Type::Tree], Type::AnyAtom),
     // if
@@ -182,6 +183,22 @@ const OPERATORS: [OperatorInfo; 83] = [
         &[Type::Cost, Type::Zero, Type::Program, Type::Tree],
         Type::Bool,
     ),
+    // divexact
+    op(37, &[Type::Int64, Type::Int64], Type::Int64),
+    // gcd
+    op(38, &[Type::Int64, Type::Int64], Type::Int64),
+    // mul_div_floor
+    op(39, &[Type::Int64, Type::Int64, Type::Int64], Type::Int64),
+    // sqrt_floor
+    op(40, &[Type::Int64], Type::Int64),
+    // popcount
+    op(41, &[Type::AnyAtom], Type::Int64),
+    // bitlength
+    op(42, &[Type::AnyAtom], Type::Int64),
+    // tree_size
+    op(43, &[Type::Tree], Type::Int64),
+    // tree_depth
+    op(44, &[Type::Tree], Type::Int64),
     // BLS extensions
 
     // coinid
@@ -208,6 +225,12 @@ const OPERATORS: [OperatorInfo; 83] = [
     op(54, &[Type::G2Point, Type::Bytes96], Type::G2Point),
     // bls_g2_negate
     op(55, &[Type::G2Point], Type::G2Point),
+    // bls_g1_valid
+    op(62, &[Type::G1Point], Type::Bool),
+    op(62, &[Type::AnyAtom], Type::Bool),
+    // bls_g2_valid
+    op(63, &[Type::G2Point], Type::Bool),
+    op(63, &[Type::AnyAtom], Type::Bool),
     // bls_map_to_g1
     op(56, &[Type::AnyAtom, Type::AnyAtom], Type::G1Point),
     op(56, &[Type::AnyAtom], Type::G1Point),
@@ -281,6 +304,12 @@ const OPERATORS: [OperatorInfo; 83] = [
         &[Type::Sec1, Type::Bytes32, Type::Sig],
         Type::Zero,
     ),
+    // op_ed25519_verify
+    op(
+        0x06ddcf00,
+        &[Type::Bytes32, Type::AnyAtom, Type::Sig],
+        Type::Zero,
+    ),
     // modpow
     op(60, &[Type::Int64, Type::Int64, Type::Int64], Type::Int64),
     op(
@@ -0,0 +1,62 @@
+use clap::Parser;
+use clvmr::dialect::OperatorSet;
+use clvmr::hex;
+use clvmr::operator_info::{aliases_of, chia_operators, CHIA_OPERATORS};
+use serde_json::json;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// only include the operators available with these ChiaDialect flags
+    #[arg(long)]
+    flags: Option<u32>,
+
+    /// when filtering by flags, include the operators available inside the
+    /// BLS softfork guard
+    #[arg(long, default_value_t = false)]
+    softfork: bool,
+}
+
+// Prints a JSON description of the operators in ChiaDialect. The table is
+// derived from the same data structures and cost constants as the
+// interpreter, so downstream documentation doesn't need to maintain its own.
+pub fn main() {
+    let args = Args::parse();
+
+    let extension = if args.softfork {
+        OperatorSet::BLS
+    } else {
+        OperatorSet::Default
+    };
+    let ops: Vec<_> = match args.flags {
+        Some(flags) => chia_operators(flags, extension).collect(),
+        None => CHIA_OPERATORS.iter().collect(),
+    };
+    // without a filter, all aliases are included
+    let (alias_flags, alias_extension) = match args.flags {
+        Some(flags) => (flags, extension),
+        None => (u32::MAX, OperatorSet::BLS),
+    };
+
+    let ops: Vec<_> = ops
+        .iter()
+        .map(|op| {
+            let aliases: Vec<_> = aliases_of(op, alias_flags, alias_extension)
+                .map(|alias| alias.name)
+                .collect();
+            json!({
+                "opcode": hex::encode(op.opcode),
+                "name": op.name,
+                "min_args": op.min_args,
+                "max_args": op.max_args,
+                "base_cost": op.base_cost,
+                "cost_per_arg": op.cost_per_arg,
+                "cost_per_byte": op.cost_per_byte,
+                "flags": op.flags,
+                "bls_extension": op.bls_extension,
+                "aliases": aliases,
+            })
+        })
+        .collect();
+    println!("{}", serde_json::to_string_pretty(&ops).unwrap());
+}
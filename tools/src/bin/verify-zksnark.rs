@@ -1,4 +1,5 @@
 use chia_bls::{aggregate_pairing, G1Element, G2Element};
+use clvmr::hex;
 use num_bigint::BigInt;
 use serde::Deserialize;
 
@@ -0,0 +1,196 @@
+use crate::argument_generator::ProgramGenerator;
+use clvmr::allocator::{Allocator, NodePtr};
+use clvmr::chia_dialect::ChiaDialect;
+use clvmr::cost::Cost;
+use clvmr::dialect::{Dialect, OperatorSet};
+use clvmr::hex;
+use clvmr::reduction::Response;
+use clvmr::run_program::run_program;
+use clvmr::serde::node_from_bytes;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashSet};
+
+// Distills a fuzz corpus down to the inputs that add coverage. Every input is
+// run, recording which operators it calls and how each call (and the program
+// as a whole) ends: successfully or with a specific error message. Distinct
+// error messages correspond to distinct error branches in the operators.
+// Inputs are considered smallest first, and an input is kept only if it covers
+// something no input kept before it does. Duplicate inputs are dropped.
+
+const MAX_COST: Cost = 11000000000;
+
+// the fuzz target a corpus belongs to, which determines how its inputs are
+// turned into a program and environment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CorpusTarget {
+    // fuzz_run_program: the input is a serialized program, run with a nil
+    // environment
+    RunProgram,
+    // sandbox: the input is passed to ProgramGenerator
+    Sandbox,
+}
+
+// a single thing an input covers, e.g. "op 0e ok" or "op 0e err: atom too
+// large"
+pub type Feature = String;
+
+// wraps a dialect and records the features of every operator call
+struct CoverageDialect<'a, D: Dialect> {
+    dialect: &'a D,
+    features: RefCell<BTreeSet<Feature>>,
+}
+
+fn outcome(r: &Response) -> String {
+    match r {
+        Ok(_) => "ok".to_string(),
+        Err(e) => format!("err: {}", e.1),
+    }
+}
+
+impl<D: Dialect> Dialect for CoverageDialect<'_, D> {
+    fn op(
+        &self,
+        allocator: &mut Allocator,
+        o: NodePtr,
+        argument_list: NodePtr,
+        max_cost: Cost,
+        extension: OperatorSet,
+    ) -> Response {
+        let op = hex::encode(allocator.atom(o).as_ref());
+        let r = self
+            .dialect
+            .op(allocator, o, argument_list, max_cost, extension);
+        self.features
+            .borrow_mut()
+            .insert(format!("op {op} {}", outcome(&r)));
+        r
+    }
+
+    fn quote_kw(&self) -> u32 {
+        self.dialect.quote_kw()
+    }
+    fn apply_kw(&self) -> u32 {
+        self.dialect.apply_kw()
+    }
+    fn softfork_kw(&self) -> u32 {
+        self.dialect.softfork_kw()
+    }
+    fn softfork_extension(&self, ext: u32) -> OperatorSet {
+        self.dialect.softfork_extension(ext)
+    }
+    fn allow_unknown_ops(&self) -> bool {
+        self.dialect.allow_unknown_ops()
+    }
+    fn cost_schedule_version(&self) -> u32 {
+        self.dialect.cost_schedule_version()
+    }
+}
+
+// returns the features covered by running the input with each of the
+// ChiaDialect flags. An input that can't be turned into a program covers
+// nothing
+pub fn coverage(target: CorpusTarget, input: &[u8], flags: &[u32]) -> BTreeSet<Feature> {
+    let mut ret = BTreeSet::new();
+    for f in flags {
+        let mut a = Allocator::new();
+        let (program, env) = match target {
+            CorpusTarget::RunProgram => match node_from_bytes(&mut a, input) {
+                Ok(program) => (program, a.nil()),
+                Err(_) => return ret,
+            },
+            CorpusTarget::Sandbox => ProgramGenerator::new(input, *f).generate(&mut a),
+        };
+        let inner = ChiaDialect::new(*f);
+        let dialect = CoverageDialect {
+            dialect: &inner,
+            features: RefCell::new(BTreeSet::new()),
+        };
+        let r = run_program(&mut a, &dialect, program, env, MAX_COST);
+        let result = format!("result {}", outcome(&r));
+        for feature in dialect.features.into_inner().into_iter().chain([result]) {
+            ret.insert(format!("{f:x} {feature}"));
+        }
+    }
+    ret
+}
+
+// returns the indices of the inputs to keep, in the order they were kept,
+// along with the number of features they cover
+pub fn distill(target: CorpusTarget, inputs: &[Vec<u8>], flags: &[u32]) -> (Vec<usize>, usize) {
+    let mut order: Vec<usize> = (0..inputs.len()).collect();
+    // ties are broken by content, to make the result independent of the order
+    // the inputs were listed in
+    order.sort_by(|lhs, rhs| {
+        let (lhs, rhs) = (&inputs[*lhs], &inputs[*rhs]);
+        lhs.len().cmp(&rhs.len()).then(lhs.cmp(rhs))
+    });
+
+    let mut seen = HashSet::<&[u8]>::new();
+    let mut covered = BTreeSet::<Feature>::new();
+    let mut kept = Vec::new();
+    for idx in order {
+        if !seen.insert(&inputs[idx]) {
+            continue;
+        }
+        let features = coverage(target, &inputs[idx], flags);
+        let count = covered.len();
+        covered.extend(features);
+        if covered.len() > count {
+            kept.push(idx);
+        }
+    }
+    (kept, covered.len())
+}
+
+#[test]
+fn test_coverage() {
+    // (+ (q . 1) (q . 2))
+    let program = hex::decode("ff10ffff0101ffff010280").unwrap();
+    assert_eq!(
+        coverage(CorpusTarget::RunProgram, &program, &[0]),
+        BTreeSet::from(["0 op 10 ok".to_string(), "0 result ok".to_string()])
+    );
+
+    // (x (q . 1))
+    let program = hex::decode("ff08ffff010180").unwrap();
+    assert_eq!(
+        coverage(CorpusTarget::RunProgram, &program, &[0, 2]),
+        BTreeSet::from([
+            "0 op 08 err: clvm raise".to_string(),
+            "0 result err: clvm raise".to_string(),
+            "2 op 08 err: clvm raise".to_string(),
+            "2 result err: clvm raise".to_string(),
+        ])
+    );
+
+    // not a valid serialization
+    assert!(coverage(CorpusTarget::RunProgram, &[0xff], &[0]).is_empty());
+
+    // generated programs are run
+    assert!(!coverage(CorpusTarget::Sandbox, &[], &[0]).is_empty());
+}
+
+#[test]
+fn test_distill() {
+    let inputs: Vec<Vec<u8>> = [
+        // (+ (q . 1) (q . 2) (q . 3)), covers the same as the next, but is
+        // larger
+        "ff10ffff0101ffff0102ffff010380",
+        // (+ (q . 1) (q . 2))
+        "ff10ffff0101ffff010280",
+        // a duplicate
+        "ff10ffff0101ffff010280",
+        // (x (q . 1))
+        "ff08ffff010180",
+        // (+ (x (q . 1))), covers nothing new
+        "ff10ffff08ffff01018080",
+        // invalid
+        "ff",
+    ]
+    .iter()
+    .map(|h| hex::decode(h).unwrap())
+    .collect();
+    let (kept, features) = distill(CorpusTarget::RunProgram, &inputs, &[0]);
+    assert_eq!(kept, [3, 1]);
+    assert_eq!(features, 4);
+}
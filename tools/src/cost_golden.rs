@@ -0,0 +1,85 @@
+use crate::argument_generator::ArgumentGenerator;
+use clvmr::allocator::Allocator;
+use clvmr::chia_dialect::{ChiaDialect, ENABLE_BLS_OPS_OUTSIDE_GUARD, MEMPOOL_MODE};
+use clvmr::cost::Cost;
+use clvmr::hex;
+use clvmr::reduction::Reduction;
+use clvmr::run_program::run_program;
+use clvmr::serde::node_to_bytes;
+use clvmr::sha2::{Digest, Sha256};
+
+// the number of (program, env) pairs recorded in the golden file
+pub const NUM_ENTRIES: u32 = 1000;
+
+// every program is run once per set of flags
+pub const FLAGS: [u32; 2] = [0, MEMPOOL_MODE | ENABLE_BLS_OPS_OUTSIDE_GUARD];
+
+const MAX_COST: Cost = 11000000000;
+
+// the size of the buffer the argument generator samples from
+const SEED_BUFFER_SIZE: usize = 256;
+
+// expands the seed into a buffer for the argument generator. This must not
+// depend on anything platform specific, so it's built from SHA-256 rather
+// than a PRNG
+pub fn seed_buffer(seed: u32) -> Vec<u8> {
+    let mut ret = Vec::with_capacity(SEED_BUFFER_SIZE);
+    let mut counter: u32 = 0;
+    while ret.len() < SEED_BUFFER_SIZE {
+        let mut ctx = Sha256::new();
+        ctx.update(seed.to_be_bytes());
+        ctx.update(counter.to_be_bytes());
+        ret.extend_from_slice(&ctx.finalize());
+        counter += 1;
+    }
+    ret
+}
+
+// runs the program generated from the specified seed with every set of
+// flags and returns one line per run, in the format of the golden file:
+// <seed> <flags> <cost> <sha256 of serialized result>
+// or, in case the program fails:
+// <seed> <flags> FAIL <error message>
+pub fn golden_entries(seed: u32) -> Vec<String> {
+    let buf = seed_buffer(seed);
+    let mut a = Allocator::new();
+    let (program, env) = ArgumentGenerator::new(&buf).generate(&mut a);
+    let checkpoint = a.checkpoint();
+
+    let mut ret = Vec::new();
+    for flags in FLAGS {
+        a.restore_checkpoint(&checkpoint);
+        let dialect = ChiaDialect::new(flags);
+        match run_program(&mut a, &dialect, program, env, MAX_COST) {
+            Ok(Reduction(cost, result)) => {
+                let hash = match node_to_bytes(&a, result) {
+                    Ok(bytes) => hex::encode(Sha256::digest(bytes)),
+                    Err(_) => "serialization-failed".to_string(),
+                };
+                ret.push(format!("{seed} {flags:#x} {cost} {hash}"));
+            }
+            Err(e) => {
+                ret.push(format!("{seed} {flags:#x} FAIL {}", e.1));
+            }
+        }
+    }
+    ret
+}
+
+pub fn all_golden_entries() -> Vec<String> {
+    (0..NUM_ENTRIES).flat_map(golden_entries).collect()
+}
+
+#[test]
+fn test_cost_golden() {
+    // if this test fails after an intentional change to the generator or to
+    // the cost model, regenerate the file with:
+    // cargo run -p clvm-rs-test-tools --bin generate-cost-golden
+    let expected = include_str!("../cost-golden.txt");
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual = all_golden_entries();
+    assert_eq!(expected.len(), actual.len());
+    for (e, a) in expected.iter().zip(actual.iter()) {
+        assert_eq!(e, a);
+    }
+}
@@ -0,0 +1,312 @@
+use clvmr::allocator::{Allocator, NodePtr, SExp};
+use clvmr::cost::Cost;
+use clvmr::dialect::{Dialect, OperatorSet};
+use clvmr::operator_info::{chia_operators, OperatorInfo};
+use clvmr::reduction::{EvalErr, Response};
+use clvmr::run_program::{run_program_with_pre_eval, PostEval};
+use clvmr::test_dialect::format_node;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::rc::Rc;
+
+// A cost-annotated disassembly of a program. Every operator call site in the
+// program is listed on its own line, indented by how deeply it's nested in
+// other call sites, along with the operator's static base cost. When the
+// program has been run with profile_program(), each line also has the number
+// of times the call site was evaluated and the total cost of the operator
+// calls made from it, which makes it easy to spot the hot-spots of a puzzle.
+//
+// Call sites are identified by their NodePtr, so only call sites that are part
+// of the program tree (including quoted code run with apply) are listed, not
+// ones passed in through the environment.
+
+// the number of characters of a call site's disassembly to print
+const MAX_SITE_LEN: usize = 60;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SiteCost {
+    // the number of times the operator at this call site was called
+    pub calls: u64,
+    // the total cost of those calls. This is the cost the operator charged,
+    // not including the cost of evaluating its arguments
+    pub cost: Cost,
+}
+
+// the measured cost of every call site evaluated while running a program
+pub type Profile = HashMap<NodePtr, SiteCost>;
+
+// wraps a dialect and attributes the cost of every operator call to the call
+// site being evaluated
+struct ProfileDialect<'a, D: Dialect> {
+    dialect: &'a D,
+    // the call sites currently being evaluated, innermost last
+    sites: Rc<RefCell<Vec<NodePtr>>>,
+    profile: RefCell<Profile>,
+}
+
+impl<D: Dialect> Dialect for ProfileDialect<'_, D> {
+    fn op(
+        &self,
+        allocator: &mut Allocator,
+        o: NodePtr,
+        argument_list: NodePtr,
+        max_cost: Cost,
+        extension: OperatorSet,
+    ) -> Response {
+        let r = self
+            .dialect
+            .op(allocator, o, argument_list, max_cost, extension);
+        if let (Ok(reduction), Some(site)) = (&r, self.sites.borrow().last()) {
+            let mut profile = self.profile.borrow_mut();
+            let entry = profile.entry(*site).or_default();
+            entry.calls += 1;
+            entry.cost += reduction.0;
+        }
+        r
+    }
+
+    fn quote_kw(&self) -> u32 {
+        self.dialect.quote_kw()
+    }
+    fn apply_kw(&self) -> u32 {
+        self.dialect.apply_kw()
+    }
+    fn softfork_kw(&self) -> u32 {
+        self.dialect.softfork_kw()
+    }
+    fn softfork_extension(&self, ext: u32) -> OperatorSet {
+        self.dialect.softfork_extension(ext)
+    }
+    fn allow_unknown_ops(&self) -> bool {
+        self.dialect.allow_unknown_ops()
+    }
+    fn cost_schedule_version(&self) -> u32 {
+        self.dialect.cost_schedule_version()
+    }
+}
+
+// runs the program and returns its result along with the cost attributed to
+// each call site
+pub fn profile_program<D: Dialect>(
+    a: &mut Allocator,
+    dialect: &D,
+    program: NodePtr,
+    env: NodePtr,
+    max_cost: Cost,
+) -> (Response, Profile) {
+    let sites = Rc::new(RefCell::new(Vec::<NodePtr>::new()));
+    let dialect = ProfileDialect {
+        dialect,
+        sites: sites.clone(),
+        profile: RefCell::new(Profile::new()),
+    };
+    let pre_eval = Box::new(
+        move |a: &mut Allocator,
+              program: NodePtr,
+              _env: NodePtr|
+              -> Result<Option<Box<PostEval>>, EvalErr> {
+            // atoms are paths into the environment, not calls
+            if !matches!(a.sexp(program), SExp::Pair(..)) {
+                return Ok(None);
+            }
+            sites.borrow_mut().push(program);
+            let sites = sites.clone();
+            Ok(Some(Box::new(move |_result: Option<NodePtr>| {
+                sites.borrow_mut().pop();
+            })))
+        },
+    );
+    let r = run_program_with_pre_eval(a, &dialect, program, env, max_cost, Some(pre_eval));
+    (r, dialect.profile.into_inner())
+}
+
+fn operator_info(a: &Allocator, op: NodePtr, flags: u32) -> Option<&'static OperatorInfo> {
+    let opcode = a.atom(op);
+    // operators available in the BLS extension are a superset of the default
+    // ones
+    chia_operators(flags, OperatorSet::BLS).find(|info| info.opcode == opcode.as_ref())
+}
+
+fn format_site(a: &Allocator, info: &OperatorInfo, args: NodePtr) -> String {
+    let mut ret = format!("({}", info.name);
+    if info.opcode == [1] {
+        match a.sexp(args) {
+            // (q . (1 2)) is printed as (q 1 2)
+            SExp::Pair(..) => return truncate(ret + " " + &format_node(a, args)[1..]),
+            SExp::Atom => {
+                ret += " . ";
+                ret += &format_node(a, args);
+            }
+        }
+    } else {
+        let mut args = args;
+        while let Some((arg, rest)) = a.next(args) {
+            ret += " ";
+            ret += &format_node(a, arg);
+            args = rest;
+        }
+    }
+    truncate(ret + ")")
+}
+
+fn truncate(mut ret: String) -> String {
+    if ret.len() > MAX_SITE_LEN {
+        let mut end = MAX_SITE_LEN - 3;
+        while !ret.is_char_boundary(end) {
+            end -= 1;
+        }
+        ret.truncate(end);
+        ret += "...";
+    }
+    ret
+}
+
+// returns the cost-annotated disassembly of program, for the operators
+// available with the specified ChiaDialect flags. If profile is set, it's
+// expected to come from running the same program (in the same allocator) with
+// profile_program()
+pub fn cost_listing(
+    a: &Allocator,
+    program: NodePtr,
+    flags: u32,
+    profile: Option<&Profile>,
+) -> String {
+    let mut ret = String::new();
+    if profile.is_some() {
+        ret += "      cost   calls   base  site\n";
+    } else {
+        ret += "   base  site\n";
+    }
+
+    // (node, depth). Everything is walked, including quoted values, since
+    // those are often code run with apply
+    let mut stack = vec![(program, 0)];
+    while let Some((node, depth)) = stack.pop() {
+        let SExp::Pair(op, args) = a.sexp(node) else {
+            continue;
+        };
+        let info = match a.sexp(op) {
+            SExp::Atom => operator_info(a, op, flags),
+            SExp::Pair(..) => None,
+        };
+        let child_depth = match info {
+            None => depth,
+            Some(info) => {
+                if let Some(profile) = profile {
+                    match profile.get(&node) {
+                        Some(c) => write!(ret, "{:10} {:7} ", c.cost, c.calls).unwrap(),
+                        None => ret += "         -       - ",
+                    }
+                }
+                writeln!(
+                    ret,
+                    "{:7}  {}{}",
+                    info.base_cost,
+                    "  ".repeat(depth),
+                    format_site(a, info, args)
+                )
+                .unwrap();
+                depth + 1
+            }
+        };
+        let children = match info {
+            // the quoted value may be any value, not just a list
+            Some(info) if info.opcode == [1] => vec![args],
+            Some(_) => list_items(a, args),
+            None => {
+                let mut items = list_items(a, args);
+                items.insert(0, op);
+                items
+            }
+        };
+        // push in reverse order, to list call sites in the order they appear
+        stack.extend(children.into_iter().rev().map(|n| (n, child_depth)));
+    }
+    ret
+}
+
+fn list_items(a: &Allocator, mut list: NodePtr) -> Vec<NodePtr> {
+    let mut ret = vec![];
+    while let SExp::Pair(first, rest) = a.sexp(list) {
+        ret.push(first);
+        list = rest;
+    }
+    ret
+}
+
+// returns the call sites in profile, most expensive first. Ties are broken by
+// the number of calls
+pub fn hot_spots(profile: &Profile) -> Vec<(NodePtr, SiteCost)> {
+    let mut ret: Vec<(NodePtr, SiteCost)> = profile.iter().map(|(n, c)| (*n, *c)).collect();
+    ret.sort_by(|lhs, rhs| {
+        rhs.1
+            .cost
+            .cmp(&lhs.1.cost)
+            .then(rhs.1.calls.cmp(&lhs.1.calls))
+    });
+    ret
+}
+
+#[cfg(test)]
+use clvmr::chia_dialect::ChiaDialect;
+#[cfg(test)]
+use clvmr::serde::node_from_bytes;
+
+#[test]
+fn test_static_listing() {
+    let mut a = Allocator::new();
+    // (+ (q . 2) (sha256 (q . "foo") 1))
+    let program = node_from_bytes(
+        &mut a,
+        &clvmr::hex::decode("ff10ffff0102ffff0bffff0183666f6fff018080").unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        cost_listing(&a, program, 0, None),
+        "   base  site\n     \
+         99  (+ (1 . 2) (11 (1 . 6713199) 1))\n      \
+         0    (q . 2)\n     \
+         87    (sha256 (1 . 6713199) 1)\n      \
+         0      (q . 6713199)\n"
+    );
+}
+
+#[test]
+fn test_profile() {
+    let mut a = Allocator::new();
+    // (a (q . (+ 2 5)) (q 7 8)). The call site of + is quoted
+    let program = node_from_bytes(
+        &mut a,
+        &clvmr::hex::decode("ff02ffff01ff10ff02ff0580ffff01ff07ff088080").unwrap(),
+    )
+    .unwrap();
+    let env = a.nil();
+    let dialect = ChiaDialect::new(0);
+    let (r, profile) = profile_program(&mut a, &dialect, program, env, 11000000000);
+    assert_eq!(a.number(r.unwrap().1), 15.into());
+
+    // only + is implemented by the dialect
+    assert_eq!(profile.len(), 1);
+    let (site, cost) = hot_spots(&profile)[0];
+    assert_eq!(format_node(&a, site), "(16 2 5)");
+    // including the malloc cost of the 1 byte result
+    assert_eq!(
+        cost,
+        SiteCost {
+            calls: 1,
+            cost: 99 + 2 * 320 + 2 * 3 + 10
+        }
+    );
+
+    // quoted values are listed as code, even when they're data, like (7 8)
+    assert_eq!(
+        cost_listing(&a, program, 0, Some(&profile)),
+        "      cost   calls   base  site\n         \
+         -       -       0  (a (1 16 2 5) (1 7 8))\n         \
+         -       -       0    (q 16 2 5)\n       \
+         755       1      99      (+ 2 5)\n         \
+         -       -       0    (q 7 8)\n         \
+         -       -      19      (l 8)\n"
+    );
+}
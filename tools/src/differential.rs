@@ -0,0 +1,123 @@
+use crate::argument_generator::ProgramGenerator;
+use clvmr::allocator::Allocator;
+use clvmr::chia_dialect::{ChiaDialect, MEMPOOL_MODE};
+use clvmr::cost::Cost;
+use clvmr::reduction::Reduction;
+use clvmr::run_program::run_program;
+use clvmr::serde::{node_from_bytes, node_to_bytes};
+
+// Runs programs in both the permissive (consensus) mode and the strict
+// (mempool) mode, and checks that the strict mode only ever rejects more
+// programs. Any program accepted in strict mode must be accepted in permissive
+// mode too, with the same result and cost. A program that's only rejected in
+// strict mode must be rejected for a policy reason, not because the two modes
+// evaluate it differently.
+
+const MAX_COST: Cost = 11000000000;
+
+// the errors the strict mode may fail with where the permissive mode succeeds.
+// Malformed softfork invocations are also allowed, since the permissive mode
+// treats those as no-ops
+const POLICY_ERRORS: [&str; 3] = [
+    "unimplemented operator",
+    "unknown softfork extension",
+    "out of memory",
+];
+
+fn is_policy_error(e: &str) -> bool {
+    POLICY_ERRORS.contains(&e) || e.starts_with("softfork")
+}
+
+// runs the serialized program and environment, returning the cost and the
+// serialized result
+fn run(program: &[u8], env: &[u8], flags: u32) -> Result<(Cost, Vec<u8>), String> {
+    let mut a = Allocator::new_with_flags(flags);
+    let program = node_from_bytes(&mut a, program).map_err(|e| e.to_string())?;
+    let env = node_from_bytes(&mut a, env).map_err(|e| e.to_string())?;
+    let dialect = ChiaDialect::new(flags);
+    let Reduction(cost, result) =
+        run_program(&mut a, &dialect, program, env, MAX_COST).map_err(|e| e.1)?;
+    let result = node_to_bytes(&a, result).map_err(|e| e.to_string())?;
+    Ok((cost, result))
+}
+
+// runs the serialized program with the permissive flags, and with the
+// permissive flags plus MEMPOOL_MODE. Returns a description of the divergence,
+// if the strict run doesn't agree with the permissive one
+pub fn check_strict_differential(program: &[u8], env: &[u8], flags: u32) -> Result<(), String> {
+    let permissive = run(program, env, flags & !MEMPOOL_MODE);
+    let strict = run(program, env, flags | MEMPOOL_MODE);
+    match (permissive, strict) {
+        (Ok(p), Ok(s)) if p != s => Err(format!(
+            "strict run returned {s:?}, permissive run returned {p:?}"
+        )),
+        (Err(e), Ok(_)) => Err(format!(
+            "strict run succeeded, permissive run failed with \"{e}\""
+        )),
+        (Ok(_), Err(e)) if !is_policy_error(&e) => Err(format!(
+            "strict run failed with \"{e}\", permissive run succeeded"
+        )),
+        _ => Ok(()),
+    }
+}
+
+// generates a program and environment from data, with operators available in
+// ChiaDialect with the specified flags, and checks it with
+// check_strict_differential(). Returns the serialized program along with the
+// result, to make divergences easy to reproduce
+pub fn check_generated(data: &[u8], flags: u32) -> (Vec<u8>, Result<(), String>) {
+    let mut a = Allocator::new();
+    let (program, env) = ProgramGenerator::new(data, flags).generate(&mut a);
+    let program = node_to_bytes(&a, program).expect("node_to_bytes");
+    let env = node_to_bytes(&a, env).expect("node_to_bytes");
+    let r = check_strict_differential(&program, &env, flags);
+    (program, r)
+}
+
+#[test]
+fn test_strict_differential() {
+    use clvmr::hex;
+
+    let check = |program: &str, env: &str| {
+        check_strict_differential(
+            &hex::decode(program).unwrap(),
+            &hex::decode(env).unwrap(),
+            0,
+        )
+    };
+
+    // (+ (q . 2) (q . 3)) succeeds in both modes
+    assert_eq!(check("ff10ffff0102ffff010380", "80"), Ok(()));
+
+    // (x) fails in both modes
+    assert_eq!(check("ff0880", "80"), Ok(()));
+
+    // (0x7f), an unknown operator, only fails in strict mode
+    assert_eq!(check("ff7f80", "80"), Ok(()));
+
+    // (softfork (q . 10) (q . 1)), a malformed softfork invocation
+    assert_eq!(check("ff24ffff010affff010180", "80"), Ok(()));
+
+    assert!(is_policy_error("unimplemented operator"));
+    assert!(is_policy_error("softfork takes exactly 4 arguments"));
+    assert!(!is_policy_error("clvm raise"));
+    assert!(!is_policy_error("cost exceeded"));
+}
+
+#[test]
+fn test_strict_differential_generated() {
+    use clvmr::argument_generator::random_inputs;
+    use clvmr::chia_dialect::ENABLE_BLS_OPS_OUTSIDE_GUARD;
+
+    for data in random_inputs(0x8765_4321, 200) {
+        for flags in [0, ENABLE_BLS_OPS_OUTSIDE_GUARD] {
+            let (program, r) = check_generated(&data, flags);
+            assert_eq!(
+                r,
+                Ok(()),
+                "program {} flags {flags:x}",
+                clvmr::hex::encode(program)
+            );
+        }
+    }
+}
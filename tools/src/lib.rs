@@ -0,0 +1,12 @@
+// the generator lives in clvmr, behind the fuzzing feature, so the fuzz
+// targets and the tools always use the same one
+pub use clvmr::argument_generator;
+
+pub mod bench;
+pub mod corpus;
+pub mod cost_golden;
+pub mod cost_listing;
+pub mod differential;
+pub mod rpc;
+pub mod sandbox;
+pub mod trace;
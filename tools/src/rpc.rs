@@ -0,0 +1,267 @@
+use crate::cost_listing::{cost_listing, profile_program};
+use clvmr::allocator::Allocator;
+use clvmr::chia_dialect::ChiaDialect;
+use clvmr::cost::Cost;
+use clvmr::hex;
+use clvmr::reduction::Reduction;
+use clvmr::run_program::run_program;
+use clvmr::serde::{node_from_bytes_backrefs, node_to_bytes, tree_hash_from_stream};
+use clvmr::test_dialect::format_node;
+use serde_json::{json, Value};
+use std::io::Cursor;
+
+// A JSON-RPC 2.0 service meant to be used as a backend by editor plugins. Every
+// request is a single line of JSON, and every response is written as a single
+// line. Programs and values are passed as hex encoded serialized CLVM.
+//
+// methods:
+// run:         {program, env?, max_cost?, flags?} -> {cost, result}
+// tree_hash:   {program} -> hex encoded tree hash
+// disassemble: {program, annotate?, env?, max_cost?, flags?} -> human readable
+//              form of the program. With annotate, it's a cost listing (see
+//              cost_listing.rs), including measured costs if env is passed
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// the program failed, or the input is not valid CLVM
+const CLVM_ERROR: i64 = -32000;
+
+const DEFAULT_MAX_COST: Cost = 11000000000;
+
+struct RpcError(i64, String);
+
+fn invalid_params(msg: &str) -> RpcError {
+    RpcError(INVALID_PARAMS, msg.to_string())
+}
+
+fn hex_param(params: &Value, name: &str, default: Option<&str>) -> Result<Vec<u8>, RpcError> {
+    let value = match params.get(name) {
+        Some(v) => v
+            .as_str()
+            .ok_or_else(|| invalid_params(&format!("{name} must be a string")))?,
+        None => default.ok_or_else(|| invalid_params(&format!("missing parameter {name}")))?,
+    };
+    hex::decode(value).map_err(|e| invalid_params(&format!("{name}: {e}")))
+}
+
+fn u64_param(params: &Value, name: &str, default: u64) -> Result<u64, RpcError> {
+    match params.get(name) {
+        Some(v) => v
+            .as_u64()
+            .ok_or_else(|| invalid_params(&format!("{name} must be an unsigned integer"))),
+        None => Ok(default),
+    }
+}
+
+fn bool_param(params: &Value, name: &str) -> Result<bool, RpcError> {
+    match params.get(name) {
+        Some(v) => v
+            .as_bool()
+            .ok_or_else(|| invalid_params(&format!("{name} must be a boolean"))),
+        None => Ok(false),
+    }
+}
+
+fn flags_param(params: &Value) -> Result<u32, RpcError> {
+    let flags = u64_param(params, "flags", 0)?;
+    u32::try_from(flags).map_err(|_| invalid_params("flags out of range"))
+}
+
+fn parse(a: &mut Allocator, buf: &[u8]) -> Result<clvmr::NodePtr, RpcError> {
+    node_from_bytes_backrefs(a, buf).map_err(|e| RpcError(CLVM_ERROR, e.to_string()))
+}
+
+fn run(params: &Value) -> Result<Value, RpcError> {
+    let program = hex_param(params, "program", None)?;
+    let env = hex_param(params, "env", Some("80"))?;
+    let max_cost = u64_param(params, "max_cost", DEFAULT_MAX_COST)?;
+    let flags = flags_param(params)?;
+
+    let mut a = Allocator::new();
+    let program = parse(&mut a, &program)?;
+    let env = parse(&mut a, &env)?;
+    let dialect = ChiaDialect::new(flags);
+    match run_program(&mut a, &dialect, program, env, max_cost) {
+        Ok(Reduction(cost, result)) => {
+            let result =
+                node_to_bytes(&a, result).map_err(|e| RpcError(CLVM_ERROR, e.to_string()))?;
+            Ok(json!({"cost": cost, "result": hex::encode(result)}))
+        }
+        Err(e) => Err(RpcError(CLVM_ERROR, e.1)),
+    }
+}
+
+fn tree_hash(params: &Value) -> Result<Value, RpcError> {
+    let program = hex_param(params, "program", None)?;
+    let hash = tree_hash_from_stream(&mut Cursor::new(&program))
+        .map_err(|e| RpcError(CLVM_ERROR, e.to_string()))?;
+    Ok(Value::String(hex::encode(hash)))
+}
+
+fn disassemble(params: &Value) -> Result<Value, RpcError> {
+    let program = hex_param(params, "program", None)?;
+    let mut a = Allocator::new();
+    let program = parse(&mut a, &program)?;
+    if !bool_param(params, "annotate")? {
+        return Ok(Value::String(format_node(&a, program)));
+    }
+    let flags = flags_param(params)?;
+    let profile = match params.get("env") {
+        None => None,
+        Some(_) => {
+            let env = hex_param(params, "env", None)?;
+            let env = parse(&mut a, &env)?;
+            let max_cost = u64_param(params, "max_cost", DEFAULT_MAX_COST)?;
+            let dialect = ChiaDialect::new(flags);
+            // the listing is useful even if the program fails
+            let (_, profile) = profile_program(&mut a, &dialect, program, env, max_cost);
+            Some(profile)
+        }
+    };
+    Ok(Value::String(cost_listing(
+        &a,
+        program,
+        flags,
+        profile.as_ref(),
+    )))
+}
+
+fn dispatch(request: &Value) -> Result<Value, RpcError> {
+    let method = request
+        .get("method")
+        .and_then(Value::as_str)
+        .ok_or_else(|| RpcError(INVALID_REQUEST, "missing method".to_string()))?;
+    let params = request.get("params").unwrap_or(&Value::Null);
+    match method {
+        "run" => run(params),
+        "tree_hash" => tree_hash(params),
+        "disassemble" => disassemble(params),
+        _ => Err(RpcError(
+            METHOD_NOT_FOUND,
+            format!("unknown method {method}"),
+        )),
+    }
+}
+
+// handles a single line of input and returns the response line
+pub fn handle_line(line: &str) -> String {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(r) => r,
+        Err(e) => {
+            return json!({
+                "jsonrpc": "2.0",
+                "id": Value::Null,
+                "error": {"code": PARSE_ERROR, "message": e.to_string()},
+            })
+            .to_string();
+        }
+    };
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    match dispatch(&request) {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(RpcError(code, message)) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {"code": code, "message": message},
+        }),
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+fn call(request: &str) -> Value {
+    serde_json::from_str(&handle_line(request)).unwrap()
+}
+
+#[test]
+fn test_run() {
+    // (+ (q . 2) (q . 3))
+    let r = call(
+        r#"{"jsonrpc": "2.0", "id": 1, "method": "run", "params": {"program": "ff10ffff0102ffff010380"}}"#,
+    );
+    assert_eq!(r["id"], 1);
+    assert_eq!(r["result"]["result"], "05");
+    assert_eq!(r["result"]["cost"], 796);
+
+    // the environment is passed in as the program's arguments
+    let r =
+        call(r#"{"id": "a", "method": "run", "params": {"program": "02", "env": "ff8200ff80"}}"#);
+    assert_eq!(r["id"], "a");
+    assert_eq!(r["result"]["result"], "8200ff");
+
+    let r = call(r#"{"id": 2, "method": "run", "params": {"program": "ff08ffff012a80"}}"#);
+    assert_eq!(r["error"]["code"], CLVM_ERROR);
+    assert_eq!(r["error"]["message"], "clvm raise");
+
+    let r = call(
+        r#"{"id": 3, "method": "run", "params": {"program": "ff10ffff0102ffff010380", "max_cost": 10}}"#,
+    );
+    assert_eq!(r["error"]["message"], "cost exceeded");
+}
+
+#[test]
+fn test_tree_hash() {
+    let r = call(r#"{"id": 1, "method": "tree_hash", "params": {"program": "80"}}"#);
+    assert_eq!(
+        r["result"],
+        "4bf5122f344554c53bde2ebb8cd2b7e3d1600ad631c385a5d7cce23c7785459a"
+    );
+}
+
+#[test]
+fn test_disassemble() {
+    let r = call(
+        r#"{"id": 1, "method": "disassemble", "params": {"program": "ff10ffff0102ffff010380"}}"#,
+    );
+    assert_eq!(r["result"], "(16 (1 . 2) (1 . 3))");
+
+    let r = call(
+        r#"{"id": 1, "method": "disassemble", "params": {"program": "ff10ffff0102ffff010380", "annotate": true}}"#,
+    );
+    assert_eq!(
+        r["result"],
+        "   base  site\n     99  (+ (1 . 2) (1 . 3))\n      0    (q . 2)\n      0    (q . 3)\n"
+    );
+
+    let r = call(
+        r#"{"id": 1, "method": "disassemble", "params": {"program": "ff10ffff0102ffff010380", "annotate": true, "env": "80"}}"#,
+    );
+    assert_eq!(
+        r["result"],
+        "      cost   calls   base  site\n       \
+         755       1      99  (+ (1 . 2) (1 . 3))\n         \
+         -       -       0    (q . 2)\n         \
+         -       -       0    (q . 3)\n"
+    );
+}
+
+#[test]
+fn test_errors() {
+    let r = call("{not json");
+    assert_eq!(r["id"], Value::Null);
+    assert_eq!(r["error"]["code"], PARSE_ERROR);
+
+    let r = call(r#"{"id": 1}"#);
+    assert_eq!(r["error"]["code"], INVALID_REQUEST);
+
+    let r = call(r#"{"id": 1, "method": "step"}"#);
+    assert_eq!(r["error"]["code"], METHOD_NOT_FOUND);
+    assert_eq!(r["error"]["message"], "unknown method step");
+
+    let r = call(r#"{"id": 1, "method": "run", "params": {}}"#);
+    assert_eq!(r["error"]["code"], INVALID_PARAMS);
+    assert_eq!(r["error"]["message"], "missing parameter program");
+
+    let r = call(r#"{"id": 1, "method": "run", "params": {"program": "0xff1"}}"#);
+    assert_eq!(r["error"]["code"], INVALID_PARAMS);
+    assert_eq!(
+        r["error"]["message"],
+        "program: odd number of hex digits at offset 4"
+    );
+
+    let r = call(r#"{"id": 1, "method": "run", "params": {"program": "ff"}}"#);
+    assert_eq!(r["error"]["code"], CLVM_ERROR);
+}
@@ -0,0 +1,201 @@
+use crate::argument_generator::ProgramGenerator;
+use clvmr::allocator::Allocator;
+use clvmr::chia_dialect::ChiaDialect;
+use clvmr::cost::Cost;
+use clvmr::reduction::Reduction;
+use clvmr::run_program::run_program;
+use clvmr::serde::{node_from_bytes, node_to_bytes};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Runs untrusted programs under a watchdog, to audit that the interpreter
+// upholds its guarantees for any input: it never panics, never grows the heap
+// past the allocator's limits and finishes in bounded time (which is what the
+// cost limit is meant to ensure). The interpreter doesn't make system calls
+// (other than allocating memory), so these are the only ways a program can
+// affect its host.
+//
+// The program runs on its own thread. A run that times out can't be stopped;
+// its thread is left running in the background.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SandboxLimits {
+    // passed to Allocator::new_limited()
+    pub heap_limit: usize,
+    // passed to Allocator::set_atom_limit()
+    pub atom_limit: usize,
+    pub max_cost: Cost,
+    // the wall-clock time the run may take
+    pub timeout: Duration,
+}
+
+impl Default for SandboxLimits {
+    fn default() -> Self {
+        Self {
+            heap_limit: 64 * 1024 * 1024,
+            atom_limit: 1024 * 1024,
+            max_cost: 11000000000,
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SandboxOutcome {
+    // the program ran to completion, the result is serialized
+    Ok { cost: Cost, result: Vec<u8> },
+    // the program failed, this is not a violation
+    Err(String),
+    // the following are violations of the interpreter's guarantees
+    Panicked(String),
+    // the heap grew past the limit. This is the number of bytes in use
+    HeapExceeded(usize),
+    TimedOut,
+}
+
+impl SandboxOutcome {
+    // returns true if the interpreter upheld its guarantees
+    pub fn is_sound(&self) -> bool {
+        matches!(self, Self::Ok { .. } | Self::Err(_))
+    }
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+fn run(program: &[u8], env: &[u8], flags: u32, limits: &SandboxLimits) -> SandboxOutcome {
+    let mut a = Allocator::new_limited(limits.heap_limit);
+    a.set_atom_limit(limits.atom_limit);
+    let (program, env) = match (
+        node_from_bytes(&mut a, program),
+        node_from_bytes(&mut a, env),
+    ) {
+        (Ok(p), Ok(e)) => (p, e),
+        (Err(e), _) | (_, Err(e)) => return SandboxOutcome::Err(e.to_string()),
+    };
+    let dialect = ChiaDialect::new(flags);
+    let r = run_program(&mut a, &dialect, program, env, limits.max_cost);
+    if a.heap_size() > limits.heap_limit {
+        return SandboxOutcome::HeapExceeded(a.heap_size());
+    }
+    match r {
+        Ok(Reduction(cost, result)) => match node_to_bytes(&a, result) {
+            Ok(result) => SandboxOutcome::Ok { cost, result },
+            Err(e) => SandboxOutcome::Err(e.to_string()),
+        },
+        Err(e) => SandboxOutcome::Err(e.1),
+    }
+}
+
+// runs the serialized program with the serialized environment, with the
+// specified ChiaDialect flags, under the limits
+pub fn run_sandboxed(
+    program: &[u8],
+    env: &[u8],
+    flags: u32,
+    limits: &SandboxLimits,
+) -> SandboxOutcome {
+    let (tx, rx) = mpsc::channel();
+    let program = program.to_vec();
+    let env = env.to_vec();
+    let limits = *limits;
+    let start = Instant::now();
+    thread::spawn(move || {
+        let r = catch_unwind(AssertUnwindSafe(|| run(&program, &env, flags, &limits)));
+        // the receiver is gone if the run timed out
+        let _ =
+            tx.send(r.unwrap_or_else(|payload| SandboxOutcome::Panicked(panic_message(payload))));
+    });
+    match rx.recv_timeout(limits.timeout) {
+        // a run that completes after the deadline still counts as timed out
+        Ok(_) if start.elapsed() > limits.timeout => SandboxOutcome::TimedOut,
+        Ok(outcome) => outcome,
+        Err(_) => SandboxOutcome::TimedOut,
+    }
+}
+
+// generates a program and environment from data, with operators available in
+// ChiaDialect with the specified flags, and runs them in the sandbox. Returns
+// the serialized program along with the outcome, to make violations easy to
+// reproduce
+pub fn run_generated(data: &[u8], flags: u32, limits: &SandboxLimits) -> (Vec<u8>, SandboxOutcome) {
+    let mut a = Allocator::new();
+    let (program, env) = ProgramGenerator::new(data, flags).generate(&mut a);
+    let program = node_to_bytes(&a, program).expect("node_to_bytes");
+    let env = node_to_bytes(&a, env).expect("node_to_bytes");
+    let outcome = run_sandboxed(&program, &env, flags, limits);
+    (program, outcome)
+}
+
+#[test]
+fn test_sandbox_outcomes() {
+    let limits = SandboxLimits::default();
+    // (+ (q . 2) (q . 3))
+    let program = clvmr::hex::decode("ff10ffff0102ffff010380").unwrap();
+    assert_eq!(
+        run_sandboxed(&program, &[0x80], 0, &limits),
+        SandboxOutcome::Ok {
+            cost: 796,
+            result: vec![5]
+        }
+    );
+
+    // (x)
+    let program = clvmr::hex::decode("ff0880").unwrap();
+    assert_eq!(
+        run_sandboxed(&program, &[0x80], 0, &limits),
+        SandboxOutcome::Err("clvm raise".to_string())
+    );
+
+    // invalid serialization
+    assert!(run_sandboxed(&[0xff], &[0x80], 0, &limits).is_sound());
+
+    // (concat 1 1 1 1), an atom larger than the atom limit
+    let program = clvmr::hex::decode("ff0eff01ff01ff01ff0180").unwrap();
+    let env = clvmr::hex::decode("8400000000").unwrap();
+    let limits = SandboxLimits {
+        atom_limit: 8,
+        ..limits
+    };
+    assert_eq!(
+        run_sandboxed(&program, &env, 0, &limits),
+        SandboxOutcome::Err("atom too large".to_string())
+    );
+}
+
+#[test]
+fn test_sandbox_generated_programs() {
+    use clvmr::argument_generator::random_inputs;
+    use clvmr::chia_dialect::{ENABLE_BLS_OPS_OUTSIDE_GUARD, MEMPOOL_MODE, STRICT_INT_OUTPUTS};
+
+    // deliberately small limits, to exercise the failure paths
+    let limits = SandboxLimits {
+        heap_limit: 1024 * 1024,
+        atom_limit: 64 * 1024,
+        max_cost: 1000000000,
+        timeout: Duration::from_secs(60),
+    };
+    for data in random_inputs(0x1234_5678, 200) {
+        for flags in [
+            0,
+            ENABLE_BLS_OPS_OUTSIDE_GUARD,
+            MEMPOOL_MODE | STRICT_INT_OUTPUTS,
+        ] {
+            let (program, outcome) = run_generated(&data, flags, &limits);
+            assert!(
+                outcome.is_sound(),
+                "program {} flags {flags:x}: {outcome:?}",
+                clvmr::hex::encode(program)
+            );
+        }
+    }
+}
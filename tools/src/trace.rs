@@ -0,0 +1,325 @@
+use clvmr::allocator::{Allocator, NodePtr};
+use clvmr::cost::Cost;
+use clvmr::dialect::{Dialect, OperatorSet};
+use clvmr::hex;
+use clvmr::reduction::{Reduction, Response};
+use clvmr::run_program::run_program;
+use clvmr::serde::{node_to_bytes, tree_hash_from_stream};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, Cursor, Read, Write};
+
+// A trace records every operator invoked while running a program, along with
+// the tree hash of its arguments and its outcome, followed by the outcome of
+// the whole program. Traces are written as JSON and are deterministic, so
+// traces from different implementations (or versions) of the interpreter can
+// be compared with compare_traces() to find the first operator where they
+// disagree.
+//
+// The version is bumped whenever the format changes.
+pub const TRACE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    // the cost and the hex encoded tree hash of the result
+    Ok { cost: Cost, hash: String },
+    Err { message: String },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent {
+    // the hex encoded opcode
+    pub op: String,
+    // the hex encoded tree hash of the argument list
+    pub args: String,
+    pub outcome: Outcome,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    pub version: u32,
+    pub events: Vec<TraceEvent>,
+    pub result: Outcome,
+}
+
+impl Trace {
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *w, self)?;
+        writeln!(w)
+    }
+
+    pub fn read<R: Read>(r: &mut R) -> io::Result<Self> {
+        let trace: Self = serde_json::from_reader(r)?;
+        if trace.version != TRACE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported trace version {}", trace.version),
+            ));
+        }
+        Ok(trace)
+    }
+}
+
+fn tree_hash(a: &Allocator, node: NodePtr) -> String {
+    let buf = node_to_bytes(a, node).expect("node_to_bytes");
+    hex::encode(tree_hash_from_stream(&mut Cursor::new(&buf)).expect("tree_hash_from_stream"))
+}
+
+fn outcome(a: &Allocator, r: &Response) -> Outcome {
+    match r {
+        Ok(Reduction(cost, node)) => Outcome::Ok {
+            cost: *cost,
+            hash: tree_hash(a, *node),
+        },
+        Err(e) => Outcome::Err {
+            message: e.1.clone(),
+        },
+    }
+}
+
+// wraps a dialect and records every operator call
+struct TraceDialect<'a, D: Dialect> {
+    dialect: &'a D,
+    events: RefCell<Vec<TraceEvent>>,
+}
+
+impl<D: Dialect> Dialect for TraceDialect<'_, D> {
+    fn op(
+        &self,
+        allocator: &mut Allocator,
+        o: NodePtr,
+        argument_list: NodePtr,
+        max_cost: Cost,
+        extension: OperatorSet,
+    ) -> Response {
+        let op = hex::encode(allocator.atom(o).as_ref());
+        let args = tree_hash(allocator, argument_list);
+        let r = self
+            .dialect
+            .op(allocator, o, argument_list, max_cost, extension);
+        let outcome = outcome(allocator, &r);
+        self.events
+            .borrow_mut()
+            .push(TraceEvent { op, args, outcome });
+        r
+    }
+
+    fn quote_kw(&self) -> u32 {
+        self.dialect.quote_kw()
+    }
+    fn apply_kw(&self) -> u32 {
+        self.dialect.apply_kw()
+    }
+    fn softfork_kw(&self) -> u32 {
+        self.dialect.softfork_kw()
+    }
+    fn softfork_extension(&self, ext: u32) -> OperatorSet {
+        self.dialect.softfork_extension(ext)
+    }
+    fn allow_unknown_ops(&self) -> bool {
+        self.dialect.allow_unknown_ops()
+    }
+    fn cost_schedule_version(&self) -> u32 {
+        self.dialect.cost_schedule_version()
+    }
+}
+
+// runs the program and returns its trace
+pub fn trace_program<D: Dialect>(
+    a: &mut Allocator,
+    dialect: &D,
+    program: NodePtr,
+    env: NodePtr,
+    max_cost: Cost,
+) -> Trace {
+    let dialect = TraceDialect {
+        dialect,
+        events: RefCell::new(Vec::new()),
+    };
+    let r = run_program(a, &dialect, program, env, max_cost);
+    Trace {
+        version: TRACE_VERSION,
+        events: dialect.events.into_inner(),
+        result: outcome(a, &r),
+    }
+}
+
+// the first difference between two traces
+#[derive(Debug, PartialEq, Eq)]
+pub enum Divergence {
+    Version(u32, u32),
+    // the index of the first event that differs. One of the events is None if
+    // that trace ended early
+    Event {
+        index: usize,
+        left: Option<TraceEvent>,
+        right: Option<TraceEvent>,
+    },
+    Result(Outcome, Outcome),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Ok { cost, hash } => write!(f, "cost {cost}, result {hash}"),
+            Outcome::Err { message } => write!(f, "error: {message}"),
+        }
+    }
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "op {} args {} -> {}", self.op, self.args, self.outcome)
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let event = |e: &Option<TraceEvent>| match e {
+            Some(e) => e.to_string(),
+            None => "end of trace".to_string(),
+        };
+        match self {
+            Divergence::Version(l, r) => write!(f, "trace versions differ: {l} vs. {r}"),
+            Divergence::Event { index, left, right } => write!(
+                f,
+                "traces diverge at event {index}:\n  left:  {}\n  right: {}",
+                event(left),
+                event(right)
+            ),
+            Divergence::Result(l, r) => {
+                write!(f, "results differ:\n  left:  {l}\n  right: {r}")
+            }
+        }
+    }
+}
+
+// returns the first difference between the traces, or None if they're
+// identical
+pub fn compare_traces(left: &Trace, right: &Trace) -> Option<Divergence> {
+    if left.version != right.version {
+        return Some(Divergence::Version(left.version, right.version));
+    }
+    let len = std::cmp::max(left.events.len(), right.events.len());
+    for index in 0..len {
+        let l = left.events.get(index);
+        let r = right.events.get(index);
+        if l != r {
+            return Some(Divergence::Event {
+                index,
+                left: l.cloned(),
+                right: r.cloned(),
+            });
+        }
+    }
+    if left.result != right.result {
+        return Some(Divergence::Result(
+            left.result.clone(),
+            right.result.clone(),
+        ));
+    }
+    None
+}
+
+#[cfg(test)]
+use clvmr::chia_dialect::ChiaDialect;
+#[cfg(test)]
+use clvmr::serde::node_from_bytes;
+
+#[cfg(test)]
+fn trace_hex(program: &str, env: &str) -> Trace {
+    let mut a = Allocator::new();
+    let program = node_from_bytes(&mut a, &hex::decode(program).unwrap()).unwrap();
+    let env = node_from_bytes(&mut a, &hex::decode(env).unwrap()).unwrap();
+    trace_program(&mut a, &ChiaDialect::new(0), program, env, 11000000000)
+}
+
+#[test]
+fn test_trace() {
+    // (+ 2 (q . 5))
+    let trace = trace_hex("ff10ff02ffff010580", "ff0380");
+    assert_eq!(trace.version, TRACE_VERSION);
+    assert_eq!(trace.events.len(), 1);
+    assert_eq!(trace.events[0].op, "10");
+    assert!(matches!(
+        trace.events[0].outcome,
+        Outcome::Ok { cost: 755, .. }
+    ));
+    assert!(matches!(trace.result, Outcome::Ok { .. }));
+
+    // round trip through the file format
+    let mut buf = Vec::new();
+    trace.write(&mut buf).unwrap();
+    let loaded = Trace::read(&mut buf.as_slice()).unwrap();
+    assert_eq!(loaded, trace);
+    assert_eq!(compare_traces(&trace, &loaded), None);
+
+    // traces are deterministic
+    assert_eq!(
+        compare_traces(&trace, &trace_hex("ff10ff02ffff010580", "ff0380")),
+        None
+    );
+}
+
+#[test]
+fn test_trace_error() {
+    // (x 2)
+    let trace = trace_hex("ff08ff0280", "ff0380");
+    assert_eq!(trace.events.len(), 1);
+    assert_eq!(
+        trace.events[0].outcome,
+        Outcome::Err {
+            message: "clvm raise".to_string()
+        }
+    );
+    assert_eq!(trace.result, trace.events[0].outcome);
+}
+
+#[test]
+fn test_compare_traces() {
+    // (+ 2 (q . 5)) with different environments
+    let left = trace_hex("ff10ff02ffff010580", "ff0380");
+    let right = trace_hex("ff10ff02ffff010580", "ff0480");
+    let Some(Divergence::Event { index, left, right }) = compare_traces(&left, &right) else {
+        panic!("expected event divergence");
+    };
+    assert_eq!(index, 0);
+    assert_ne!(left.unwrap().args, right.unwrap().args);
+
+    // one trace ends early
+    let left = trace_hex("ff10ff02ffff010580", "ff0380");
+    let mut right = left.clone();
+    right.events.push(left.events[0].clone());
+    let d = compare_traces(&left, &right).unwrap();
+    assert!(matches!(
+        d,
+        Divergence::Event {
+            index: 1,
+            left: None,
+            right: Some(_)
+        }
+    ));
+    assert!(d.to_string().contains("end of trace"));
+
+    let mut right = left.clone();
+    right.result = Outcome::Err {
+        message: "foo".to_string(),
+    };
+    assert!(matches!(
+        compare_traces(&left, &right),
+        Some(Divergence::Result(_, _))
+    ));
+
+    let mut right = left.clone();
+    right.version = 2;
+    assert_eq!(
+        compare_traces(&left, &right),
+        Some(Divergence::Version(1, 2))
+    );
+
+    let mut buf = Vec::new();
+    right.write(&mut buf).unwrap();
+    assert!(Trace::read(&mut buf.as_slice()).is_err());
+}
@@ -7,6 +7,7 @@ use clvmr::allocator::Allocator;
 use clvmr::chia_dialect::ChiaDialect;
 use clvmr::chia_dialect::NO_UNKNOWN_OPS as _no_unknown_ops;
 use clvmr::cost::Cost;
+use clvmr::cost_report::run_program_with_cost_report;
 use clvmr::run_program::run_program;
 use clvmr::serde::{node_from_bytes, node_to_bytes, serialized_length_from_bytes};
 
@@ -83,3 +84,26 @@ pub fn run_chia_program(
         Err(_eval_err) => Err(format!("{:?}", _eval_err)),
     }
 }
+
+// runs the program, and returns a breakdown of what it spent its cost on, as
+// [table, json]. The program failing is not an error, the report says how it
+// failed
+#[wasm_bindgen]
+pub fn chia_program_cost_report(
+    program: &[u8],
+    args: &[u8],
+    max_cost: Cost, // Expecting `BigInt` to be passed from JavaScript world
+    flag: u32,
+) -> Result<Array, String> {
+    let mut allocator = Allocator::new();
+    let program = node_from_bytes(&mut allocator, program).map_err(|e| e.to_string())?;
+    let args = node_from_bytes(&mut allocator, args).map_err(|e| e.to_string())?;
+    let dialect = ChiaDialect::new(flag);
+
+    let (_, report) =
+        run_program_with_cost_report(&mut allocator, &dialect, program, args, max_cost);
+    let tuple = Array::new_with_length(2);
+    tuple.set(0, JsValue::from(report.to_string()));
+    tuple.set(1, JsValue::from(report.to_json()));
+    Ok(tuple)
+}
@@ -133,3 +133,15 @@ test_case("Test serialized_length", function(){
         expect_equal(e, "bad encoding");
     }
 });
+
+test_case("Test chia_program_cost_report", function(){
+    // (+ (sha256 (q . 1)) (q . 2))
+    const prog = bytesFromHex("ff10ffff0bffff010180ffff010280");
+    const arg = bytesFromHex("80");
+    const max_cost = BigInt("100000");
+    const [table, json] = wasm.chia_program_cost_report(prog, arg, max_cost, 0);
+    const report = JSON.parse(json);
+    expect_equal(report.error, null);
+    expect_equal(report.operators.map(op => op.name).join(","), "+,sha256");
+    expect_equal(table.includes("sha256"), true);
+});
@@ -2,7 +2,7 @@
 Some utilities to cast python types to and from clvm.
 """
 
-from typing import Callable, List, SupportsBytes, Tuple, Union, cast
+from typing import Callable, List, Optional, SupportsBytes, Tuple, Union, cast
 
 from .clvm_storage import CLVMStorage, is_clvm_storage
 
@@ -90,10 +90,19 @@ def to_atom_type(v: AtomCastableType) -> bytes:
     raise ValueError("can't cast %s (%s) to bytes" % (type(v), v))
 
 
+def check_depth(depth: int, max_depth: Optional[int]) -> None:
+    if max_depth is not None and depth > max_depth:
+        raise ValueError(
+            "can't cast: nesting depth %d exceeds max_depth %d" % (depth, max_depth)
+        )
+
+
 def to_clvm_object(
     castable: CastableType,
     to_atom_f: Callable[[bytes], CLVMStorage],
     to_pair_f: Callable[[CLVMStorage, CLVMStorage], CLVMStorage],
+    max_depth: Optional[int] = None,
+    max_items: Optional[int] = None,
 ) -> CLVMStorage:
     """
     Convert a python object to clvm object.
@@ -103,14 +112,22 @@ def to_clvm_object(
     But the entire hiearachy must be traversed, so execution time is
     proportional to hierarchy depth, and thus potentially unbounded.
 
-    So don't use on untrusted input. Also, the case where a list that transitively
-    contains itself (eg `t = []; t.append(t)`) in a loop is not detected, and
-    this function will never return because it acts like a hiearachy with
-    infinite depth.
+    So don't use on untrusted input without limits. `max_depth` limits the
+    nesting of tuples and lists (the object itself is at depth 0, and the
+    elements of a list or tuple are one level deeper than it). `max_items`
+    limits the number of python objects converted, including the list and
+    tuple containers (objects that already are clvm objects aren't counted).
+    Exceeding either raises a `ValueError` naming the limit and, for depth,
+    the nesting level. Without `max_depth`, a list that transitively contains
+    itself (eg `t = []; t.append(t)`) is not detected, and this function will
+    never return because it acts like a hiearachy with infinite depth.
     """
     to_convert: List[CastableType] = [castable]
+    # the nesting depth of each item in `to_convert`
+    depths: List[int] = [0]
     did_convert: List[CLVMStorage] = []
     ops: List[int] = [0]
+    item_count = 0
 
     # operations:
     #  0: pop `to_convert` and convert if possible, storing result on `did_convert`,
@@ -123,13 +140,18 @@ def to_clvm_object(
         # convert value
         if op == 0:
             v = to_convert.pop()
+            depth = depths.pop()
             if is_clvm_storage(v):
                 v = cast(CLVMStorage, v)
                 did_convert.append(v)
                 continue
+            item_count += 1
+            if max_items is not None and item_count > max_items:
+                raise ValueError("can't cast: more than %d items" % max_items)
             if isinstance(v, tuple):
                 if len(v) != 2:
                     raise ValueError("can't cast tuple of size %d" % len(v))
+                check_depth(depth + 1, max_depth)
                 left, right = v
                 ll_right = is_clvm_storage(right)
                 ll_left = is_clvm_storage(left)
@@ -140,20 +162,26 @@ def to_clvm_object(
                 else:
                     ops.append(1)  # cons
                     to_convert.append(left)
+                    depths.append(depth + 1)
                     ops.append(0)  # convert
                     to_convert.append(right)
+                    depths.append(depth + 1)
                     ops.append(0)  # convert
                 continue
             if isinstance(v, list):
+                if len(v) > 0:
+                    check_depth(depth + 1, max_depth)
                 for _ in v:
                     ops.append(2)  # rcons
 
                 # add and convert the null terminator
                 to_convert.append(to_atom_f(NULL_BLOB))
+                depths.append(depth)
                 ops.append(0)  # convert
 
                 for _ in reversed(v):
                     to_convert.append(_)
+                    depths.append(depth + 1)
                     ops.append(0)  # convert
                 continue
             v = cast(AtomCastableType, v)
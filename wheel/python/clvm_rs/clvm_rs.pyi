@@ -5,6 +5,9 @@ from .clvm_storage import CLVMStorage
 def run_serialized_chia_program(
     program: bytes, environment: bytes, max_cost: int, flags: int
 ) -> Tuple[int, CLVMStorage]: ...
+def chia_program_cost_report(
+    program: bytes, environment: bytes, max_cost: int, flags: int
+) -> Tuple[str, str]: ...
 def deserialize_as_tree(
     blob: bytes, calculate_tree_hashes: bool
 ) -> Tuple[List[Tuple[int, int, int]], Optional[List[bytes]]]: ...
@@ -14,9 +17,16 @@ NO_NEG_DIV: int
 NO_UNKNOWN_OPS: int
 LIMIT_HEAP: int
 MEMPOOL_MODE: int
+ENABLE_ED25519_VERIFY: int
+ENABLE_DIVEXACT_GCD: int
+ENABLE_FIXED_POINT_OPS: int
+ENABLE_BIT_COUNT_OPS: int
+ENABLE_TREE_SIZE_OPS: int
+COST_SCHEDULE_VERSION: int
 
 class LazyNode(CLVMStorage):
     atom: Optional[bytes]
 
     @property
     def pair(self) -> Optional[Tuple[CLVMStorage, CLVMStorage]]: ...
+    def clone_to_new_allocator(self) -> LazyNode: ...
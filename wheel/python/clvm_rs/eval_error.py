@@ -1,4 +1,14 @@
+from typing import Any, Dict, Optional
+
+
 class EvalError(ValueError):
-    def __init__(self, message: str, sexp):
+    def __init__(
+        self, message: str, sexp, cost_exceeded: Optional[Dict[str, Any]] = None
+    ):
         super().__init__(message)
         self._sexp = sexp
+        # when the program ran out of cost, a dict with "max_cost", "cost"
+        # (spent before the step that exceeded max_cost), "required" (a lower
+        # bound of the cost needed to complete that step) and "operator" (the
+        # operator applied in that step, or None)
+        self.cost_exceeded = cost_exceeded
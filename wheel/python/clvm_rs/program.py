@@ -1,10 +1,12 @@
 from __future__ import annotations
-from typing import Iterator, List, Tuple, Optional, BinaryIO
+from typing import Any, Dict, Iterator, List, Tuple, Optional, BinaryIO
+
+import json
 
 from .at import at
 from .casts import CastableType, to_clvm_object, int_from_bytes, int_to_bytes
 from .chia_dialect import CHIA_DIALECT
-from .clvm_rs import run_serialized_chia_program
+from .clvm_rs import chia_program_cost_report, run_serialized_chia_program
 from .clvm_storage import CLVMStorage
 from .clvm_tree import CLVMTree
 from .curry_and_treehash import CurryTreehasher
@@ -98,8 +100,19 @@ class Program(CLVMStorage):
         return self._pair
 
     @classmethod
-    def to(cls, v: CastableType) -> Program:
-        return cls.wrap(to_clvm_object(v, cls.new_atom, cls.new_pair))
+    def to(
+        cls,
+        v: CastableType,
+        max_depth: Optional[int] = None,
+        max_items: Optional[int] = None,
+    ) -> Program:
+        """
+        Convert a python object to a `Program`. See `to_clvm_object` for the
+        limits on nesting depth and number of items.
+        """
+        return cls.wrap(
+            to_clvm_object(v, cls.new_atom, cls.new_pair, max_depth, max_items)
+        )
 
     @classmethod
     def wrap(cls, v: CLVMStorage) -> Program:
@@ -268,9 +281,27 @@ class Program(CLVMStorage):
             )
             r = self.wrap(lazy_node)
         except ValueError as ve:
-            raise EvalError(ve.args[0], self.wrap(ve.args[1]))
+            cost_exceeded = getattr(ve, "cost_exceeded", None)
+            if cost_exceeded is not None and cost_exceeded["operator"] is not None:
+                cost_exceeded["operator"] = self.wrap(cost_exceeded["operator"])
+            raise EvalError(ve.args[0], self.wrap(ve.args[1]), cost_exceeded)
         return cost, r
 
+    def cost_report(
+        self, args, max_cost: int, flags: int = 0
+    ) -> Tuple[str, Dict[str, Any]]:
+        """
+        Run the program and return a breakdown of what it spent its cost on,
+        per operator, both as a printable table and as a dict. A failing run
+        isn't an exception; its error is part of the report.
+        """
+        prog_bytes = bytes(self)
+        args_bytes = bytes(self.to(args))
+        table, report = chia_program_cost_report(
+            prog_bytes, args_bytes, max_cost, flags
+        )
+        return table, json.loads(report)
+
     def run(self, args) -> "Program":
         """
         Run with the default `UNSAFE_MAX_COST` value. Using too high a value with
@@ -34,6 +34,20 @@ class TestProgram(TestCase):
         self.assertEqual(p.at_many("f", "rrrfrf"), [10, 17])
         self.assertEqual(p.at_many("fff", "rrrfff"), [None, None])
 
+    def test_to_limits(self):
+        v = [1, [2, 3]]
+        self.assertEqual(Program.to(v, max_depth=2, max_items=5), Program.to(v))
+        with self.assertRaisesRegex(ValueError, "nesting depth 2 exceeds max_depth 1"):
+            Program.to(v, max_depth=1)
+        with self.assertRaisesRegex(ValueError, "more than 4 items"):
+            Program.to(v, max_items=4)
+
+        # a list containing itself
+        t: list = []
+        t.append(t)
+        with self.assertRaisesRegex(ValueError, "nesting depth 101 exceeds max_depth 100"):
+            Program.to(t, max_depth=100)
+
     def test_replace(self):
         p1 = Program.to([100, 200, 300])
         self.assertEqual(p1.replace(f=105), Program.to([105, 200, 300]))
@@ -82,6 +96,41 @@ class TestProgram(TestCase):
             err = ee
         self.assertEqual(err.args, ("clvm raise",))
         self.assertEqual(err._sexp, ["foo", "bar"])
+        self.assertIsNone(err.cost_exceeded)
+
+    def test_run_cost_exceeded(self):
+        p = Program.fromhex("ff10ffff0101ffff010280")  # `(+ (q . 1) (q . 2))`
+        err = None
+        try:
+            p.run_with_cost(0, max_cost=100)
+        except EvalError as ee:
+            err = ee
+        self.assertEqual(err.args, ("cost exceeded",))
+        info = err.cost_exceeded
+        self.assertEqual(info["max_cost"], 100)
+        self.assertGreater(info["required"], 100)
+        self.assertLessEqual(info["cost"], 100)
+        self.assertEqual(info["operator"], 16)
+
+    def test_cost_report(self):
+        # `(+ (sha256 (q . 1)) (q . 2))`
+        p = Program.fromhex("ff10ffff0bffff010180ffff010280")
+        table, report = p.cost_report(0, max_cost=100000)
+        self.assertEqual(report["max_cost"], 100000)
+        self.assertIsNone(report["error"])
+        self.assertEqual(
+            [op["name"] for op in report["operators"]], ["+", "sha256"]
+        )
+        self.assertEqual(
+            report["cost"],
+            report["evaluation_cost"]
+            + sum(op["cost"] for op in report["operators"]),
+        )
+        self.assertEqual(p.run_with_cost(0, max_cost=100000)[0], report["cost"])
+        self.assertIn("sha256", table)
+
+        _, report = p.cost_report(0, max_cost=100)
+        self.assertEqual(report["error"], "cost exceeded")
 
     def test_hash(self):
         p1 = Program.fromhex("80")
@@ -493,3 +542,12 @@ class ProgramTest(TestCase):
         self.assertEqual(p3p._cached_sha256_treehash.hex(), eh3)
         self.assertEqual(p._cached_sha256_treehash.hex(), eh)
         self.assertEqual(p2._cached_sha256_treehash.hex(), eh2)
+
+    def test_clone_to_new_allocator(self):
+        from clvm_rs.clvm_rs import run_serialized_chia_program
+
+        prg = Program.to((Q_KW, [100, (200, 300), [400]]))
+        cost, node = run_serialized_chia_program(bytes(prg), b"\x80", 1000, 0)
+        copy = node.pair[1].clone_to_new_allocator()
+        self.assertEqual(Program.to(copy), Program.to([(200, 300), [400]]))
+        self.assertEqual(Program.to(node), Program.to([100, (200, 300), [400]]))
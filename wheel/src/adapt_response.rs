@@ -1,28 +1,44 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::lazy_node::LazyNode;
 use clvmr::allocator::Allocator;
 use clvmr::reduction::Response;
+use clvmr::run_program::CostExceeded;
 
 use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
-use pyo3::types::PyTuple;
+use pyo3::types::{PyDict, PyTuple};
 
 pub fn adapt_response(
     py: Python,
     allocator: Allocator,
     response: Response,
+    cost_exceeded: Option<CostExceeded>,
 ) -> PyResult<(u64, LazyNode)> {
     match response {
         Ok(reduction) => {
-            let val = LazyNode::new(Rc::new(allocator), reduction.1);
+            let val = LazyNode::new(Arc::new(allocator), reduction.1);
             Ok((reduction.0, val))
         }
         Err(eval_err) => {
-            let sexp = LazyNode::new(Rc::new(allocator), eval_err.0).to_object(py);
+            let allocator = Arc::new(allocator);
+            let sexp = LazyNode::new(allocator.clone(), eval_err.0).to_object(py);
             let msg = eval_err.1.to_object(py);
             let tuple = PyTuple::new(py, [msg, sexp]);
             let value_error: PyErr = PyValueError::new_err(tuple.to_object(py));
+            // failures to run within max_cost also carry a dict describing how
+            // the cost was exceeded, as the cost_exceeded attribute
+            if let Some(ce) = cost_exceeded {
+                let info = PyDict::new(py);
+                info.set_item("max_cost", ce.max_cost)?;
+                info.set_item("cost", ce.cost)?;
+                info.set_item("required", ce.required)?;
+                let operator = ce
+                    .operator
+                    .map(|op| LazyNode::new(allocator.clone(), op).to_object(py));
+                info.set_item("operator", operator)?;
+                value_error.value(py).setattr("cost_exceeded", info)?;
+            }
             Err(value_error)
         }
     }
@@ -5,10 +5,15 @@ use crate::adapt_response::adapt_response;
 use clvmr::allocator::Allocator;
 use clvmr::chia_dialect::ChiaDialect;
 use clvmr::cost::Cost;
+use clvmr::cost_report::run_program_with_cost_report;
+use clvmr::cost_schedule_v1::COST_SCHEDULE_VERSION;
 use clvmr::reduction::Response;
-use clvmr::run_program::run_program;
+use clvmr::run_program::{run_program_with_cost_info, CostExceeded};
 use clvmr::serde::{node_from_bytes, parse_triples, serialized_length_from_bytes, ParsedTriple};
-use clvmr::{LIMIT_HEAP, MEMPOOL_MODE, NO_UNKNOWN_OPS};
+use clvmr::{
+    ENABLE_BIT_COUNT_OPS, ENABLE_DIVEXACT_GCD, ENABLE_ED25519_VERIFY, ENABLE_FIXED_POINT_OPS,
+    ENABLE_TREE_SIZE_OPS, LIMIT_HEAP, MEMPOOL_MODE, NO_UNKNOWN_OPS,
+};
 use pyo3::prelude::*;
 use pyo3::types::{PyBytes, PyTuple};
 use pyo3::wrap_pyfunction;
@@ -26,20 +31,40 @@ pub fn run_serialized_chia_program(
     max_cost: Cost,
     flags: u32,
 ) -> PyResult<(u64, LazyNode)> {
-    let mut allocator = if flags & LIMIT_HEAP != 0 {
-        Allocator::new_limited(500000000)
-    } else {
-        Allocator::new()
-    };
+    let mut allocator = Allocator::new_with_flags(flags);
 
-    let r: Response = (|| -> PyResult<Response> {
+    let (cost_exceeded, r) = (|| -> PyResult<(Option<CostExceeded>, Response)> {
         let program = node_from_bytes(&mut allocator, program)?;
         let args = node_from_bytes(&mut allocator, args)?;
         let dialect = ChiaDialect::new(flags);
 
-        Ok(py.allow_threads(|| run_program(&mut allocator, &dialect, program, args, max_cost)))
+        Ok(py.allow_threads(|| {
+            run_program_with_cost_info(&mut allocator, &dialect, program, args, max_cost)
+        }))
     })()?;
-    adapt_response(py, allocator, r)
+    adapt_response(py, allocator, r, cost_exceeded)
+}
+
+// runs the program, and returns a breakdown of what it spent its cost on, as
+// a printable table and as JSON. The program failing is not an error, the
+// report says how it failed
+#[pyfunction]
+pub fn chia_program_cost_report(
+    py: Python,
+    program: &[u8],
+    args: &[u8],
+    max_cost: Cost,
+    flags: u32,
+) -> PyResult<(String, String)> {
+    let mut allocator = Allocator::new_with_flags(flags);
+    let program = node_from_bytes(&mut allocator, program)?;
+    let args = node_from_bytes(&mut allocator, args)?;
+    let dialect = ChiaDialect::new(flags);
+
+    let (_, report) = py.allow_threads(|| {
+        run_program_with_cost_report(&mut allocator, &dialect, program, args, max_cost)
+    });
+    Ok((report.to_string(), report.to_json()))
 }
 
 fn tuple_for_parsed_triple(py: Python<'_>, p: &ParsedTriple) -> PyObject {
@@ -74,12 +99,19 @@ fn deserialize_as_tree(
 #[pymodule]
 fn clvm_rs(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(run_serialized_chia_program, m)?)?;
+    m.add_function(wrap_pyfunction!(chia_program_cost_report, m)?)?;
     m.add_function(wrap_pyfunction!(serialized_length, m)?)?;
     m.add_function(wrap_pyfunction!(deserialize_as_tree, m)?)?;
 
     m.add("NO_UNKNOWN_OPS", NO_UNKNOWN_OPS)?;
     m.add("LIMIT_HEAP", LIMIT_HEAP)?;
     m.add("MEMPOOL_MODE", MEMPOOL_MODE)?;
+    m.add("ENABLE_ED25519_VERIFY", ENABLE_ED25519_VERIFY)?;
+    m.add("ENABLE_DIVEXACT_GCD", ENABLE_DIVEXACT_GCD)?;
+    m.add("ENABLE_FIXED_POINT_OPS", ENABLE_FIXED_POINT_OPS)?;
+    m.add("ENABLE_BIT_COUNT_OPS", ENABLE_BIT_COUNT_OPS)?;
+    m.add("ENABLE_TREE_SIZE_OPS", ENABLE_TREE_SIZE_OPS)?;
+    m.add("COST_SCHEDULE_VERSION", COST_SCHEDULE_VERSION)?;
     m.add_class::<LazyNode>()?;
 
     Ok(())
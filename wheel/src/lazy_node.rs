@@ -1,13 +1,17 @@
-use clvmr::allocator::{Allocator, NodePtr, SExp};
-use std::rc::Rc;
+use clvmr::allocator::{copy_node, Allocator, NodePtr, SExp};
+use std::sync::Arc;
 
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 use pyo3::types::{PyBytes, PyTuple};
 
-#[pyclass(subclass, unsendable)]
+// A LazyNode keeps the allocator its node lives in alive, for as long as any
+// Python object refers to it. The allocator is immutable while shared, so the
+// node stays valid. No API takes a LazyNode back into Rust.
+#[pyclass(subclass)]
 #[derive(Clone)]
 pub struct LazyNode {
-    allocator: Rc<Allocator>,
+    allocator: Arc<Allocator>,
     node: NodePtr,
 }
 
@@ -41,10 +45,21 @@ impl LazyNode {
             _ => None,
         }
     }
+
+    // copies the tree this node refers to into a new allocator, and returns a
+    // node referring to the copy. This lets the (possibly much larger)
+    // allocator this node was created in be freed, while holding on to a small
+    // part of it
+    pub fn clone_to_new_allocator(&self) -> PyResult<Self> {
+        let mut a = Allocator::new();
+        let node = copy_node(&self.allocator, self.node, &mut a)
+            .map_err(|e| PyValueError::new_err(e.1))?;
+        Ok(Self::new(Arc::new(a), node))
+    }
 }
 
 impl LazyNode {
-    pub const fn new(a: Rc<Allocator>, n: NodePtr) -> Self {
+    pub fn new(a: Arc<Allocator>, n: NodePtr) -> Self {
         Self {
             allocator: a,
             node: n,